@@ -0,0 +1,77 @@
+use classfile::{get_utf8, parse, parse_lazy, ClassFile, ParseError};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::io::Read;
+
+fn rt_jar() -> Vec<Vec<u8>> {
+    let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+    let mut zip = zip::ZipArchive::new(file).unwrap();
+    let mut classes = vec![];
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).unwrap();
+        if file.name().ends_with(".class") {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            classes.push(bytes);
+        }
+    }
+    classes
+}
+
+// what a class path scan needs: the class name and its direct supertypes
+fn hierarchy(class_file: &ClassFile) -> usize {
+    let name = |index: u16| match index {
+        0 => 0,
+        _ => get_utf8(class_file.constant_pool.clone(), index as usize)
+            .unwrap()
+            .len(),
+    };
+    let supertypes = class_file
+        .interfaces
+        .iter()
+        .copied()
+        .map(name)
+        .sum::<usize>();
+    name(class_file.this_class) + name(class_file.super_class) + supertypes
+}
+
+fn scan(
+    classes: &[Vec<u8>],
+    parse: impl for<'a> Fn(&'a [u8]) -> Result<ClassFile<'a>, ParseError>,
+) -> usize {
+    classes
+        .iter()
+        .map(|bytes| hierarchy(&parse(bytes).unwrap()))
+        .sum()
+}
+
+// what a class loader needs: the names and every method body
+fn load(classes: &[Vec<u8>]) -> usize {
+    let mut code_length = 0;
+    for bytes in classes {
+        let class_file = parse_lazy(bytes).unwrap();
+        for method in &class_file.methods {
+            let code = method
+                .decode_code_attribute(class_file.constant_pool.clone())
+                .unwrap();
+            if let Some(code) = code {
+                code_length += code.code.len();
+            }
+        }
+        code_length += hierarchy(&class_file);
+    }
+    code_length
+}
+
+fn bench_rt_jar(c: &mut Criterion) {
+    let classes = rt_jar();
+    let mut group = c.benchmark_group("rt.jar");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(classes.len() as u64));
+    group.bench_function("parse", |b| b.iter(|| scan(&classes, parse)));
+    group.bench_function("parse_lazy", |b| b.iter(|| scan(&classes, parse_lazy)));
+    group.bench_function("parse_lazy + Code", |b| b.iter(|| load(&classes)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_rt_jar);
+criterion_main!(benches);
@@ -0,0 +1,379 @@
+//! Annotations with their element values resolved from the constant pool, JVMS 4.7.16.
+
+use crate::attribute::{self, Element, ElementValue};
+use crate::descriptor::FieldType;
+use crate::errors::Error;
+use crate::{mutf8, Attribute, AttributeType, ClassFile, Constant, MethodInfo};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// The binary name of the annotation interface, e.g. `java/lang/Deprecated`.
+    pub type_name: String,
+    /// Whether the annotation is kept at run time, i.e. was found in a
+    /// `RuntimeVisible*` rather than a `RuntimeInvisible*` attribute.
+    pub visible: bool,
+    /// The elements given explicitly, in class file order; defaults are in the
+    /// `AnnotationDefault` attributes of the annotation interface.
+    pub elements: Vec<(String, Value)>,
+}
+
+impl Annotation {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.elements
+            .iter()
+            .find(|(element, _)| element == name)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Byte(i8),
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Int(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(String),
+    Enum {
+        /// The binary name of the enum class.
+        type_name: String,
+        const_name: String,
+    },
+    /// A class literal by its return descriptor, e.g. `[Ljava/lang/String;`, `I` or `V`.
+    Class(String),
+    Annotation(Annotation),
+    Array(Vec<Value>),
+}
+
+/// The annotations of a class, field, method or record component, given its
+/// attributes.
+pub fn annotations(
+    class_file: &ClassFile,
+    attributes: &[Attribute],
+) -> Result<Vec<Annotation>, Error> {
+    let mut resolved = vec![];
+    for attribute in attributes {
+        let attribute = attribute
+            .decode(class_file.constant_pool.clone())
+            .map_err(|e| e.kind)?;
+        let (annotations, visible) = match &attribute.attribute_type {
+            AttributeType::RuntimeVisibleAnnotations { annotations } => (annotations, true),
+            AttributeType::RuntimeInvisibleAnnotations { annotations } => (annotations, false),
+            _ => continue,
+        };
+        for annotation in annotations {
+            resolved.push(resolve(class_file, annotation, visible)?);
+        }
+    }
+    Ok(resolved)
+}
+
+/// The annotations of each parameter of a method. There may be fewer entries
+/// than parameters in the descriptor, e.g. for the constructors of inner classes.
+pub fn parameter_annotations(
+    class_file: &ClassFile,
+    method: &MethodInfo,
+) -> Result<Vec<Vec<Annotation>>, Error> {
+    let mut resolved: Vec<Vec<Annotation>> = vec![];
+    for attribute in &method.attributes {
+        let attribute = attribute
+            .decode(class_file.constant_pool.clone())
+            .map_err(|e| e.kind)?;
+        let (parameters, visible) = match &attribute.attribute_type {
+            AttributeType::RuntimeVisibleParameterAnnotations {
+                parameter_annotations,
+            } => (parameter_annotations, true),
+            AttributeType::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations,
+            } => (parameter_annotations, false),
+            _ => continue,
+        };
+        if resolved.len() < parameters.len() {
+            resolved.resize(parameters.len(), vec![]);
+        }
+        for (i, parameter) in parameters.iter().enumerate() {
+            for annotation in &parameter.annotations {
+                resolved[i].push(resolve(class_file, annotation, visible)?);
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// The default value of an element of an annotation interface, given its method.
+pub fn default_value(class_file: &ClassFile, method: &MethodInfo) -> Result<Option<Value>, Error> {
+    for attribute in &method.attributes {
+        let attribute = attribute
+            .decode(class_file.constant_pool.clone())
+            .map_err(|e| e.kind)?;
+        if let AttributeType::AnnotationDefault { default_value } = &attribute.attribute_type {
+            // like the methods of the annotation interface, defaults are always visible
+            return value(class_file, default_value, true).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+fn resolve(
+    class_file: &ClassFile,
+    annotation: &attribute::Annotation,
+    visible: bool,
+) -> Result<Annotation, Error> {
+    let mut elements = Vec::with_capacity(annotation.element_value_pairs.len());
+    for pair in &annotation.element_value_pairs {
+        let name = utf8(class_file, pair.element_name_index)?;
+        elements.push((name, value(class_file, &pair.value, visible)?));
+    }
+    Ok(Annotation {
+        type_name: class_name(class_file, annotation.type_index)?,
+        visible,
+        elements,
+    })
+}
+
+// nested annotations are as visible as the enclosing one
+fn value(
+    class_file: &ClassFile,
+    element_value: &ElementValue,
+    visible: bool,
+) -> Result<Value, Error> {
+    let tag = element_value.tag;
+    Ok(match &element_value.value {
+        Element::ConstValueIndex(index) => {
+            let constant = class_file.constant_pool.get(*index as usize);
+            match (tag, constant) {
+                (b'B', Some(Constant::Integer(i))) => Value::Byte(*i as i8),
+                (b'C', Some(Constant::Integer(i))) => Value::Char(*i as u16),
+                (b'I', Some(Constant::Integer(i))) => Value::Int(*i),
+                (b'S', Some(Constant::Integer(i))) => Value::Short(*i as i16),
+                (b'Z', Some(Constant::Integer(i))) => Value::Boolean(*i != 0),
+                (b'D', Some(Constant::Double(d))) => Value::Double(*d),
+                (b'F', Some(Constant::Float(f))) => Value::Float(*f),
+                (b'J', Some(Constant::Long(l))) => Value::Long(*l),
+                (b's', _) => Value::String(utf8(class_file, *index)?),
+                (_, None | Some(Constant::Placeholder)) => {
+                    return Err(Error::InvalidConstantIndex(*index as usize))
+                }
+                _ => return Err(Error::MismatchConstantType),
+            }
+        }
+        Element::EnumConstValue {
+            type_name_index,
+            const_name_index,
+        } => Value::Enum {
+            type_name: class_name(class_file, *type_name_index)?,
+            const_name: utf8(class_file, *const_name_index)?,
+        },
+        Element::ClassInfoIndex(index) => Value::Class(utf8(class_file, *index)?),
+        Element::AnnotationValue(annotation) => {
+            Value::Annotation(resolve(class_file, annotation, visible)?)
+        }
+        Element::ArrayValue(values) => Value::Array(
+            values
+                .iter()
+                .map(|element_value| value(class_file, element_value, visible))
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+// the binary name of a class given by its field descriptor
+fn class_name(class_file: &ClassFile, index: u16) -> Result<String, Error> {
+    match FieldType::parse(&utf8(class_file, index)?) {
+        Ok(FieldType::Object(name)) => Ok(name),
+        _ => Err(Error::InvalidElementValue),
+    }
+}
+
+fn utf8(class_file: &ClassFile, index: u16) -> Result<String, Error> {
+    match class_file.constant_pool.get(index as usize) {
+        Some(Constant::Utf8(bytes)) => mutf8::decode(bytes),
+        None | Some(Constant::Placeholder) => Err(Error::InvalidConstantIndex(index as usize)),
+        Some(_) => Err(Error::MismatchConstantType),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::annotation::{annotations, default_value, parameter_annotations, Annotation, Value};
+    use crate::attribute::{self, Element};
+    use crate::{get_str, parse, parse_lazy, AttributeType, ClassFile, Error, MethodInfo};
+    use std::io::Read;
+
+    fn invisible(value: &str) -> Annotation {
+        Annotation {
+            type_name: "Annotated$Invisible".to_string(),
+            visible: false,
+            elements: vec![("value".to_string(), Value::String(value.to_string()))],
+        }
+    }
+
+    fn deprecated() -> Annotation {
+        Annotation {
+            type_name: "java/lang/Deprecated".to_string(),
+            visible: true,
+            elements: vec![],
+        }
+    }
+
+    fn method<'c>(class_file: &'c ClassFile, name: &str) -> &'c MethodInfo<'c> {
+        let cp = class_file.constant_pool.clone();
+        class_file
+            .methods
+            .iter()
+            .find(|method| get_str(cp.clone(), method.name_index as usize).unwrap() == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_annotations() {
+        let bytes = std::fs::read("../data/jvm8/Annotated.class").unwrap();
+        for class_file in [parse(&bytes).unwrap(), parse_lazy(&bytes).unwrap()] {
+            let class = annotations(&class_file, &class_file.attributes).unwrap();
+            assert_eq!(class.len(), 2);
+            assert_eq!(class[0].type_name, "Annotated$Values");
+            assert!(class[0].visible);
+            let expected = [
+                ("b", Value::Byte(1)),
+                ("c", Value::Char('c' as u16)),
+                ("d", Value::Double(1.5)),
+                ("f", Value::Float(2.5)),
+                ("i", Value::Int(3)),
+                ("j", Value::Long(4)),
+                ("s", Value::Short(5)),
+                ("z", Value::Boolean(true)),
+                ("string", Value::String("text".to_string())),
+                (
+                    "policy",
+                    Value::Enum {
+                        type_name: "java/lang/annotation/RetentionPolicy".to_string(),
+                        const_name: "CLASS".to_string(),
+                    },
+                ),
+                ("type", Value::Class("[Ljava/lang/String;".to_string())),
+                ("primitive", Value::Class("V".to_string())),
+                (
+                    "nested",
+                    Value::Annotation(Annotation {
+                        visible: true,
+                        ..invisible("inner")
+                    }),
+                ),
+                ("array", Value::Array(vec![Value::Int(1), Value::Int(2)])),
+            ];
+            for (name, value) in &expected {
+                assert_eq!(class[0].get(name), Some(value), "{}", name);
+            }
+            assert_eq!(class[0].elements.len(), expected.len());
+            assert_eq!(class[0].get("missing"), None);
+            assert_eq!(class[1], deprecated());
+
+            let field = &class_file.fields[0];
+            let field = annotations(&class_file, &field.attributes).unwrap();
+            assert_eq!(field, vec![invisible("field")]);
+
+            let method = method(&class_file, "method");
+            assert_eq!(
+                annotations(&class_file, &method.attributes).unwrap(),
+                vec![deprecated()]
+            );
+            assert_eq!(
+                parameter_annotations(&class_file, method).unwrap(),
+                vec![
+                    vec![invisible("first")],
+                    vec![],
+                    vec![deprecated(), invisible("third")]
+                ]
+            );
+            assert_eq!(default_value(&class_file, method).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_default_value() {
+        let bytes = std::fs::read("../data/jvm8/Annotated$Values.class").unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let missing = method(&class_file, "missing");
+        assert_eq!(
+            default_value(&class_file, missing).unwrap(),
+            Some(Value::String("default".to_string()))
+        );
+        let string = method(&class_file, "string");
+        assert_eq!(default_value(&class_file, string).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        fn values<'c>(class_file: &'c mut ClassFile) -> &'c mut attribute::Annotation {
+            class_file
+                .attributes
+                .iter_mut()
+                .find_map(|attribute| match &mut attribute.attribute_type {
+                    AttributeType::RuntimeVisibleAnnotations { annotations } => {
+                        annotations.first_mut()
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        }
+
+        let bytes = std::fs::read("../data/jvm8/Annotated.class").unwrap();
+        let mut class_file = parse(&bytes).unwrap();
+        values(&mut class_file).type_index = 0;
+        assert!(matches!(
+            annotations(&class_file, &class_file.attributes),
+            Err(Error::InvalidConstantIndex(0))
+        ));
+
+        // b = 1 read as a double
+        let mut class_file = parse(&bytes).unwrap();
+        values(&mut class_file).element_value_pairs[0].value.tag = b'D';
+        assert!(matches!(
+            annotations(&class_file, &class_file.attributes),
+            Err(Error::MismatchConstantType)
+        ));
+
+        // a class literal is not an annotation type
+        let mut class_file = parse(&bytes).unwrap();
+        let type_index = match values(&mut class_file).element_value_pairs[10].value.value {
+            Element::ClassInfoIndex(index) => index,
+            _ => unreachable!(),
+        };
+        values(&mut class_file).type_index = type_index;
+        assert!(matches!(
+            annotations(&class_file, &class_file.attributes),
+            Err(Error::InvalidElementValue)
+        ));
+    }
+
+    #[test]
+    fn test_rt_jar() {
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut count = 0;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            let class_file = parse_lazy(&bytes).unwrap();
+            count += annotations(&class_file, &class_file.attributes)
+                .unwrap()
+                .len();
+            for field in &class_file.fields {
+                count += annotations(&class_file, &field.attributes).unwrap().len();
+            }
+            for method in &class_file.methods {
+                count += annotations(&class_file, &method.attributes).unwrap().len();
+                parameter_annotations(&class_file, method).unwrap();
+                default_value(&class_file, method).unwrap();
+            }
+        }
+        assert!(count > 0);
+    }
+}
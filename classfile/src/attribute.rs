@@ -1,4 +1,8 @@
+use crate::{ConstantPoolRef, ParseError};
+use std::borrow::Cow;
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Attribute<'a> {
     pub attribute_name_index: u16,
     pub attribute_length: u32,
@@ -6,6 +10,7 @@ pub struct Attribute<'a> {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AttributeType<'a> {
     ConstantValue {
         constant_value_index: u16,
@@ -102,10 +107,172 @@ pub enum AttributeType<'a> {
         classes: Vec<u16>,
     },
     Unknown {
-        data: &'a [u8],
+        data: Cow<'a, [u8]>,
+    },
+    /// An attribute body left undecoded by `parse_lazy`, see `Attribute::decode`.
+    Lazy {
+        data: Cow<'a, [u8]>,
     },
 }
 
+impl Attribute<'_> {
+    /// Decodes the body of an attribute left as `AttributeType::Lazy` by
+    /// `parse_lazy`. Attributes that are already decoded are borrowed.
+    pub fn decode(
+        &self,
+        constant_pool: ConstantPoolRef<'_>,
+    ) -> Result<Cow<'_, Attribute<'_>>, ParseError> {
+        match &self.attribute_type {
+            AttributeType::Lazy { data } => Ok(Cow::Owned(Attribute {
+                attribute_name_index: self.attribute_name_index,
+                attribute_length: self.attribute_length,
+                attribute_type: crate::decode_attribute(
+                    constant_pool,
+                    self.attribute_name_index,
+                    data,
+                )?,
+            })),
+            _ => Ok(Cow::Borrowed(self)),
+        }
+    }
+
+    pub fn into_static(self) -> Attribute<'static> {
+        Attribute {
+            attribute_name_index: self.attribute_name_index,
+            attribute_length: self.attribute_length,
+            attribute_type: self.attribute_type.into_static(),
+        }
+    }
+}
+
+impl AttributeType<'_> {
+    pub fn into_static(self) -> AttributeType<'static> {
+        match self {
+            AttributeType::ConstantValue {
+                constant_value_index,
+            } => AttributeType::ConstantValue {
+                constant_value_index,
+            },
+            AttributeType::Code { code } => AttributeType::Code {
+                code: code.into_static(),
+            },
+            AttributeType::StackMapTable { entries } => AttributeType::StackMapTable { entries },
+            AttributeType::Exceptions {
+                exception_index_table,
+            } => AttributeType::Exceptions {
+                exception_index_table,
+            },
+            AttributeType::InnerClasses { classes } => AttributeType::InnerClasses { classes },
+            AttributeType::EnclosingMethod {
+                class_index,
+                method_index,
+            } => AttributeType::EnclosingMethod {
+                class_index,
+                method_index,
+            },
+            AttributeType::Synthetic => AttributeType::Synthetic,
+            AttributeType::Signature { signature_index } => {
+                AttributeType::Signature { signature_index }
+            }
+            AttributeType::SourceFile { sourcefile_index } => {
+                AttributeType::SourceFile { sourcefile_index }
+            }
+            AttributeType::SourceDebugExtension { debug_extension } => {
+                AttributeType::SourceDebugExtension { debug_extension }
+            }
+            AttributeType::LineNumberTable { line_number_table } => {
+                AttributeType::LineNumberTable { line_number_table }
+            }
+            AttributeType::LocalVariableTable {
+                local_variable_table,
+            } => AttributeType::LocalVariableTable {
+                local_variable_table,
+            },
+            AttributeType::LocalVariableTypeTable {
+                local_variable_type_table,
+            } => AttributeType::LocalVariableTypeTable {
+                local_variable_type_table,
+            },
+            AttributeType::Deprecated => AttributeType::Deprecated,
+            AttributeType::RuntimeVisibleAnnotations { annotations } => {
+                AttributeType::RuntimeVisibleAnnotations { annotations }
+            }
+            AttributeType::RuntimeInvisibleAnnotations { annotations } => {
+                AttributeType::RuntimeInvisibleAnnotations { annotations }
+            }
+            AttributeType::RuntimeVisibleParameterAnnotations {
+                parameter_annotations,
+            } => AttributeType::RuntimeVisibleParameterAnnotations {
+                parameter_annotations,
+            },
+            AttributeType::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations,
+            } => AttributeType::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations,
+            },
+            AttributeType::RuntimeVisibleTypeAnnotations { annotations } => {
+                AttributeType::RuntimeVisibleTypeAnnotations { annotations }
+            }
+            AttributeType::RuntimeInvisibleTypeAnnotations { annotations } => {
+                AttributeType::RuntimeInvisibleTypeAnnotations { annotations }
+            }
+            AttributeType::AnnotationDefault { default_value } => {
+                AttributeType::AnnotationDefault { default_value }
+            }
+            AttributeType::BootstrapMethods { bootstrap_methods } => {
+                AttributeType::BootstrapMethods { bootstrap_methods }
+            }
+            AttributeType::MethodParameters { parameters } => {
+                AttributeType::MethodParameters { parameters }
+            }
+            AttributeType::Module {
+                module_name_index,
+                module_flags,
+                module_version_index,
+                requires,
+                exports,
+                opens,
+                uses,
+                provides,
+            } => AttributeType::Module {
+                module_name_index,
+                module_flags,
+                module_version_index,
+                requires,
+                exports,
+                opens,
+                uses,
+                provides,
+            },
+            AttributeType::ModulePackages { package_index } => {
+                AttributeType::ModulePackages { package_index }
+            }
+            AttributeType::ModuleMainClass { main_class_index } => {
+                AttributeType::ModuleMainClass { main_class_index }
+            }
+            AttributeType::NestHost { host_class_index } => {
+                AttributeType::NestHost { host_class_index }
+            }
+            AttributeType::NestMembers { classes } => AttributeType::NestMembers { classes },
+            AttributeType::Record { components } => AttributeType::Record {
+                components: components
+                    .into_iter()
+                    .map(RecordComponent::into_static)
+                    .collect(),
+            },
+            AttributeType::PermittedSubclasses { classes } => {
+                AttributeType::PermittedSubclasses { classes }
+            }
+            AttributeType::Unknown { data } => AttributeType::Unknown {
+                data: Cow::Owned(data.into_owned()),
+            },
+            AttributeType::Lazy { data } => AttributeType::Lazy {
+                data: Cow::Owned(data.into_owned()),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum AttributeTag {
     ConstantValue,
@@ -184,15 +351,33 @@ impl From<&[u8]> for AttributeTag {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodeAttribute<'a> {
     pub max_stack: u16,
     pub max_locals: u16,
-    pub code: &'a [u8],
+    pub code: Cow<'a, [u8]>,
     pub exception_table: Vec<Exception>,
     pub attributes: Vec<Attribute<'a>>,
 }
 
+impl CodeAttribute<'_> {
+    pub fn into_static(self) -> CodeAttribute<'static> {
+        CodeAttribute {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: Cow::Owned(self.code.into_owned()),
+            exception_table: self.exception_table,
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(Attribute::into_static)
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Exception {
     pub start_pc: u16,
     pub end_pc: u16,
@@ -201,12 +386,14 @@ pub struct Exception {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StackMap {
     pub frame_type: u8,
     pub frame: StackMapFrame,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StackMapFrame {
     SameFrame,
     SameLocals1StackItemFrame {
@@ -234,6 +421,7 @@ pub enum StackMapFrame {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VerificationTypeInfo {
     Top,
     Integer,
@@ -247,6 +435,7 @@ pub enum VerificationTypeInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InnerClass {
     pub inner_class_info_index: u16,
     pub outer_class_info_index: u16,
@@ -255,12 +444,14 @@ pub struct InnerClass {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineNumber {
     pub start_pc: u16,
     pub line_number: u16,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalVariable {
     pub start_pc: u16,
     pub length: u16,
@@ -270,6 +461,7 @@ pub struct LocalVariable {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalVariableType {
     pub start_pc: u16,
     pub length: u16,
@@ -279,24 +471,28 @@ pub struct LocalVariableType {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Annotation {
     pub type_index: u16,
     pub element_value_pairs: Vec<ElementValuePair>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElementValuePair {
     pub element_name_index: u16,
     pub value: ElementValue,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElementValue {
     pub tag: u8,
     pub value: Element,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Element {
     ConstValueIndex(u16),
     EnumConstValue {
@@ -309,11 +505,13 @@ pub enum Element {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParameterAnnotation {
     pub annotations: Vec<Annotation>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeAnnotation {
     pub target_type: u8,
     pub target_info: TargetInfo,
@@ -323,6 +521,7 @@ pub struct TypeAnnotation {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TargetInfo {
     TypeParameterTarget {
         type_parameter_index: u8,
@@ -355,17 +554,20 @@ pub enum TargetInfo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypePath {
     pub path: Vec<TypePathPair>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypePathPair {
     pub type_path_kind: u8,
     pub type_argument_index: u8,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LocalVar {
     pub start_pc: u16,
     pub length: u16,
@@ -373,18 +575,21 @@ pub struct LocalVar {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BootstrapMethod {
     pub bootstrap_method_ref: u16,
     pub bootstrap_arguments: Vec<u16>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MethodParameter {
     pub name_index: u16,
     pub access_flags: u16,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Require {
     pub require_index: u16,
     pub require_flags: u16,
@@ -392,6 +597,7 @@ pub struct Require {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Export {
     pub export_index: u16,
     pub export_flags: u16,
@@ -399,6 +605,7 @@ pub struct Export {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Open {
     pub open_index: u16,
     pub open_flags: u16,
@@ -406,14 +613,30 @@ pub struct Open {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Provide {
     pub provide_index: u16,
     pub provide_with_index: Vec<u16>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordComponent<'a> {
     pub name_index: u16,
     pub descriptor_index: u16,
     pub attributes: Vec<Attribute<'a>>,
 }
+
+impl RecordComponent<'_> {
+    pub fn into_static(self) -> RecordComponent<'static> {
+        RecordComponent {
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(Attribute::into_static)
+                .collect(),
+        }
+    }
+}
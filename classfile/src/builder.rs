@@ -0,0 +1,1450 @@
+//! Assembles class files without a compiler.
+//!
+//! ```
+//! use classfile::builder::ClassBuilder;
+//!
+//! let bytes = ClassBuilder::new("com/acme/Gen")
+//!     .method("run", "()I", |code| code.iconst(3).ireturn())
+//!     .build()
+//!     .unwrap();
+//! assert!(classfile::parse(&bytes).is_ok());
+//! ```
+
+use crate::descriptor::{DescriptorError, FieldType, MethodDescriptor};
+use crate::errors::Error;
+use crate::frames::{analyze, compress, ClassHierarchy, EmptyHierarchy};
+use crate::{
+    get_str, mutf8, write, Attribute, AttributeType, ClassFile, CodeAttribute, Constant, Exception,
+    FieldInfo, MethodInfo, StackMap,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
+pub const ACC_PROTECTED: u16 = 0x0004;
+pub const ACC_STATIC: u16 = 0x0008;
+pub const ACC_FINAL: u16 = 0x0010;
+pub const ACC_SUPER: u16 = 0x0020;
+pub const ACC_INTERFACE: u16 = 0x0200;
+pub const ACC_ABSTRACT: u16 = 0x0400;
+
+/// Classes are built for this version unless told otherwise. It is the latest
+/// one whose methods need no `StackMapTable`.
+pub const DEFAULT_MAJOR_VERSION: u16 = 49;
+
+#[derive(Debug, Clone)]
+pub enum BuildError {
+    Descriptor(DescriptorError),
+    Code { method: String, message: String },
+    Write(Error),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::Descriptor(e) => write!(f, "{}", e),
+            BuildError::Code { method, message } => write!(f, "{} in method {}", message, method),
+            BuildError::Write(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Entry {
+    Utf8(Vec<u8>),
+    Integer(i32),
+    Float(u32),
+    Long(i64),
+    Double(u64),
+    Class(u16),
+    String(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    InterfaceMethodRef(u16, u16),
+    NameAndType(u16, u16),
+}
+
+// interns constants, so each is added once
+#[derive(Debug, Default)]
+struct ConstantPool {
+    entries: Vec<Entry>,
+    indices: HashMap<Entry, u16>,
+    // the index of the next entry
+    len: usize,
+    overflow: bool,
+}
+
+impl ConstantPool {
+    fn intern(&mut self, entry: Entry) -> u16 {
+        if let Some(index) = self.indices.get(&entry) {
+            return *index;
+        }
+        let index = self.len.max(1);
+        let slots = match entry {
+            Entry::Long(_) | Entry::Double(_) => 2,
+            _ => 1,
+        };
+        if index + slots > u16::MAX as usize {
+            self.overflow = true;
+            return 0;
+        }
+        self.len = index + slots;
+        self.indices.insert(entry.clone(), index as u16);
+        self.entries.push(entry);
+        index as u16
+    }
+
+    fn utf8(&mut self, s: &str) -> u16 {
+        self.intern(Entry::Utf8(mutf8::encode(s)))
+    }
+
+    fn class(&mut self, name: &str) -> u16 {
+        let name_index = self.utf8(name);
+        self.intern(Entry::Class(name_index))
+    }
+
+    fn string(&mut self, s: &str) -> u16 {
+        let string_index = self.utf8(s);
+        self.intern(Entry::String(string_index))
+    }
+
+    fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+        let name_index = self.utf8(name);
+        let descriptor_index = self.utf8(descriptor);
+        self.intern(Entry::NameAndType(name_index, descriptor_index))
+    }
+
+    fn member(&mut self, owner: &str, name: &str, descriptor: &str) -> (u16, u16) {
+        (self.class(owner), self.name_and_type(name, descriptor))
+    }
+
+    fn constants(&self) -> Vec<Constant<'_>> {
+        let mut constants = Vec::with_capacity(self.len.max(1));
+        constants.push(Constant::Placeholder);
+        for entry in &self.entries {
+            constants.push(match entry {
+                Entry::Utf8(bytes) => Constant::Utf8(Cow::Borrowed(bytes)),
+                Entry::Integer(value) => Constant::Integer(*value),
+                Entry::Float(bits) => Constant::Float(f32::from_bits(*bits)),
+                Entry::Long(value) => Constant::Long(*value),
+                Entry::Double(bits) => Constant::Double(f64::from_bits(*bits)),
+                Entry::Class(name_index) => Constant::Class {
+                    name_index: *name_index,
+                },
+                Entry::String(string_index) => Constant::String {
+                    string_index: *string_index,
+                },
+                Entry::FieldRef(class_index, name_and_type_index) => Constant::FieldRef {
+                    class_index: *class_index,
+                    name_and_type_index: *name_and_type_index,
+                },
+                Entry::MethodRef(class_index, name_and_type_index) => Constant::MethodRef {
+                    class_index: *class_index,
+                    name_and_type_index: *name_and_type_index,
+                },
+                Entry::InterfaceMethodRef(class_index, name_and_type_index) => {
+                    Constant::InterfaceMethodRef {
+                        class_index: *class_index,
+                        name_and_type_index: *name_and_type_index,
+                    }
+                }
+                Entry::NameAndType(name_index, descriptor_index) => Constant::NameAndType {
+                    name_index: *name_index,
+                    descriptor_index: *descriptor_index,
+                },
+            });
+            if let Entry::Long(_) | Entry::Double(_) = entry {
+                constants.push(Constant::Placeholder);
+            }
+        }
+        constants
+    }
+}
+
+struct Field {
+    access_flags: u16,
+    name_index: u16,
+    descriptor_index: u16,
+}
+
+struct Method {
+    access_flags: u16,
+    name_index: u16,
+    descriptor_index: u16,
+    code: Option<Code>,
+}
+
+struct Code {
+    max_stack: u16,
+    max_locals: u16,
+    code: Vec<u8>,
+    exception_table: Vec<Exception>,
+}
+
+pub struct ClassBuilder {
+    constant_pool: ConstantPool,
+    major_version: u16,
+    minor_version: u16,
+    access_flags: u16,
+    this_class: u16,
+    name: String,
+    super_class: u16,
+    super_name: String,
+    interfaces: Vec<u16>,
+    fields: Vec<Field>,
+    methods: Vec<Method>,
+    error: Option<BuildError>,
+}
+
+impl ClassBuilder {
+    /// A public class extending `java.lang.Object`.
+    pub fn new(name: &str) -> Self {
+        let mut constant_pool = ConstantPool::default();
+        let this_class = constant_pool.class(name);
+        let super_class = constant_pool.class("java/lang/Object");
+        ClassBuilder {
+            constant_pool,
+            major_version: DEFAULT_MAJOR_VERSION,
+            minor_version: 0,
+            access_flags: ACC_PUBLIC | ACC_SUPER,
+            this_class,
+            name: name.to_string(),
+            super_class,
+            super_name: "java/lang/Object".to_string(),
+            interfaces: vec![],
+            fields: vec![],
+            methods: vec![],
+            error: None,
+        }
+    }
+
+    pub fn version(mut self, major_version: u16, minor_version: u16) -> Self {
+        self.major_version = major_version;
+        self.minor_version = minor_version;
+        self
+    }
+
+    pub fn access_flags(mut self, access_flags: u16) -> Self {
+        self.access_flags = access_flags;
+        self
+    }
+
+    pub fn super_class(mut self, name: &str) -> Self {
+        self.super_class = self.constant_pool.class(name);
+        self.super_name = name.to_string();
+        self
+    }
+
+    pub fn interface(mut self, name: &str) -> Self {
+        let interface = self.constant_pool.class(name);
+        self.interfaces.push(interface);
+        self
+    }
+
+    pub fn field(mut self, access_flags: u16, name: &str, descriptor: &str) -> Self {
+        if let Err(e) = FieldType::parse(descriptor) {
+            self.error.get_or_insert(BuildError::Descriptor(e));
+        }
+        let name_index = self.constant_pool.utf8(name);
+        let descriptor_index = self.constant_pool.utf8(descriptor);
+        self.fields.push(Field {
+            access_flags,
+            name_index,
+            descriptor_index,
+        });
+        self
+    }
+
+    /// A public instance method.
+    pub fn method(
+        self,
+        name: &str,
+        descriptor: &str,
+        code: impl FnOnce(&mut CodeBuilder) -> &mut CodeBuilder,
+    ) -> Self {
+        self.method_with_flags(ACC_PUBLIC, name, descriptor, code)
+    }
+
+    pub fn method_with_flags(
+        mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        code: impl FnOnce(&mut CodeBuilder) -> &mut CodeBuilder,
+    ) -> Self {
+        let parameter_slots = match MethodDescriptor::parse(descriptor) {
+            Ok(descriptor) => descriptor.parameter_slots(),
+            Err(e) => {
+                self.error.get_or_insert(BuildError::Descriptor(e));
+                return self;
+            }
+        };
+        let this_slots = if access_flags & ACC_STATIC == 0 { 1 } else { 0 };
+        let mut builder = CodeBuilder::new(
+            std::mem::take(&mut self.constant_pool),
+            parameter_slots + this_slots,
+        );
+        code(&mut builder);
+        let result = builder.finish();
+        self.constant_pool = builder.constant_pool;
+        match result {
+            Ok(code) => {
+                let name_index = self.constant_pool.utf8(name);
+                let descriptor_index = self.constant_pool.utf8(descriptor);
+                self.methods.push(Method {
+                    access_flags,
+                    name_index,
+                    descriptor_index,
+                    code: Some(code),
+                });
+            }
+            Err(message) => {
+                self.error.get_or_insert(BuildError::Code {
+                    method: format!("{}{}", name, descriptor),
+                    message,
+                });
+            }
+        }
+        self
+    }
+
+    /// An abstract or native method, which has no code.
+    pub fn abstract_method(mut self, access_flags: u16, name: &str, descriptor: &str) -> Self {
+        if let Err(e) = MethodDescriptor::parse(descriptor) {
+            self.error.get_or_insert(BuildError::Descriptor(e));
+        }
+        let name_index = self.constant_pool.utf8(name);
+        let descriptor_index = self.constant_pool.utf8(descriptor);
+        self.methods.push(Method {
+            access_flags,
+            name_index,
+            descriptor_index,
+            code: None,
+        });
+        self
+    }
+
+    /// A public constructor without parameters calling the superclass's.
+    pub fn default_constructor(self) -> Self {
+        let super_name = self.super_name.clone();
+        self.method("<init>", "()V", |code| {
+            code.aload(0)
+                .invokespecial(&super_name, "<init>", "()V")
+                .return_()
+        })
+    }
+
+    /// Writes the class file. From version 50 on, methods get a
+    /// `StackMapTable` in which two different classes other than this one and
+    /// its superclass merge to `java/lang/Object`.
+    pub fn build(self) -> Result<Vec<u8>, BuildError> {
+        self.build_with_hierarchy(&EmptyHierarchy)
+    }
+
+    /// Like `build`, merging classes through `hierarchy` when computing the
+    /// stack maps.
+    pub fn build_with_hierarchy(
+        mut self,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<Vec<u8>, BuildError> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let code_index = match self.methods.iter().any(|method| method.code.is_some()) {
+            true => self.constant_pool.utf8("Code"),
+            false => 0,
+        };
+        let stack_maps = match self.major_version {
+            0..=49 => vec![],
+            _ => self.stack_maps(hierarchy)?,
+        };
+        let stack_map_table_index = match stack_maps.iter().any(|entries| !entries.is_empty()) {
+            true => self.constant_pool.utf8("StackMapTable"),
+            false => 0,
+        };
+        if self.constant_pool.overflow {
+            return Err(BuildError::Write(Error::TooLarge("constants")));
+        }
+        write(&self.class_file(code_index, stack_map_table_index, &stack_maps))
+            .map_err(BuildError::Write)
+    }
+
+    fn stack_maps(
+        &mut self,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<Vec<Vec<StackMap>>, BuildError> {
+        let hierarchy = WithClass {
+            name: self.name.clone(),
+            super_name: self.super_name.clone(),
+            hierarchy,
+        };
+        let mut frames = vec![];
+        {
+            let class_file = self.class_file(0, 0, &[]);
+            for method in &class_file.methods {
+                if method.code_attribute().is_none() {
+                    frames.push(None);
+                    continue;
+                }
+                let computed = analyze(&class_file, method, &hierarchy).map_err(|e| {
+                    let cp = &class_file.constant_pool;
+                    let name = get_str(cp.clone(), method.name_index as usize).unwrap_or_default();
+                    let descriptor =
+                        get_str(cp.clone(), method.descriptor_index as usize).unwrap_or_default();
+                    BuildError::Code {
+                        method: format!("{}{}", name, descriptor),
+                        message: e.to_string(),
+                    }
+                })?;
+                frames.push(Some(computed));
+            }
+        }
+        let constant_pool = &mut self.constant_pool;
+        Ok(frames
+            .into_iter()
+            .map(|frames| match frames {
+                Some((initial, frames)) => {
+                    compress(&initial, &frames, |name| constant_pool.class(name))
+                }
+                None => vec![],
+            })
+            .collect())
+    }
+
+    fn class_file<'s>(
+        &'s self,
+        code_index: u16,
+        stack_map_table_index: u16,
+        stack_maps: &[Vec<StackMap>],
+    ) -> ClassFile<'s> {
+        let methods = self
+            .methods
+            .iter()
+            .enumerate()
+            .map(|(i, method)| MethodInfo {
+                access_flags: method.access_flags,
+                name_index: method.name_index,
+                descriptor_index: method.descriptor_index,
+                attributes: method
+                    .code
+                    .iter()
+                    .map(|code| Attribute {
+                        attribute_name_index: code_index,
+                        attribute_length: 0,
+                        attribute_type: AttributeType::Code {
+                            code: CodeAttribute {
+                                max_stack: code.max_stack,
+                                max_locals: code.max_locals,
+                                code: Cow::Borrowed(&code.code),
+                                exception_table: code.exception_table.clone(),
+                                attributes: match stack_maps.get(i) {
+                                    Some(entries) if !entries.is_empty() => vec![Attribute {
+                                        attribute_name_index: stack_map_table_index,
+                                        attribute_length: 0,
+                                        attribute_type: AttributeType::StackMapTable {
+                                            entries: entries.clone(),
+                                        },
+                                    }],
+                                    _ => vec![],
+                                },
+                            },
+                        },
+                    })
+                    .collect(),
+                code_attr_index: method.code.as_ref().map(|_| 0),
+            })
+            .collect();
+        ClassFile {
+            minor_version: self.minor_version,
+            major_version: self.major_version,
+            constant_pool: Arc::new(self.constant_pool.constants()),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces.clone(),
+            fields: self
+                .fields
+                .iter()
+                .map(|field| FieldInfo {
+                    access_flags: field.access_flags,
+                    name_index: field.name_index,
+                    descriptor_index: field.descriptor_index,
+                    attributes: vec![],
+                })
+                .collect(),
+            methods,
+            attributes: vec![],
+        }
+    }
+}
+
+// the class being built is not known to the hierarchy yet
+struct WithClass<'a> {
+    name: String,
+    super_name: String,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl ClassHierarchy for WithClass<'_> {
+    fn super_class(&self, class: &str) -> Option<String> {
+        match class == self.name {
+            true => Some(self.super_name.clone()),
+            false => self.hierarchy.super_class(class),
+        }
+    }
+
+    fn is_interface(&self, class: &str) -> bool {
+        class != self.name && self.hierarchy.is_interface(class)
+    }
+}
+
+/// A position in the code, which branches may refer to before it is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+struct Insn {
+    offset: usize,
+    pop: usize,
+    push: usize,
+    targets: Vec<Label>,
+    falls_through: bool,
+}
+
+struct Fixup {
+    // the instruction the offset is relative to
+    base: usize,
+    // where the offset is stored, with two or four bytes
+    at: usize,
+    wide: bool,
+    label: Label,
+}
+
+struct Handler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: u16,
+}
+
+/// Emits the code of one method. `max_stack` and `max_locals` are computed, and
+/// branch offsets are filled in once their labels are bound.
+///
+/// Mistakes such as an unbound label or a descriptor that does not parse are
+/// reported by `ClassBuilder::build`.
+pub struct CodeBuilder {
+    constant_pool: ConstantPool,
+    code: Vec<u8>,
+    insns: Vec<Insn>,
+    labels: Vec<Option<usize>>,
+    fixups: Vec<Fixup>,
+    handlers: Vec<Handler>,
+    max_locals: usize,
+    error: Option<String>,
+}
+
+macro_rules! simple_instructions {
+    ($($name:ident = $opcode:literal),* $(,)?) => {
+        $(
+            pub fn $name(&mut self) -> &mut Self {
+                self.op($opcode)
+            }
+        )*
+    };
+}
+
+macro_rules! branch_instructions {
+    ($($name:ident = $opcode:literal, $pop:literal),* $(,)?) => {
+        $(
+            pub fn $name(&mut self, label: Label) -> &mut Self {
+                self.branch($opcode, $pop, label)
+            }
+        )*
+    };
+}
+
+impl CodeBuilder {
+    fn new(constant_pool: ConstantPool, parameter_slots: usize) -> Self {
+        CodeBuilder {
+            constant_pool,
+            code: vec![],
+            insns: vec![],
+            labels: vec![],
+            fixups: vec![],
+            handlers: vec![],
+            max_locals: parameter_slots,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, message: String) -> &mut Self {
+        self.error.get_or_insert(message);
+        self
+    }
+
+    fn emit(&mut self, bytes: &[u8], pop: usize, push: usize) -> &mut Self {
+        self.insns.push(Insn {
+            offset: self.code.len(),
+            pop,
+            push,
+            targets: vec![],
+            falls_through: !matches!(bytes[0], 0xac..=0xb1 | 0xbf),
+        });
+        self.code.extend(bytes);
+        self
+    }
+
+    fn fixup(&mut self, base: usize, wide: bool, label: Label) {
+        self.fixups.push(Fixup {
+            base,
+            at: self.code.len(),
+            wide,
+            label,
+        });
+        self.code
+            .extend(if wide { &[0; 4][..] } else { &[0; 2][..] });
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Binds `label` to the next instruction.
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        match self.labels.get_mut(label.0) {
+            Some(offset @ None) => *offset = Some(self.code.len()),
+            _ => return self.fail(format!("label {} is bound twice", label.0)),
+        }
+        self
+    }
+
+    /// Instructions from `start` up to `end` are handled at `handler`, for
+    /// exceptions of class `catch_type`, or any exception if `None`.
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: Option<&str>,
+    ) -> &mut Self {
+        let catch_type = catch_type.map_or(0, |name| self.constant_pool.class(name));
+        self.handlers.push(Handler {
+            start,
+            end,
+            handler,
+            catch_type,
+        });
+        self
+    }
+
+    /// Any instruction without operands that does not access locals.
+    pub fn op(&mut self, opcode: u8) -> &mut Self {
+        match stack_effect(opcode) {
+            Some((pop, push)) => self.emit(&[opcode], pop, push),
+            None => self.fail(format!("opcode 0x{:02x} has operands", opcode)),
+        }
+    }
+
+    simple_instructions! {
+        nop = 0x00, aconst_null = 0x01,
+        iaload = 0x2e, laload = 0x2f, faload = 0x30, daload = 0x31, aaload = 0x32,
+        baload = 0x33, caload = 0x34, saload = 0x35,
+        iastore = 0x4f, lastore = 0x50, fastore = 0x51, dastore = 0x52, aastore = 0x53,
+        bastore = 0x54, castore = 0x55, sastore = 0x56,
+        pop = 0x57, pop2 = 0x58, dup = 0x59, dup_x1 = 0x5a, dup_x2 = 0x5b, dup2 = 0x5c,
+        dup2_x1 = 0x5d, dup2_x2 = 0x5e, swap = 0x5f,
+        iadd = 0x60, ladd = 0x61, fadd = 0x62, dadd = 0x63,
+        isub = 0x64, lsub = 0x65, fsub = 0x66, dsub = 0x67,
+        imul = 0x68, lmul = 0x69, fmul = 0x6a, dmul = 0x6b,
+        idiv = 0x6c, ldiv = 0x6d, fdiv = 0x6e, ddiv = 0x6f,
+        irem = 0x70, lrem = 0x71, frem = 0x72, drem = 0x73,
+        ineg = 0x74, lneg = 0x75, fneg = 0x76, dneg = 0x77,
+        ishl = 0x78, lshl = 0x79, ishr = 0x7a, lshr = 0x7b, iushr = 0x7c, lushr = 0x7d,
+        iand = 0x7e, land = 0x7f, ior = 0x80, lor = 0x81, ixor = 0x82, lxor = 0x83,
+        i2l = 0x85, i2f = 0x86, i2d = 0x87, l2i = 0x88, l2f = 0x89, l2d = 0x8a,
+        f2i = 0x8b, f2l = 0x8c, f2d = 0x8d, d2i = 0x8e, d2l = 0x8f, d2f = 0x90,
+        i2b = 0x91, i2c = 0x92, i2s = 0x93,
+        lcmp = 0x94, fcmpl = 0x95, fcmpg = 0x96, dcmpl = 0x97, dcmpg = 0x98,
+        ireturn = 0xac, lreturn = 0xad, freturn = 0xae, dreturn = 0xaf, areturn = 0xb0,
+        return_ = 0xb1,
+        arraylength = 0xbe, athrow = 0xbf, monitorenter = 0xc2, monitorexit = 0xc3,
+    }
+
+    pub fn iconst(&mut self, value: i32) -> &mut Self {
+        match value {
+            -1..=5 => self.emit(&[(0x03 + value) as u8], 0, 1),
+            -128..=127 => self.emit(&[0x10, value as u8], 0, 1),
+            -32768..=32767 => self.emit(&[0x11, (value >> 8) as u8, value as u8], 0, 1),
+            _ => {
+                let index = self.constant_pool.intern(Entry::Integer(value));
+                self.ldc(index)
+            }
+        }
+    }
+
+    pub fn lconst(&mut self, value: i64) -> &mut Self {
+        match value {
+            0 | 1 => self.emit(&[0x09 + value as u8], 0, 2),
+            _ => {
+                let index = self.constant_pool.intern(Entry::Long(value));
+                self.ldc2_w(index)
+            }
+        }
+    }
+
+    pub fn fconst(&mut self, value: f32) -> &mut Self {
+        // comparing bits keeps -0.0 in the constant pool
+        match value.to_bits() {
+            bits if bits == 0.0f32.to_bits() => self.emit(&[0x0b], 0, 1),
+            bits if bits == 1.0f32.to_bits() => self.emit(&[0x0c], 0, 1),
+            bits if bits == 2.0f32.to_bits() => self.emit(&[0x0d], 0, 1),
+            bits => {
+                let index = self.constant_pool.intern(Entry::Float(bits));
+                self.ldc(index)
+            }
+        }
+    }
+
+    pub fn dconst(&mut self, value: f64) -> &mut Self {
+        match value.to_bits() {
+            bits if bits == 0.0f64.to_bits() => self.emit(&[0x0e], 0, 2),
+            bits if bits == 1.0f64.to_bits() => self.emit(&[0x0f], 0, 2),
+            bits => {
+                let index = self.constant_pool.intern(Entry::Double(bits));
+                self.ldc2_w(index)
+            }
+        }
+    }
+
+    pub fn ldc_string(&mut self, value: &str) -> &mut Self {
+        let index = self.constant_pool.string(value);
+        self.ldc(index)
+    }
+
+    pub fn ldc_class(&mut self, name: &str) -> &mut Self {
+        let index = self.constant_pool.class(name);
+        self.ldc(index)
+    }
+
+    fn ldc(&mut self, index: u16) -> &mut Self {
+        match u8::try_from(index) {
+            Ok(index) => self.emit(&[0x12, index], 0, 1),
+            Err(_) => self.emit(&[0x13, (index >> 8) as u8, index as u8], 0, 1),
+        }
+    }
+
+    fn ldc2_w(&mut self, index: u16) -> &mut Self {
+        self.emit(&[0x14, (index >> 8) as u8, index as u8], 0, 2)
+    }
+
+    fn local(&mut self, opcode: u8, short: u8, index: u16, size: usize, load: bool) -> &mut Self {
+        let (pop, push) = if load { (0, size) } else { (size, 0) };
+        self.max_locals = self.max_locals.max(index as usize + size);
+        match index {
+            0..=3 => self.emit(&[short + index as u8], pop, push),
+            4..=255 => self.emit(&[opcode, index as u8], pop, push),
+            _ => self.emit(&[0xc4, opcode, (index >> 8) as u8, index as u8], pop, push),
+        }
+    }
+
+    pub fn iload(&mut self, index: u16) -> &mut Self {
+        self.local(0x15, 0x1a, index, 1, true)
+    }
+
+    pub fn lload(&mut self, index: u16) -> &mut Self {
+        self.local(0x16, 0x1e, index, 2, true)
+    }
+
+    pub fn fload(&mut self, index: u16) -> &mut Self {
+        self.local(0x17, 0x22, index, 1, true)
+    }
+
+    pub fn dload(&mut self, index: u16) -> &mut Self {
+        self.local(0x18, 0x26, index, 2, true)
+    }
+
+    pub fn aload(&mut self, index: u16) -> &mut Self {
+        self.local(0x19, 0x2a, index, 1, true)
+    }
+
+    pub fn istore(&mut self, index: u16) -> &mut Self {
+        self.local(0x36, 0x3b, index, 1, false)
+    }
+
+    pub fn lstore(&mut self, index: u16) -> &mut Self {
+        self.local(0x37, 0x3f, index, 2, false)
+    }
+
+    pub fn fstore(&mut self, index: u16) -> &mut Self {
+        self.local(0x38, 0x43, index, 1, false)
+    }
+
+    pub fn dstore(&mut self, index: u16) -> &mut Self {
+        self.local(0x39, 0x47, index, 2, false)
+    }
+
+    pub fn astore(&mut self, index: u16) -> &mut Self {
+        self.local(0x3a, 0x4b, index, 1, false)
+    }
+
+    pub fn iinc(&mut self, index: u16, delta: i16) -> &mut Self {
+        self.max_locals = self.max_locals.max(index as usize + 1);
+        match (u8::try_from(index), i8::try_from(delta)) {
+            (Ok(index), Ok(delta)) => self.emit(&[0x84, index, delta as u8], 0, 0),
+            _ => {
+                let [index_high, index_low] = index.to_be_bytes();
+                let [delta_high, delta_low] = delta.to_be_bytes();
+                self.emit(
+                    &[0xc4, 0x84, index_high, index_low, delta_high, delta_low],
+                    0,
+                    0,
+                )
+            }
+        }
+    }
+
+    fn field_insn(&mut self, opcode: u8, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let size = match FieldType::parse(descriptor) {
+            Ok(field_type) => field_type.slot_size(),
+            Err(e) => return self.fail(e.to_string()),
+        };
+        let (class_index, name_and_type_index) = self.constant_pool.member(owner, name, descriptor);
+        let [high, low] = self
+            .constant_pool
+            .intern(Entry::FieldRef(class_index, name_and_type_index))
+            .to_be_bytes();
+        let (pop, push) = match opcode {
+            0xb2 => (0, size),
+            0xb3 => (size, 0),
+            0xb4 => (1, size),
+            _ => (1 + size, 0),
+        };
+        self.emit(&[opcode, high, low], pop, push)
+    }
+
+    pub fn getstatic(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field_insn(0xb2, owner, name, descriptor)
+    }
+
+    pub fn putstatic(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field_insn(0xb3, owner, name, descriptor)
+    }
+
+    pub fn getfield(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field_insn(0xb4, owner, name, descriptor)
+    }
+
+    pub fn putfield(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field_insn(0xb5, owner, name, descriptor)
+    }
+
+    fn invoke(&mut self, opcode: u8, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let parsed = match MethodDescriptor::parse(descriptor) {
+            Ok(parsed) => parsed,
+            Err(e) => return self.fail(e.to_string()),
+        };
+        let (class_index, name_and_type_index) = self.constant_pool.member(owner, name, descriptor);
+        let entry = match opcode {
+            0xb9 => Entry::InterfaceMethodRef(class_index, name_and_type_index),
+            _ => Entry::MethodRef(class_index, name_and_type_index),
+        };
+        let [high, low] = self.constant_pool.intern(entry).to_be_bytes();
+        let receiver = if opcode == 0xb8 { 0 } else { 1 };
+        let pop = parsed.parameter_slots() + receiver;
+        let push = parsed.return_slots();
+        match opcode {
+            0xb9 => self.emit(&[opcode, high, low, pop as u8, 0], pop, push),
+            _ => self.emit(&[opcode, high, low], pop, push),
+        }
+    }
+
+    pub fn invokevirtual(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.invoke(0xb6, owner, name, descriptor)
+    }
+
+    pub fn invokespecial(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.invoke(0xb7, owner, name, descriptor)
+    }
+
+    pub fn invokestatic(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.invoke(0xb8, owner, name, descriptor)
+    }
+
+    pub fn invokeinterface(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.invoke(0xb9, owner, name, descriptor)
+    }
+
+    fn class_insn(&mut self, opcode: u8, name: &str, pop: usize) -> &mut Self {
+        let [high, low] = self.constant_pool.class(name).to_be_bytes();
+        self.emit(&[opcode, high, low], pop, 1)
+    }
+
+    pub fn new_instance(&mut self, class: &str) -> &mut Self {
+        self.class_insn(0xbb, class, 0)
+    }
+
+    pub fn anewarray(&mut self, component: &str) -> &mut Self {
+        self.class_insn(0xbd, component, 1)
+    }
+
+    pub fn checkcast(&mut self, class: &str) -> &mut Self {
+        self.class_insn(0xc0, class, 1)
+    }
+
+    pub fn instanceof(&mut self, class: &str) -> &mut Self {
+        self.class_insn(0xc1, class, 1)
+    }
+
+    /// `atype` is one of the codes of JVMS 6.5 newarray, e.g. 10 for `int`.
+    pub fn newarray(&mut self, atype: u8) -> &mut Self {
+        self.emit(&[0xbc, atype], 1, 1)
+    }
+
+    pub fn multianewarray(&mut self, class: &str, dimensions: u8) -> &mut Self {
+        let [high, low] = self.constant_pool.class(class).to_be_bytes();
+        self.emit(&[0xc5, high, low, dimensions], dimensions as usize, 1)
+    }
+
+    fn branch(&mut self, opcode: u8, pop: usize, label: Label) -> &mut Self {
+        let offset = self.code.len();
+        self.insns.push(Insn {
+            offset,
+            pop,
+            push: 0,
+            targets: vec![label],
+            falls_through: opcode != 0xa7,
+        });
+        self.code.push(opcode);
+        self.fixup(offset, false, label);
+        self
+    }
+
+    branch_instructions! {
+        ifeq = 0x99, 1, ifne = 0x9a, 1, iflt = 0x9b, 1, ifge = 0x9c, 1, ifgt = 0x9d, 1,
+        ifle = 0x9e, 1,
+        if_icmpeq = 0x9f, 2, if_icmpne = 0xa0, 2, if_icmplt = 0xa1, 2, if_icmpge = 0xa2, 2,
+        if_icmpgt = 0xa3, 2, if_icmple = 0xa4, 2, if_acmpeq = 0xa5, 2, if_acmpne = 0xa6, 2,
+        goto = 0xa7, 0,
+        ifnull = 0xc6, 1, ifnonnull = 0xc7, 1,
+    }
+
+    fn switch(&mut self, opcode: u8, default: Label, targets: Vec<Label>) -> usize {
+        let offset = self.code.len();
+        self.insns.push(Insn {
+            offset,
+            pop: 1,
+            push: 0,
+            targets: [vec![default], targets].concat(),
+            falls_through: false,
+        });
+        self.code.push(opcode);
+        while !self.code.len().is_multiple_of(4) {
+            self.code.push(0);
+        }
+        self.fixup(offset, true, default);
+        offset
+    }
+
+    /// Jumps to `targets[i]` for the value `low + i`, otherwise to `default`.
+    pub fn tableswitch(&mut self, low: i32, default: Label, targets: &[Label]) -> &mut Self {
+        let high = match i32::try_from(targets.len() as i64 + low as i64 - 1) {
+            Ok(high) if !targets.is_empty() => high,
+            _ => return self.fail("tableswitch needs between 1 and 2^31 targets".to_string()),
+        };
+        let offset = self.switch(0xaa, default, targets.to_vec());
+        self.code.extend(low.to_be_bytes());
+        self.code.extend(high.to_be_bytes());
+        for target in targets {
+            self.fixup(offset, true, *target);
+        }
+        self
+    }
+
+    pub fn lookupswitch(&mut self, default: Label, pairs: &[(i32, Label)]) -> &mut Self {
+        let mut pairs = pairs.to_vec();
+        pairs.sort_by_key(|(key, _)| *key);
+        if pairs.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return self.fail("lookupswitch has duplicate keys".to_string());
+        }
+        let targets = pairs.iter().map(|(_, target)| *target).collect();
+        let offset = self.switch(0xab, default, targets);
+        self.code.extend((pairs.len() as u32).to_be_bytes());
+        for (key, target) in pairs {
+            self.code.extend(key.to_be_bytes());
+            self.fixup(offset, true, target);
+        }
+        self
+    }
+
+    fn label_offset(&self, label: Label) -> Result<usize, String> {
+        match self.labels.get(label.0) {
+            Some(Some(offset)) => Ok(*offset),
+            _ => Err(format!("label {} is not bound", label.0)),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Code, String> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        for fixup in &self.fixups {
+            let target = self.label_offset(fixup.label)? as i64;
+            let offset = target - fixup.base as i64;
+            if fixup.wide {
+                self.code[fixup.at..fixup.at + 4].copy_from_slice(&(offset as i32).to_be_bytes());
+            } else {
+                let offset = i16::try_from(offset)
+                    .map_err(|_| format!("branch at {} is too far", fixup.base))?;
+                self.code[fixup.at..fixup.at + 2].copy_from_slice(&offset.to_be_bytes());
+            }
+        }
+        if self.code.len() > u16::MAX as usize {
+            return Err(format!("code is {} bytes long", self.code.len()));
+        }
+        let mut exception_table = vec![];
+        for handler in &self.handlers {
+            let start_pc = self.label_offset(handler.start)?;
+            let end_pc = self.label_offset(handler.end)?;
+            if start_pc >= end_pc {
+                return Err(format!("exception range {}..{} is empty", start_pc, end_pc));
+            }
+            exception_table.push(Exception {
+                start_pc: start_pc as u16,
+                end_pc: end_pc as u16,
+                handler_pc: self.label_offset(handler.handler)? as u16,
+                catch_type: handler.catch_type,
+            });
+        }
+        let max_stack = self.max_stack(&exception_table)?;
+        let max_locals = u16::try_from(self.max_locals)
+            .map_err(|_| format!("{} locals are too many", self.max_locals))?;
+        Ok(Code {
+            max_stack,
+            max_locals,
+            code: std::mem::take(&mut self.code),
+            exception_table,
+        })
+    }
+
+    // the deepest the operand stack gets, following every path from the start
+    // and from each handler
+    fn max_stack(&self, exception_table: &[Exception]) -> Result<u16, String> {
+        let mut index_of = vec![None; self.code.len() + 1];
+        for (index, insn) in self.insns.iter().enumerate() {
+            index_of[insn.offset] = Some(index);
+        }
+        let index_at =
+            |offset: usize| index_of[offset].ok_or_else(|| format!("no instruction at {}", offset));
+        let mut depths: Vec<Option<usize>> = vec![None; self.insns.len()];
+        let mut worklist = vec![];
+        if !self.insns.is_empty() {
+            worklist.push((0, 0));
+        }
+        for handler in exception_table {
+            worklist.push((index_at(handler.handler_pc as usize)?, 1));
+        }
+        let mut max_stack = 0;
+        while let Some((index, depth)) = worklist.pop() {
+            let insn = &self.insns[index];
+            match depths[index] {
+                Some(current) if current == depth => continue,
+                Some(current) => {
+                    return Err(format!(
+                        "stack depth at {} is both {} and {}",
+                        insn.offset, current, depth
+                    ))
+                }
+                None => depths[index] = Some(depth),
+            }
+            if insn.pop > depth {
+                return Err(format!("stack underflow at {}", insn.offset));
+            }
+            let next = depth - insn.pop + insn.push;
+            max_stack = max_stack.max(depth).max(next);
+            for target in &insn.targets {
+                worklist.push((index_at(self.label_offset(*target)?)?, next));
+            }
+            if insn.falls_through {
+                match self.insns.get(index + 1) {
+                    Some(_) => worklist.push((index + 1, next)),
+                    None => return Err("falling off the end of the code".to_string()),
+                }
+            }
+        }
+        u16::try_from(max_stack).map_err(|_| format!("stack depth {} is too deep", max_stack))
+    }
+}
+
+// the operand stack slots popped and pushed by instructions without operands
+// that do not access locals
+fn stack_effect(opcode: u8) -> Option<(usize, usize)> {
+    Some(match opcode {
+        0x00 => (0, 0),
+        0x01..=0x08 | 0x0b..=0x0d => (0, 1),
+        0x09 | 0x0a | 0x0e | 0x0f => (0, 2),
+        0x2e | 0x30 | 0x32..=0x35 => (2, 1),
+        0x2f | 0x31 => (2, 2),
+        0x4f | 0x51 | 0x53..=0x56 => (3, 0),
+        0x50 | 0x52 => (4, 0),
+        0x57 => (1, 0),
+        0x58 => (2, 0),
+        0x59 => (1, 2),
+        0x5a => (2, 3),
+        0x5b => (3, 4),
+        0x5c => (2, 4),
+        0x5d => (3, 5),
+        0x5e => (4, 6),
+        0x5f => (2, 2),
+        // add, sub, mul, div and rem for int, long, float and double
+        0x60..=0x73 if opcode.is_multiple_of(2) => (2, 1),
+        0x60..=0x73 => (4, 2),
+        0x74 | 0x76 => (1, 1),
+        0x75 | 0x77 => (2, 2),
+        0x78 | 0x7a | 0x7c => (2, 1),
+        0x79 | 0x7b | 0x7d => (3, 2),
+        0x7e | 0x80 | 0x82 => (2, 1),
+        0x7f | 0x81 | 0x83 => (4, 2),
+        0x86 | 0x8b | 0x91..=0x93 => (1, 1),
+        0x85 | 0x87 | 0x8c | 0x8d => (1, 2),
+        0x88 | 0x89 | 0x8e | 0x90 => (2, 1),
+        0x8a | 0x8f => (2, 2),
+        0x94 | 0x97 | 0x98 => (4, 1),
+        0x95 | 0x96 => (2, 1),
+        0xac | 0xae | 0xb0 => (1, 0),
+        0xad | 0xaf => (2, 0),
+        0xb1 => (0, 0),
+        0xbe => (1, 1),
+        0xbf | 0xc2 | 0xc3 => (1, 0),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{BuildError, ClassBuilder, ACC_STATIC};
+    use crate::{get_str, parse, Constant};
+
+    // the code, max_stack and max_locals of the last method
+    fn code(builder: ClassBuilder) -> (Vec<u8>, u16, u16) {
+        let bytes = builder.build().unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let code = class_file.methods.last().unwrap().code_attribute().unwrap();
+        (code.code.to_vec(), code.max_stack, code.max_locals)
+    }
+
+    #[test]
+    fn test_build() {
+        let bytes = ClassBuilder::new("com/acme/Gen")
+            .default_constructor()
+            .field(0, "count", "J")
+            .method("run", "()I", |code| code.iconst(3).ireturn())
+            .build()
+            .unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let cp = &class_file.constant_pool;
+        assert_eq!(class_file.major_version, 49);
+        assert_eq!(
+            get_str(cp.clone(), class_file.this_class as usize).unwrap(),
+            "com/acme/Gen"
+        );
+        assert_eq!(
+            get_str(cp.clone(), class_file.super_class as usize).unwrap(),
+            "java/lang/Object"
+        );
+        assert_eq!(class_file.fields.len(), 1);
+        let names: Vec<_> = class_file
+            .methods
+            .iter()
+            .map(|method| get_str(cp.clone(), method.name_index as usize).unwrap())
+            .collect();
+        assert_eq!(names, ["<init>", "run"]);
+        let code = class_file.methods[1].code_attribute().unwrap();
+        assert_eq!(code.code[..], [0x06, 0xac]);
+        assert_eq!((code.max_stack, code.max_locals), (1, 1));
+    }
+
+    #[test]
+    fn test_interning() {
+        let bytes = ClassBuilder::new("A")
+            .method_with_flags(ACC_STATIC, "m", "()V", |code| {
+                code.ldc_string("x")
+                    .ldc_string("x")
+                    .iconst(100000)
+                    .iconst(100000)
+                    .lconst(7)
+                    .lconst(7)
+                    .getstatic("A", "f", "I")
+                    .getstatic("A", "f", "I")
+                    .pop2()
+                    .pop2()
+                    .pop2()
+                    .pop2()
+                    .pop()
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let cp = &class_file.constant_pool;
+        let count = |f: fn(&Constant) -> bool| cp.iter().filter(|c| f(c)).count();
+        assert_eq!(count(|c| matches!(c, Constant::String { .. })), 1);
+        assert_eq!(count(|c| matches!(c, Constant::Integer(100000))), 1);
+        assert_eq!(count(|c| matches!(c, Constant::Long(7))), 1);
+        assert_eq!(count(|c| matches!(c, Constant::FieldRef { .. })), 1);
+        // "A" is both the class name and the field owner
+        assert_eq!(
+            count(|c| matches!(c, Constant::Utf8(bytes) if bytes.as_ref() == b"A")),
+            1
+        );
+
+        let code = class_file.methods[0].code_attribute().unwrap();
+        assert_eq!(code.code[0], 0x12);
+        assert_eq!(code.code[0..2], code.code[2..4]);
+        // ldc2_w of the long
+        assert_eq!(code.code[8], 0x14);
+        assert_eq!(code.max_stack, 10);
+    }
+
+    #[test]
+    fn test_constants_and_locals() {
+        let (code, max_stack, max_locals) =
+            code(
+                ClassBuilder::new("A").method_with_flags(ACC_STATIC, "m", "(IJ)V", |code| {
+                    code.iconst(-1)
+                        .iconst(-2)
+                        .iconst(1000)
+                        .pop2()
+                        .pop()
+                        .iload(0)
+                        .lload(1)
+                        .dconst(1.0)
+                        .dstore(4)
+                        .pop2()
+                        .istore(300)
+                        .iinc(3, -1)
+                        .iinc(3, 1000)
+                        .return_()
+                }),
+            );
+        assert_eq!(
+            code,
+            [
+                0x02, 0x10, 0xfe, 0x11, 0x03, 0xe8, 0x58, 0x57, 0x1a, 0x1f, 0x0f, 0x39, 0x04, 0x58,
+                0xc4, 0x36, 0x01, 0x2c, 0x84, 0x03, 0xff, 0xc4, 0x84, 0x00, 0x03, 0x03, 0xe8, 0xb1
+            ]
+        );
+        assert_eq!(max_stack, 5);
+        assert_eq!(max_locals, 301);
+    }
+
+    #[test]
+    fn test_labels() {
+        // sum of 1..=n: a forward branch to the test and a backward one to the body
+        let (code, max_stack, max_locals) =
+            code(
+                ClassBuilder::new("A").method_with_flags(ACC_STATIC, "sum", "(I)I", |code| {
+                    let body = code.new_label();
+                    let test = code.new_label();
+                    code.iconst(0)
+                        .istore(1)
+                        .goto(test)
+                        .bind(body)
+                        .iload(1)
+                        .iload(0)
+                        .iadd()
+                        .istore(1)
+                        .iinc(0, -1)
+                        .bind(test)
+                        .iload(0)
+                        .ifgt(body)
+                        .iload(1)
+                        .ireturn()
+                }),
+            );
+        assert_eq!(
+            code,
+            [
+                0x03, 0x3c, 0xa7, 0x00, 0x0a, 0x1b, 0x1a, 0x60, 0x3c, 0x84, 0x00, 0xff, 0x1a, 0x9d,
+                0xff, 0xf8, 0x1b, 0xac
+            ]
+        );
+        assert_eq!((max_stack, max_locals), (2, 2));
+    }
+
+    #[test]
+    fn test_switch() {
+        let (code, max_stack, _) =
+            code(
+                ClassBuilder::new("A").method_with_flags(ACC_STATIC, "m", "(I)I", |code| {
+                    let one = code.new_label();
+                    let two = code.new_label();
+                    let default = code.new_label();
+                    code.iload(0)
+                        .tableswitch(1, default, &[one, two])
+                        .bind(one)
+                        .iconst(10)
+                        .ireturn()
+                        .bind(two)
+                        .iload(0)
+                        .lookupswitch(default, &[(7, one), (-3, two)])
+                        .bind(default)
+                        .iconst(0)
+                        .ireturn()
+                }),
+            );
+        // tableswitch at 1 is padded to 4, its targets are at 24 and 27
+        assert_eq!(code[1..4], [0xaa, 0, 0]);
+        assert_eq!(code[4..8], 55i32.to_be_bytes());
+        assert_eq!(code[8..16], [0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!(code[16..24], [0, 0, 0, 23, 0, 0, 0, 26]);
+        // lookupswitch at 28 is padded to 32, its keys are sorted
+        assert_eq!(code[27..32], [0x1a, 0xab, 0, 0, 0]);
+        assert_eq!(code[32..36], 28i32.to_be_bytes());
+        assert_eq!(code[36..40], 2i32.to_be_bytes());
+        assert_eq!(code[40..44], (-3i32).to_be_bytes());
+        assert_eq!(code[44..48], (-1i32).to_be_bytes());
+        assert_eq!(code[48..52], 7i32.to_be_bytes());
+        assert_eq!(code[52..56], (-4i32).to_be_bytes());
+        assert_eq!(code[56..], [0x03, 0xac]);
+        assert_eq!(max_stack, 1);
+    }
+
+    #[test]
+    fn test_exception_table() {
+        let bytes = ClassBuilder::new("A")
+            .method_with_flags(ACC_STATIC, "m", "()I", |code| {
+                let start = code.new_label();
+                let end = code.new_label();
+                let handler = code.new_label();
+                let finally = code.new_label();
+                code.bind(start)
+                    .iconst(1)
+                    .iconst(0)
+                    .idiv()
+                    .bind(end)
+                    .ireturn()
+                    .bind(handler)
+                    .pop()
+                    .iconst(-1)
+                    .ireturn()
+                    .bind(finally)
+                    .athrow()
+                    .try_catch(start, end, handler, Some("java/lang/ArithmeticException"))
+                    .try_catch(start, end, finally, None)
+            })
+            .build()
+            .unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let code = class_file.methods[0].code_attribute().unwrap();
+        let table: Vec<_> = code
+            .exception_table
+            .iter()
+            .map(|e| (e.start_pc, e.end_pc, e.handler_pc))
+            .collect();
+        assert_eq!(table, [(0, 3, 4), (0, 3, 7)]);
+        let catch_type = code.exception_table[0].catch_type;
+        assert_eq!(
+            get_str(class_file.constant_pool.clone(), catch_type as usize).unwrap(),
+            "java/lang/ArithmeticException"
+        );
+        assert_eq!(code.exception_table[1].catch_type, 0);
+        assert_eq!(code.max_stack, 2);
+    }
+
+    #[test]
+    fn test_invoke() {
+        let (code, max_stack, max_locals) =
+            code(
+                ClassBuilder::new("A").method("m", "(Ljava/util/List;)J", |code| {
+                    code.aload(1)
+                        .iconst(0)
+                        .invokeinterface("java/util/List", "get", "(I)Ljava/lang/Object;")
+                        .checkcast("java/lang/Long")
+                        .invokevirtual("java/lang/Long", "longValue", "()J")
+                        .lreturn()
+                }),
+            );
+        assert_eq!(code[2], 0xb9);
+        // the count byte includes the receiver
+        assert_eq!(code[5..7], [2, 0]);
+        assert_eq!((max_stack, max_locals), (2, 2));
+    }
+
+    fn build_error(
+        code: impl FnOnce(&mut super::CodeBuilder) -> &mut super::CodeBuilder,
+    ) -> String {
+        match ClassBuilder::new("A").method("m", "()V", code).build() {
+            Err(BuildError::Code { method, message }) => {
+                assert_eq!(method, "m()V");
+                message
+            }
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            build_error(|code| code.pop().return_()),
+            "stack underflow at 0"
+        );
+        assert_eq!(
+            build_error(|code| code.iconst(0)),
+            "falling off the end of the code"
+        );
+        let message = build_error(|code| {
+            let label = code.new_label();
+            code.goto(label)
+        });
+        assert_eq!(message, "label 0 is not bound");
+        let message = build_error(|code| {
+            let label = code.new_label();
+            code.bind(label).bind(label).return_()
+        });
+        assert_eq!(message, "label 0 is bound twice");
+        // the stack holds one int or none at the join
+        let message = build_error(|code| {
+            let join = code.new_label();
+            code.iconst(0)
+                .iconst(0)
+                .ifeq(join)
+                .pop()
+                .bind(join)
+                .return_()
+        });
+        assert!(
+            message.starts_with("stack depth at 6 is both"),
+            "{}",
+            message
+        );
+        let message = build_error(|code| {
+            let far = code.new_label();
+            code.goto(far);
+            for _ in 0..40000 {
+                code.nop();
+            }
+            code.bind(far).return_()
+        });
+        assert_eq!(message, "branch at 0 is too far");
+        assert_eq!(
+            build_error(|code| code.getstatic("A", "f", "Q").return_()),
+            "expected a field type, found 'Q' at position 0 in \"Q\""
+        );
+        assert_eq!(
+            build_error(|code| code.op(0x10)),
+            "opcode 0x10 has operands"
+        );
+
+        assert!(matches!(
+            ClassBuilder::new("A")
+                .method("m", "(", |code| code.return_())
+                .build(),
+            Err(BuildError::Descriptor(_))
+        ));
+    }
+}
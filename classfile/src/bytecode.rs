@@ -0,0 +1,914 @@
+//! Decoding of the `code` array of a `Code` attribute, JVMS 6.5, and its
+//! disassembly in the format of `javap -c`.
+
+use crate::errors::{Error, ParseError};
+use crate::{mutf8, ClassFile, Constant};
+
+pub const WIDE: u8 = 0xc4;
+
+#[rustfmt::skip]
+const MNEMONICS: [&str; 202] = [
+    "nop", "aconst_null", "iconst_m1", "iconst_0", "iconst_1", "iconst_2", "iconst_3", "iconst_4",
+    "iconst_5", "lconst_0", "lconst_1", "fconst_0", "fconst_1", "fconst_2", "dconst_0", "dconst_1",
+    "bipush", "sipush", "ldc", "ldc_w", "ldc2_w", "iload", "lload", "fload",
+    "dload", "aload", "iload_0", "iload_1", "iload_2", "iload_3", "lload_0", "lload_1",
+    "lload_2", "lload_3", "fload_0", "fload_1", "fload_2", "fload_3", "dload_0", "dload_1",
+    "dload_2", "dload_3", "aload_0", "aload_1", "aload_2", "aload_3", "iaload", "laload",
+    "faload", "daload", "aaload", "baload", "caload", "saload", "istore", "lstore",
+    "fstore", "dstore", "astore", "istore_0", "istore_1", "istore_2", "istore_3", "lstore_0",
+    "lstore_1", "lstore_2", "lstore_3", "fstore_0", "fstore_1", "fstore_2", "fstore_3", "dstore_0",
+    "dstore_1", "dstore_2", "dstore_3", "astore_0", "astore_1", "astore_2", "astore_3", "iastore",
+    "lastore", "fastore", "dastore", "aastore", "bastore", "castore", "sastore", "pop",
+    "pop2", "dup", "dup_x1", "dup_x2", "dup2", "dup2_x1", "dup2_x2", "swap",
+    "iadd", "ladd", "fadd", "dadd", "isub", "lsub", "fsub", "dsub",
+    "imul", "lmul", "fmul", "dmul", "idiv", "ldiv", "fdiv", "ddiv",
+    "irem", "lrem", "frem", "drem", "ineg", "lneg", "fneg", "dneg",
+    "ishl", "lshl", "ishr", "lshr", "iushr", "lushr", "iand", "land",
+    "ior", "lor", "ixor", "lxor", "iinc", "i2l", "i2f", "i2d",
+    "l2i", "l2f", "l2d", "f2i", "f2l", "f2d", "d2i", "d2l",
+    "d2f", "i2b", "i2c", "i2s", "lcmp", "fcmpl", "fcmpg", "dcmpl",
+    "dcmpg", "ifeq", "ifne", "iflt", "ifge", "ifgt", "ifle", "if_icmpeq",
+    "if_icmpne", "if_icmplt", "if_icmpge", "if_icmpgt", "if_icmple", "if_acmpeq", "if_acmpne", "goto",
+    "jsr", "ret", "tableswitch", "lookupswitch", "ireturn", "lreturn", "freturn", "dreturn",
+    "areturn", "return", "getstatic", "putstatic", "getfield", "putfield", "invokevirtual", "invokespecial",
+    "invokestatic", "invokeinterface", "invokedynamic", "new", "newarray", "anewarray", "arraylength", "athrow",
+    "checkcast", "instanceof", "monitorenter", "monitorexit", "wide", "multianewarray", "ifnull", "ifnonnull",
+    "goto_w", "jsr_w",
+];
+
+/// The name of an opcode, `None` for those that may not appear in class files.
+pub fn mnemonic(opcode: u8) -> Option<&'static str> {
+    MNEMONICS.get(opcode as usize).copied()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// A local variable index, implicit for the `xload_n`/`xstore_n` forms.
+    Local(u16),
+    Iinc {
+        index: u16,
+        value: i16,
+    },
+    /// The value pushed by `bipush` or `sipush`.
+    Int(i32),
+    /// A constant pool index.
+    Constant(u16),
+    InvokeInterface {
+        index: u16,
+        count: u8,
+    },
+    /// The absolute offset a branch jumps to.
+    Branch(usize),
+    TableSwitch {
+        default: usize,
+        low: i32,
+        targets: Vec<usize>,
+    },
+    LookupSwitch {
+        default: usize,
+        pairs: Vec<(i32, usize)>,
+    },
+    NewArray(u8),
+    MultiANewArray {
+        index: u16,
+        dimensions: u8,
+    },
+}
+
+/// A decoded instruction. The opcode of a `wide` instruction is the one it
+/// modifies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub offset: usize,
+    pub length: usize,
+    pub opcode: u8,
+    pub wide: bool,
+    pub operand: Operand,
+}
+
+impl Instruction {
+    /// The name `javap` shows, which for a `wide` instruction is that of the
+    /// modified one with `_w` appended.
+    pub fn mnemonic(&self) -> String {
+        let name = mnemonic(self.opcode).unwrap_or("unknown");
+        match self.wide {
+            true => format!("{}_w", name),
+            false => name.to_string(),
+        }
+    }
+
+    /// The offsets this instruction may jump to, other than the next one.
+    pub fn branch_targets(&self) -> Vec<usize> {
+        match &self.operand {
+            Operand::Branch(target) => vec![*target],
+            Operand::TableSwitch {
+                default, targets, ..
+            } => [&[*default][..], targets].concat(),
+            Operand::LookupSwitch { default, pairs } => [*default]
+                .into_iter()
+                .chain(pairs.iter().map(|(_, target)| *target))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether execution may continue with the next instruction.
+    pub fn falls_through(&self) -> bool {
+        // goto, jsr, ret, the switches, returns, athrow, goto_w and jsr_w
+        !matches!(self.opcode, 0xa7..=0xb1 | 0xbf | 0xc8 | 0xc9)
+    }
+}
+
+/// Decodes a whole `code` array.
+pub fn decode_all(code: &[u8]) -> Result<Vec<Instruction>, ParseError> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+    while offset < code.len() {
+        let instruction = decode(code, offset)?;
+        offset += instruction.length;
+        instructions.push(instruction);
+    }
+    Ok(instructions)
+}
+
+/// Decodes the instruction at `offset`.
+pub fn decode(code: &[u8], offset: usize) -> Result<Instruction, ParseError> {
+    let mut reader = Reader { code, pos: offset };
+    decode_operands(&mut reader, offset).map_err(|kind| ParseError {
+        offset,
+        context: vec![],
+        kind,
+    })
+}
+
+fn decode_operands(reader: &mut Reader, offset: usize) -> Result<Instruction, Error> {
+    let opcode = reader.u8()?;
+    let code_length = reader.code.len();
+    let branch = |delta: i64| {
+        let target = offset as i64 + delta;
+        match target >= 0 && target < code_length as i64 {
+            true => Ok(target as usize),
+            false => Err(Error::InvalidBranchTarget(target)),
+        }
+    };
+    let operand = match opcode {
+        // 0xca breakpoint and the implementation-dependent opcodes may not
+        // appear in class files
+        0xca.. => return Err(Error::InvalidOpcode(opcode)),
+        0x10 => Operand::Int(reader.u8()? as i8 as i32),
+        0x11 => Operand::Int(reader.u16()? as i16 as i32),
+        0x12 => Operand::Constant(reader.u8()? as u16),
+        0x13 | 0x14 | 0xb2..=0xb8 | 0xbb | 0xbd | 0xc0 | 0xc1 => Operand::Constant(reader.u16()?),
+        0x15..=0x19 | 0x36..=0x3a | 0xa9 => Operand::Local(reader.u8()? as u16),
+        // xload_n and xstore_n come in groups of four per type
+        0x1a..=0x2d => Operand::Local(((opcode - 0x1a) % 4) as u16),
+        0x3b..=0x4e => Operand::Local(((opcode - 0x3b) % 4) as u16),
+        0x84 => Operand::Iinc {
+            index: reader.u8()? as u16,
+            value: reader.u8()? as i8 as i16,
+        },
+        0x99..=0xa8 | 0xc6 | 0xc7 => Operand::Branch(branch(reader.u16()? as i16 as i64)?),
+        0xc8 | 0xc9 => Operand::Branch(branch(reader.i32()? as i64)?),
+        0xaa => {
+            reader.align()?;
+            let default = branch(reader.i32()? as i64)?;
+            let low = reader.i32()?;
+            let high = reader.i32()?;
+            let count = high as i64 - low as i64 + 1;
+            if count < 1 || count as usize > code_length / 4 {
+                return Err(Error::InvalidOperands(opcode));
+            }
+            let targets = (0..count)
+                .map(|_| branch(reader.i32()? as i64))
+                .collect::<Result<_, _>>()?;
+            Operand::TableSwitch {
+                default,
+                low,
+                targets,
+            }
+        }
+        0xab => {
+            reader.align()?;
+            let default = branch(reader.i32()? as i64)?;
+            let count = reader.i32()?;
+            if count < 0 || count as usize > code_length / 8 {
+                return Err(Error::InvalidOperands(opcode));
+            }
+            let pairs = (0..count)
+                .map(|_| Ok((reader.i32()?, branch(reader.i32()? as i64)?)))
+                .collect::<Result<_, Error>>()?;
+            Operand::LookupSwitch { default, pairs }
+        }
+        0xb9 => {
+            let index = reader.u16()?;
+            let count = reader.u8()?;
+            if count == 0 || reader.u8()? != 0 {
+                return Err(Error::InvalidOperands(opcode));
+            }
+            Operand::InvokeInterface { index, count }
+        }
+        0xba => {
+            let index = reader.u16()?;
+            if reader.u16()? != 0 {
+                return Err(Error::InvalidOperands(opcode));
+            }
+            Operand::Constant(index)
+        }
+        0xbc => Operand::NewArray(reader.u8()?),
+        0xc5 => Operand::MultiANewArray {
+            index: reader.u16()?,
+            dimensions: reader.u8()?,
+        },
+        WIDE => {
+            let modified = reader.u8()?;
+            let operand = match modified {
+                0x15..=0x19 | 0x36..=0x3a | 0xa9 => Operand::Local(reader.u16()?),
+                0x84 => Operand::Iinc {
+                    index: reader.u16()?,
+                    value: reader.u16()? as i16,
+                },
+                _ => return Err(Error::InvalidOperands(opcode)),
+            };
+            return Ok(Instruction {
+                offset,
+                length: reader.pos - offset,
+                opcode: modified,
+                wide: true,
+                operand,
+            });
+        }
+        _ => Operand::None,
+    };
+    Ok(Instruction {
+        offset,
+        length: reader.pos - offset,
+        opcode,
+        wide: false,
+        operand,
+    })
+}
+
+/// Disassembles `code` into the lines `javap -c` prints under `Code:`,
+/// without their indentation.
+pub fn disassemble(class_file: &ClassFile, code: &[u8]) -> Result<String, ParseError> {
+    let mut out = String::new();
+    for instruction in decode_all(code)? {
+        let mut line = format!("{:4}: {:<13} ", instruction.offset, instruction.mnemonic());
+        let reference = |line: &mut String, index: u16, value: Option<u8>| {
+            line.push_str(&format!("#{}", index));
+            if let Some(value) = value {
+                line.push_str(&format!(",  {}", value));
+            }
+            // comments start at the next tab stop, or after a single space
+            let padding = 40usize.saturating_sub(line.len()).max(1);
+            line.push_str(&" ".repeat(padding));
+            line.push_str("// ");
+            line.push_str(&describe_constant(class_file, index as usize));
+        };
+        match &instruction.operand {
+            Operand::None => {}
+            // xload_n and xstore_n imply their index
+            Operand::Local(_) if instruction.length == 1 => {}
+            Operand::Local(index) => line.push_str(&index.to_string()),
+            Operand::Iinc { index, value } => line.push_str(&format!("{}, {}", index, value)),
+            Operand::Int(value) => line.push_str(&value.to_string()),
+            Operand::Constant(index) if instruction.opcode == 0xba => {
+                reference(&mut line, *index, Some(0))
+            }
+            Operand::Constant(index) => reference(&mut line, *index, None),
+            Operand::InvokeInterface { index, count } => reference(&mut line, *index, Some(*count)),
+            Operand::MultiANewArray { index, dimensions } => {
+                reference(&mut line, *index, Some(*dimensions))
+            }
+            Operand::Branch(target) => line.push_str(&target.to_string()),
+            Operand::NewArray(atype) => {
+                line.push(' ');
+                line.push_str(array_type(*atype))
+            }
+            Operand::TableSwitch {
+                default,
+                low,
+                targets,
+            } => {
+                let high = *low as i64 + targets.len() as i64 - 1;
+                line.push_str(&format!("{{ // {} to {}", low, high));
+                for (i, target) in targets.iter().enumerate() {
+                    let key = *low as i64 + i as i64;
+                    line.push_str(&format!("\n      {:12}: {}", key, target));
+                }
+                line.push_str(&format!("\n      {:>12}: {}\n      }}", "default", default));
+            }
+            Operand::LookupSwitch { default, pairs } => {
+                line.push_str(&format!("{{ // {}", pairs.len()));
+                for (key, target) in pairs {
+                    line.push_str(&format!("\n      {:12}: {}", key, target));
+                }
+                line.push_str(&format!("\n      {:>12}: {}\n      }}", "default", default));
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn array_type(atype: u8) -> &'static str {
+    match atype {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "unknown",
+    }
+}
+
+/// How `javap` refers to a constant, e.g. after `//` for an instruction
+/// operand: the kind of constant and its value, leaving out the class name of
+/// members of the class itself.
+pub fn describe_constant(class_file: &ClassFile, index: usize) -> String {
+    let cp = &class_file.constant_pool;
+    match cp.get(index) {
+        _ if index == 0 => "#0".to_string(),
+        Some(
+            Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            }
+            | Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            }
+            | Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            },
+        ) if *class_index == class_file.this_class => format!(
+            "{} {}",
+            constant_kind(&cp[index]),
+            constant_value(class_file, *name_and_type_index as usize)
+        ),
+        None | Some(Constant::Placeholder) => format!("#{}", index),
+        Some(constant) => format!(
+            "{} {}",
+            constant_kind(constant),
+            constant_value(class_file, index)
+        ),
+    }
+}
+
+fn constant_kind(constant: &Constant) -> &'static str {
+    match constant {
+        Constant::Placeholder => "",
+        Constant::Class { .. } => "class",
+        Constant::FieldRef { .. } => "Field",
+        Constant::MethodRef { .. } => "Method",
+        Constant::InterfaceMethodRef { .. } => "InterfaceMethod",
+        Constant::String { .. } => "String",
+        Constant::Integer(_) => "int",
+        Constant::Float(_) => "float",
+        Constant::Long(_) => "long",
+        Constant::Double(_) => "double",
+        Constant::NameAndType { .. } => "NameAndType",
+        Constant::Utf8(_) => "Utf8",
+        Constant::MethodHandle { .. } => "MethodHandle",
+        Constant::MethodType { .. } => "MethodType",
+        Constant::Dynamic { .. } => "Dynamic",
+        Constant::InvokeDynamic { .. } => "InvokeDynamic",
+        Constant::Module { .. } => "(unknown tag 19)",
+        Constant::Package { .. } => "(unknown tag 20)",
+    }
+}
+
+/// The value of a constant as `javap` shows it, e.g.
+/// `java/lang/Object."<init>":()V` for a method reference. Indices that do
+/// not resolve are shown as `#index`.
+pub fn constant_value(class_file: &ClassFile, index: usize) -> String {
+    let cp = &class_file.constant_pool;
+    let utf8 = |index: u16| match cp.get(index as usize) {
+        Some(Constant::Utf8(bytes)) => {
+            let units = mutf8::decode_utf16(bytes).unwrap_or_default();
+            Some(
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect::<String>(),
+            )
+        }
+        _ => None,
+    };
+    let name = |index: u16| {
+        utf8(index)
+            .map(|name| checked_name(&name))
+            .unwrap_or_else(|| format!("#{}", index))
+    };
+    let class_name = |index: u16| match cp.get(index as usize) {
+        Some(Constant::Class { name_index }) => name(*name_index),
+        _ => format!("#{}", index),
+    };
+    let name_and_type = |index: u16| match cp.get(index as usize) {
+        Some(Constant::NameAndType {
+            name_index,
+            descriptor_index,
+        }) => format!(
+            "{}:{}",
+            name(*name_index),
+            utf8(*descriptor_index).unwrap_or_else(|| format!("#{}", descriptor_index))
+        ),
+        _ => format!("#{}", index),
+    };
+    let member_ref = |index: u16| match cp.get(index as usize) {
+        Some(
+            Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            }
+            | Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            }
+            | Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            },
+        ) => format!(
+            "{}.{}",
+            class_name(*class_index),
+            name_and_type(*name_and_type_index)
+        ),
+        _ => format!("#{}", index),
+    };
+    // constants referring to other constants are resolved one level deep only,
+    // so a malformed pool referring back to itself cannot recurse
+    match cp.get(index) {
+        Some(Constant::Utf8(_)) => escape(&utf8(index as u16).unwrap_or_default()),
+        Some(Constant::Class { name_index }) => name(*name_index),
+        Some(
+            Constant::FieldRef { .. }
+            | Constant::MethodRef { .. }
+            | Constant::InterfaceMethodRef { .. },
+        ) => member_ref(index as u16),
+        Some(Constant::String { string_index }) => utf8(*string_index)
+            .map(|string| escape(&string))
+            .unwrap_or_else(|| format!("#{}", string_index)),
+        Some(Constant::Integer(value)) => value.to_string(),
+        Some(Constant::Float(value)) => format!("{}f", java_float(*value as f64, true)),
+        Some(Constant::Long(value)) => format!("{}l", value),
+        Some(Constant::Double(value)) => format!("{}d", java_float(*value, false)),
+        Some(Constant::NameAndType { .. }) => name_and_type(index as u16),
+        Some(Constant::MethodHandle {
+            reference_kind,
+            reference_index,
+        }) => format!(
+            "{} {}",
+            reference_kind_name(*reference_kind),
+            member_ref(*reference_index)
+        ),
+        Some(Constant::MethodType { descriptor_index }) => utf8(*descriptor_index)
+            .map(|descriptor| escape(&descriptor))
+            .unwrap_or_else(|| format!("#{}", descriptor_index)),
+        Some(
+            Constant::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }
+            | Constant::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            },
+        ) => format!(
+            "#{}:{}",
+            bootstrap_method_attr_index,
+            name_and_type(*name_and_type_index)
+        ),
+        Some(Constant::Module { name_index } | Constant::Package { name_index }) => {
+            name(*name_index)
+        }
+        None | Some(Constant::Placeholder) => format!("#{}", index),
+    }
+}
+
+fn reference_kind_name(kind: u8) -> &'static str {
+    match kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_unknown",
+    }
+}
+
+// names are quoted unless each part between slashes is a Java identifier
+fn checked_name(name: &str) -> String {
+    let is_start = |c: char| c.is_alphabetic() || c == '_' || c == '$';
+    let mut after_slash = true;
+    let valid = name.chars().all(|c| {
+        let valid = match after_slash {
+            true => is_start(c),
+            false => c == '/' || is_start(c) || c.is_alphanumeric(),
+        };
+        after_slash = c == '/';
+        valid
+    });
+    match valid {
+        true => name.to_string(),
+        false => {
+            let escaped = name
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\t', "\\t");
+            format!("\"{}\"", escaped)
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            '"' => escaped.push_str("\\\""),
+            '\'' => escaped.push_str("\\'"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Float.toString and Double.toString: plain notation between 10^-3 and
+// 10^7, computerized scientific notation otherwise
+fn java_float(value: f64, single: bool) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return match value > 0.0 {
+            true => "Infinity",
+            false => "-Infinity",
+        }
+        .to_string();
+    }
+    if value == 0.0 {
+        return match value.is_sign_negative() {
+            true => "-0.0",
+            false => "0.0",
+        }
+        .to_string();
+    }
+    // the shortest digits that round trip, e.g. "-1.25e-3", but at least two
+    // of them, so that the minimum double is 4.9E-324 rather than 5.0E-324
+    let scientific = match single {
+        true => format!("{:e}", value as f32),
+        false => format!("{:e}", value),
+    };
+    let scientific = match scientific.contains('.') {
+        true => scientific,
+        false => {
+            let two_digits = match single {
+                true => format!("{:.1e}", value as f32),
+                false => format!("{:.1e}", value),
+            };
+            let round_trips = match single {
+                true => two_digits.parse::<f32>() == Ok(value as f32),
+                false => two_digits.parse::<f64>() == Ok(value),
+            };
+            match round_trips {
+                true => two_digits,
+                false => scientific,
+            }
+        }
+    };
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    let digits = digits.trim_end_matches('0');
+    let fraction = |digits: &str| match digits.is_empty() {
+        true => "0".to_string(),
+        false => digits.to_string(),
+    };
+    if (-3..7).contains(&exponent) {
+        if exponent < 0 {
+            let zeros = "0".repeat((-exponent - 1) as usize);
+            format!("{}0.{}{}", sign, zeros, digits)
+        } else {
+            let point = exponent as usize + 1;
+            let digits = format!("{:0<width$}", digits, width = point);
+            let (integer, rest) = digits.split_at(point);
+            format!("{}{}.{}", sign, integer, fraction(rest))
+        }
+    } else {
+        let (first, rest) = digits.split_at(1);
+        format!("{}{}.{}E{}", sign, first, fraction(rest), exponent)
+    }
+}
+
+struct Reader<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let bytes = self
+            .code
+            .get(self.pos..self.pos + N)
+            .ok_or(Error::UnexpectedEof)?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.bytes()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_be_bytes(self.bytes()?))
+    }
+
+    // switch operands start at the next multiple of four
+    fn align(&mut self) -> Result<(), Error> {
+        while !self.pos.is_multiple_of(4) {
+            self.u8()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{ClassBuilder, ACC_STATIC};
+    use crate::bytecode::{
+        constant_value, decode, decode_all, disassemble, Instruction, Operand, WIDE,
+    };
+    use crate::{parse, Constant, Error};
+    use std::io::Read;
+    use std::sync::Arc;
+
+    fn generated() -> Vec<u8> {
+        ClassBuilder::new("Gen")
+            .field(ACC_STATIC, "count", "I")
+            .method_with_flags(ACC_STATIC, "run", "(I)[I", |code| {
+                let (one, two, other, end) = (
+                    code.new_label(),
+                    code.new_label(),
+                    code.new_label(),
+                    code.new_label(),
+                );
+                code.iload(0)
+                    .istore(300)
+                    .iinc(300, -1000)
+                    .getstatic("Gen", "count", "I")
+                    .tableswitch(1, other, &[one, two])
+                    .bind(one)
+                    .ldc_string("a\t\"b\"\u{1}")
+                    .invokestatic(
+                        "java/lang/String",
+                        "valueOf",
+                        "(Ljava/lang/Object;)Ljava/lang/String;",
+                    )
+                    .op(0x57)
+                    .goto(end)
+                    .bind(two)
+                    .dconst(0.5)
+                    .fconst(1e10)
+                    .lconst(-7)
+                    .op(0x58)
+                    .op(0x57)
+                    .op(0x58)
+                    .goto(end)
+                    .bind(other)
+                    .new_instance("java/lang/Object")
+                    .op(0x59)
+                    .invokespecial("java/lang/Object", "<init>", "()V")
+                    .op(0x57)
+                    .bind(end)
+                    .iload(300)
+                    .newarray(10)
+                    .op(0xb0)
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn code(bytes: &[u8], name: &str) -> String {
+        let class_file = parse(bytes).unwrap();
+        let method = class_file
+            .methods
+            .iter()
+            .find(|method| {
+                crate::get_str(class_file.constant_pool.clone(), method.name_index as usize)
+                    .unwrap()
+                    == name
+            })
+            .unwrap();
+        disassemble(&class_file, &method.code_attribute().unwrap().code).unwrap()
+    }
+
+    #[test]
+    fn test_decode() {
+        let bytes = generated();
+        let class_file = parse(&bytes).unwrap();
+        let code = &class_file.methods[0].code_attribute().unwrap().code;
+        let instructions = decode_all(code).unwrap();
+        assert_eq!(
+            instructions[2],
+            Instruction {
+                offset: 5,
+                length: 6,
+                opcode: 0x84,
+                wide: true,
+                operand: Operand::Iinc {
+                    index: 300,
+                    value: -1000
+                },
+            }
+        );
+        assert_eq!(instructions[2].mnemonic(), "iinc_w");
+        // one byte of padding after the opcode at 14
+        assert_eq!(
+            instructions[4],
+            Instruction {
+                offset: 14,
+                length: 22,
+                opcode: 0xaa,
+                wide: false,
+                operand: Operand::TableSwitch {
+                    default: 59,
+                    low: 1,
+                    targets: vec![36, 45],
+                },
+            }
+        );
+        assert_eq!(instructions[4].branch_targets(), vec![59, 36, 45]);
+        assert!(!instructions[4].falls_through());
+
+        let error = |code: &[u8], offset: usize| {
+            let error = decode(code, offset).unwrap_err();
+            (error.offset, error.kind.to_string())
+        };
+        assert_eq!(
+            error(&[0x00, 0xca], 1),
+            (1, "invalid opcode 0xca".to_string())
+        );
+        assert_eq!(
+            error(&[0x00, 0x11, 0x01], 1),
+            (1, "unexpected end of input".to_string())
+        );
+        assert_eq!(
+            error(&[0x00, 0xa7, 0xff, 0xfe], 1),
+            (1, "branch target -1 is outside the code".to_string())
+        );
+        assert!(matches!(
+            decode(&[WIDE, 0x10, 0x00, 0x01], 0).unwrap_err().kind,
+            Error::InvalidOperands(WIDE)
+        ));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(
+            code(&generated(), "run"),
+            r#"   0: iload_0
+   1: istore_w      300
+   5: iinc_w        300, -1000
+  11: getstatic     #8                  // Field count:I
+  14: tableswitch   { // 1 to 2
+                 1: 36
+                 2: 45
+           default: 59
+      }
+  36: ldc           #10                 // String a\t\"b\"\u0001
+  38: invokestatic  #16                 // Method java/lang/String.valueOf:(Ljava/lang/Object;)Ljava/lang/String;
+  41: pop
+  42: goto          67
+  45: ldc2_w        #17                 // double 0.5d
+  48: ldc           #19                 // float 1.0E10f
+  50: ldc2_w        #20                 // long -7l
+  53: pop2
+  54: pop
+  55: pop2
+  56: goto          67
+  59: new           #4                  // class java/lang/Object
+  62: dup
+  63: invokespecial #25                 // Method java/lang/Object."<init>":()V
+  66: pop
+  67: iload_w       300
+  71: newarray       int
+  73: areturn
+"#
+        );
+    }
+
+    #[test]
+    fn test_self_referencing_constants() {
+        let bytes = generated();
+        let mut class_file = parse(&bytes).unwrap();
+        let mut constant_pool = class_file.constant_pool.to_vec();
+        constant_pool[10] = Constant::String { string_index: 10 };
+        constant_pool[17] = Constant::MethodHandle {
+            reference_kind: 6,
+            reference_index: 17,
+        };
+        constant_pool[19] = Constant::MethodType {
+            descriptor_index: 10,
+        };
+        class_file.constant_pool = Arc::new(constant_pool);
+        assert_eq!(constant_value(&class_file, 10), "#10");
+        assert_eq!(constant_value(&class_file, 17), "REF_invokeStatic #17");
+        assert_eq!(constant_value(&class_file, 19), "#10");
+        assert!(code(&bytes, "run").contains("// String a"));
+    }
+
+    #[test]
+    fn test_rt_jar() {
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut bytes = Vec::new();
+        zip.by_name("java/util/Locale.class")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        // as printed by javap -c, with an empty string constant
+        assert_eq!(
+            code(&bytes, "formatList"),
+            r#"   0: aload_1
+   1: ifnonnull     23
+   4: aload_0
+   5: invokestatic  #686                // Method java/util/Arrays.stream:([Ljava/lang/Object;)Ljava/util/stream/Stream;
+   8: ldc_w         #689                // String ,
+  11: invokestatic  #691                // Method java/util/stream/Collectors.joining:(Ljava/lang/CharSequence;)Ljava/util/stream/Collector;
+  14: invokeinterface #697,  2          // InterfaceMethod java/util/stream/Stream.collect:(Ljava/util/stream/Collector;)Ljava/lang/Object;
+  19: checkcast     #143                // class java/lang/String
+  22: areturn
+  23: aload_0
+  24: arraylength
+  25: lookupswitch  { // 2
+                 0: 52
+                 1: 57
+           default: 63
+      }
+  52: ldc           #43                 // String
+  54: goto          83
+  57: aload_0
+  58: iconst_0
+  59: aaload
+  60: goto          83
+  63: aload_0
+  64: invokestatic  #686                // Method java/util/Arrays.stream:([Ljava/lang/Object;)Ljava/util/stream/Stream;
+  67: ldc           #43                 // String
+  69: aload_1
+  70: invokedynamic #701,  0            // InvokeDynamic #4:apply:(Ljava/lang/String;)Ljava/util/function/BinaryOperator;
+  75: invokeinterface #704,  3          // InterfaceMethod java/util/stream/Stream.reduce:(Ljava/lang/Object;Ljava/util/function/BinaryOperator;)Ljava/lang/Object;
+  80: checkcast     #143                // class java/lang/String
+  83: areturn
+"#
+        );
+    }
+
+    #[test]
+    fn test_java_float() {
+        use crate::bytecode::java_float;
+
+        assert_eq!(java_float(0.5, false), "0.5");
+        assert_eq!(java_float(-0.0, false), "-0.0");
+        assert_eq!(java_float(100.0, false), "100.0");
+        assert_eq!(java_float(0.001, false), "0.001");
+        assert_eq!(java_float(1e-4, false), "1.0E-4");
+        assert_eq!(java_float(1e7, false), "1.0E7");
+        assert_eq!(java_float(1234567.0, false), "1234567.0");
+        assert_eq!(
+            java_float(0.017453292519943295, false),
+            "0.017453292519943295"
+        );
+        assert_eq!(
+            java_float(f64::MIN_POSITIVE * f64::EPSILON, false),
+            "4.9E-324"
+        );
+        assert_eq!(java_float(f32::MAX as f64, true), "3.4028235E38");
+        assert_eq!(java_float(f64::NEG_INFINITY, false), "-Infinity");
+        assert_eq!(java_float(f64::NAN, true), "NaN");
+    }
+}
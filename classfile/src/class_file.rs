@@ -1,9 +1,11 @@
 use crate::attribute::Attribute;
 use crate::field::FieldInfo;
 use crate::method::MethodInfo;
-use crate::ConstantPoolRef;
+use crate::{Constant, ConstantPoolRef};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClassFile<'a> {
     pub minor_version: u16,
     pub major_version: u16,
@@ -16,3 +18,41 @@ pub struct ClassFile<'a> {
     pub methods: Vec<MethodInfo<'a>>,
     pub attributes: Vec<Attribute<'a>>,
 }
+
+impl ClassFile<'_> {
+    /// Copies what the class file borrows from its input, so that it can be
+    /// kept after the input is gone, e.g. in a cache or on another thread.
+    pub fn into_static(self) -> ClassFile<'static> {
+        let constant_pool = Arc::try_unwrap(self.constant_pool)
+            .unwrap_or_else(|constant_pool| constant_pool.as_ref().clone());
+        ClassFile {
+            minor_version: self.minor_version,
+            major_version: self.major_version,
+            constant_pool: Arc::new(
+                constant_pool
+                    .into_iter()
+                    .map(Constant::into_static)
+                    .collect(),
+            ),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self
+                .fields
+                .into_iter()
+                .map(FieldInfo::into_static)
+                .collect(),
+            methods: self
+                .methods
+                .into_iter()
+                .map(MethodInfo::into_static)
+                .collect(),
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(Attribute::into_static)
+                .collect(),
+        }
+    }
+}
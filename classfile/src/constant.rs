@@ -1,4 +1,8 @@
+use crate::errors::Error;
+use std::borrow::Cow;
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constant<'a> {
     Placeholder,
     Class {
@@ -27,7 +31,7 @@ pub enum Constant<'a> {
         name_index: u16,
         descriptor_index: u16,
     },
-    Utf8(&'a [u8]),
+    Utf8(Cow<'a, [u8]>),
     MethodHandle {
         reference_kind: u8,
         reference_index: u16,
@@ -51,7 +55,75 @@ pub enum Constant<'a> {
     },
 }
 
+impl Constant<'_> {
+    pub fn into_static(self) -> Constant<'static> {
+        match self {
+            Constant::Placeholder => Constant::Placeholder,
+            Constant::Class { name_index } => Constant::Class { name_index },
+            Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            } => Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            },
+            Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            },
+            Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            },
+            Constant::String { string_index } => Constant::String { string_index },
+            Constant::Integer(value) => Constant::Integer(value),
+            Constant::Float(value) => Constant::Float(value),
+            Constant::Long(value) => Constant::Long(value),
+            Constant::Double(value) => Constant::Double(value),
+            Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            } => Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            },
+            Constant::Utf8(bytes) => Constant::Utf8(Cow::Owned(bytes.into_owned())),
+            Constant::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => Constant::MethodHandle {
+                reference_kind,
+                reference_index,
+            },
+            Constant::MethodType { descriptor_index } => Constant::MethodType { descriptor_index },
+            Constant::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => Constant::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            },
+            Constant::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => Constant::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            },
+            Constant::Module { name_index } => Constant::Module { name_index },
+            Constant::Package { name_index } => Constant::Package { name_index },
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantTag {
     Class,
     FieldRef,
@@ -72,9 +144,11 @@ pub enum ConstantTag {
     Package,
 }
 
-impl From<u8> for ConstantTag {
-    fn from(tag: u8) -> Self {
-        match tag {
+impl TryFrom<u8> for ConstantTag {
+    type Error = Error;
+
+    fn try_from(tag: u8) -> Result<Self, Error> {
+        Ok(match tag {
             7 => ConstantTag::Class,
             9 => ConstantTag::FieldRef,
             10 => ConstantTag::MethodRef,
@@ -92,7 +166,7 @@ impl From<u8> for ConstantTag {
             18 => ConstantTag::InvokeDynamic,
             19 => ConstantTag::Module,
             20 => ConstantTag::Package,
-            _ => panic!("unknown constant tag: {}", tag),
-        }
+            _ => return Err(Error::InvalidConstantTag(tag)),
+        })
     }
 }
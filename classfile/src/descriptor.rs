@@ -0,0 +1,928 @@
+//! Field and method descriptors, JVMS 4.3, and generic signatures, JVMS 4.7.9.1.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Array types may have at most this many dimensions.
+pub const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+/// Type arguments may be nested at most this deep in a generic signature.
+pub const MAX_TYPE_ARGUMENT_DEPTH: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorError {
+    pub input: String,
+    /// The byte offset in `input` where parsing failed.
+    pub position: usize,
+    pub message: String,
+}
+
+impl Display for DescriptorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at position {} in {:?}",
+            self.message, self.position, self.input
+        )
+    }
+}
+
+impl std::error::Error for DescriptorError {}
+
+pub type Result<T> = std::result::Result<T, DescriptorError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+impl BaseType {
+    fn from_char(c: u8) -> Option<BaseType> {
+        Some(match c {
+            b'B' => BaseType::Byte,
+            b'C' => BaseType::Char,
+            b'D' => BaseType::Double,
+            b'F' => BaseType::Float,
+            b'I' => BaseType::Int,
+            b'J' => BaseType::Long,
+            b'S' => BaseType::Short,
+            b'Z' => BaseType::Boolean,
+            _ => return None,
+        })
+    }
+
+    pub fn descriptor(&self) -> char {
+        match self {
+            BaseType::Byte => 'B',
+            BaseType::Char => 'C',
+            BaseType::Double => 'D',
+            BaseType::Float => 'F',
+            BaseType::Int => 'I',
+            BaseType::Long => 'J',
+            BaseType::Short => 'S',
+            BaseType::Boolean => 'Z',
+        }
+    }
+
+    /// The Java keyword for the type, e.g. `int`.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            BaseType::Byte => "byte",
+            BaseType::Char => "char",
+            BaseType::Double => "double",
+            BaseType::Float => "float",
+            BaseType::Int => "int",
+            BaseType::Long => "long",
+            BaseType::Short => "short",
+            BaseType::Boolean => "boolean",
+        }
+    }
+
+    /// The number of local variable or operand stack slots a value takes.
+    pub fn slot_size(&self) -> usize {
+        match self {
+            BaseType::Long | BaseType::Double => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// A parsed field descriptor. Its `Display` is the descriptor again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Base(BaseType),
+    /// A class or interface by its binary name, e.g. `java/lang/String`.
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> Result<FieldType> {
+        let mut parser = Parser::new(descriptor);
+        let field_type = parser.field_type()?;
+        parser.end()?;
+        Ok(field_type)
+    }
+
+    pub fn slot_size(&self) -> usize {
+        match self {
+            FieldType::Base(base) => base.slot_size(),
+            _ => 1,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        !matches!(self, FieldType::Base(_))
+    }
+
+    /// The number of dimensions, 0 if this is not an array type.
+    pub fn dimensions(&self) -> usize {
+        match self {
+            FieldType::Array(component) => 1 + component.dimensions(),
+            _ => 0,
+        }
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = DescriptorError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        FieldType::parse(s)
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::Base(base) => write!(f, "{}", base.descriptor()),
+            FieldType::Object(name) => write!(f, "L{};", name),
+            FieldType::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+/// A parsed method descriptor, the return type is `None` for `void`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> Result<MethodDescriptor> {
+        let mut parser = Parser::new(descriptor);
+        parser.expect(b'(')?;
+        let mut parameters = vec![];
+        while !parser.eat(b')') {
+            parameters.push(parser.field_type()?);
+        }
+        let return_type = match parser.eat(b'V') {
+            true => None,
+            false => Some(parser.field_type()?),
+        };
+        parser.end()?;
+        Ok(MethodDescriptor {
+            parameters,
+            return_type,
+        })
+    }
+
+    /// The slots the parameters take, without `this`. At most 255 are allowed, JVMS 4.3.3.
+    pub fn parameter_slots(&self) -> usize {
+        self.parameters.iter().map(FieldType::slot_size).sum()
+    }
+
+    pub fn return_slots(&self) -> usize {
+        self.return_type.as_ref().map_or(0, FieldType::slot_size)
+    }
+}
+
+impl FromStr for MethodDescriptor {
+    type Err = DescriptorError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        MethodDescriptor::parse(s)
+    }
+}
+
+impl Display for MethodDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for parameter in self.parameters.iter() {
+            write!(f, "{}", parameter)?;
+        }
+        match &self.return_type {
+            Some(return_type) => write!(f, "){}", return_type),
+            None => write!(f, ")V"),
+        }
+    }
+}
+
+/// A Java type in a generic signature. Its `Display` is the signature again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeSignature {
+    Base(BaseType),
+    Class(ClassTypeSignature),
+    TypeVariable(String),
+    Array(Box<TypeSignature>),
+}
+
+impl TypeSignature {
+    /// Parses the signature of a field, which is a reference type.
+    pub fn parse_field(signature: &str) -> Result<TypeSignature> {
+        let mut parser = Parser::new(signature);
+        let field = parser.reference_type_signature()?;
+        parser.end()?;
+        Ok(field)
+    }
+}
+
+impl Display for TypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeSignature::Base(base) => write!(f, "{}", base.descriptor()),
+            TypeSignature::Class(class) => write!(f, "{}", class),
+            TypeSignature::TypeVariable(name) => write!(f, "T{};", name),
+            TypeSignature::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+/// `java/util/Map<TK;TV;>.Entry` has the package `java/util/` and the classes `Map<K, V>`
+/// and `Entry`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature {
+    /// The package specifier with its trailing `/`, empty for the unnamed package.
+    pub package: String,
+    /// The outermost class first, followed by the inner classes.
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+impl ClassTypeSignature {
+    /// The binary name of the erased type, e.g. `java/util/Map$Entry`.
+    pub fn binary_name(&self) -> String {
+        let names = self
+            .classes
+            .iter()
+            .map(|class| class.name.as_str())
+            .collect::<Vec<_>>();
+        format!("{}{}", self.package, names.join("$"))
+    }
+}
+
+impl Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L{}", self.package)?;
+        for (i, class) in self.classes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", class)?;
+        }
+        write!(f, ";")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+impl Display for SimpleClassTypeSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.type_arguments.is_empty() {
+            write!(f, "<")?;
+            for argument in self.type_arguments.iter() {
+                write!(f, "{}", argument)?;
+            }
+            write!(f, ">")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeArgument {
+    /// `*`
+    Any,
+    Exact(TypeSignature),
+    /// `+`, i.e. `? extends`
+    Extends(TypeSignature),
+    /// `-`, i.e. `? super`
+    Super(TypeSignature),
+}
+
+impl Display for TypeArgument {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeArgument::Any => write!(f, "*"),
+            TypeArgument::Exact(signature) => write!(f, "{}", signature),
+            TypeArgument::Extends(signature) => write!(f, "+{}", signature),
+            TypeArgument::Super(signature) => write!(f, "-{}", signature),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TypeParameter {
+    pub name: String,
+    /// Absent in `<T::Ljava/lang/Comparable<TT;>;>`, where the only bound is an interface.
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+impl Display for TypeParameter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.name)?;
+        if let Some(bound) = &self.class_bound {
+            write!(f, "{}", bound)?;
+        }
+        for bound in self.interface_bounds.iter() {
+            write!(f, ":{}", bound)?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_type_parameters(f: &mut Formatter<'_>, parameters: &[TypeParameter]) -> std::fmt::Result {
+    if !parameters.is_empty() {
+        write!(f, "<")?;
+        for parameter in parameters {
+            write!(f, "{}", parameter)?;
+        }
+        write!(f, ">")?;
+    }
+    Ok(())
+}
+
+/// The `Signature` attribute of a class.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> Result<ClassSignature> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let super_class = parser.class_type_signature()?;
+        let mut interfaces = vec![];
+        while !parser.at_end() {
+            interfaces.push(parser.class_type_signature()?);
+        }
+        Ok(ClassSignature {
+            type_parameters,
+            super_class,
+            interfaces,
+        })
+    }
+}
+
+impl Display for ClassSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt_type_parameters(f, &self.type_parameters)?;
+        write!(f, "{}", self.super_class)?;
+        for interface in self.interfaces.iter() {
+            write!(f, "{}", interface)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `Signature` attribute of a method.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    /// `None` for `void`.
+    pub return_type: Option<TypeSignature>,
+    /// Class types or type variables.
+    pub throws: Vec<TypeSignature>,
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> Result<MethodSignature> {
+        let mut parser = Parser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        parser.expect(b'(')?;
+        let mut parameters = vec![];
+        while !parser.eat(b')') {
+            parameters.push(parser.java_type_signature()?);
+        }
+        let return_type = match parser.eat(b'V') {
+            true => None,
+            false => Some(parser.java_type_signature()?),
+        };
+        let mut throws = vec![];
+        while parser.eat(b'^') {
+            let throw = match parser.peek() {
+                Some(b'T') => parser.type_variable_signature()?,
+                _ => TypeSignature::Class(parser.class_type_signature()?),
+            };
+            throws.push(throw);
+        }
+        parser.end()?;
+        Ok(MethodSignature {
+            type_parameters,
+            parameters,
+            return_type,
+            throws,
+        })
+    }
+}
+
+impl Display for MethodSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt_type_parameters(f, &self.type_parameters)?;
+        write!(f, "(")?;
+        for parameter in self.parameters.iter() {
+            write!(f, "{}", parameter)?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{}", return_type)?,
+            None => write!(f, "V")?,
+        }
+        for throw in self.throws.iter() {
+            write!(f, "^{}", throw)?;
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    // the type argument lists currently open
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T> {
+        Err(DescriptorError {
+            input: self.input.to_string(),
+            position: self.pos,
+            message: message.into(),
+        })
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T> {
+        match self.input[self.pos..].chars().next() {
+            Some(c) => self.error(format!("expected {}, found '{}'", expected, c)),
+            None => self.error(format!("expected {}, found end of input", expected)),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => self.unexpected(&format!("'{}'", c as char)),
+        }
+    }
+
+    fn end(&self) -> Result<()> {
+        match self.at_end() {
+            true => Ok(()),
+            false => self.unexpected("end of input"),
+        }
+    }
+
+    fn base_type(&mut self) -> Option<BaseType> {
+        let base = BaseType::from_char(self.peek()?)?;
+        self.pos += 1;
+        Some(base)
+    }
+
+    // consumes the `[`s of an array type, checking the number of dimensions
+    fn dimensions(&mut self) -> Result<usize> {
+        let start = self.pos;
+        while self.eat(b'[') {}
+        let dimensions = self.pos - start;
+        if dimensions > MAX_ARRAY_DIMENSIONS {
+            self.pos = start;
+            return self.error(format!(
+                "array type has {} dimensions, at most {} are allowed",
+                dimensions, MAX_ARRAY_DIMENSIONS
+            ));
+        }
+        Ok(dimensions)
+    }
+
+    // the characters up to one of `terminators`, which must not be empty
+    fn identifier(&mut self, terminators: &[u8], what: &str) -> Result<&'a str> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if terminators.contains(&c) {
+                break;
+            }
+            if b".;[/<>:".contains(&c) {
+                return self.error(format!("unexpected '{}' in {}", c as char, what));
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return self.unexpected(what);
+        }
+        Ok(&self.input[start..self.pos])
+    }
+
+    fn field_type(&mut self) -> Result<FieldType> {
+        let dimensions = self.dimensions()?;
+        let mut field_type = if let Some(base) = self.base_type() {
+            FieldType::Base(base)
+        } else if self.eat(b'L') {
+            let start = self.pos;
+            loop {
+                self.identifier(b"/;", "class name")?;
+                if !self.eat(b'/') {
+                    break;
+                }
+            }
+            let name = &self.input[start..self.pos];
+            self.expect(b';')?;
+            FieldType::Object(name.to_string())
+        } else {
+            return self.unexpected("a field type");
+        };
+        for _ in 0..dimensions {
+            field_type = FieldType::Array(Box::new(field_type));
+        }
+        Ok(field_type)
+    }
+
+    fn java_type_signature(&mut self) -> Result<TypeSignature> {
+        match self.base_type() {
+            Some(base) => Ok(TypeSignature::Base(base)),
+            None => self.reference_type_signature(),
+        }
+    }
+
+    fn reference_type_signature(&mut self) -> Result<TypeSignature> {
+        match self.peek() {
+            Some(b'L') => Ok(TypeSignature::Class(self.class_type_signature()?)),
+            Some(b'T') => self.type_variable_signature(),
+            Some(b'[') => {
+                let dimensions = self.dimensions()?;
+                let mut signature = self.java_type_signature()?;
+                for _ in 0..dimensions {
+                    signature = TypeSignature::Array(Box::new(signature));
+                }
+                Ok(signature)
+            }
+            _ => self.unexpected("a reference type signature"),
+        }
+    }
+
+    fn type_variable_signature(&mut self) -> Result<TypeSignature> {
+        self.expect(b'T')?;
+        let name = self.identifier(b";", "type variable name")?;
+        self.expect(b';')?;
+        Ok(TypeSignature::TypeVariable(name.to_string()))
+    }
+
+    fn class_type_signature(&mut self) -> Result<ClassTypeSignature> {
+        self.expect(b'L')?;
+        let start = self.pos;
+        let mut name = self.identifier(b"/<.;", "class name")?;
+        while self.eat(b'/') {
+            name = self.identifier(b"/<.;", "class name")?;
+        }
+        let package = &self.input[start..self.pos - name.len()];
+        let mut classes = vec![self.simple_class_type_signature(name)?];
+        while self.eat(b'.') {
+            let name = self.identifier(b"<.;", "inner class name")?;
+            classes.push(self.simple_class_type_signature(name)?);
+        }
+        self.expect(b';')?;
+        Ok(ClassTypeSignature {
+            package: package.to_string(),
+            classes,
+        })
+    }
+
+    fn simple_class_type_signature(&mut self, name: &str) -> Result<SimpleClassTypeSignature> {
+        let mut type_arguments = vec![];
+        if self.eat(b'<') {
+            // each level recurses, so bound it rather than overflow the stack
+            if self.depth == MAX_TYPE_ARGUMENT_DEPTH {
+                self.pos -= 1;
+                return self.error(format!(
+                    "type arguments are nested more than {} deep",
+                    MAX_TYPE_ARGUMENT_DEPTH
+                ));
+            }
+            self.depth += 1;
+            while !self.eat(b'>') {
+                let argument = match self.peek() {
+                    Some(b'*') => {
+                        self.pos += 1;
+                        TypeArgument::Any
+                    }
+                    Some(b'+') => {
+                        self.pos += 1;
+                        TypeArgument::Extends(self.reference_type_signature()?)
+                    }
+                    Some(b'-') => {
+                        self.pos += 1;
+                        TypeArgument::Super(self.reference_type_signature()?)
+                    }
+                    _ => TypeArgument::Exact(self.reference_type_signature()?),
+                };
+                type_arguments.push(argument);
+            }
+            if type_arguments.is_empty() {
+                self.pos -= 1;
+                return self.error("empty type argument list");
+            }
+            self.depth -= 1;
+        }
+        Ok(SimpleClassTypeSignature {
+            name: name.to_string(),
+            type_arguments,
+        })
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>> {
+        let mut parameters = vec![];
+        if !self.eat(b'<') {
+            return Ok(parameters);
+        }
+        while !self.eat(b'>') {
+            let name = self.identifier(b":", "type parameter name")?.to_string();
+            self.expect(b':')?;
+            let class_bound = match self.peek() {
+                Some(b'L' | b'T' | b'[') => Some(self.reference_type_signature()?),
+                _ => None,
+            };
+            let mut interface_bounds = vec![];
+            while self.eat(b':') {
+                interface_bounds.push(self.reference_type_signature()?);
+            }
+            parameters.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+        }
+        if parameters.is_empty() {
+            self.pos -= 1;
+            return self.error("empty type parameter list");
+        }
+        Ok(parameters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::descriptor::{
+        BaseType, ClassSignature, ClassTypeSignature, FieldType, MethodDescriptor, MethodSignature,
+        SimpleClassTypeSignature, TypeArgument, TypeParameter, TypeSignature,
+        MAX_TYPE_ARGUMENT_DEPTH,
+    };
+
+    #[test]
+    fn test_field_type() {
+        assert_eq!(
+            FieldType::parse("I").unwrap(),
+            FieldType::Base(BaseType::Int)
+        );
+        assert_eq!(
+            FieldType::parse("[[Ljava/lang/String;").unwrap(),
+            FieldType::Array(Box::new(FieldType::Array(Box::new(FieldType::Object(
+                "java/lang/String".to_string()
+            )))))
+        );
+        assert_eq!(FieldType::parse("J").unwrap().slot_size(), 2);
+        assert_eq!(FieldType::parse("[D").unwrap().slot_size(), 1);
+        assert_eq!(FieldType::parse("[[[I").unwrap().dimensions(), 3);
+        for descriptor in ["Z", "[B", "Ljava/util/Map$Entry;", "[[Ljava/lang/Object;"] {
+            assert_eq!(
+                FieldType::parse(descriptor).unwrap().to_string(),
+                descriptor
+            );
+        }
+        assert!(FieldType::parse(&format!("{}I", "[".repeat(255))).is_ok());
+    }
+
+    #[test]
+    fn test_field_type_errors() {
+        let error = FieldType::parse("Ljava/lang/String").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected ';', found end of input at position 17 in \"Ljava/lang/String\""
+        );
+        let error = FieldType::parse("II").unwrap_err();
+        assert_eq!(error.message, "expected end of input, found 'I'");
+        assert_eq!(error.position, 1);
+        let error = FieldType::parse("V").unwrap_err();
+        assert_eq!(error.message, "expected a field type, found 'V'");
+        let error = FieldType::parse("L;").unwrap_err();
+        assert_eq!(error.message, "expected class name, found ';'");
+        let error = FieldType::parse("Ljava//String;").unwrap_err();
+        assert_eq!(error.position, 6);
+        let error = FieldType::parse("La.b;").unwrap_err();
+        assert_eq!(error.message, "unexpected '.' in class name");
+        let error = FieldType::parse(&format!("{}I", "[".repeat(256))).unwrap_err();
+        assert_eq!(
+            error.message,
+            "array type has 256 dimensions, at most 255 are allowed"
+        );
+    }
+
+    #[test]
+    fn test_method_descriptor() {
+        let descriptor =
+            MethodDescriptor::parse("(IDLjava/lang/Thread;[J)Ljava/lang/Object;").unwrap();
+        assert_eq!(descriptor.parameters.len(), 4);
+        assert_eq!(descriptor.parameter_slots(), 5);
+        assert_eq!(descriptor.return_slots(), 1);
+        assert_eq!(
+            descriptor.to_string(),
+            "(IDLjava/lang/Thread;[J)Ljava/lang/Object;"
+        );
+
+        let descriptor = MethodDescriptor::parse("()V").unwrap();
+        assert!(descriptor.parameters.is_empty());
+        assert_eq!(descriptor.return_type, None);
+        assert_eq!(descriptor.return_slots(), 0);
+
+        let error = MethodDescriptor::parse("(V)V").unwrap_err();
+        assert_eq!(
+            (error.position, error.message.as_str()),
+            (1, "expected a field type, found 'V'")
+        );
+        let error = MethodDescriptor::parse("I)V").unwrap_err();
+        assert_eq!(error.message, "expected '(', found 'I'");
+        let error = MethodDescriptor::parse("(I").unwrap_err();
+        assert_eq!(error.message, "expected a field type, found end of input");
+        let error = MethodDescriptor::parse("()VV").unwrap_err();
+        assert_eq!(error.position, 3);
+    }
+
+    fn class(name: &str, type_arguments: Vec<TypeArgument>) -> SimpleClassTypeSignature {
+        SimpleClassTypeSignature {
+            name: name.to_string(),
+            type_arguments,
+        }
+    }
+
+    fn object() -> TypeSignature {
+        TypeSignature::Class(ClassTypeSignature {
+            package: "java/lang/".to_string(),
+            classes: vec![class("Object", vec![])],
+        })
+    }
+
+    fn type_variable(name: &str) -> TypeSignature {
+        TypeSignature::TypeVariable(name.to_string())
+    }
+
+    #[test]
+    fn test_field_signature() {
+        let signature = "Ljava/util/Map<TK;+Ljava/util/List<*>;>.Entry<-[TV;>;";
+        let parsed = TypeSignature::parse_field(signature).unwrap();
+        let expected = TypeSignature::Class(ClassTypeSignature {
+            package: "java/util/".to_string(),
+            classes: vec![
+                class(
+                    "Map",
+                    vec![
+                        TypeArgument::Exact(type_variable("K")),
+                        TypeArgument::Extends(TypeSignature::Class(ClassTypeSignature {
+                            package: "java/util/".to_string(),
+                            classes: vec![class("List", vec![TypeArgument::Any])],
+                        })),
+                    ],
+                ),
+                class(
+                    "Entry",
+                    vec![TypeArgument::Super(TypeSignature::Array(Box::new(
+                        type_variable("V"),
+                    )))],
+                ),
+            ],
+        });
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.to_string(), signature);
+        match parsed {
+            TypeSignature::Class(class) => assert_eq!(class.binary_name(), "java/util/Map$Entry"),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(
+            TypeSignature::parse_field("TT;").unwrap(),
+            type_variable("T")
+        );
+        let error = TypeSignature::parse_field("I").unwrap_err();
+        assert_eq!(
+            error.message,
+            "expected a reference type signature, found 'I'"
+        );
+        let error = TypeSignature::parse_field("Ljava/util/List<>;").unwrap_err();
+        assert_eq!(
+            (error.position, error.message.as_str()),
+            (16, "empty type argument list")
+        );
+        let error = TypeSignature::parse_field("Ljava/util/List<TT;;").unwrap_err();
+        assert_eq!(
+            error.message,
+            "expected a reference type signature, found ';'"
+        );
+    }
+
+    #[test]
+    fn test_nested_type_arguments() {
+        let nested = |depth: usize| format!("{}La;{}", "La<".repeat(depth), ">;".repeat(depth));
+        assert!(TypeSignature::parse_field(&nested(MAX_TYPE_ARGUMENT_DEPTH)).is_ok());
+        let error = TypeSignature::parse_field(&nested(MAX_TYPE_ARGUMENT_DEPTH + 1)).unwrap_err();
+        assert_eq!(
+            (error.position, error.message.as_str()),
+            (3 * 255 + 2, "type arguments are nested more than 255 deep")
+        );
+        // deep enough to overflow the stack of a release build without the limit
+        let deep = nested(13_000);
+        assert!(TypeSignature::parse_field(&deep).is_err());
+        assert!(ClassSignature::parse(&format!("{}{}", deep, deep)).is_err());
+        assert!(MethodSignature::parse(&format!("<T:{}>({})V", deep, deep)).is_err());
+    }
+
+    #[test]
+    fn test_class_signature() {
+        // class Foo<T extends Comparable<T>, U> extends Object implements Comparable<Foo<T, U>>
+        let signature =
+            "<T::Ljava/lang/Comparable<TT;>;U:Ljava/lang/Object;>Ljava/lang/Object;Ljava/lang/Comparable<LFoo<TT;TU;>;>;";
+        let parsed = ClassSignature::parse(signature).unwrap();
+        assert_eq!(
+            parsed.type_parameters,
+            vec![
+                TypeParameter {
+                    name: "T".to_string(),
+                    class_bound: None,
+                    interface_bounds: vec![TypeSignature::Class(ClassTypeSignature {
+                        package: "java/lang/".to_string(),
+                        classes: vec![class(
+                            "Comparable",
+                            vec![TypeArgument::Exact(type_variable("T"))]
+                        )],
+                    })],
+                },
+                TypeParameter {
+                    name: "U".to_string(),
+                    class_bound: Some(object()),
+                    interface_bounds: vec![],
+                },
+            ]
+        );
+        assert_eq!(TypeSignature::Class(parsed.super_class.clone()), object());
+        assert_eq!(parsed.interfaces.len(), 1);
+        assert_eq!(parsed.interfaces[0].classes[0].type_arguments.len(), 1);
+        assert_eq!(parsed.to_string(), signature);
+
+        let error = ClassSignature::parse("<T>Ljava/lang/Object;").unwrap_err();
+        assert_eq!(error.message, "unexpected '>' in type parameter name");
+        let error = ClassSignature::parse("<>Ljava/lang/Object;").unwrap_err();
+        assert_eq!(error.message, "empty type parameter list");
+    }
+
+    #[test]
+    fn test_method_signature() {
+        // <T extends Throwable> void m(List<? super T>[] a, int b) throws T, IOException
+        let signature =
+            "<T:Ljava/lang/Throwable;>([Ljava/util/List<-TT;>;I)V^TT;^Ljava/io/IOException;";
+        let parsed = MethodSignature::parse(signature).unwrap();
+        assert_eq!(parsed.type_parameters.len(), 1);
+        assert_eq!(parsed.parameters.len(), 2);
+        assert_eq!(parsed.parameters[1], TypeSignature::Base(BaseType::Int));
+        assert_eq!(parsed.return_type, None);
+        assert_eq!(parsed.throws[0], type_variable("T"));
+        assert_eq!(parsed.to_string(), signature);
+
+        let parsed = MethodSignature::parse("()[TT;").unwrap();
+        assert_eq!(
+            parsed.return_type,
+            Some(TypeSignature::Array(Box::new(type_variable("T"))))
+        );
+
+        let error = MethodSignature::parse("()V^I").unwrap_err();
+        assert_eq!(
+            (error.position, error.message.as_str()),
+            (4, "expected 'L', found 'I'")
+        );
+    }
+}
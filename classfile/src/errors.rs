@@ -1,14 +1,22 @@
 use crate::attribute::StackMapFrame;
+use std::fmt::{Display, Formatter};
 
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Error {
+    UnexpectedEof,
+
+    InvalidMagic,
+
+    TrailingBytes(usize),
+
     InvalidLength,
 
     InvalidString(String),
 
     InvalidConstantTag(u8),
 
+    InvalidConstantIndex(usize),
+
     InvalidTargetType(u8),
 
     InvalidTargetInfo,
@@ -28,4 +36,79 @@ pub enum Error {
     InvalidFrameType,
 
     MismatchFrameType(u8, StackMapFrame),
+
+    TooDeeplyNested,
+
+    TooLarge(&'static str),
+
+    // code
+    InvalidOpcode(u8),
+
+    InvalidOperands(u8),
+
+    InvalidBranchTarget(i64),
 }
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnexpectedEof => write!(f, "unexpected end of input"),
+            Error::InvalidMagic => write!(f, "bad magic number"),
+            Error::TrailingBytes(n) => write!(f, "{} trailing bytes after the class file", n),
+            Error::InvalidLength => write!(f, "attribute length does not match its contents"),
+            Error::InvalidString(s) => write!(f, "invalid string {:?}", s),
+            Error::InvalidConstantTag(tag) => write!(f, "unknown constant tag {}", tag),
+            Error::InvalidConstantIndex(index) => {
+                write!(f, "invalid constant pool index {}", index)
+            }
+            Error::InvalidTargetType(target_type) => {
+                write!(f, "invalid target_type 0x{:02x}", target_type)
+            }
+            Error::InvalidTargetInfo => write!(f, "invalid target_info"),
+            Error::MismatchConstantType => write!(f, "constant pool entry has the wrong type"),
+            Error::InvalidElementValueTag(tag) => {
+                write!(f, "invalid element_value tag {:?}", tag)
+            }
+            Error::InvalidElementValue => write!(f, "invalid element_value"),
+            Error::InvalidVerificationTypeInfo => write!(f, "invalid verification_type_info"),
+            Error::InvalidFrameType => write!(f, "invalid stack map frame type"),
+            Error::MismatchFrameType(frame_type, frame) => {
+                write!(f, "frame type {} does not match {:?}", frame_type, frame)
+            }
+            Error::TooDeeplyNested => write!(f, "too deeply nested"),
+            Error::TooLarge(what) => write!(f, "too many {} to encode", what),
+            Error::InvalidOpcode(opcode) => write!(f, "invalid opcode 0x{:02x}", opcode),
+            Error::InvalidOperands(opcode) => {
+                write!(f, "invalid operands of opcode 0x{:02x}", opcode)
+            }
+            Error::InvalidBranchTarget(target) => {
+                write!(f, "branch target {} is outside the code", target)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A class file that could not be parsed.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    /// The byte offset in the input where parsing failed.
+    pub offset: usize,
+    /// The structures being parsed, outermost first, e.g.
+    /// `["method #3", "Code", "StackMapTable"]`.
+    pub context: Vec<String>,
+    pub kind: Error,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)?;
+        if !self.context.is_empty() {
+            write!(f, " in {}", self.context.join(" / "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -1,9 +1,25 @@
 use crate::attribute::Attribute;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldInfo<'a> {
     pub access_flags: u16,
     pub name_index: u16,
     pub descriptor_index: u16,
     pub attributes: Vec<Attribute<'a>>,
 }
+
+impl FieldInfo<'_> {
+    pub fn into_static(self) -> FieldInfo<'static> {
+        FieldInfo {
+            access_flags: self.access_flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(Attribute::into_static)
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,1244 @@
+//! The format checks of JVMS 4.8, which a class file must pass to be loaded
+//! beyond being parsed: constant pool entries refer to entries of the right
+//! kinds, access flags, names and descriptors are legal, and attributes appear
+//! only where and from the class file version they are defined for.
+//!
+//! The access flag rules follow HotSpot, which relaxes some of them for old
+//! class file versions.
+
+use crate::descriptor::{BaseType, FieldType, MethodDescriptor};
+use crate::{
+    mutf8, Attribute, AttributeType, ClassFile, CodeAttribute, Constant, FieldInfo, MethodInfo,
+};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::mem::discriminant;
+
+const ACC_PUBLIC: u16 = 0x0001;
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_PROTECTED: u16 = 0x0004;
+const ACC_STATIC: u16 = 0x0008;
+const ACC_FINAL: u16 = 0x0010;
+const ACC_SUPER: u16 = 0x0020;
+const ACC_SYNCHRONIZED: u16 = 0x0020;
+const ACC_VOLATILE: u16 = 0x0040;
+const ACC_BRIDGE: u16 = 0x0040;
+const ACC_TRANSIENT: u16 = 0x0080;
+const ACC_NATIVE: u16 = 0x0100;
+const ACC_INTERFACE: u16 = 0x0200;
+const ACC_ABSTRACT: u16 = 0x0400;
+const ACC_STRICT: u16 = 0x0800;
+const ACC_ANNOTATION: u16 = 0x2000;
+const ACC_ENUM: u16 = 0x4000;
+const ACC_MODULE: u16 = 0x8000;
+
+const OBJECT: &str = "java/lang/Object";
+const MAX_PARAMETER_SLOTS: usize = 255;
+const MAX_CODE_LENGTH: usize = 65535;
+
+/// A class file that parses, but breaks a rule of JVMS 4.8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    /// Where the check failed, outermost first, e.g. `["method #3", "Code"]`.
+    pub context: Vec<String>,
+    pub message: String,
+}
+
+impl FormatError {
+    fn within(mut self, context: impl Into<String>) -> Self {
+        self.context.insert(0, context.into());
+        self
+    }
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.context.is_empty() {
+            write!(f, " in {}", self.context.join(" / "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+type Result<T> = std::result::Result<T, FormatError>;
+
+fn error<T>(message: impl Into<String>) -> Result<T> {
+    Err(FormatError {
+        context: vec![],
+        message: message.into(),
+    })
+}
+
+/// Checks the class file, decoding any attributes left undecoded by
+/// [`parse_lazy`](crate::parse_lazy).
+pub fn check(class_file: &ClassFile) -> Result<()> {
+    let bootstrap_methods = class_file.attributes.iter().find_map(|attribute| {
+        let attribute = attribute.decode(class_file.constant_pool.clone()).ok()?;
+        match &attribute.attribute_type {
+            AttributeType::BootstrapMethods { bootstrap_methods } => Some(bootstrap_methods.len()),
+            _ => None,
+        }
+    });
+    let checker = Checker {
+        class_file,
+        bootstrap_methods,
+    };
+    checker
+        .constant_pool()
+        .map_err(|e| e.within("constant pool"))?;
+    let attributes = checker.attributes(&class_file.attributes, Location::Class)?;
+    checker.class(&attributes)?;
+    let mut fields = HashSet::new();
+    for (i, field) in class_file.fields.iter().enumerate() {
+        checker
+            .field(field, &mut fields)
+            .map_err(|e| e.within(format!("field #{}", i)))?;
+    }
+    let mut methods = HashSet::new();
+    for (i, method) in class_file.methods.iter().enumerate() {
+        checker
+            .method(method, &mut methods)
+            .map_err(|e| e.within(format!("method #{}", i)))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Class,
+    Field,
+    Method,
+    Code,
+    RecordComponent,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Class => write!(f, "a class"),
+            Location::Field => write!(f, "a field"),
+            Location::Method => write!(f, "a method"),
+            Location::Code => write!(f, "a Code attribute"),
+            Location::RecordComponent => write!(f, "a record component"),
+        }
+    }
+}
+
+// the first class file version of a predefined attribute and where it may
+// appear, JVMS tables 4.7-B and 4.7-C
+fn predefined(attribute_type: &AttributeType) -> Option<(u16, &'static [Location])> {
+    use Location::*;
+    const ANNOTATED: &[Location] = &[Class, Field, Method, RecordComponent];
+    Some(match attribute_type {
+        AttributeType::ConstantValue { .. } => (45, &[Field]),
+        AttributeType::Code { .. } => (45, &[Method]),
+        AttributeType::StackMapTable { .. } => (50, &[Code]),
+        AttributeType::Exceptions { .. } => (45, &[Method]),
+        AttributeType::InnerClasses { .. } => (45, &[Class]),
+        AttributeType::EnclosingMethod { .. } => (49, &[Class]),
+        AttributeType::Synthetic => (45, &[Class, Field, Method]),
+        AttributeType::Signature { .. } => (49, ANNOTATED),
+        AttributeType::SourceFile { .. } => (45, &[Class]),
+        AttributeType::SourceDebugExtension { .. } => (49, &[Class]),
+        AttributeType::LineNumberTable { .. } => (45, &[Code]),
+        AttributeType::LocalVariableTable { .. } => (45, &[Code]),
+        AttributeType::LocalVariableTypeTable { .. } => (49, &[Code]),
+        AttributeType::Deprecated => (45, &[Class, Field, Method]),
+        AttributeType::RuntimeVisibleAnnotations { .. }
+        | AttributeType::RuntimeInvisibleAnnotations { .. } => (49, ANNOTATED),
+        AttributeType::RuntimeVisibleParameterAnnotations { .. }
+        | AttributeType::RuntimeInvisibleParameterAnnotations { .. } => (49, &[Method]),
+        AttributeType::RuntimeVisibleTypeAnnotations { .. }
+        | AttributeType::RuntimeInvisibleTypeAnnotations { .. } => {
+            (52, &[Class, Field, Method, Code, RecordComponent])
+        }
+        AttributeType::AnnotationDefault { .. } => (49, &[Method]),
+        AttributeType::BootstrapMethods { .. } => (51, &[Class]),
+        AttributeType::MethodParameters { .. } => (52, &[Method]),
+        AttributeType::Module { .. }
+        | AttributeType::ModulePackages { .. }
+        | AttributeType::ModuleMainClass { .. } => (53, &[Class]),
+        AttributeType::NestHost { .. } | AttributeType::NestMembers { .. } => (55, &[Class]),
+        AttributeType::Record { .. } => (60, &[Class]),
+        AttributeType::PermittedSubclasses { .. } => (61, &[Class]),
+        AttributeType::Unknown { .. } | AttributeType::Lazy { .. } => return None,
+    })
+}
+
+// the predefined attributes that may appear more than once in one place
+fn repeatable(attribute_type: &AttributeType) -> bool {
+    matches!(
+        attribute_type,
+        AttributeType::LineNumberTable { .. }
+            | AttributeType::LocalVariableTable { .. }
+            | AttributeType::LocalVariableTypeTable { .. }
+            | AttributeType::Synthetic
+            | AttributeType::Deprecated
+    )
+}
+
+fn module_attribute(attribute_type: &AttributeType) -> bool {
+    matches!(
+        attribute_type,
+        AttributeType::Module { .. }
+            | AttributeType::ModulePackages { .. }
+            | AttributeType::ModuleMainClass { .. }
+    )
+}
+
+// the predefined attributes a module-info class may have, JVMS 4.1
+fn allowed_in_module(attribute_type: &AttributeType) -> bool {
+    module_attribute(attribute_type)
+        || matches!(
+            attribute_type,
+            AttributeType::InnerClasses { .. }
+                | AttributeType::SourceFile { .. }
+                | AttributeType::SourceDebugExtension { .. }
+                | AttributeType::RuntimeVisibleAnnotations { .. }
+                | AttributeType::RuntimeInvisibleAnnotations { .. }
+        )
+}
+
+// JVMS 4.2.2, method names also must not contain '<' or '>'
+fn unqualified_name(name: &str, method: bool) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| matches!(c, '.' | ';' | '[' | '/') || method && matches!(c, '<' | '>'))
+}
+
+fn field_name(name: &str) -> Result<()> {
+    match unqualified_name(name, false) {
+        true => Ok(()),
+        false => error(format!("illegal field name {:?}", name)),
+    }
+}
+
+fn method_name(name: &str) -> Result<()> {
+    match unqualified_name(name, true) {
+        true => Ok(()),
+        false => error(format!("illegal method name {:?}", name)),
+    }
+}
+
+// a binary name in internal form, JVMS 4.2.1, or an array type
+fn class_name(name: &str) -> Result<()> {
+    let legal = match name.starts_with('[') {
+        true => FieldType::parse(name).is_ok(),
+        false => name.split('/').all(|part| unqualified_name(part, false)),
+    };
+    match legal {
+        true => Ok(()),
+        false => error(format!("illegal class name {:?}", name)),
+    }
+}
+
+fn field_descriptor(descriptor: &str) -> Result<FieldType> {
+    FieldType::parse(descriptor).or_else(|e| error(format!("illegal field descriptor: {}", e)))
+}
+
+fn method_descriptor(descriptor: &str) -> Result<MethodDescriptor> {
+    MethodDescriptor::parse(descriptor)
+        .or_else(|e| error(format!("illegal method descriptor: {}", e)))
+}
+
+fn visibility(flags: u16) -> bool {
+    (flags & (ACC_PUBLIC | ACC_PRIVATE | ACC_PROTECTED)).count_ones() <= 1
+}
+
+struct Checker<'c, 'a> {
+    class_file: &'c ClassFile<'a>,
+    // the number of entries of the BootstrapMethods attribute, if there is one
+    bootstrap_methods: Option<usize>,
+}
+
+impl Checker<'_, '_> {
+    fn major(&self) -> u16 {
+        self.class_file.major_version
+    }
+
+    fn is_interface(&self) -> bool {
+        self.class_file.access_flags & ACC_INTERFACE != 0
+    }
+
+    fn is_module(&self) -> bool {
+        self.class_file.access_flags & ACC_MODULE != 0
+    }
+
+    fn requires(&self, version: u16, what: &str) -> Result<()> {
+        match self.major() < version {
+            true => error(format!(
+                "{} requires class file version {} or above",
+                what, version
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn constant(&self, index: u16) -> Result<&Constant<'_>> {
+        match self.class_file.constant_pool.get(index as usize) {
+            None | Some(Constant::Placeholder) => {
+                error(format!("invalid constant pool index {}", index))
+            }
+            Some(constant) => Ok(constant),
+        }
+    }
+
+    fn utf8(&self, index: u16) -> Result<String> {
+        match self.constant(index)? {
+            Constant::Utf8(bytes) => match mutf8::decode_utf16(bytes) {
+                Ok(chars) => Ok(String::from_utf16_lossy(&chars)),
+                Err(e) => error(e.to_string()),
+            },
+            _ => error(format!("constant #{} is not a CONSTANT_Utf8", index)),
+        }
+    }
+
+    fn class_name(&self, index: u16) -> Result<String> {
+        match self.constant(index)? {
+            Constant::Class { name_index } => self.utf8(*name_index),
+            _ => error(format!("constant #{} is not a CONSTANT_Class", index)),
+        }
+    }
+
+    fn name_and_type(&self, index: u16) -> Result<(String, String)> {
+        match self.constant(index)? {
+            Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            } => Ok((self.utf8(*name_index)?, self.utf8(*descriptor_index)?)),
+            _ => error(format!("constant #{} is not a CONSTANT_NameAndType", index)),
+        }
+    }
+
+    fn module(&self, index: u16) -> Result<String> {
+        match self.constant(index)? {
+            Constant::Module { name_index } => self.utf8(*name_index),
+            _ => error(format!("constant #{} is not a CONSTANT_Module", index)),
+        }
+    }
+
+    fn package(&self, index: u16) -> Result<String> {
+        match self.constant(index)? {
+            Constant::Package { name_index } => self.utf8(*name_index),
+            _ => error(format!("constant #{} is not a CONSTANT_Package", index)),
+        }
+    }
+
+    // an index that may be 0 for none
+    fn optional<T>(&self, index: u16, f: impl FnOnce(u16) -> Result<T>) -> Result<()> {
+        match index {
+            0 => Ok(()),
+            index => f(index).map(drop),
+        }
+    }
+
+    fn constant_pool(&self) -> Result<()> {
+        for (i, constant) in self.class_file.constant_pool.iter().enumerate() {
+            self.check_constant(constant)
+                .map_err(|e| e.within(format!("constant #{}", i)))?;
+        }
+        Ok(())
+    }
+
+    fn check_constant(&self, constant: &Constant) -> Result<()> {
+        match constant {
+            Constant::Placeholder
+            | Constant::Integer(_)
+            | Constant::Float(_)
+            | Constant::Long(_)
+            | Constant::Double(_) => Ok(()),
+            Constant::Utf8(bytes) => match mutf8::decode_utf16(bytes) {
+                Ok(_) => Ok(()),
+                Err(e) => error(e.to_string()),
+            },
+            Constant::Class { name_index } => class_name(&self.utf8(*name_index)?),
+            Constant::String { string_index } => self.utf8(*string_index).map(drop),
+            Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                self.class_name(*class_index)?;
+                let (name, descriptor) = self.name_and_type(*name_and_type_index)?;
+                field_name(&name)?;
+                field_descriptor(&descriptor).map(drop)
+            }
+            Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                self.class_name(*class_index)?;
+                let (name, descriptor) = self.name_and_type(*name_and_type_index)?;
+                let descriptor = method_descriptor(&descriptor)?;
+                match name.as_str() {
+                    "<init>" if descriptor.return_type.is_some() => {
+                        error("<init> must return void")
+                    }
+                    "<init>" => Ok(()),
+                    _ => method_name(&name),
+                }
+            }
+            Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                self.class_name(*class_index)?;
+                let (name, descriptor) = self.name_and_type(*name_and_type_index)?;
+                method_name(&name)?;
+                method_descriptor(&descriptor).map(drop)
+            }
+            Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            } => {
+                self.utf8(*name_index)?;
+                self.utf8(*descriptor_index).map(drop)
+            }
+            Constant::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => {
+                self.requires(51, "CONSTANT_MethodHandle")?;
+                self.method_handle(*reference_kind, *reference_index)
+            }
+            Constant::MethodType { descriptor_index } => {
+                self.requires(51, "CONSTANT_MethodType")?;
+                method_descriptor(&self.utf8(*descriptor_index)?).map(drop)
+            }
+            Constant::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                self.requires(55, "CONSTANT_Dynamic")?;
+                self.bootstrap_method(*bootstrap_method_attr_index)?;
+                let (name, descriptor) = self.name_and_type(*name_and_type_index)?;
+                field_name(&name)?;
+                field_descriptor(&descriptor).map(drop)
+            }
+            Constant::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                self.requires(51, "CONSTANT_InvokeDynamic")?;
+                self.bootstrap_method(*bootstrap_method_attr_index)?;
+                let (name, descriptor) = self.name_and_type(*name_and_type_index)?;
+                method_name(&name)?;
+                method_descriptor(&descriptor).map(drop)
+            }
+            Constant::Module { name_index } | Constant::Package { name_index } => {
+                let what = match constant {
+                    Constant::Module { .. } => "CONSTANT_Module",
+                    _ => "CONSTANT_Package",
+                };
+                self.requires(53, what)?;
+                if !self.is_module() {
+                    return error(format!("{} outside of a module-info class", what));
+                }
+                self.utf8(*name_index).map(drop)
+            }
+        }
+    }
+
+    // JVMS 4.4.8
+    fn method_handle(&self, kind: u8, index: u16) -> Result<()> {
+        let name_and_type_index = match (kind, self.constant(index)?) {
+            (1..=4, Constant::FieldRef { .. }) => return Ok(()),
+            (
+                5 | 8,
+                Constant::MethodRef {
+                    name_and_type_index,
+                    ..
+                },
+            )
+            | (
+                6 | 7,
+                Constant::MethodRef {
+                    name_and_type_index,
+                    ..
+                },
+            )
+            | (
+                9,
+                Constant::InterfaceMethodRef {
+                    name_and_type_index,
+                    ..
+                },
+            ) => name_and_type_index,
+            (
+                6 | 7,
+                Constant::InterfaceMethodRef {
+                    name_and_type_index,
+                    ..
+                },
+            ) if self.major() >= 52 => name_and_type_index,
+            (1..=9, _) => {
+                return error(format!(
+                    "method handle of kind {} refers to the wrong kind of constant #{}",
+                    kind, index
+                ))
+            }
+            _ => return error(format!("illegal method handle kind {}", kind)),
+        };
+        let (name, _) = self.name_and_type(*name_and_type_index)?;
+        match (kind, name.as_str()) {
+            (8, "<init>") => Ok(()),
+            (8, _) => error("a newInvokeSpecial method handle must refer to <init>"),
+            (_, "<init>" | "<clinit>") => error(format!(
+                "a method handle of kind {} must not refer to {}",
+                kind, name
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn bootstrap_method(&self, index: u16) -> Result<()> {
+        match self.bootstrap_methods {
+            None => error("missing BootstrapMethods attribute"),
+            Some(count) if index as usize >= count => {
+                error(format!("bootstrap method index {} is out of range", index))
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    // checks and decodes the attributes of one place
+    fn attributes<'s>(
+        &self,
+        attributes: &'s [Attribute],
+        location: Location,
+    ) -> Result<Vec<Cow<'s, Attribute<'s>>>> {
+        let mut decoded = Vec::with_capacity(attributes.len());
+        let mut seen = HashSet::new();
+        for (i, attribute) in attributes.iter().enumerate() {
+            let name = self
+                .utf8(attribute.attribute_name_index)
+                .map_err(|e| e.within(format!("attribute #{}", i)))?;
+            let attribute = attribute
+                .decode(self.class_file.constant_pool.clone())
+                .map_err(|e| FormatError {
+                    context: vec![name.clone()],
+                    message: e.to_string(),
+                })?;
+            let attribute_type = &attribute.attribute_type;
+            if let Some((version, locations)) = predefined(attribute_type) {
+                self.requires(version, &name)?;
+                if !locations.contains(&location) {
+                    return error(format!("{} is not allowed in {}", name, location));
+                }
+                if module_attribute(attribute_type) && !self.is_module() {
+                    return error(format!("{} outside of a module-info class", name));
+                }
+                if self.is_module()
+                    && location == Location::Class
+                    && !allowed_in_module(attribute_type)
+                {
+                    return error(format!("{} is not allowed in a module-info class", name));
+                }
+                if !repeatable(attribute_type) && !seen.insert(discriminant(attribute_type)) {
+                    return error(format!("duplicate {} attribute", name));
+                }
+            }
+            self.attribute(attribute_type).map_err(|e| e.within(name))?;
+            decoded.push(attribute);
+        }
+        Ok(decoded)
+    }
+
+    // the constant pool references of an attribute, those of Code and
+    // ConstantValue are checked with their method and field
+    fn attribute(&self, attribute_type: &AttributeType) -> Result<()> {
+        match attribute_type {
+            AttributeType::Exceptions {
+                exception_index_table,
+            } => {
+                for index in exception_index_table {
+                    self.class_name(*index)?;
+                }
+            }
+            AttributeType::InnerClasses { classes } => {
+                for class in classes {
+                    self.class_name(class.inner_class_info_index)?;
+                    self.optional(class.outer_class_info_index, |i| self.class_name(i))?;
+                    self.optional(class.inner_name_index, |i| self.utf8(i))?;
+                }
+            }
+            AttributeType::EnclosingMethod {
+                class_index,
+                method_index,
+            } => {
+                self.class_name(*class_index)?;
+                self.optional(*method_index, |i| self.name_and_type(i))?;
+            }
+            AttributeType::Signature { signature_index } => {
+                self.utf8(*signature_index)?;
+            }
+            AttributeType::SourceFile { sourcefile_index } => {
+                self.utf8(*sourcefile_index)?;
+            }
+            AttributeType::BootstrapMethods { bootstrap_methods } => {
+                for bootstrap_method in bootstrap_methods {
+                    match self.constant(bootstrap_method.bootstrap_method_ref)? {
+                        Constant::MethodHandle { .. } => {}
+                        _ => {
+                            return error(format!(
+                                "constant #{} is not a CONSTANT_MethodHandle",
+                                bootstrap_method.bootstrap_method_ref
+                            ))
+                        }
+                    }
+                    for index in &bootstrap_method.bootstrap_arguments {
+                        match self.constant(*index)? {
+                            Constant::Integer(_)
+                            | Constant::Float(_)
+                            | Constant::Long(_)
+                            | Constant::Double(_)
+                            | Constant::Class { .. }
+                            | Constant::String { .. }
+                            | Constant::MethodHandle { .. }
+                            | Constant::MethodType { .. }
+                            | Constant::Dynamic { .. } => {}
+                            _ => return error(format!("constant #{} is not loadable", index)),
+                        }
+                    }
+                }
+            }
+            AttributeType::MethodParameters { parameters } => {
+                for parameter in parameters {
+                    self.optional(parameter.name_index, |i| self.utf8(i))?;
+                }
+            }
+            AttributeType::Module {
+                module_name_index,
+                module_version_index,
+                requires,
+                exports,
+                opens,
+                uses,
+                provides,
+                ..
+            } => {
+                self.module(*module_name_index)?;
+                self.optional(*module_version_index, |i| self.utf8(i))?;
+                for require in requires {
+                    self.module(require.require_index)?;
+                    self.optional(require.require_version_index, |i| self.utf8(i))?;
+                }
+                for export in exports {
+                    self.package(export.export_index)?;
+                    for index in &export.export_to_index {
+                        self.module(*index)?;
+                    }
+                }
+                for open in opens {
+                    self.package(open.open_index)?;
+                    for index in &open.open_to_index {
+                        self.module(*index)?;
+                    }
+                }
+                for index in uses {
+                    self.class_name(*index)?;
+                }
+                for provide in provides {
+                    self.class_name(provide.provide_index)?;
+                    for index in &provide.provide_with_index {
+                        self.class_name(*index)?;
+                    }
+                }
+            }
+            AttributeType::ModulePackages { package_index } => {
+                for index in package_index {
+                    self.package(*index)?;
+                }
+            }
+            AttributeType::ModuleMainClass { main_class_index } => {
+                self.class_name(*main_class_index)?;
+            }
+            AttributeType::NestHost { host_class_index } => {
+                self.class_name(*host_class_index)?;
+            }
+            AttributeType::NestMembers { classes }
+            | AttributeType::PermittedSubclasses { classes } => {
+                for index in classes {
+                    self.class_name(*index)?;
+                }
+            }
+            AttributeType::Record { components } => {
+                for (i, component) in components.iter().enumerate() {
+                    let check = || {
+                        field_name(&self.utf8(component.name_index)?)?;
+                        field_descriptor(&self.utf8(component.descriptor_index)?)?;
+                        self.attributes(&component.attributes, Location::RecordComponent)
+                    };
+                    check().map_err(|e| e.within(format!("record component #{}", i)))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn class(&self, attributes: &[Cow<Attribute>]) -> Result<()> {
+        let class_file = self.class_file;
+        let flags = class_file.access_flags;
+        if self.is_module() {
+            self.requires(53, "ACC_MODULE")?;
+            if flags != ACC_MODULE {
+                return error(format!("illegal module-info modifiers 0x{:04x}", flags));
+            }
+            if self.class_name(class_file.this_class)? != "module-info" {
+                return error("a class with ACC_MODULE must be named module-info");
+            }
+            if class_file.super_class != 0
+                || !class_file.interfaces.is_empty()
+                || !class_file.fields.is_empty()
+                || !class_file.methods.is_empty()
+            {
+                return error(
+                    "a module-info class has no superclass, interfaces, fields or methods",
+                );
+            }
+            if !attributes
+                .iter()
+                .any(|attribute| matches!(attribute.attribute_type, AttributeType::Module { .. }))
+            {
+                return error("missing Module attribute");
+            }
+            return Ok(());
+        }
+
+        let is = |flag: u16| flags & flag != 0;
+        let legacy = self.major() < 49;
+        if is(ACC_ABSTRACT) && is(ACC_FINAL)
+            || is(ACC_INTERFACE) && !is(ACC_ABSTRACT)
+            || is(ACC_INTERFACE) && !legacy && (is(ACC_SUPER) || is(ACC_ENUM))
+            || !is(ACC_INTERFACE) && !legacy && is(ACC_ANNOTATION)
+        {
+            return error(format!("illegal class modifiers 0x{:04x}", flags));
+        }
+
+        let this_class = self
+            .class_name(class_file.this_class)
+            .map_err(|e| e.within("this_class"))?;
+        if this_class.starts_with('[') {
+            return error(format!("this_class {:?} is an array type", this_class));
+        }
+        match class_file.super_class {
+            0 if this_class == OBJECT => {}
+            0 => return error("only java/lang/Object has no superclass"),
+            index => {
+                let super_class = self
+                    .class_name(index)
+                    .map_err(|e| e.within("super_class"))?;
+                if super_class.starts_with('[') {
+                    return error(format!("superclass {:?} is an array type", super_class));
+                }
+                if self.is_interface() && super_class != OBJECT {
+                    return error("the superclass of an interface must be java/lang/Object");
+                }
+            }
+        }
+        let mut interfaces = HashSet::new();
+        for (i, index) in class_file.interfaces.iter().enumerate() {
+            let interface = self
+                .class_name(*index)
+                .map_err(|e| e.within(format!("interface #{}", i)))?;
+            if interface.starts_with('[') {
+                return error(format!("interface {:?} is an array type", interface));
+            }
+            if !interfaces.insert(interface.clone()) {
+                return error(format!("duplicate interface {}", interface));
+            }
+        }
+        Ok(())
+    }
+
+    fn field(&self, field: &FieldInfo, fields: &mut HashSet<(String, String)>) -> Result<()> {
+        let name = self.utf8(field.name_index)?;
+        field_name(&name)?;
+        let descriptor = self.utf8(field.descriptor_index)?;
+        let field_type = field_descriptor(&descriptor)?;
+
+        let flags = field.access_flags;
+        let is = |flag: u16| flags & flag != 0;
+        let legal = match self.is_interface() {
+            true => {
+                is(ACC_PUBLIC)
+                    && is(ACC_STATIC)
+                    && is(ACC_FINAL)
+                    && !is(ACC_PRIVATE | ACC_PROTECTED | ACC_VOLATILE | ACC_TRANSIENT)
+                    && !(self.major() >= 49 && is(ACC_ENUM))
+            }
+            false => visibility(flags) && !(is(ACC_FINAL) && is(ACC_VOLATILE)),
+        };
+        if !legal {
+            return error(format!("illegal field modifiers 0x{:04x}", flags));
+        }
+        if !fields.insert((name.clone(), descriptor.clone())) {
+            return error(format!("duplicate field {} {}", name, descriptor));
+        }
+
+        for attribute in self.attributes(&field.attributes, Location::Field)? {
+            if let AttributeType::ConstantValue {
+                constant_value_index,
+            } = attribute.attribute_type
+            {
+                self.constant_value(constant_value_index, &field_type)
+                    .map_err(|e| e.within("ConstantValue"))?;
+            }
+        }
+        Ok(())
+    }
+
+    // JVMS 4.7.2
+    fn constant_value(&self, index: u16, field_type: &FieldType) -> Result<()> {
+        let matches = match (field_type, self.constant(index)?) {
+            (FieldType::Base(BaseType::Long), Constant::Long(_))
+            | (FieldType::Base(BaseType::Float), Constant::Float(_))
+            | (FieldType::Base(BaseType::Double), Constant::Double(_)) => true,
+            (
+                FieldType::Base(
+                    BaseType::Int
+                    | BaseType::Short
+                    | BaseType::Char
+                    | BaseType::Byte
+                    | BaseType::Boolean,
+                ),
+                Constant::Integer(_),
+            ) => true,
+            (FieldType::Object(name), Constant::String { .. }) => name == "java/lang/String",
+            _ => false,
+        };
+        match matches {
+            true => Ok(()),
+            false => error(format!(
+                "constant #{} does not match the field type {}",
+                index, field_type
+            )),
+        }
+    }
+
+    fn method(&self, method: &MethodInfo, methods: &mut HashSet<(String, String)>) -> Result<()> {
+        let name = self.utf8(method.name_index)?;
+        let descriptor = self.utf8(method.descriptor_index)?;
+        let parsed = method_descriptor(&descriptor)?;
+
+        let mut flags = method.access_flags;
+        match name.as_str() {
+            "<init>" => {
+                if self.is_interface() {
+                    return error("an interface cannot have an <init> method");
+                }
+                if parsed.return_type.is_some() {
+                    return error("<init> must return void");
+                }
+                self.method_flags(flags, &name)?;
+            }
+            // the other flags of a class initializer are ignored, JVMS 4.6
+            "<clinit>" => {
+                if parsed.return_type.is_some()
+                    || self.major() >= 51 && !parsed.parameters.is_empty()
+                {
+                    return error("<clinit> must have the descriptor ()V");
+                }
+                if self.major() >= 51 && flags & ACC_STATIC == 0 {
+                    return error("<clinit> is not static");
+                }
+                flags = ACC_STATIC;
+            }
+            _ => {
+                method_name(&name)?;
+                self.method_flags(flags, &name)?;
+            }
+        }
+        let slots = parsed.parameter_slots() + usize::from(flags & ACC_STATIC == 0);
+        if slots > MAX_PARAMETER_SLOTS {
+            return error(format!(
+                "the parameters take {} slots, at most {} are allowed",
+                slots, MAX_PARAMETER_SLOTS
+            ));
+        }
+        if !methods.insert((name.clone(), descriptor.clone())) {
+            return error(format!("duplicate method {}{}", name, descriptor));
+        }
+
+        let attributes = self.attributes(&method.attributes, Location::Method)?;
+        let code = attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute_type {
+                AttributeType::Code { code } => Some(code),
+                _ => None,
+            });
+        match (code, flags & (ACC_ABSTRACT | ACC_NATIVE) != 0) {
+            (Some(_), true) => error("abstract and native methods must not have a Code attribute"),
+            (None, false) => error("missing Code attribute"),
+            (Some(code), false) => self.code(code, slots).map_err(|e| e.within("Code")),
+            (None, true) => Ok(()),
+        }
+    }
+
+    fn method_flags(&self, flags: u16, name: &str) -> Result<()> {
+        let is = |flag: u16| flags & flag != 0;
+        let major = self.major();
+        // ACC_STRICT is obsolete from version 61 on
+        let strict = major < 61 && is(ACC_STRICT);
+        let legal = if self.is_interface() {
+            if major >= 52 {
+                is(ACC_PUBLIC) != is(ACC_PRIVATE)
+                    && !is(ACC_PROTECTED | ACC_FINAL | ACC_SYNCHRONIZED | ACC_NATIVE)
+                    && !(is(ACC_ABSTRACT) && (is(ACC_PRIVATE | ACC_STATIC) || strict))
+            } else if major >= 49 {
+                is(ACC_PUBLIC)
+                    && is(ACC_ABSTRACT)
+                    && !is(ACC_PRIVATE | ACC_PROTECTED | ACC_STATIC | ACC_FINAL)
+                    && !is(ACC_SYNCHRONIZED | ACC_NATIVE | ACC_STRICT)
+            } else {
+                is(ACC_PUBLIC) && is(ACC_ABSTRACT) && !is(ACC_STATIC | ACC_FINAL | ACC_NATIVE)
+            }
+        } else if !visibility(flags) {
+            false
+        } else if name == "<init>" {
+            !(is(ACC_STATIC | ACC_FINAL | ACC_SYNCHRONIZED | ACC_NATIVE | ACC_ABSTRACT)
+                || major >= 49 && is(ACC_BRIDGE))
+        } else if is(ACC_ABSTRACT) {
+            !is(ACC_FINAL | ACC_NATIVE | ACC_PRIVATE | ACC_STATIC)
+                && !(major >= 49 && (is(ACC_SYNCHRONIZED) || strict))
+        } else {
+            true
+        };
+        match legal {
+            true => Ok(()),
+            false => error(format!("illegal method modifiers 0x{:04x}", flags)),
+        }
+    }
+
+    // JVMS 4.7.3, `slots` being those of the parameters and `this`
+    fn code(&self, code: &CodeAttribute, slots: usize) -> Result<()> {
+        let length = code.code.len();
+        if length == 0 || length > MAX_CODE_LENGTH {
+            return error(format!(
+                "code length {} is not between 1 and {}",
+                length, MAX_CODE_LENGTH
+            ));
+        }
+        if (code.max_locals as usize) < slots {
+            return error(format!(
+                "max_locals {} is less than the {} slots of the parameters",
+                code.max_locals, slots
+            ));
+        }
+        for (i, handler) in code.exception_table.iter().enumerate() {
+            let check = || {
+                let (start, end) = (handler.start_pc as usize, handler.end_pc as usize);
+                if start >= end || end > length || handler.handler_pc as usize >= length {
+                    return error("exception handler is outside the code");
+                }
+                self.optional(handler.catch_type, |i| self.class_name(i))
+            };
+            check().map_err(|e| e.within(format!("exception handler #{}", i)))?;
+        }
+
+        for attribute in self.attributes(&code.attributes, Location::Code)? {
+            let check = |start_pc: u16, length_pc: u16, name_index: u16| {
+                if start_pc as usize + length_pc as usize > length {
+                    return error(format!("start_pc {} is outside the code", start_pc));
+                }
+                field_name(&self.utf8(name_index)?)
+            };
+            let name = || {
+                self.utf8(attribute.attribute_name_index)
+                    .unwrap_or_default()
+            };
+            match &attribute.attribute_type {
+                AttributeType::LineNumberTable { line_number_table } => {
+                    for line_number in line_number_table {
+                        if line_number.start_pc as usize >= length {
+                            return error(format!(
+                                "start_pc {} is outside the code",
+                                line_number.start_pc
+                            ))
+                            .map_err(|e| e.within(name()));
+                        }
+                    }
+                }
+                AttributeType::LocalVariableTable {
+                    local_variable_table,
+                } => {
+                    for variable in local_variable_table {
+                        check(variable.start_pc, variable.length, variable.name_index)
+                            .and_then(|_| field_descriptor(&self.utf8(variable.descriptor_index)?))
+                            .map_err(|e| e.within(name()))?;
+                    }
+                }
+                AttributeType::LocalVariableTypeTable {
+                    local_variable_type_table,
+                } => {
+                    for variable in local_variable_type_table {
+                        check(variable.start_pc, variable.length, variable.name_index)
+                            .and_then(|_| self.utf8(variable.signature_index))
+                            .map_err(|e| e.within(name()))?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{ClassBuilder, ACC_STATIC};
+    use crate::format::{check, FormatError};
+    use crate::{parse, parse_lazy, Attribute, AttributeType, ClassFile, Constant};
+    use std::borrow::Cow;
+    use std::io::Read;
+    use std::sync::Arc;
+
+    fn gauss_test() -> Vec<u8> {
+        std::fs::read("../data/jvm8/GaussTest.class").unwrap()
+    }
+
+    fn utf8(class_file: &mut ClassFile, s: &'static str) -> u16 {
+        let constant_pool = Arc::make_mut(&mut class_file.constant_pool);
+        constant_pool.push(Constant::Utf8(Cow::Borrowed(s.as_bytes())));
+        (constant_pool.len() - 1) as u16
+    }
+
+    fn attribute(attribute_name_index: u16, attribute_type: AttributeType) -> Attribute {
+        Attribute {
+            attribute_name_index,
+            attribute_length: 0,
+            attribute_type,
+        }
+    }
+
+    fn assert_error(class_file: &ClassFile, context: &[&str], message: &str) {
+        assert_eq!(
+            check(class_file),
+            Err(FormatError {
+                context: context.iter().map(|s| s.to_string()).collect(),
+                message: message.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let bytes = gauss_test();
+        check(&parse(&bytes).unwrap()).unwrap();
+        check(&parse_lazy(&bytes).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_class() {
+        let bytes = gauss_test();
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.access_flags = 0x0201;
+        assert_error(&class_file, &[], "illegal class modifiers 0x0201");
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.interfaces = vec![2, 8, 2];
+        assert_error(&class_file, &[], "duplicate interface java/lang/Object");
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.super_class = 4;
+        assert_error(
+            &class_file,
+            &["super_class"],
+            "constant #4 is not a CONSTANT_Class",
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.major_version = 53;
+        class_file.access_flags = 0x8000;
+        assert_error(
+            &class_file,
+            &[],
+            "a class with ACC_MODULE must be named module-info",
+        );
+        class_file.major_version = 52;
+        assert_error(
+            &class_file,
+            &[],
+            "ACC_MODULE requires class file version 53 or above",
+        );
+    }
+
+    #[test]
+    fn test_constant_pool() {
+        let bytes = gauss_test();
+        let mut class_file = parse(&bytes).unwrap();
+        Arc::make_mut(&mut class_file.constant_pool)[1] = Constant::MethodRef {
+            class_index: 4,
+            name_and_type_index: 3,
+        };
+        assert_error(
+            &class_file,
+            &["constant pool", "constant #1"],
+            "constant #4 is not a CONSTANT_Class",
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        Arc::make_mut(&mut class_file.constant_pool)[3] = Constant::NameAndType {
+            name_index: 5,
+            descriptor_index: 12,
+        };
+        assert_error(
+            &class_file,
+            &["constant pool", "constant #1"],
+            "illegal method descriptor: expected '(', found 'L' at position 0 in \"Ljava/io/PrintStream;\"",
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        Arc::make_mut(&mut class_file.constant_pool)[1] = Constant::InvokeDynamic {
+            bootstrap_method_attr_index: 0,
+            name_and_type_index: 15,
+        };
+        assert_error(
+            &class_file,
+            &["constant pool", "constant #1"],
+            "missing BootstrapMethods attribute",
+        );
+    }
+
+    #[test]
+    fn test_fields() {
+        let bytes = ClassBuilder::new("A")
+            .field(ACC_STATIC, "count", "I")
+            .build()
+            .unwrap();
+        let mut class_file = parse(&bytes).unwrap();
+        check(&class_file).unwrap();
+
+        class_file.fields[0].access_flags = 0x0003;
+        assert_error(&class_file, &["field #0"], "illegal field modifiers 0x0003");
+
+        let mut class_file = parse(&bytes).unwrap();
+        let name = utf8(&mut class_file, "ConstantValue");
+        let value = utf8(&mut class_file, "1");
+        let constant_value = AttributeType::ConstantValue {
+            constant_value_index: value,
+        };
+        class_file.fields[0]
+            .attributes
+            .push(attribute(name, constant_value));
+        assert_error(
+            &class_file,
+            &["field #0", "ConstantValue"],
+            &format!("constant #{} does not match the field type I", value),
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.fields.push(class_file.fields[0].clone());
+        assert_error(&class_file, &["field #1"], "duplicate field count I");
+    }
+
+    #[test]
+    fn test_methods() {
+        let bytes = gauss_test();
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.methods.push(class_file.methods[1].clone());
+        assert_error(
+            &class_file,
+            &["method #2"],
+            "duplicate method main([Ljava/lang/String;)V",
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.methods[0].access_flags = 0x0009;
+        assert_error(
+            &class_file,
+            &["method #0"],
+            "illegal method modifiers 0x0009",
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.methods[1].access_flags = 0x0409;
+        assert_error(
+            &class_file,
+            &["method #1"],
+            "illegal method modifiers 0x0409",
+        );
+        class_file.methods[1].access_flags = 0x0401;
+        assert_error(
+            &class_file,
+            &["method #1"],
+            "abstract and native methods must not have a Code attribute",
+        );
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.methods[1].name_index = 22;
+        class_file.methods[1].attributes.clear();
+        assert_error(&class_file, &["method #1"], "missing Code attribute");
+    }
+
+    #[test]
+    fn test_attributes() {
+        let bytes = gauss_test();
+        let mut class_file = parse(&bytes).unwrap();
+        let stack_map_table = AttributeType::StackMapTable { entries: vec![] };
+        class_file.attributes.push(attribute(25, stack_map_table));
+        assert_error(&class_file, &[], "StackMapTable is not allowed in a class");
+
+        let mut class_file = parse(&bytes).unwrap();
+        class_file.attributes.push(class_file.attributes[0].clone());
+        assert_error(&class_file, &[], "duplicate SourceFile attribute");
+
+        let mut class_file = parse(&bytes).unwrap();
+        let name = utf8(&mut class_file, "ModulePackages");
+        let module_packages = AttributeType::ModulePackages {
+            package_index: vec![],
+        };
+        class_file.attributes.push(attribute(name, module_packages));
+        assert_error(
+            &class_file,
+            &[],
+            "ModulePackages requires class file version 53 or above",
+        );
+        class_file.major_version = 53;
+        assert_error(
+            &class_file,
+            &[],
+            "ModulePackages outside of a module-info class",
+        );
+
+        // the line number table refers to offsets past the code
+        let mut class_file = parse(&bytes).unwrap();
+        let code = &mut class_file.methods[1].attributes[0].attribute_type;
+        if let AttributeType::Code { code } = code {
+            code.code = Cow::Borrowed(&[0xb1]);
+        }
+        assert_error(
+            &class_file,
+            &["method #1", "Code", "LineNumberTable"],
+            "start_pc 2 is outside the code",
+        );
+    }
+
+    #[test]
+    fn test_rt_jar() {
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut failures = vec![];
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            if let Err(e) = check(&crate::parse(&bytes).unwrap()) {
+                failures.push(format!("{}: {}", file.name(), e));
+            }
+        }
+        assert!(
+            failures.is_empty(),
+            "{:#?}",
+            &failures[..failures.len().min(20)]
+        );
+    }
+}
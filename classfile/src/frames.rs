@@ -0,0 +1,1255 @@
+//! Computes the `StackMapTable` of a method, JVMS 4.7.4, for code that was
+//! generated or rewritten and so has no valid frames.
+//!
+//! The types of locals and operand stack are found by data-flow analysis, like
+//! the type-inference verifier does, JVMS 4.10.2. Where control flow merges,
+//! two classes become their closest common superclass, which is looked up in a
+//! [`ClassHierarchy`].
+
+use crate::bytecode::{decode_all, Instruction, Operand};
+use crate::descriptor::{BaseType, FieldType, MethodDescriptor};
+use crate::{
+    get_str, ClassFile, Constant, ConstantPoolRef, MethodInfo, StackMap, StackMapFrame,
+    VerificationTypeInfo,
+};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+const OBJECT: &str = "java/lang/Object";
+const THROWABLE: &str = "java/lang/Throwable";
+const ACC_STATIC: u16 = 0x0008;
+
+/// What frame computation needs to know about other classes.
+pub trait ClassHierarchy {
+    /// The name of the direct superclass, `None` for `java/lang/Object` or an
+    /// unknown class.
+    fn super_class(&self, class: &str) -> Option<String>;
+
+    fn is_interface(&self, class: &str) -> bool;
+}
+
+/// Knows no classes, so two different classes merge to `java/lang/Object`.
+pub struct EmptyHierarchy;
+
+impl ClassHierarchy for EmptyHierarchy {
+    fn super_class(&self, _: &str) -> Option<String> {
+        None
+    }
+
+    fn is_interface(&self, _: &str) -> bool {
+        false
+    }
+}
+
+/// A verification type, JVMS 4.10.1.2, naming classes rather than referring
+/// to the constant pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// An object created by the `new` at the given offset, not yet initialized.
+    Uninitialized(u16),
+    /// A class or interface by its internal name, or an array type by its
+    /// descriptor.
+    Object(String),
+}
+
+impl VerificationType {
+    fn is_category2(&self) -> bool {
+        matches!(self, VerificationType::Long | VerificationType::Double)
+    }
+
+    fn merge(&self, other: &VerificationType, hierarchy: &dyn ClassHierarchy) -> Self {
+        match (self, other) {
+            _ if self == other => self.clone(),
+            (VerificationType::Null, VerificationType::Object(_)) => other.clone(),
+            (VerificationType::Object(_), VerificationType::Null) => self.clone(),
+            (VerificationType::Object(a), VerificationType::Object(b)) => {
+                VerificationType::Object(common_super_class(a, b, hierarchy))
+            }
+            _ => VerificationType::Top,
+        }
+    }
+}
+
+impl From<&FieldType> for VerificationType {
+    fn from(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::Base(BaseType::Float) => VerificationType::Float,
+            FieldType::Base(BaseType::Long) => VerificationType::Long,
+            FieldType::Base(BaseType::Double) => VerificationType::Double,
+            FieldType::Base(_) => VerificationType::Integer,
+            FieldType::Object(name) => VerificationType::Object(name.clone()),
+            FieldType::Array(_) => VerificationType::Object(field_type.to_string()),
+        }
+    }
+}
+
+impl Display for VerificationType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationType::Top => write!(f, "top"),
+            VerificationType::Integer => write!(f, "int"),
+            VerificationType::Float => write!(f, "float"),
+            VerificationType::Long => write!(f, "long"),
+            VerificationType::Double => write!(f, "double"),
+            VerificationType::Null => write!(f, "null"),
+            VerificationType::UninitializedThis => write!(f, "uninitialized this"),
+            VerificationType::Uninitialized(offset) => write!(f, "uninitialized {}", offset),
+            VerificationType::Object(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The types before the instruction at `offset`, as a `StackMapTable` holds
+/// them: a long or double is a single entry and trailing `Top` locals are left
+/// out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub offset: u16,
+    pub locals: Vec<VerificationType>,
+    pub stack: Vec<VerificationType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameError {
+    /// The offset of the offending instruction, if there is one.
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(offset) = self.offset {
+            write!(f, "at offset {}: ", offset)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+fn error(offset: Option<usize>) -> impl Fn(String) -> FrameError {
+    move |message| FrameError { offset, message }
+}
+
+/// The frames a `StackMapTable` needs for `method`: at every branch target,
+/// exception handler and instruction following an unconditional branch.
+///
+/// Code that is never reached is an error, since its frame is unknown.
+pub fn compute_frames(
+    class_file: &ClassFile,
+    method: &MethodInfo,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<Vec<Frame>, FrameError> {
+    Analysis::new(class_file, method, hierarchy)?.run()
+}
+
+/// Computes the frames of `method` and compresses them into stack map
+/// entries. `class_index` gives the constant pool index of the `Class` entry
+/// for a class name.
+pub fn stack_map_table(
+    class_file: &ClassFile,
+    method: &MethodInfo,
+    hierarchy: &dyn ClassHierarchy,
+    class_index: impl FnMut(&str) -> u16,
+) -> Result<Vec<StackMap>, FrameError> {
+    let (initial, frames) = analyze(class_file, method, hierarchy)?;
+    Ok(compress(&initial, &frames, class_index))
+}
+
+/// The locals of the implicit initial frame and the computed frames.
+pub(crate) fn analyze(
+    class_file: &ClassFile,
+    method: &MethodInfo,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<(Vec<VerificationType>, Vec<Frame>), FrameError> {
+    let analysis = Analysis::new(class_file, method, hierarchy)?;
+    Ok((encode_locals(&analysis.initial.locals), analysis.run()?))
+}
+
+// the types before an instruction, a long or double takes two locals, the
+// second being Top, but one stack entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    locals: Vec<VerificationType>,
+    stack: Vec<VerificationType>,
+}
+
+impl State {
+    fn merge(&self, other: &State, hierarchy: &dyn ClassHierarchy) -> Result<State, String> {
+        if self.stack.len() != other.stack.len() {
+            return Err("inconsistent stack height".to_string());
+        }
+        let mut stack = Vec::with_capacity(self.stack.len());
+        for (a, b) in self.stack.iter().zip(other.stack.iter()) {
+            match a.merge(b, hierarchy) {
+                VerificationType::Top => {
+                    return Err(format!("mismatched stack types {} and {}", a, b))
+                }
+                merged => stack.push(merged),
+            }
+        }
+        let locals = self
+            .locals
+            .iter()
+            .zip(other.locals.iter())
+            .map(|(a, b)| a.merge(b, hierarchy))
+            .collect();
+        Ok(State { locals, stack })
+    }
+
+    fn pop(&mut self) -> Result<VerificationType, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "operand stack underflow".to_string())
+    }
+
+    fn pop_n(&mut self, n: usize) -> Result<(), String> {
+        for _ in 0..n {
+            self.pop()?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, vtype: VerificationType) {
+        self.stack.push(vtype);
+    }
+
+    fn store(&mut self, index: usize, vtype: VerificationType) -> Result<(), String> {
+        let size = if vtype.is_category2() { 2 } else { 1 };
+        if index + size > self.locals.len() {
+            return Err(format!("local {} exceeds max_locals", index + size - 1));
+        }
+        // overwriting half of a long or double invalidates the other half
+        if index > 0 && self.locals[index - 1].is_category2() {
+            self.locals[index - 1] = VerificationType::Top;
+        }
+        if size == 2 {
+            self.locals[index + 1] = VerificationType::Top;
+        }
+        self.locals[index] = vtype;
+        Ok(())
+    }
+
+    fn replace(&mut self, from: &VerificationType, to: &VerificationType) {
+        for vtype in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if vtype == from {
+                *vtype = to.clone();
+            }
+        }
+    }
+}
+
+struct Analysis<'a, 'b> {
+    class_file: &'b ClassFile<'a>,
+    class_name: String,
+    code: &'b [u8],
+    exception_table: Vec<(usize, usize, usize, VerificationType)>,
+    hierarchy: &'b dyn ClassHierarchy,
+    initial: State,
+}
+
+impl<'a, 'b> Analysis<'a, 'b> {
+    fn new(
+        class_file: &'b ClassFile<'a>,
+        method: &'b MethodInfo<'a>,
+        hierarchy: &'b dyn ClassHierarchy,
+    ) -> Result<Self, FrameError> {
+        let cp = &class_file.constant_pool;
+        let e = error(None);
+        let this_class = class_name(cp, class_file.this_class).map_err(&e)?;
+        let code = method
+            .code_attribute()
+            .ok_or_else(|| e("method has no Code attribute".to_string()))?;
+        let name = get_str(cp.clone(), method.name_index as usize).map_err(|x| e(x.to_string()))?;
+        let descriptor =
+            get_str(cp.clone(), method.descriptor_index as usize).map_err(|x| e(x.to_string()))?;
+        let descriptor = MethodDescriptor::parse(&descriptor).map_err(|x| e(x.to_string()))?;
+
+        let mut initial = State {
+            locals: vec![VerificationType::Top; code.max_locals as usize],
+            stack: vec![],
+        };
+        let mut parameters = vec![];
+        if method.access_flags & ACC_STATIC == 0 {
+            parameters.push(match name.as_str() {
+                "<init>" if this_class != OBJECT => VerificationType::UninitializedThis,
+                _ => VerificationType::Object(this_class.clone()),
+            });
+        }
+        parameters.extend(descriptor.parameters.iter().map(VerificationType::from));
+        let mut index = 0;
+        for parameter in parameters {
+            let size = if parameter.is_category2() { 2 } else { 1 };
+            initial
+                .store(index, parameter)
+                .map_err(|_| e("the parameters exceed max_locals".to_string()))?;
+            index += size;
+        }
+
+        let mut exception_table = vec![];
+        for handler in &code.exception_table {
+            let catch_type = match handler.catch_type {
+                0 => THROWABLE.to_string(),
+                index => class_name(cp, index).map_err(&e)?,
+            };
+            exception_table.push((
+                handler.start_pc as usize,
+                handler.end_pc as usize,
+                handler.handler_pc as usize,
+                VerificationType::Object(catch_type),
+            ));
+        }
+        Ok(Analysis {
+            class_file,
+            class_name: this_class,
+            code: &code.code,
+            exception_table,
+            hierarchy,
+            initial,
+        })
+    }
+
+    fn run(&self) -> Result<Vec<Frame>, FrameError> {
+        let code = self.code;
+        let insns = decode_all(code).map_err(|e| FrameError {
+            offset: Some(e.offset),
+            message: e.kind.to_string(),
+        })?;
+        if insns.is_empty() {
+            return Err(error(None)("the code is empty".to_string()));
+        }
+        let mut index_of = vec![None; code.len()];
+        for (index, insn) in insns.iter().enumerate() {
+            index_of[insn.offset] = Some(index);
+        }
+        let mut needs_frame = vec![false; insns.len()];
+        let mut states: Vec<Option<State>> = vec![None; insns.len()];
+        states[0] = Some(self.initial.clone());
+        let mut worklist = BTreeSet::from([0]);
+
+        while let Some(index) = worklist.pop_first() {
+            let insn = &insns[index];
+            let at = error(Some(insn.offset));
+            let state = states[index].clone().unwrap();
+            let mut next = state.clone();
+            self.execute(&mut next, insn).map_err(&at)?;
+            let mut successors = vec![];
+            for target in insn.branch_targets() {
+                successors.push((target, true, next.clone()));
+            }
+            if insn.falls_through() {
+                successors.push((insn.offset + insn.length, false, next.clone()));
+            } else if let Some(following) = needs_frame.get_mut(index + 1) {
+                *following = true;
+            }
+            for (start, end, handler, catch_type) in &self.exception_table {
+                if !(*start..*end).contains(&insn.offset) {
+                    continue;
+                }
+                // a store changes the locals the handler may observe
+                for locals in [&state.locals, &next.locals] {
+                    let caught = State {
+                        locals: locals.clone(),
+                        stack: vec![catch_type.clone()],
+                    };
+                    successors.push((*handler, true, caught));
+                }
+            }
+            for (offset, jump, successor) in successors {
+                let target = match index_of.get(offset) {
+                    Some(Some(target)) => *target,
+                    Some(None) => return Err(at(format!("no instruction at {}", offset))),
+                    None => return Err(at("falling off the end of the code".to_string())),
+                };
+                if jump {
+                    needs_frame[target] = true;
+                }
+                let merged = match &states[target] {
+                    Some(current) => current
+                        .merge(&successor, self.hierarchy)
+                        .map_err(|e| at(format!("inconsistent frames at {}: {}", offset, e)))?,
+                    None => successor,
+                };
+                if states[target].as_ref() != Some(&merged) {
+                    states[target] = Some(merged);
+                    worklist.insert(target);
+                }
+            }
+        }
+
+        let mut frames = vec![];
+        for (index, insn) in insns.iter().enumerate() {
+            let state = match &states[index] {
+                Some(state) => state,
+                None => return Err(error(Some(insn.offset))("unreachable code".to_string())),
+            };
+            if needs_frame[index] {
+                frames.push(Frame {
+                    offset: insn.offset as u16,
+                    locals: encode_locals(&state.locals),
+                    stack: state.stack.clone(),
+                });
+            }
+        }
+        Ok(frames)
+    }
+
+    fn cp(&self) -> &ConstantPoolRef<'a> {
+        &self.class_file.constant_pool
+    }
+
+    fn execute(&self, state: &mut State, insn: &Instruction) -> Result<(), String> {
+        use VerificationType::*;
+        let opcode = insn.opcode;
+        let local = || match insn.operand {
+            Operand::Local(index) => index as usize,
+            _ => unreachable!(),
+        };
+        let constant = || match insn.operand {
+            Operand::Constant(index)
+            | Operand::InvokeInterface { index, .. }
+            | Operand::MultiANewArray { index, .. } => index,
+            _ => unreachable!(),
+        };
+        // int, long, float and double come in this order in most groups
+        let numeric = |n: u8| [Integer, Long, Float, Double][n as usize % 4].clone();
+        match opcode {
+            0x00 | 0x84 | 0xa7 | 0xb1 | 0xc8 => {}
+            0x01 => state.push(Null),
+            0x02..=0x08 | 0x10 | 0x11 => state.push(Integer),
+            0x09 | 0x0a => state.push(Long),
+            0x0b..=0x0d => state.push(Float),
+            0x0e | 0x0f => state.push(Double),
+            0x12..=0x14 => state.push(self.constant_type(constant())?),
+            0x15..=0x18 => state.push(numeric(opcode - 0x15)),
+            0x1a..=0x29 => state.push(numeric((opcode - 0x1a) / 4)),
+            0x19 | 0x2a..=0x2d => {
+                let vtype = state
+                    .locals
+                    .get(local())
+                    .ok_or_else(|| format!("local {} exceeds max_locals", local()))?
+                    .clone();
+                state.push(vtype);
+            }
+            0x2e..=0x31 => {
+                state.pop_n(2)?;
+                state.push(numeric(opcode - 0x2e));
+            }
+            0x32 => {
+                state.pop()?;
+                let component = match state.pop()? {
+                    Null => Null,
+                    Object(array) if array.starts_with("[[") => Object(array[1..].to_string()),
+                    Object(array) if array.starts_with("[L") => {
+                        Object(array[2..array.len() - 1].to_string())
+                    }
+                    other => return Err(format!("aaload from {}", other)),
+                };
+                state.push(component);
+            }
+            0x33..=0x35 => {
+                state.pop_n(2)?;
+                state.push(Integer);
+            }
+            0x36..=0x4e => {
+                let value = state.pop()?;
+                state.store(local(), value)?;
+            }
+            0x4f..=0x56 => state.pop_n(3)?,
+            0x57 => state.pop_n(1)?,
+            0x58 => {
+                if !state.pop()?.is_category2() {
+                    state.pop()?;
+                }
+            }
+            0x59..=0x5f => stack_op(state, opcode)?,
+            0x60..=0x73 => {
+                state.pop_n(2)?;
+                state.push(numeric(opcode - 0x60));
+            }
+            0x74..=0x77 => {
+                let value = state.pop()?;
+                state.push(value);
+            }
+            0x78..=0x83 => {
+                state.pop_n(2)?;
+                state.push(numeric(opcode % 2));
+            }
+            0x85..=0x93 => {
+                state.pop()?;
+                state.push(match opcode {
+                    0x85 | 0x8c | 0x8f => Long,
+                    0x86 | 0x89 | 0x90 => Float,
+                    0x87 | 0x8a | 0x8d => Double,
+                    _ => Integer,
+                });
+            }
+            0x94..=0x98 => {
+                state.pop_n(2)?;
+                state.push(Integer);
+            }
+            0x99..=0x9e | 0xaa | 0xab | 0xac..=0xb0 | 0xbf | 0xc2 | 0xc3 | 0xc6 | 0xc7 => {
+                state.pop()?;
+            }
+            0x9f..=0xa6 => state.pop_n(2)?,
+            0xa8 | 0xa9 | 0xc9 => {
+                return Err("jsr and ret are not allowed with stack maps".to_string())
+            }
+            0xb2..=0xb5 => {
+                let (_, _, descriptor) = self.member(constant())?;
+                let field_type = FieldType::parse(&descriptor).map_err(|e| e.to_string())?;
+                match opcode {
+                    0xb2 => state.push(VerificationType::from(&field_type)),
+                    0xb3 => state.pop_n(1)?,
+                    0xb4 => {
+                        state.pop()?;
+                        state.push(VerificationType::from(&field_type));
+                    }
+                    _ => state.pop_n(2)?,
+                }
+            }
+            0xb6..=0xba => {
+                let (_, name, descriptor) = self.member(constant())?;
+                let descriptor = MethodDescriptor::parse(&descriptor).map_err(|e| e.to_string())?;
+                state.pop_n(descriptor.parameters.len())?;
+                if opcode != 0xb8 && opcode != 0xba {
+                    let receiver = state.pop()?;
+                    if opcode == 0xb7 && name == "<init>" {
+                        let initialized = match &receiver {
+                            UninitializedThis => Object(self.class_name.clone()),
+                            Uninitialized(offset) => Object(self.new_class(*offset as usize)?),
+                            _ => receiver.clone(),
+                        };
+                        state.replace(&receiver, &initialized);
+                    }
+                }
+                if let Some(return_type) = &descriptor.return_type {
+                    state.push(VerificationType::from(return_type));
+                }
+            }
+            0xbb => {
+                let vtype = Uninitialized(insn.offset as u16);
+                // a loop may execute the same new again, the earlier object is lost
+                state.replace(&vtype, &Top);
+                state.push(vtype);
+            }
+            0xbc => {
+                let descriptor = match insn.operand {
+                    Operand::NewArray(4) => "[Z",
+                    Operand::NewArray(5) => "[C",
+                    Operand::NewArray(6) => "[F",
+                    Operand::NewArray(7) => "[D",
+                    Operand::NewArray(8) => "[B",
+                    Operand::NewArray(9) => "[S",
+                    Operand::NewArray(10) => "[I",
+                    Operand::NewArray(11) => "[J",
+                    _ => return Err("invalid newarray type".to_string()),
+                };
+                state.pop()?;
+                state.push(Object(descriptor.to_string()));
+            }
+            0xbd => {
+                let class = class_name(self.cp(), constant())?;
+                state.pop()?;
+                state.push(Object(array_of(&class)));
+            }
+            0xbe | 0xc1 => {
+                state.pop()?;
+                state.push(Integer);
+            }
+            0xc0 => {
+                let class = class_name(self.cp(), constant())?;
+                state.pop()?;
+                state.push(Object(class));
+            }
+            0xc5 => {
+                let dimensions = match insn.operand {
+                    Operand::MultiANewArray { dimensions, .. } => dimensions,
+                    _ => unreachable!(),
+                };
+                let class = class_name(self.cp(), constant())?;
+                state.pop_n(dimensions as usize)?;
+                state.push(Object(class));
+            }
+            _ => return Err(format!("invalid opcode 0x{:02x}", opcode)),
+        }
+        Ok(())
+    }
+
+    fn constant_type(&self, index: u16) -> Result<VerificationType, String> {
+        Ok(match self.cp().get(index as usize) {
+            Some(Constant::Integer(_)) => VerificationType::Integer,
+            Some(Constant::Float(_)) => VerificationType::Float,
+            Some(Constant::Long(_)) => VerificationType::Long,
+            Some(Constant::Double(_)) => VerificationType::Double,
+            Some(Constant::String { .. }) => VerificationType::Object("java/lang/String".into()),
+            Some(Constant::Class { .. }) => VerificationType::Object("java/lang/Class".into()),
+            Some(Constant::MethodType { .. }) => {
+                VerificationType::Object("java/lang/invoke/MethodType".into())
+            }
+            Some(Constant::MethodHandle { .. }) => {
+                VerificationType::Object("java/lang/invoke/MethodHandle".into())
+            }
+            Some(Constant::Dynamic { .. }) => {
+                let (_, _, descriptor) = self.member(index)?;
+                let field_type = FieldType::parse(&descriptor).map_err(|e| e.to_string())?;
+                VerificationType::from(&field_type)
+            }
+            _ => return Err(format!("constant #{} is not loadable", index)),
+        })
+    }
+
+    // the class, name and descriptor of a field, method or dynamic constant
+    fn member(&self, index: u16) -> Result<(u16, String, String), String> {
+        let cp = self.cp();
+        let (class_index, name_and_type_index) = match cp.get(index as usize) {
+            Some(
+                Constant::FieldRef {
+                    class_index,
+                    name_and_type_index,
+                }
+                | Constant::MethodRef {
+                    class_index,
+                    name_and_type_index,
+                }
+                | Constant::InterfaceMethodRef {
+                    class_index,
+                    name_and_type_index,
+                },
+            ) => (*class_index, *name_and_type_index),
+            Some(
+                Constant::Dynamic {
+                    name_and_type_index,
+                    ..
+                }
+                | Constant::InvokeDynamic {
+                    name_and_type_index,
+                    ..
+                },
+            ) => (0, *name_and_type_index),
+            _ => return Err(format!("constant #{} is not a member reference", index)),
+        };
+        match cp.get(name_and_type_index as usize) {
+            Some(Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            }) => {
+                let utf8 = |index: u16| get_str(cp.clone(), index as usize);
+                let name = utf8(*name_index).map_err(|e| e.to_string())?;
+                let descriptor = utf8(*descriptor_index).map_err(|e| e.to_string())?;
+                Ok((class_index, name, descriptor))
+            }
+            _ => Err(format!(
+                "constant #{} is not a name and type",
+                name_and_type_index
+            )),
+        }
+    }
+
+    // the class created by the new at `offset`
+    fn new_class(&self, offset: usize) -> Result<String, String> {
+        match self.code.get(offset..offset + 3) {
+            Some([0xbb, high, low]) => class_name(self.cp(), u16::from_be_bytes([*high, *low])),
+            _ => Err(format!("no new instruction at {}", offset)),
+        }
+    }
+}
+
+fn class_name(cp: &ConstantPoolRef, index: u16) -> Result<String, String> {
+    match cp.get(index as usize) {
+        Some(Constant::Class { .. }) => {
+            get_str(cp.clone(), index as usize).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("constant #{} is not a class", index)),
+    }
+}
+
+// the dup, pop and swap forms by category of the values, JVMS 6.5
+fn stack_op(state: &mut State, opcode: u8) -> Result<(), String> {
+    let v1 = state.pop()?;
+    let values = match opcode {
+        0x59 => vec![v1.clone(), v1],
+        0x5a => {
+            let v2 = state.pop()?;
+            vec![v1.clone(), v2, v1]
+        }
+        0x5b => {
+            let v2 = state.pop()?;
+            if v2.is_category2() {
+                vec![v1.clone(), v2, v1]
+            } else {
+                let v3 = state.pop()?;
+                vec![v1.clone(), v3, v2, v1]
+            }
+        }
+        0x5c if v1.is_category2() => vec![v1.clone(), v1],
+        0x5c => {
+            let v2 = state.pop()?;
+            vec![v2.clone(), v1.clone(), v2, v1]
+        }
+        0x5d if v1.is_category2() => {
+            let v2 = state.pop()?;
+            vec![v1.clone(), v2, v1]
+        }
+        0x5d => {
+            let v2 = state.pop()?;
+            let v3 = state.pop()?;
+            vec![v2.clone(), v1.clone(), v3, v2, v1]
+        }
+        0x5e if v1.is_category2() => {
+            let v2 = state.pop()?;
+            if v2.is_category2() {
+                vec![v1.clone(), v2, v1]
+            } else {
+                let v3 = state.pop()?;
+                vec![v1.clone(), v3, v2, v1]
+            }
+        }
+        0x5e => {
+            let v2 = state.pop()?;
+            let v3 = state.pop()?;
+            if v3.is_category2() {
+                vec![v2.clone(), v1.clone(), v3, v2, v1]
+            } else {
+                let v4 = state.pop()?;
+                vec![v2.clone(), v1.clone(), v4, v3, v2, v1]
+            }
+        }
+        _ => {
+            let v2 = state.pop()?;
+            vec![v1, v2]
+        }
+    };
+    state.stack.extend(values);
+    Ok(())
+}
+
+fn array_of(class: &str) -> String {
+    match class.starts_with('[') {
+        true => format!("[{}", class),
+        false => format!("[L{};", class),
+    }
+}
+
+fn common_super_class(a: &str, b: &str, hierarchy: &dyn ClassHierarchy) -> String {
+    // arrays of references merge by their components, any other two arrays
+    // only have Object in common
+    let component = |array: &str| match array.strip_prefix('[') {
+        Some(component) if component.starts_with('[') => Some(component.to_string()),
+        Some(component) => component
+            .strip_prefix('L')
+            .map(|name| name.trim_end_matches(';').to_string()),
+        None => None,
+    };
+    match (a.starts_with('['), b.starts_with('[')) {
+        (true, true) => match (component(a), component(b)) {
+            (Some(a), Some(b)) => array_of(&common_super_class(&a, &b, hierarchy)),
+            _ => OBJECT.to_string(),
+        },
+        (false, false) if !hierarchy.is_interface(a) && !hierarchy.is_interface(b) => {
+            let mut supers_of_b = vec![];
+            let mut class = Some(b.to_string());
+            while let Some(name) = class {
+                class = hierarchy.super_class(&name);
+                supers_of_b.push(name);
+            }
+            let mut class = Some(a.to_string());
+            while let Some(name) = class {
+                if supers_of_b.contains(&name) {
+                    return name;
+                }
+                class = hierarchy.super_class(&name);
+            }
+            OBJECT.to_string()
+        }
+        _ => OBJECT.to_string(),
+    }
+}
+
+// long and double take one entry, and trailing Top locals are left out
+fn encode_locals(locals: &[VerificationType]) -> Vec<VerificationType> {
+    let mut encoded = vec![];
+    let mut i = 0;
+    while i < locals.len() {
+        encoded.push(locals[i].clone());
+        i += if locals[i].is_category2() { 2 } else { 1 };
+    }
+    while encoded.last() == Some(&VerificationType::Top) {
+        encoded.pop();
+    }
+    encoded
+}
+
+/// Encodes each frame relative to the one before, in the most compact form.
+pub(crate) fn compress(
+    initial_locals: &[VerificationType],
+    frames: &[Frame],
+    mut class_index: impl FnMut(&str) -> u16,
+) -> Vec<StackMap> {
+    let mut info = |vtype: &VerificationType| match vtype {
+        VerificationType::Top => VerificationTypeInfo::Top,
+        VerificationType::Integer => VerificationTypeInfo::Integer,
+        VerificationType::Float => VerificationTypeInfo::Float,
+        VerificationType::Long => VerificationTypeInfo::Long,
+        VerificationType::Double => VerificationTypeInfo::Double,
+        VerificationType::Null => VerificationTypeInfo::Null,
+        VerificationType::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+        VerificationType::Uninitialized(offset) => {
+            VerificationTypeInfo::Uninitialized { offset: *offset }
+        }
+        VerificationType::Object(name) => VerificationTypeInfo::Object {
+            cpool_index: class_index(name),
+        },
+    };
+    let mut stack_maps = vec![];
+    let mut previous_locals = initial_locals;
+    let mut previous_offset = None;
+    for frame in frames {
+        let offset_delta = match previous_offset {
+            None => frame.offset,
+            Some(previous) => frame.offset - previous - 1,
+        };
+        let (locals, previous) = (frame.locals.len(), previous_locals.len());
+        let same_locals = frame.locals == previous_locals;
+        let (frame_type, stack_map_frame) = match frame.stack.len() {
+            0 if same_locals && offset_delta < 64 => (offset_delta as u8, StackMapFrame::SameFrame),
+            0 if same_locals => (251, StackMapFrame::SameFrameExtended { offset_delta }),
+            1 if same_locals && offset_delta < 64 => (
+                64 + offset_delta as u8,
+                StackMapFrame::SameLocals1StackItemFrame {
+                    stack: info(&frame.stack[0]),
+                },
+            ),
+            1 if same_locals => (
+                247,
+                StackMapFrame::SameLocals1StackItemFrameExtended {
+                    offset_delta,
+                    stack: info(&frame.stack[0]),
+                },
+            ),
+            0 if locals > previous
+                && locals - previous <= 3
+                && frame.locals.starts_with(previous_locals) =>
+            {
+                (
+                    (251 + locals - previous) as u8,
+                    StackMapFrame::AppendFrame {
+                        offset_delta,
+                        locals: frame.locals[previous..].iter().map(&mut info).collect(),
+                    },
+                )
+            }
+            0 if previous > locals
+                && previous - locals <= 3
+                && previous_locals.starts_with(&frame.locals) =>
+            {
+                (
+                    (251 - (previous - locals)) as u8,
+                    StackMapFrame::ChopFrame { offset_delta },
+                )
+            }
+            _ => (
+                255,
+                StackMapFrame::FullFrame {
+                    offset_delta,
+                    locals: frame.locals.iter().map(&mut info).collect(),
+                    stack: frame.stack.iter().map(&mut info).collect(),
+                },
+            ),
+        };
+        stack_maps.push(StackMap {
+            frame_type,
+            frame: stack_map_frame,
+        });
+        previous_locals = &frame.locals[..];
+        previous_offset = Some(frame.offset);
+    }
+    stack_maps
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{ClassBuilder, CodeBuilder, ACC_STATIC};
+    use crate::frames::{
+        compress, compute_frames, ClassHierarchy, EmptyHierarchy, Frame, VerificationType,
+    };
+    use crate::{get_str, parse, AttributeType, StackMap, StackMapFrame};
+    use std::io::Read;
+
+    use VerificationType::*;
+
+    /// `a/B` and `a/C` extend `a/A`, `a/I` is an interface.
+    struct Hierarchy;
+
+    impl ClassHierarchy for Hierarchy {
+        fn super_class(&self, class: &str) -> Option<String> {
+            match class {
+                "java/lang/Object" => None,
+                "a/B" | "a/C" => Some("a/A".to_string()),
+                _ => Some("java/lang/Object".to_string()),
+            }
+        }
+
+        fn is_interface(&self, class: &str) -> bool {
+            class == "a/I"
+        }
+    }
+
+    fn object(name: &str) -> VerificationType {
+        Object(name.to_string())
+    }
+
+    // the frames of a static method
+    fn frames(
+        descriptor: &str,
+        hierarchy: &dyn ClassHierarchy,
+        code: impl FnOnce(&mut CodeBuilder) -> &mut CodeBuilder,
+    ) -> Vec<Frame> {
+        let bytes = ClassBuilder::new("Gen")
+            .method_with_flags(ACC_STATIC, "m", descriptor, code)
+            .build()
+            .unwrap();
+        let class_file = parse(&bytes).unwrap();
+        compute_frames(&class_file, &class_file.methods[0], hierarchy).unwrap()
+    }
+
+    fn offsets(entries: &[StackMap]) -> Vec<usize> {
+        let mut offsets: Vec<usize> = vec![];
+        for entry in entries {
+            let offset_delta = match &entry.frame {
+                StackMapFrame::SameFrame => entry.frame_type as usize,
+                StackMapFrame::SameLocals1StackItemFrame { .. } => entry.frame_type as usize - 64,
+                StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. }
+                | StackMapFrame::ChopFrame { offset_delta }
+                | StackMapFrame::SameFrameExtended { offset_delta }
+                | StackMapFrame::AppendFrame { offset_delta, .. }
+                | StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta as usize,
+            };
+            offsets.push(match offsets.last() {
+                Some(last) => last + offset_delta + 1,
+                None => offset_delta,
+            });
+        }
+        offsets
+    }
+
+    #[test]
+    fn test_loop() {
+        let bytes = ClassBuilder::new("Gen")
+            .version(52, 0)
+            .method_with_flags(ACC_STATIC, "sum", "(I)I", |code| {
+                let body = code.new_label();
+                let test = code.new_label();
+                code.iconst(0)
+                    .istore(1)
+                    .goto(test)
+                    .bind(body)
+                    .iload(1)
+                    .iload(0)
+                    .iadd()
+                    .istore(1)
+                    .iinc(0, -1)
+                    .bind(test)
+                    .iload(0)
+                    .ifgt(body)
+                    .iload(1)
+                    .ireturn()
+            })
+            .build()
+            .unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let code = class_file.methods[0].code_attribute().unwrap();
+        let entries = match &code.attributes[0].attribute_type {
+            AttributeType::StackMapTable { entries } => entries,
+            _ => panic!(),
+        };
+        // an int is appended to the parameter at the body, the same at the test
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].frame_type, 252);
+        assert!(matches!(
+            entries[0].frame,
+            StackMapFrame::AppendFrame {
+                offset_delta: 5,
+                ..
+            }
+        ));
+        assert_eq!(entries[1].frame_type, 6);
+        assert_eq!(offsets(entries), [5, 12]);
+    }
+
+    #[test]
+    fn test_merge() {
+        fn code(code: &mut CodeBuilder) -> &mut CodeBuilder {
+            let other = code.new_label();
+            let join = code.new_label();
+            code.iload(0)
+                .ifeq(other)
+                .aconst_null()
+                .checkcast("a/B")
+                .goto(join)
+                .bind(other)
+                .aconst_null()
+                .checkcast("a/C")
+                .bind(join)
+                .areturn()
+        }
+        assert_eq!(
+            frames("(Z)Ljava/lang/Object;", &Hierarchy, code),
+            [
+                Frame {
+                    offset: 11,
+                    locals: vec![Integer],
+                    stack: vec![]
+                },
+                Frame {
+                    offset: 15,
+                    locals: vec![Integer],
+                    stack: vec![object("a/A")]
+                }
+            ]
+        );
+        let merged = frames("(Z)Ljava/lang/Object;", &EmptyHierarchy, code);
+        assert_eq!(merged[1].stack, [object("java/lang/Object")]);
+
+        // arrays of references merge by component, an interface becomes Object
+        for (a, b, merged) in [
+            ("[La/B;", "[La/C;", "[La/A;"),
+            ("[[La/B;", "[[La/C;", "[[La/A;"),
+            ("[I", "[La/B;", "java/lang/Object"),
+            ("[[I", "[[J", "[Ljava/lang/Object;"),
+            ("a/B", "a/I", "java/lang/Object"),
+            ("a/B", "a/A", "a/A"),
+        ] {
+            assert_eq!(object(a).merge(&object(b), &Hierarchy), object(merged));
+        }
+        assert_eq!(Null.merge(&object("a/B"), &Hierarchy), object("a/B"));
+        assert_eq!(Integer.merge(&Float, &Hierarchy), Top);
+    }
+
+    #[test]
+    fn test_uninitialized() {
+        // new StringBuilder(b ? "a" : "b")
+        let computed = frames("(Z)Ljava/lang/Object;", &EmptyHierarchy, |code| {
+            let other = code.new_label();
+            let join = code.new_label();
+            code.new_instance("java/lang/StringBuilder")
+                .dup()
+                .iload(0)
+                .ifeq(other)
+                .ldc_string("a")
+                .goto(join)
+                .bind(other)
+                .ldc_string("b")
+                .bind(join)
+                .invokespecial("java/lang/StringBuilder", "<init>", "(Ljava/lang/String;)V")
+                .areturn()
+        });
+        assert_eq!(computed[0].stack, [Uninitialized(0), Uninitialized(0)]);
+        assert_eq!(
+            computed[1].stack,
+            [
+                Uninitialized(0),
+                Uninitialized(0),
+                object("java/lang/String")
+            ]
+        );
+
+        // this is uninitialized in a constructor until super() is called
+        let bytes = ClassBuilder::new("Gen")
+            .method("<init>", "(Z)V", |code| {
+                let join = code.new_label();
+                code.iload(1)
+                    .ifeq(join)
+                    .bind(join)
+                    .aload(0)
+                    .invokespecial("java/lang/Object", "<init>", "()V")
+                    .iload(1)
+                    .ifeq(join)
+                    .return_()
+            })
+            .build();
+        // the join is reached with this both uninitialized and initialized
+        assert!(bytes.is_ok());
+        let bytes = ClassBuilder::new("Gen")
+            .method("<init>", "(Z)V", |code| {
+                let join = code.new_label();
+                code.aload(0)
+                    .iload(1)
+                    .ifeq(join)
+                    .bind(join)
+                    .invokespecial("java/lang/Object", "<init>", "()V")
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        let class_file = parse(&bytes).unwrap();
+        let computed = compute_frames(&class_file, &class_file.methods[0], &EmptyHierarchy);
+        assert_eq!(
+            computed.unwrap(),
+            [Frame {
+                offset: 5,
+                locals: vec![UninitializedThis, Integer],
+                stack: vec![UninitializedThis]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_exception_handler() {
+        let frames = frames("(I)I", &EmptyHierarchy, |code| {
+            let start = code.new_label();
+            let end = code.new_label();
+            let handler = code.new_label();
+            code.bind(start)
+                .iconst(1)
+                .iload(0)
+                .idiv()
+                .istore(1)
+                .bind(end)
+                .iload(1)
+                .ireturn()
+                .bind(handler)
+                .astore(2)
+                .iconst(0)
+                .ireturn()
+                .try_catch(start, end, handler, Some("java/lang/ArithmeticException"))
+        });
+        // local 1 is unset before the store, so the handler cannot use it
+        assert_eq!(
+            frames,
+            [Frame {
+                offset: 6,
+                locals: vec![Integer],
+                stack: vec![object("java/lang/ArithmeticException")]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_compress() {
+        let frame = |offset, locals: &[VerificationType], stack: &[VerificationType]| Frame {
+            offset,
+            locals: locals.to_vec(),
+            stack: stack.to_vec(),
+        };
+        let frames = [
+            frame(100, &[Integer], &[]),
+            frame(300, &[Integer], &[]),
+            frame(301, &[Integer], &[Long]),
+            frame(400, &[Integer], &[Long]),
+            frame(401, &[Integer, Float, Float, Float], &[]),
+            frame(
+                402,
+                &[Integer, Float, Float, Float, Float, object("A")],
+                &[],
+            ),
+            frame(403, &[], &[]),
+            frame(404, &[], &[object("B"), Null]),
+        ];
+        let mut classes = vec![];
+        let entries = compress(&[Integer, Long], &frames, |name| {
+            classes.push(name.to_string());
+            classes.len() as u16
+        });
+        let types: Vec<_> = entries.iter().map(|entry| entry.frame_type).collect();
+        assert_eq!(types, [250, 251, 64, 247, 254, 253, 255, 255]);
+        assert_eq!(offsets(&entries), [100, 300, 301, 400, 401, 402, 403, 404]);
+        assert_eq!(classes, ["A", "B"]);
+        assert!(matches!(
+            entries[6].frame,
+            StackMapFrame::FullFrame { ref locals, ref stack, .. }
+                if locals.is_empty() && stack.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let error = |descriptor: &str, code: fn(&mut CodeBuilder) -> &mut CodeBuilder| {
+            let bytes = ClassBuilder::new("Gen")
+                .method_with_flags(ACC_STATIC, "m", descriptor, code)
+                .build()
+                .unwrap();
+            let class_file = parse(&bytes).unwrap();
+            compute_frames(&class_file, &class_file.methods[0], &EmptyHierarchy)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("()V", |code| code.return_().return_()),
+            "at offset 1: unreachable code"
+        );
+        assert_eq!(
+            error("()V", |code| {
+                let other = code.new_label();
+                let join = code.new_label();
+                code.iconst(0)
+                    .iconst(0)
+                    .ifeq(other)
+                    .fconst(0.0)
+                    .goto(join)
+                    .bind(other)
+                    .iconst(0)
+                    .bind(join)
+                    .pop()
+                    .pop()
+                    .return_()
+            }),
+            "at offset 9: inconsistent frames at 10: mismatched stack types float and int"
+        );
+    }
+
+    #[test]
+    fn test_rt_jar_offsets() {
+        // javac puts frames at least where they are needed, and at the head of
+        // some loops that are not branched to
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut methods = 0;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if !file.name().starts_with("java/util/") || !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            let class_file = parse(&bytes).unwrap();
+            for method in &class_file.methods {
+                let code = match method.code_attribute() {
+                    Some(code) => code,
+                    None => continue,
+                };
+                let entries = code
+                    .attributes
+                    .iter()
+                    .find_map(|attribute| match &attribute.attribute_type {
+                        AttributeType::StackMapTable { entries } => Some(&entries[..]),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                let name = get_str(class_file.constant_pool.clone(), method.name_index as usize);
+                let frames = compute_frames(&class_file, method, &EmptyHierarchy)
+                    .unwrap_or_else(|e| panic!("{} {:?}: {}", file.name(), name, e));
+                let javac = offsets(entries);
+                for frame in frames {
+                    let offset = frame.offset as usize;
+                    assert!(
+                        javac.contains(&offset),
+                        "{} {:?} {}",
+                        file.name(),
+                        name,
+                        offset
+                    );
+                }
+                methods += 1;
+            }
+        }
+        assert!(methods > 10000, "{}", methods);
+    }
+}
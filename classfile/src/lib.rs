@@ -6,75 +6,193 @@ pub use attribute::{
 };
 pub use class_file::ClassFile;
 pub use constant::{Constant, ConstantTag};
+pub use errors::{Error, ParseError};
 pub use field::FieldInfo;
 pub use method::MethodInfo;
+pub use writer::write;
 
 use crate::attribute::{AttributeTag, ElementValuePair, TypePathPair};
 use nom::bytes::complete::{tag, take};
-use nom::combinator::{all_consuming, map, success};
-use nom::error::{context, ErrorKind, ParseError, VerboseError, VerboseErrorKind};
+use nom::combinator::success;
+use nom::error::{context, ContextError, ErrorKind};
 use nom::multi::length_count;
 use nom::number::complete::{be_f32, be_f64, be_i32, be_i64, be_u16, be_u32, be_u8};
 use nom::sequence::{pair, tuple};
-use nom::Err as NomErr;
+use nom::{Err as NomErr, Offset, ToUsize};
+use std::borrow::Cow;
 use std::sync::Arc;
 
+pub mod annotation;
 mod attribute;
+pub mod builder;
+pub mod bytecode;
 mod class_file;
 mod constant;
+pub mod descriptor;
 mod errors;
 mod field;
+pub mod format;
+pub mod frames;
 mod method;
+pub mod module;
+pub mod mutf8;
+mod writer;
 
 const MAGIC: &[u8] = b"\xCA\xFE\xBA\xBE";
 
+// legal class files nest attributes at most one level, in Code and Record
+const MAX_ATTRIBUTE_NESTING: usize = 8;
+const MAX_ELEMENT_VALUE_NESTING: usize = 256;
+
 pub type ConstantPoolRef<'a> = Arc<Vec<Constant<'a>>>;
 
-type IResult<I, O, E = (I, ErrorKind)> = Result<(I, O), NomErr<E>>;
-type Res<T, U> = IResult<T, U, VerboseError<T>>;
+type IResult<I, O, E> = Result<(I, O), NomErr<E>>;
+type Res<T, U> = IResult<T, U, Failure<T>>;
 
-pub fn get_utf8(constant_pool: ConstantPoolRef, index: usize) -> &[u8] {
-    match constant_pool.get(index) {
-        Some(Constant::Utf8(bytes)) => *bytes,
-        Some(Constant::Class { name_index }) => {
-            get_utf8(constant_pool.clone(), *name_index as usize)
-        }
-        Some(Constant::String { string_index }) => {
-            get_utf8(constant_pool.clone(), *string_index as usize)
+#[derive(Debug)]
+struct Failure<I> {
+    input: I,
+    kind: Error,
+    // innermost first
+    context: Vec<String>,
+}
+
+impl<I> Failure<I> {
+    fn new(input: I, kind: Error) -> Self {
+        Failure {
+            input,
+            kind,
+            context: vec![],
         }
-        Some(Constant::Module { name_index }) => {
-            get_utf8(constant_pool.clone(), *name_index as usize)
+    }
+}
+
+impl<I> nom::error::ParseError<I> for Failure<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Tag => Failure::new(input, Error::InvalidMagic),
+            _ => Failure::new(input, Error::UnexpectedEof),
         }
-        Some(Constant::Package { name_index }) => {
-            get_utf8(constant_pool.clone(), *name_index as usize)
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I> ContextError<I> for Failure<I> {
+    fn add_context(_: I, context: &'static str, mut other: Self) -> Self {
+        other.context.push(context.to_string());
+        other
+    }
+}
+
+fn fail<I, O>(input: I, kind: Error) -> IResult<I, O, Failure<I>> {
+    Err(NomErr::Error(Failure::new(input, kind)))
+}
+
+fn add_context<I>(
+    context: impl FnOnce() -> String,
+) -> impl FnOnce(NomErr<Failure<I>>) -> NomErr<Failure<I>> {
+    |e| {
+        e.map(|mut failure| {
+            failure.context.push(context());
+            failure
+        })
+    }
+}
+
+// like length_count, naming each item "{label} #{index}" in errors
+fn numbered<'a, O, N: ToUsize>(
+    label: &'static str,
+    mut count: impl FnMut(&'a [u8]) -> Res<&'a [u8], N>,
+    mut item: impl FnMut(&'a [u8]) -> Res<&'a [u8], O>,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], Vec<O>> {
+    move |input| {
+        let (mut input, count) = count(input)?;
+        let mut items = Vec::with_capacity(count.to_usize());
+        for i in 0..count.to_usize() {
+            let (next_input, o) =
+                item(input).map_err(add_context(|| format!("{} #{}", label, i)))?;
+            items.push(o);
+            input = next_input;
         }
-        _ => unreachable!("constant pool index mismatch"),
+        Ok((input, items))
     }
 }
 
-pub fn get_str(constant_pool: ConstantPoolRef, index: usize) -> String {
-    let utf8 = get_utf8(constant_pool.clone(), index);
-    String::from_utf8(utf8.to_vec()).unwrap()
+pub fn get_utf8(constant_pool: ConstantPoolRef<'_>, index: usize) -> Result<Cow<'_, [u8]>, Error> {
+    utf8(&constant_pool, index).cloned()
 }
 
-pub fn parse(input: &[u8]) -> Res<&[u8], ClassFile> {
-    context("class file", all_consuming(class_file))(input)
+fn utf8<'p, 'a>(
+    constant_pool: &'p [Constant<'a>],
+    index: usize,
+) -> Result<&'p Cow<'a, [u8]>, Error> {
+    let utf8 = |index: u16| match constant_pool.get(index as usize) {
+        Some(Constant::Utf8(bytes)) => Ok(bytes),
+        None | Some(Constant::Placeholder) => Err(Error::InvalidConstantIndex(index as usize)),
+        Some(_) => Err(Error::MismatchConstantType),
+    };
+    match constant_pool.get(index) {
+        Some(Constant::Utf8(bytes)) => Ok(bytes),
+        Some(Constant::Class { name_index }) => utf8(*name_index),
+        Some(Constant::String { string_index }) => utf8(*string_index),
+        Some(Constant::Module { name_index }) => utf8(*name_index),
+        Some(Constant::Package { name_index }) => utf8(*name_index),
+        None | Some(Constant::Placeholder) => Err(Error::InvalidConstantIndex(index)),
+        Some(_) => Err(Error::MismatchConstantType),
+    }
 }
 
-fn class_file(input: &[u8]) -> Res<&[u8], ClassFile> {
-    context(
-        "class file",
-        tuple((
-            tag(MAGIC),
-            be_u16,
-            be_u16,
-            constant_pool,
-            be_u16,
-            be_u16,
-            be_u16,
-            length_count(be_u16, be_u16),
-        )),
-    )(input)
+pub fn get_str(constant_pool: ConstantPoolRef, index: usize) -> Result<String, Error> {
+    mutf8::decode(&get_utf8(constant_pool.clone(), index)?)
+}
+
+pub fn parse(input: &[u8]) -> Result<ClassFile<'_>, ParseError> {
+    complete(input, class_file(input, false))
+}
+
+/// Parses like `parse`, but keeps the attributes of the class, its fields and
+/// methods as `AttributeType::Lazy` byte ranges, which is much cheaper when
+/// only the names and the hierarchy are needed. `Attribute::decode` decodes
+/// one of them on access.
+pub fn parse_lazy(input: &[u8]) -> Result<ClassFile<'_>, ParseError> {
+    complete(input, class_file(input, true))
+}
+
+fn complete<'a, O>(input: &'a [u8], result: Res<&'a [u8], O>) -> Result<O, ParseError> {
+    match result {
+        Ok(([], o)) => Ok(o),
+        Ok((rest, _)) => Err(ParseError {
+            offset: input.offset(rest),
+            context: vec![],
+            kind: Error::TrailingBytes(rest.len()),
+        }),
+        Err(NomErr::Error(failure)) | Err(NomErr::Failure(failure)) => Err(ParseError {
+            offset: input.offset(failure.input),
+            context: failure.context.into_iter().rev().collect(),
+            kind: failure.kind,
+        }),
+        Err(NomErr::Incomplete(_)) => Err(ParseError {
+            offset: input.len(),
+            context: vec![],
+            kind: Error::UnexpectedEof,
+        }),
+    }
+}
+
+fn class_file(input: &[u8], lazy: bool) -> Res<&[u8], ClassFile<'_>> {
+    tuple((
+        tag(MAGIC),
+        be_u16,
+        be_u16,
+        constant_pool,
+        be_u16,
+        be_u16,
+        be_u16,
+        context("interfaces", length_count(be_u16, be_u16)),
+    ))(input)
     .and_then(
         |(
             input,
@@ -90,10 +208,11 @@ fn class_file(input: &[u8]) -> Res<&[u8], ClassFile> {
             ),
         )| {
             let constant_pool = Arc::new(constant_pool);
-            let (input, fields) = length_count(be_u16, field_info(constant_pool.clone()))(input)?;
-            let (input, methods) = length_count(be_u16, method_info(constant_pool.clone()))(input)?;
-            let (input, attributes) =
-                length_count(be_u16, attribute(constant_pool.clone()))(input)?;
+            let (input, fields) =
+                numbered("field", be_u16, field_info(constant_pool.clone(), lazy))(input)?;
+            let (input, methods) =
+                numbered("method", be_u16, method_info(constant_pool.clone(), lazy))(input)?;
+            let (input, attributes) = attributes(constant_pool.clone(), lazy)(input)?;
 
             Ok((
                 input,
@@ -114,13 +233,15 @@ fn class_file(input: &[u8]) -> Res<&[u8], ClassFile> {
     )
 }
 
-fn constant_pool(input: &[u8]) -> Res<&[u8], Vec<Constant>> {
+fn constant_pool(input: &[u8]) -> Res<&[u8], Vec<Constant<'_>>> {
     context("constant pool", be_u16)(input).and_then(|(mut next_input, count)| {
         let mut constant_pool = Vec::with_capacity(count as usize);
         constant_pool.push(Constant::Placeholder);
         let mut i = 1;
         while i < count {
-            let (input, constant) = constant(next_input)?;
+            let (input, constant) = constant(next_input)
+                .map_err(add_context(|| format!("constant #{}", i)))
+                .map_err(add_context(|| "constant pool".to_string()))?;
             constant_pool.push(constant.clone());
             next_input = input;
             i += 1;
@@ -136,242 +257,337 @@ fn constant_pool(input: &[u8]) -> Res<&[u8], Vec<Constant>> {
     })
 }
 
-fn attribute<'a, E: ParseError<&'a [u8]>>(
-    constant_pool: ConstantPoolRef,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Attribute, E> + '_
-where
-    NomErr<E>: From<NomErr<VerboseError<&'a [u8]>>>,
-{
-    move |input: &[u8]| {
-        let (input, attribute_name_index) = be_u16(input)?;
-        let (input, attribute_length) = be_u32(input)?;
-        let attribute_name = get_utf8(constant_pool.clone(), attribute_name_index as usize);
-        let (input, attr_type) = match attribute_name.into() {
-            AttributeTag::ConstantValue => {
-                let (input, constant_value_index) = be_u16(input)?;
-                (
-                    input,
-                    AttributeType::ConstantValue {
-                        constant_value_index,
-                    },
-                )
-            }
-            AttributeTag::Code => {
-                let (input, code) = code_attribute(constant_pool.clone())(input)?;
-                (input, AttributeType::Code { code })
-            }
-            AttributeTag::StackMapTable => {
-                let (input, entries) = length_count(be_u16, stack_map)(input)?;
-                (input, AttributeType::StackMapTable { entries })
-            }
-            AttributeTag::Exceptions => {
-                let (input, exception_index_table) = length_count(be_u16, be_u16)(input)?;
-                (
-                    input,
-                    AttributeType::Exceptions {
-                        exception_index_table,
-                    },
-                )
-            }
-            AttributeTag::InnerClasses => {
-                let (input, classes) = length_count(be_u16, inner_class)(input)?;
-                (input, AttributeType::InnerClasses { classes })
-            }
-            AttributeTag::EnclosingMethod => {
-                let (input, class_index) = be_u16(input)?;
-                let (input, method_index) = be_u16(input)?;
-                (
-                    input,
-                    AttributeType::EnclosingMethod {
-                        class_index,
-                        method_index,
-                    },
-                )
-            }
-            AttributeTag::Synthetic => (input, AttributeType::Synthetic),
-            AttributeTag::Signature => {
-                let (input, signature_index) = be_u16(input)?;
-                (input, AttributeType::Signature { signature_index })
-            }
-            AttributeTag::SourceFile => {
-                let (input, sourcefile_index) = be_u16(input)?;
-                (input, AttributeType::SourceFile { sourcefile_index })
-            }
-            AttributeTag::SourceDebugExtension => {
-                let (input, debug_extension) =
-                    length_count(success(attribute_length), be_u8)(input)?;
-                (
-                    input,
-                    AttributeType::SourceDebugExtension { debug_extension },
-                )
-            }
-            AttributeTag::LineNumberTable => {
-                let (input, line_number_table) = length_count(be_u16, line_number)(input)?;
-                (input, AttributeType::LineNumberTable { line_number_table })
-            }
-            AttributeTag::LocalVariableTable => {
-                let (input, local_variable_table) = length_count(be_u16, local_variable)(input)?;
-                (
-                    input,
-                    AttributeType::LocalVariableTable {
-                        local_variable_table,
-                    },
-                )
-            }
-            AttributeTag::LocalVariableTypeTable => {
-                let (input, local_variable_type_table) =
-                    length_count(be_u16, local_variable_type)(input)?;
-                (
-                    input,
-                    AttributeType::LocalVariableTypeTable {
-                        local_variable_type_table,
-                    },
-                )
-            }
-            AttributeTag::Deprecated => (input, AttributeType::Deprecated),
-            AttributeTag::RuntimeVisibleAnnotations => {
-                let (input, annotations) = length_count(be_u16, annotation)(input)?;
-                (
-                    input,
-                    AttributeType::RuntimeVisibleAnnotations { annotations },
-                )
-            }
-            AttributeTag::RuntimeInvisibleAnnotations => {
-                let (input, annotations) = length_count(be_u16, annotation)(input)?;
-                (
-                    input,
-                    AttributeType::RuntimeInvisibleAnnotations { annotations },
-                )
-            }
-            AttributeTag::RuntimeVisibleParameterAnnotations => {
-                let (input, parameter_annotations) =
-                    length_count(be_u8, parameter_annotation)(input)?;
-                (
-                    input,
-                    AttributeType::RuntimeVisibleParameterAnnotations {
-                        parameter_annotations,
-                    },
-                )
-            }
-            AttributeTag::RuntimeInvisibleParameterAnnotations => {
-                let (input, parameter_annotations) =
-                    length_count(be_u8, parameter_annotation)(input)?;
-                (
-                    input,
-                    AttributeType::RuntimeInvisibleParameterAnnotations {
-                        parameter_annotations,
-                    },
-                )
-            }
-            AttributeTag::RuntimeVisibleTypeAnnotations => {
-                let (input, annotations) = length_count(be_u8, type_annotation)(input)?;
-                (
-                    input,
-                    AttributeType::RuntimeVisibleTypeAnnotations { annotations },
-                )
-            }
-            AttributeTag::RuntimeInvisibleTypeAnnotations => {
-                let (input, annotations) = length_count(be_u8, type_annotation)(input)?;
-                (
-                    input,
-                    AttributeType::RuntimeInvisibleTypeAnnotations { annotations },
-                )
-            }
-            AttributeTag::AnnotationDefault => {
-                let (input, default_value) = element_value(input)?;
-                (input, AttributeType::AnnotationDefault { default_value })
-            }
-            AttributeTag::BootstrapMethods => {
-                let (input, bootstrap_methods) = length_count(be_u16, bootstrap_method)(input)?;
-                (input, AttributeType::BootstrapMethods { bootstrap_methods })
-            }
-            AttributeTag::MethodParameters => {
-                let (input, parameters) = length_count(be_u8, method_parameter)(input)?;
-                (input, AttributeType::MethodParameters { parameters })
-            }
-            AttributeTag::Module => {
-                let (input, module_name_index) = be_u16(input)?;
-                let (input, module_flags) = be_u16(input)?;
-                let (input, module_version_index) = be_u16(input)?;
-                let (input, requires) = length_count(be_u16, require)(input)?;
-                let (input, exports) = length_count(be_u16, export)(input)?;
-                let (input, opens) = length_count(be_u16, open)(input)?;
-                let (input, uses) = length_count(be_u16, be_u16)(input)?;
-                let (input, provides) = length_count(be_u16, provide)(input)?;
-                (
-                    input,
-                    AttributeType::Module {
-                        module_name_index,
-                        module_flags,
-                        module_version_index,
-                        requires,
-                        exports,
-                        opens,
-                        uses,
-                        provides,
-                    },
-                )
-            }
-            AttributeTag::ModulePackages => {
-                let (input, package_index) = length_count(be_u16, be_u16)(input)?;
-                (input, AttributeType::ModulePackages { package_index })
-            }
-            AttributeTag::ModuleMainClass => {
-                let (input, main_class_index) = be_u16(input)?;
-                (input, AttributeType::ModuleMainClass { main_class_index })
-            }
-            AttributeTag::NestHost => {
-                let (input, host_class_index) = be_u16(input)?;
-                (input, AttributeType::NestHost { host_class_index })
-            }
-            AttributeTag::NestMembers => {
-                let (input, classes) = length_count(be_u16, be_u16)(input)?;
-                (input, AttributeType::NestMembers { classes })
-            }
-            AttributeTag::Record => {
-                let (input, components) =
-                    length_count(be_u16, record_component(constant_pool.clone()))(input)?;
-                (input, AttributeType::Record { components })
+fn attributes<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    lazy: bool,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], Vec<Attribute>> + '_ {
+    move |input| match lazy {
+        true => length_count(be_u16, lazy_attribute(&constant_pool))(input),
+        false => length_count(be_u16, attribute(constant_pool.clone(), 0))(input),
+    }
+}
+
+// checks the name only, the body is decoded by decode_attribute
+fn lazy_attribute<'a, 'p>(
+    constant_pool: &'p [Constant<'_>],
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], Attribute<'a>> + 'p {
+    move |input: &'a [u8]| {
+        let (rest, attribute_name_index) = be_u16(input)?;
+        let attribute_name = match utf8(constant_pool, attribute_name_index as usize) {
+            Ok(attribute_name) => attribute_name,
+            Err(kind) => {
+                return fail(input, kind).map_err(add_context(|| "attribute name".to_string()))
             }
-            AttributeTag::PermittedSubclasses => {
-                let (input, classes) = length_count(be_u16, be_u16)(input)?;
-                (input, AttributeType::PermittedSubclasses { classes })
+        };
+        let name = || String::from_utf8_lossy(attribute_name).into_owned();
+        let (input, attribute_length) = be_u32(rest)?;
+        let (input, data) = take(attribute_length)(input).map_err(add_context(name))?;
+        Ok((
+            input,
+            Attribute {
+                attribute_name_index,
+                attribute_length,
+                attribute_type: AttributeType::Lazy {
+                    data: Cow::Borrowed(data),
+                },
+            },
+        ))
+    }
+}
+
+fn decode_attribute<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    attribute_name_index: u16,
+    data: &'a [u8],
+) -> Result<AttributeType<'a>, ParseError> {
+    let attribute_name =
+        get_utf8(constant_pool.clone(), attribute_name_index as usize).map_err(|kind| {
+            ParseError {
+                offset: 0,
+                context: vec!["attribute name".to_string()],
+                kind,
             }
-            AttributeTag::Unknown => {
-                let (input, data) = take(attribute_length)(input)?;
-                (input, AttributeType::Unknown { data })
+        })?;
+    let name = || String::from_utf8_lossy(&attribute_name).into_owned();
+    let result = attribute_type(
+        constant_pool.clone(),
+        0,
+        attribute_name.as_ref().into(),
+        data,
+    )
+    .and_then(|(rest, attribute_type)| match rest {
+        [] => Ok((rest, attribute_type)),
+        _ => fail(rest, Error::InvalidLength),
+    })
+    .map_err(add_context(name));
+    complete(data, result)
+}
+
+fn attribute<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    depth: usize,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], Attribute> + '_ {
+    move |input: &'a [u8]| {
+        if depth > MAX_ATTRIBUTE_NESTING {
+            return fail(input, Error::TooDeeplyNested);
+        }
+        let (rest, attribute_name_index) = be_u16(input)?;
+        let attribute_name = match get_utf8(constant_pool.clone(), attribute_name_index as usize) {
+            Ok(attribute_name) => attribute_name,
+            Err(kind) => {
+                return fail(input, kind).map_err(add_context(|| "attribute name".to_string()))
             }
         };
+        let name = || String::from_utf8_lossy(&attribute_name).into_owned();
+        let (input, attribute_length) = be_u32(rest)?;
+        let (input, body) = take(attribute_length)(input).map_err(add_context(name))?;
+        let (rest, attribute_type) = attribute_type(
+            constant_pool.clone(),
+            depth,
+            attribute_name.as_ref().into(),
+            body,
+        )
+        .map_err(add_context(name))?;
+        if !rest.is_empty() {
+            return fail(rest, Error::InvalidLength).map_err(add_context(name));
+        }
         Ok((
             input,
             Attribute {
                 attribute_name_index,
                 attribute_length,
-                attribute_type: attr_type,
+                attribute_type,
             },
         ))
     }
 }
 
-fn code_attribute<'a, E: ParseError<&'a [u8]>>(
-    constant_pool: ConstantPoolRef,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], CodeAttribute, E> + '_
-where
-    NomErr<E>: From<NomErr<VerboseError<&'a [u8]>>>,
-{
+fn attribute_type<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    depth: usize,
+    tag: AttributeTag,
+    input: &'a [u8],
+) -> Res<&'a [u8], AttributeType<'a>> {
+    Ok(match tag {
+        AttributeTag::ConstantValue => {
+            let (input, constant_value_index) = be_u16(input)?;
+            (
+                input,
+                AttributeType::ConstantValue {
+                    constant_value_index,
+                },
+            )
+        }
+        AttributeTag::Code => {
+            let (input, code) = code_attribute(constant_pool.clone(), depth)(input)?;
+            (input, AttributeType::Code { code })
+        }
+        AttributeTag::StackMapTable => {
+            let (input, entries) = numbered("frame", be_u16, stack_map)(input)?;
+            (input, AttributeType::StackMapTable { entries })
+        }
+        AttributeTag::Exceptions => {
+            let (input, exception_index_table) = length_count(be_u16, be_u16)(input)?;
+            (
+                input,
+                AttributeType::Exceptions {
+                    exception_index_table,
+                },
+            )
+        }
+        AttributeTag::InnerClasses => {
+            let (input, classes) = length_count(be_u16, inner_class)(input)?;
+            (input, AttributeType::InnerClasses { classes })
+        }
+        AttributeTag::EnclosingMethod => {
+            let (input, class_index) = be_u16(input)?;
+            let (input, method_index) = be_u16(input)?;
+            (
+                input,
+                AttributeType::EnclosingMethod {
+                    class_index,
+                    method_index,
+                },
+            )
+        }
+        AttributeTag::Synthetic => (input, AttributeType::Synthetic),
+        AttributeTag::Signature => {
+            let (input, signature_index) = be_u16(input)?;
+            (input, AttributeType::Signature { signature_index })
+        }
+        AttributeTag::SourceFile => {
+            let (input, sourcefile_index) = be_u16(input)?;
+            (input, AttributeType::SourceFile { sourcefile_index })
+        }
+        AttributeTag::SourceDebugExtension => {
+            let (input, debug_extension) = take(input.len())(input)?;
+            (
+                input,
+                AttributeType::SourceDebugExtension {
+                    debug_extension: debug_extension.to_vec(),
+                },
+            )
+        }
+        AttributeTag::LineNumberTable => {
+            let (input, line_number_table) = length_count(be_u16, line_number)(input)?;
+            (input, AttributeType::LineNumberTable { line_number_table })
+        }
+        AttributeTag::LocalVariableTable => {
+            let (input, local_variable_table) = length_count(be_u16, local_variable)(input)?;
+            (
+                input,
+                AttributeType::LocalVariableTable {
+                    local_variable_table,
+                },
+            )
+        }
+        AttributeTag::LocalVariableTypeTable => {
+            let (input, local_variable_type_table) =
+                length_count(be_u16, local_variable_type)(input)?;
+            (
+                input,
+                AttributeType::LocalVariableTypeTable {
+                    local_variable_type_table,
+                },
+            )
+        }
+        AttributeTag::Deprecated => (input, AttributeType::Deprecated),
+        AttributeTag::RuntimeVisibleAnnotations => {
+            let (input, annotations) = length_count(be_u16, annotation)(input)?;
+            (
+                input,
+                AttributeType::RuntimeVisibleAnnotations { annotations },
+            )
+        }
+        AttributeTag::RuntimeInvisibleAnnotations => {
+            let (input, annotations) = length_count(be_u16, annotation)(input)?;
+            (
+                input,
+                AttributeType::RuntimeInvisibleAnnotations { annotations },
+            )
+        }
+        AttributeTag::RuntimeVisibleParameterAnnotations => {
+            let (input, parameter_annotations) = length_count(be_u8, parameter_annotation)(input)?;
+            (
+                input,
+                AttributeType::RuntimeVisibleParameterAnnotations {
+                    parameter_annotations,
+                },
+            )
+        }
+        AttributeTag::RuntimeInvisibleParameterAnnotations => {
+            let (input, parameter_annotations) = length_count(be_u8, parameter_annotation)(input)?;
+            (
+                input,
+                AttributeType::RuntimeInvisibleParameterAnnotations {
+                    parameter_annotations,
+                },
+            )
+        }
+        AttributeTag::RuntimeVisibleTypeAnnotations => {
+            let (input, annotations) = length_count(be_u16, type_annotation)(input)?;
+            (
+                input,
+                AttributeType::RuntimeVisibleTypeAnnotations { annotations },
+            )
+        }
+        AttributeTag::RuntimeInvisibleTypeAnnotations => {
+            let (input, annotations) = length_count(be_u16, type_annotation)(input)?;
+            (
+                input,
+                AttributeType::RuntimeInvisibleTypeAnnotations { annotations },
+            )
+        }
+        AttributeTag::AnnotationDefault => {
+            let (input, default_value) = element_value(input)?;
+            (input, AttributeType::AnnotationDefault { default_value })
+        }
+        AttributeTag::BootstrapMethods => {
+            let (input, bootstrap_methods) = length_count(be_u16, bootstrap_method)(input)?;
+            (input, AttributeType::BootstrapMethods { bootstrap_methods })
+        }
+        AttributeTag::MethodParameters => {
+            let (input, parameters) = length_count(be_u8, method_parameter)(input)?;
+            (input, AttributeType::MethodParameters { parameters })
+        }
+        AttributeTag::Module => {
+            let (input, module_name_index) = be_u16(input)?;
+            let (input, module_flags) = be_u16(input)?;
+            let (input, module_version_index) = be_u16(input)?;
+            let (input, requires) = length_count(be_u16, require)(input)?;
+            let (input, exports) = length_count(be_u16, export)(input)?;
+            let (input, opens) = length_count(be_u16, open)(input)?;
+            let (input, uses) = length_count(be_u16, be_u16)(input)?;
+            let (input, provides) = length_count(be_u16, provide)(input)?;
+            (
+                input,
+                AttributeType::Module {
+                    module_name_index,
+                    module_flags,
+                    module_version_index,
+                    requires,
+                    exports,
+                    opens,
+                    uses,
+                    provides,
+                },
+            )
+        }
+        AttributeTag::ModulePackages => {
+            let (input, package_index) = length_count(be_u16, be_u16)(input)?;
+            (input, AttributeType::ModulePackages { package_index })
+        }
+        AttributeTag::ModuleMainClass => {
+            let (input, main_class_index) = be_u16(input)?;
+            (input, AttributeType::ModuleMainClass { main_class_index })
+        }
+        AttributeTag::NestHost => {
+            let (input, host_class_index) = be_u16(input)?;
+            (input, AttributeType::NestHost { host_class_index })
+        }
+        AttributeTag::NestMembers => {
+            let (input, classes) = length_count(be_u16, be_u16)(input)?;
+            (input, AttributeType::NestMembers { classes })
+        }
+        AttributeTag::Record => {
+            let (input, components) =
+                length_count(be_u16, record_component(constant_pool.clone(), depth))(input)?;
+            (input, AttributeType::Record { components })
+        }
+        AttributeTag::PermittedSubclasses => {
+            let (input, classes) = length_count(be_u16, be_u16)(input)?;
+            (input, AttributeType::PermittedSubclasses { classes })
+        }
+        AttributeTag::Unknown => {
+            let (input, data) = take(input.len())(input)?;
+            (
+                input,
+                AttributeType::Unknown {
+                    data: Cow::Borrowed(data),
+                },
+            )
+        }
+    })
+}
+
+fn code_attribute<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    depth: usize,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], CodeAttribute> + '_ {
     move |input: &[u8]| {
         let (input, max_stack) = be_u16(input)?;
         let (input, max_locals) = be_u16(input)?;
         let (input, code_length) = be_u32(input)?;
         let (input, code) = take(code_length)(input)?;
         let (input, exception_table) = length_count(be_u16, exception)(input)?;
-        let (input, attributes) = length_count(be_u16, attribute(constant_pool.clone()))(input)?;
+        let (input, attributes) =
+            length_count(be_u16, attribute(constant_pool.clone(), depth + 1))(input)?;
         Ok((
             input,
             CodeAttribute {
                 max_stack,
                 max_locals,
-                code,
+                code: Cow::Borrowed(code),
                 exception_table,
                 attributes,
             },
@@ -396,7 +612,8 @@ fn exception(input: &[u8]) -> Res<&[u8], Exception> {
 }
 
 fn stack_map(input: &[u8]) -> Res<&[u8], StackMap> {
-    context("stack map", be_u8)(input).and_then(|(input, frame_type)| match frame_type {
+    let at = input;
+    be_u8(input).and_then(|(input, frame_type)| match frame_type {
         0..=63 => Ok((
             input,
             StackMap {
@@ -479,19 +696,18 @@ fn stack_map(input: &[u8]) -> Res<&[u8], StackMap> {
                 },
             ))
         }
-        _ => Err(NomErr::Error(VerboseError {
-            errors: vec![(input, VerboseErrorKind::Context("invalid frame type"))],
-        })),
+        _ => fail(at, Error::InvalidFrameType),
     })
 }
 
 fn verification_type_info(input: &[u8]) -> Res<&[u8], VerificationTypeInfo> {
+    let at = input;
     context("verification type info", be_u8)(input).and_then(|(input, tag)| match tag {
         0 => Ok((input, VerificationTypeInfo::Top)),
         1 => Ok((input, VerificationTypeInfo::Integer)),
         2 => Ok((input, VerificationTypeInfo::Float)),
-        3 => Ok((input, VerificationTypeInfo::Long)),
-        4 => Ok((input, VerificationTypeInfo::Double)),
+        3 => Ok((input, VerificationTypeInfo::Double)),
+        4 => Ok((input, VerificationTypeInfo::Long)),
         5 => Ok((input, VerificationTypeInfo::Null)),
         6 => Ok((input, VerificationTypeInfo::UninitializedThis)),
         7 => {
@@ -502,9 +718,7 @@ fn verification_type_info(input: &[u8]) -> Res<&[u8], VerificationTypeInfo> {
             let (input, offset) = be_u16(input)?;
             Ok((input, VerificationTypeInfo::Uninitialized { offset }))
         }
-        _ => Err(NomErr::Error(VerboseError {
-            errors: vec![(input, VerboseErrorKind::Char(tag as char))],
-        })),
+        _ => fail(at, Error::InvalidVerificationTypeInfo),
     })
 }
 
@@ -587,9 +801,16 @@ fn local_variable_type(input: &[u8]) -> Res<&[u8], LocalVariableType> {
 }
 
 fn annotation(input: &[u8]) -> Res<&[u8], Annotation> {
+    nested_annotation(input, 0)
+}
+
+fn nested_annotation(input: &[u8], depth: usize) -> Res<&[u8], Annotation> {
     context(
         "annotation",
-        pair(be_u16, length_count(be_u16, element_value_pair)),
+        pair(
+            be_u16,
+            length_count(be_u16, |input| element_value_pair(input, depth)),
+        ),
     )(input)
     .map(|(input, (type_index, element_value_pairs))| {
         (
@@ -602,21 +823,31 @@ fn annotation(input: &[u8]) -> Res<&[u8], Annotation> {
     })
 }
 
-fn element_value_pair(input: &[u8]) -> Res<&[u8], ElementValuePair> {
-    context("element value pair", pair(be_u16, element_value))(input).map(
-        |(input, (element_name_index, value))| {
-            (
-                input,
-                ElementValuePair {
-                    element_name_index,
-                    value,
-                },
-            )
-        },
-    )
+fn element_value_pair(input: &[u8], depth: usize) -> Res<&[u8], ElementValuePair> {
+    context(
+        "element value pair",
+        pair(be_u16, |input| nested_element_value(input, depth)),
+    )(input)
+    .map(|(input, (element_name_index, value))| {
+        (
+            input,
+            ElementValuePair {
+                element_name_index,
+                value,
+            },
+        )
+    })
 }
 
 fn element_value(input: &[u8]) -> Res<&[u8], ElementValue> {
+    nested_element_value(input, 0)
+}
+
+fn nested_element_value(input: &[u8], depth: usize) -> Res<&[u8], ElementValue> {
+    if depth > MAX_ELEMENT_VALUE_NESTING {
+        return fail(input, Error::TooDeeplyNested);
+    }
+    let at = input;
     context("element value", be_u8)(input).and_then(|(input, tag)| match tag {
         b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
             let (input, value) = be_u16(input)?;
@@ -653,7 +884,7 @@ fn element_value(input: &[u8]) -> Res<&[u8], ElementValue> {
             ))
         }
         b'@' => {
-            let (input, annotation) = annotation(input)?;
+            let (input, annotation) = nested_annotation(input, depth + 1)?;
             Ok((
                 input,
                 ElementValue {
@@ -663,7 +894,8 @@ fn element_value(input: &[u8]) -> Res<&[u8], ElementValue> {
             ))
         }
         b'[' => {
-            let (input, values) = length_count(be_u16, element_value)(input)?;
+            let (input, values) =
+                length_count(be_u16, |input| nested_element_value(input, depth + 1))(input)?;
             Ok((
                 input,
                 ElementValue {
@@ -672,9 +904,7 @@ fn element_value(input: &[u8]) -> Res<&[u8], ElementValue> {
                 },
             ))
         }
-        c => Err(NomErr::Error(VerboseError {
-            errors: vec![(input, VerboseErrorKind::Char(c as char))],
-        })),
+        c => fail(at, Error::InvalidElementValueTag(c as char)),
     })
 }
 
@@ -710,6 +940,7 @@ fn type_annotation(input: &[u8]) -> Res<&[u8], TypeAnnotation> {
 }
 
 fn target_info(input: &[u8]) -> Res<&[u8], (u8, TargetInfo)> {
+    let at = input;
     context("target info", be_u8)(input).and_then(|(input, target_type)| match target_type {
         0x00 | 0x01 => {
             let (input, type_parameter_index) = be_u8(input)?;
@@ -744,7 +975,7 @@ fn target_info(input: &[u8]) -> Res<&[u8], (u8, TargetInfo)> {
                 ),
             ))
         }
-        0x13 | 0x14 | 0x15 => Ok((input, (target_type, TargetInfo::EmptyTarget))),
+        0x13..=0x15 => Ok((input, (target_type, TargetInfo::EmptyTarget))),
         0x16 => {
             let (input, formal_parameter_index) = be_u8(input)?;
             Ok((
@@ -780,11 +1011,11 @@ fn target_info(input: &[u8]) -> Res<&[u8], (u8, TargetInfo)> {
                 ),
             ))
         }
-        0x43 | 0x44 | 0x45 | 0x46 => {
+        0x43..=0x46 => {
             let (input, offset) = be_u16(input)?;
             Ok((input, (target_type, TargetInfo::OffsetTarget { offset })))
         }
-        0x47 | 0x48 | 0x49 | 0x4A | 0x4B => {
+        0x47..=0x4B => {
             let (input, offset) = be_u16(input)?;
             let (input, type_argument_index) = be_u8(input)?;
             Ok((
@@ -798,9 +1029,7 @@ fn target_info(input: &[u8]) -> Res<&[u8], (u8, TargetInfo)> {
                 ),
             ))
         }
-        _ => Err(NomErr::Error(VerboseError {
-            errors: vec![(input, VerboseErrorKind::Context("invalid target_type"))],
-        })),
+        _ => fail(at, Error::InvalidTargetType(target_type)),
     })
 }
 
@@ -869,11 +1098,14 @@ fn method_parameter(input: &[u8]) -> Res<&[u8], MethodParameter> {
 }
 
 fn constant_tag(input: &[u8]) -> Res<&[u8], ConstantTag> {
-    context("constant tag", map(be_u8, Into::into))(input)
+    be_u8(input).and_then(|(rest, tag)| match ConstantTag::try_from(tag) {
+        Ok(tag) => Ok((rest, tag)),
+        Err(kind) => fail(input, kind),
+    })
 }
 
-fn constant(input: &[u8]) -> Res<&[u8], Constant> {
-    context("constant", constant_tag)(input).and_then(|(input, tag)| match tag {
+fn constant(input: &[u8]) -> Res<&[u8], Constant<'_>> {
+    constant_tag(input).and_then(|(input, tag)| match tag {
         ConstantTag::Class => {
             let (input, name_index) = be_u16(input)?;
             Ok((input, Constant::Class { name_index }))
@@ -945,7 +1177,7 @@ fn constant(input: &[u8]) -> Res<&[u8], Constant> {
         ConstantTag::Utf8 => {
             let (input, length) = be_u16(input)?;
             let (input, bytes) = take(length)(input)?;
-            Ok((input, Constant::Utf8(bytes)))
+            Ok((input, Constant::Utf8(Cow::Borrowed(bytes))))
         }
         ConstantTag::MethodHandle => {
             let (input, reference_kind) = be_u8(input)?;
@@ -995,17 +1227,15 @@ fn constant(input: &[u8]) -> Res<&[u8], Constant> {
     })
 }
 
-fn field_info<'a, E: ParseError<&'a [u8]>>(
-    constant_pool: ConstantPoolRef,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], FieldInfo, E> + '_
-where
-    NomErr<E>: From<NomErr<VerboseError<&'a [u8]>>>,
-{
+fn field_info<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    lazy: bool,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], FieldInfo> + '_ {
     move |input| {
         let (input, access_flags) = be_u16(input)?;
         let (input, name_index) = be_u16(input)?;
         let (input, descriptor_index) = be_u16(input)?;
-        let (input, attributes) = length_count(be_u16, attribute(constant_pool.clone()))(input)?;
+        let (input, attributes) = attributes(constant_pool.clone(), lazy)(input)?;
         Ok((
             input,
             FieldInfo {
@@ -1018,21 +1248,26 @@ where
     }
 }
 
-fn method_info<'a, E: ParseError<&'a [u8]>>(
-    constant_pool: ConstantPoolRef,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], MethodInfo, E> + '_
-where
-    NomErr<E>: From<NomErr<VerboseError<&'a [u8]>>>,
-{
+fn method_info<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    lazy: bool,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], MethodInfo> + '_ {
     move |input| {
         let (input, access_flags) = be_u16(input)?;
         let (input, name_index) = be_u16(input)?;
         let (input, descriptor_index) = be_u16(input)?;
-        let (input, attributes) = length_count(be_u16, attribute(constant_pool.clone()))(input)?;
+        let (input, attributes) = attributes(constant_pool.clone(), lazy)(input)?;
         let mut code_attr_index = None;
         for (i, attr) in attributes.iter().enumerate() {
-            if let AttributeType::Code { .. } = attr.attribute_type {
-                code_attr_index = Some(i);
+            match attr.attribute_type {
+                AttributeType::Code { .. } => code_attr_index = Some(i),
+                AttributeType::Lazy { .. }
+                    if utf8(&constant_pool, attr.attribute_name_index as usize)
+                        .is_ok_and(|name| name[..] == b"Code"[..]) =>
+                {
+                    code_attr_index = Some(i)
+                }
+                _ => {}
             }
         }
         Ok((
@@ -1111,16 +1346,15 @@ fn provide(input: &[u8]) -> Res<&[u8], Provide> {
     )
 }
 
-fn record_component<'a, E: ParseError<&'a [u8]>>(
-    constant_pool: ConstantPoolRef,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], RecordComponent, E> + '_
-where
-    NomErr<E>: From<NomErr<VerboseError<&'a [u8]>>>,
-{
+fn record_component<'a>(
+    constant_pool: ConstantPoolRef<'_>,
+    depth: usize,
+) -> impl FnMut(&'a [u8]) -> Res<&'a [u8], RecordComponent> + '_ {
     move |input| {
         let (input, name_index) = be_u16(input)?;
         let (input, descriptor_index) = be_u16(input)?;
-        let (input, attributes) = length_count(be_u16, attribute(constant_pool.clone()))(input)?;
+        let (input, attributes) =
+            length_count(be_u16, attribute(constant_pool.clone(), depth + 1))(input)?;
         Ok((
             input,
             RecordComponent {
@@ -1134,21 +1368,310 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::parse;
+    use crate::{
+        get_str, get_utf8, parse, parse_lazy, Attribute, AttributeType, ClassFile, Constant, Error,
+    };
+    use std::borrow::Cow;
     use std::io::Read;
+    use std::sync::Arc;
+
+    fn gauss_test() -> Vec<u8> {
+        std::fs::read("../data/jvm8/GaussTest.class").unwrap()
+    }
+
+    fn attribute(name_index: u16, body: &[u8]) -> Vec<u8> {
+        let mut bytes = name_index.to_be_bytes().to_vec();
+        bytes.extend((body.len() as u32).to_be_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    // a class whose constant pool holds `constants` as CONSTANT_Utf8 and which has
+    // a single method "m" with the given attributes
+    fn class_file(constants: &[&str], method_attributes: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"\xCA\xFE\xBA\xBE\x00\x00\x00\x34".to_vec();
+        bytes.extend((constants.len() as u16 + 1).to_be_bytes());
+        for constant in constants {
+            bytes.push(1);
+            bytes.extend((constant.len() as u16).to_be_bytes());
+            bytes.extend(constant.as_bytes());
+        }
+        // access flags, this class, super class, interfaces, fields
+        bytes.extend([0, 0x21, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend([0, 1, 0, 1, 0, 1, 0, 1]);
+        bytes.extend((method_attributes.len() as u16).to_be_bytes());
+        for attribute in method_attributes {
+            bytes.extend(attribute);
+        }
+        bytes.extend([0, 0]);
+        bytes
+    }
+
+    fn code(attributes: &[Vec<u8>]) -> Vec<u8> {
+        // max_stack, max_locals, code: return, exception table
+        let mut bytes = vec![0, 0, 0, 1, 0, 0, 0, 1, 0xb1, 0, 0];
+        bytes.extend((attributes.len() as u16).to_be_bytes());
+        for attribute in attributes {
+            bytes.extend(attribute);
+        }
+        bytes
+    }
 
     #[test]
     fn read_class_file() {
-        let file = std::fs::File::open("../data/jvm8/GaussTest.class").unwrap();
-        let bytes: Vec<u8> = file.bytes().map(|x| x.unwrap()).collect();
-        let ret = parse(bytes.as_slice());
-
-        match ret {
-            Ok((input, class_file)) => {
-                println!("{:?}", class_file);
-                assert_eq!(input.len(), 0);
+        let mut file = std::fs::File::open("../data/jvm8/GaussTest.class").unwrap();
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).unwrap();
+        let class_file = parse(bytes.as_slice()).unwrap();
+        println!("{:?}", class_file);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = gauss_test();
+        for len in 4..bytes.len() {
+            let error = parse(&bytes[..len]).unwrap_err();
+            assert!(matches!(error.kind, Error::UnexpectedEof), "{}", error);
+            assert!(error.offset <= len, "{}", error);
+        }
+
+        let mut bytes = bytes;
+        bytes.push(0);
+        let error = parse(&bytes).unwrap_err();
+        assert!(matches!(error.kind, Error::TrailingBytes(1)));
+        assert_eq!(error.offset, bytes.len() - 1);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let mut bytes = gauss_test();
+        bytes[0] = 0xCB;
+        let error = parse(&bytes).unwrap_err();
+        assert!(matches!(error.kind, Error::InvalidMagic));
+        assert_eq!(error.offset, 0);
+
+        let mut bytes = gauss_test();
+        bytes[10] = 2;
+        let error = parse(&bytes).unwrap_err();
+        assert!(matches!(error.kind, Error::InvalidConstantTag(2)));
+        assert_eq!(error.offset, 10);
+        assert_eq!(error.context, ["constant pool", "constant #1"]);
+        assert_eq!(
+            error.to_string(),
+            "unknown constant tag 2 at offset 10 in constant pool / constant #1"
+        );
+    }
+
+    #[test]
+    fn test_context() {
+        let constants = ["Code", "StackMapTable", "m", "()V"];
+        // a single frame of the reserved type 128
+        let stack_map_table = attribute(2, &[0, 1, 128]);
+        let bytes = class_file(&constants, &[attribute(1, &code(&[stack_map_table]))]);
+        let error = parse(&bytes).unwrap_err();
+        assert!(matches!(error.kind, Error::InvalidFrameType));
+        assert_eq!(error.offset, bytes.len() - 3);
+        assert_eq!(
+            error.context,
+            ["method #0", "Code", "StackMapTable", "frame #0"]
+        );
+
+        // the attribute is longer than its contents
+        let stack_map_table = attribute(2, &[0, 1, 0, 0]);
+        let bytes = class_file(&constants, &[attribute(1, &code(&[stack_map_table]))]);
+        let error = parse(&bytes).unwrap_err();
+        assert!(matches!(error.kind, Error::InvalidLength));
+        assert_eq!(error.offset, bytes.len() - 3);
+        assert_eq!(error.context, ["method #0", "Code", "StackMapTable"]);
+
+        // the attribute name is not a CONSTANT_Utf8
+        let bytes = class_file(&constants, &[attribute(5, &[])]);
+        let error = parse(&bytes).unwrap_err();
+        assert!(matches!(error.kind, Error::InvalidConstantIndex(5)));
+        assert_eq!(error.context, ["method #0", "attribute name"]);
+        assert!(parse_lazy(&bytes).is_err());
+
+        // lazily parsed attributes fail when they are decoded
+        let stack_map_table = attribute(2, &[0, 1, 128]);
+        let bytes = class_file(&constants, &[attribute(1, &code(&[stack_map_table]))]);
+        let class_file = parse_lazy(&bytes).unwrap();
+        let error = class_file.methods[0].attributes[0]
+            .decode(class_file.constant_pool.clone())
+            .unwrap_err();
+        assert!(matches!(error.kind, Error::InvalidFrameType));
+        assert_eq!(error.context, ["Code", "StackMapTable", "frame #0"]);
+    }
+
+    #[test]
+    fn test_nesting() {
+        let constants = ["Code", "RuntimeVisibleAnnotations", "m", "()V", "LA;", "v"];
+        let mut code_attribute = attribute(1, &code(&[]));
+        for _ in 0..100 {
+            code_attribute = attribute(1, &code(&[code_attribute]));
+        }
+        let error = parse(&class_file(&constants, &[code_attribute])).unwrap_err();
+        assert!(matches!(error.kind, Error::TooDeeplyNested));
+
+        let annotations = |depth: usize| {
+            // one annotation of type #5 with a single element v = {{...{}...}}
+            let mut body = vec![0, 1, 0, 5, 0, 1, 0, 6];
+            body.extend([b'[', 0, 1].repeat(depth));
+            body.extend([b'[', 0, 0]);
+            attribute(2, &body)
+        };
+        assert!(parse(&class_file(&constants, &[annotations(100)])).is_ok());
+        let error = parse(&class_file(&constants, &[annotations(100_000)])).unwrap_err();
+        assert!(matches!(error.kind, Error::TooDeeplyNested));
+        assert_eq!(
+            error.context[..2],
+            ["method #0", "RuntimeVisibleAnnotations"]
+        );
+    }
+
+    #[test]
+    fn test_type_annotations() {
+        let constants = [
+            "RuntimeVisibleTypeAnnotations",
+            "RuntimeInvisibleTypeAnnotations",
+            "m",
+            "()V",
+            "LA;",
+        ];
+        // the count is a u16, more than 255 annotations of type #5 on the return type
+        let mut body = 257u16.to_be_bytes().to_vec();
+        body.extend([0x14, 0, 0, 5, 0, 0].repeat(257));
+        let bytes = class_file(&constants, &[attribute(1, &body), attribute(2, &body)]);
+        let class_file = parse(&bytes).unwrap();
+        for attribute in class_file.methods[0].attributes.iter() {
+            match &attribute.attribute_type {
+                AttributeType::RuntimeVisibleTypeAnnotations { annotations }
+                | AttributeType::RuntimeInvisibleTypeAnnotations { annotations } => {
+                    assert_eq!(annotations.len(), 257);
+                    assert_eq!(annotations[256].type_index, 5);
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let bytes = gauss_test();
+        for i in 0..bytes.len() {
+            for mask in [0x01, 0x80, 0xff] {
+                let mut bytes = bytes.clone();
+                bytes[i] ^= mask;
+                if let Err(error) = parse(&bytes) {
+                    assert!(error.offset <= bytes.len(), "{}", error);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_utf8() {
+        let constant_pool = Arc::new(vec![
+            Constant::Placeholder,
+            Constant::Utf8(Cow::Borrowed(b"java/lang/Object")),
+            Constant::Class { name_index: 1 },
+            Constant::Class { name_index: 2 },
+            Constant::Utf8(Cow::Borrowed(b"\xff")),
+        ]);
+        assert_eq!(
+            get_utf8(constant_pool.clone(), 2).unwrap()[..],
+            b"java/lang/Object"[..]
+        );
+        assert_eq!(
+            get_str(constant_pool.clone(), 1).unwrap(),
+            "java/lang/Object"
+        );
+        assert!(matches!(
+            get_utf8(constant_pool.clone(), 0),
+            Err(Error::InvalidConstantIndex(0))
+        ));
+        assert!(matches!(
+            get_utf8(constant_pool.clone(), 5),
+            Err(Error::InvalidConstantIndex(5))
+        ));
+        assert!(matches!(
+            get_utf8(constant_pool.clone(), 3),
+            Err(Error::MismatchConstantType)
+        ));
+        assert!(matches!(
+            get_str(constant_pool, 4),
+            Err(Error::InvalidString(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let bytes = gauss_test();
+        let json = serde_json::to_string(&parse(&bytes).unwrap()).unwrap();
+        let class_file: crate::ClassFile = serde_json::from_str(&json).unwrap();
+        assert_eq!(crate::write(&class_file).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_into_static() {
+        let bytes = gauss_test();
+        let class_file = parse(&bytes).unwrap();
+        // the constant pool is shared with the borrowed class file
+        let owned = class_file.clone().into_static();
+        assert!(owned
+            .constant_pool
+            .iter()
+            .all(|constant| !matches!(constant, Constant::Utf8(Cow::Borrowed(_)))));
+        let code = owned.methods[1].code_attribute().unwrap();
+        assert!(matches!(code.code, Cow::Owned(_)));
+        let written = std::thread::spawn(move || crate::write(&owned).unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(written, bytes);
+        drop(class_file);
+    }
+
+    #[test]
+    fn test_parse_lazy() {
+        fn attributes<'a>(class_file: &ClassFile<'a>) -> Vec<Attribute<'a>> {
+            let fields = class_file.fields.iter().map(|f| f.attributes.clone());
+            let methods = class_file.methods.iter().map(|m| m.attributes.clone());
+            fields
+                .chain(methods)
+                .chain([class_file.attributes.clone()])
+                .flatten()
+                .collect()
+        }
+
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if !file.name().starts_with("java/") || !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            let eager = parse(&bytes).unwrap();
+            let lazy = parse_lazy(&bytes).unwrap();
+            assert_eq!(crate::write(&lazy).unwrap(), bytes, "{}", file.name());
+
+            let constant_pool = lazy.constant_pool.clone();
+            for (eager, lazy) in attributes(&eager).iter().zip(attributes(&lazy).iter()) {
+                assert!(matches!(lazy.attribute_type, AttributeType::Lazy { .. }));
+                let decoded = lazy.decode(constant_pool.clone()).unwrap();
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", eager));
+            }
+            for (eager, lazy) in eager.methods.iter().zip(lazy.methods.iter()) {
+                assert_eq!(eager.code_attr_index, lazy.code_attr_index);
+                // left undecoded, which only decode_code_attribute tells apart from no code
+                assert!(lazy.code_attribute().is_none());
+                let code = lazy.decode_code_attribute(constant_pool.clone()).unwrap();
+                assert_eq!(
+                    format!("{:?}", code),
+                    format!("{:?}", eager.code_attribute())
+                );
             }
-            n => println!("{:?}", n),
         }
     }
 }
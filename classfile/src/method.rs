@@ -1,7 +1,9 @@
 use crate::attribute::Attribute;
-use crate::{AttributeType, CodeAttribute};
+use crate::{AttributeType, CodeAttribute, ConstantPoolRef, ParseError};
+use std::borrow::Cow;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MethodInfo<'a> {
     pub access_flags: u16,
     pub name_index: u16,
@@ -11,6 +13,9 @@ pub struct MethodInfo<'a> {
 }
 
 impl<'a> MethodInfo<'a> {
+    /// The decoded Code attribute. `None` if the method has none, but also if
+    /// it was left as `AttributeType::Lazy` by `parse_lazy`, which
+    /// `decode_code_attribute` decodes.
     pub fn code_attribute(&self) -> Option<&CodeAttribute<'a>> {
         if let Some(index) = self.code_attr_index {
             if let Some(attr) = self.attributes.get(index) {
@@ -21,4 +26,44 @@ impl<'a> MethodInfo<'a> {
         }
         None
     }
+
+    /// The Code attribute, decoded on access if `parse_lazy` left it undecoded.
+    /// `Ok(None)` only if the method has no code.
+    pub fn decode_code_attribute(
+        &self,
+        constant_pool: ConstantPoolRef<'_>,
+    ) -> Result<Option<Cow<'_, CodeAttribute<'_>>>, ParseError> {
+        let attribute = match self.code_attr_index {
+            Some(index) => match self.attributes.get(index) {
+                Some(attribute) => attribute.decode(constant_pool)?,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        Ok(match attribute {
+            Cow::Borrowed(Attribute {
+                attribute_type: AttributeType::Code { code },
+                ..
+            }) => Some(Cow::Borrowed(code)),
+            Cow::Owned(Attribute {
+                attribute_type: AttributeType::Code { code },
+                ..
+            }) => Some(Cow::Owned(code)),
+            _ => None,
+        })
+    }
+
+    pub fn into_static(self) -> MethodInfo<'static> {
+        MethodInfo {
+            access_flags: self.access_flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: self
+                .attributes
+                .into_iter()
+                .map(Attribute::into_static)
+                .collect(),
+            code_attr_index: self.code_attr_index,
+        }
+    }
 }
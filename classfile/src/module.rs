@@ -0,0 +1,477 @@
+//! Module descriptors, read from the `Module`, `ModulePackages` and
+//! `ModuleMainClass` attributes of a `module-info` class, JVMS 4.7.25-27.
+
+use crate::errors::Error;
+use crate::{mutf8, AttributeType, ClassFile, Constant};
+
+// ACC_OPEN is a module flag, the others are flags of requires, exports and opens
+pub const ACC_OPEN: u16 = 0x0020;
+pub const ACC_TRANSITIVE: u16 = 0x0020;
+pub const ACC_STATIC_PHASE: u16 = 0x0040;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_MANDATED: u16 = 0x8000;
+
+/// A module with its names resolved from the constant pool. Modules are named
+/// as in `java.base`, packages and classes by their binary names, as in
+/// `java/lang` and `java/lang/Object`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDescriptor {
+    pub name: String,
+    pub flags: u16,
+    pub version: Option<String>,
+    pub requires: Vec<Requires>,
+    pub exports: Vec<Exports>,
+    pub opens: Vec<Opens>,
+    /// The service interfaces the module uses.
+    pub uses: Vec<String>,
+    pub provides: Vec<Provides>,
+    /// All packages of the module, if the `ModulePackages` attribute lists them.
+    pub packages: Vec<String>,
+    pub main_class: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requires {
+    pub name: String,
+    pub flags: u16,
+    /// The version of the module when the descriptor was compiled.
+    pub compiled_version: Option<String>,
+}
+
+/// An exported package, to the given modules only if there are any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exports {
+    pub package: String,
+    pub flags: u16,
+    pub targets: Vec<String>,
+}
+
+/// A package open for reflection, to the given modules only if there are any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opens {
+    pub package: String,
+    pub flags: u16,
+    pub targets: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provides {
+    pub service: String,
+    pub providers: Vec<String>,
+}
+
+impl ModuleDescriptor {
+    /// The descriptor of a `module-info` class, `None` if the class file has
+    /// no `Module` attribute. Lazily parsed attributes are decoded.
+    pub fn read(class_file: &ClassFile) -> Result<Option<ModuleDescriptor>, Error> {
+        let mut descriptor = None;
+        let mut packages = vec![];
+        let mut main_class = None;
+        for attribute in &class_file.attributes {
+            let attribute = attribute
+                .decode(class_file.constant_pool.clone())
+                .map_err(|e| e.kind)?;
+            match &attribute.attribute_type {
+                AttributeType::Module {
+                    module_name_index,
+                    module_flags,
+                    module_version_index,
+                    requires,
+                    exports,
+                    opens,
+                    uses,
+                    provides,
+                } => {
+                    let mut module = ModuleDescriptor {
+                        name: module_name(class_file, *module_name_index)?,
+                        flags: *module_flags,
+                        version: optional(class_file, *module_version_index)?,
+                        requires: Vec::with_capacity(requires.len()),
+                        exports: Vec::with_capacity(exports.len()),
+                        opens: Vec::with_capacity(opens.len()),
+                        uses: Vec::with_capacity(uses.len()),
+                        provides: Vec::with_capacity(provides.len()),
+                        packages: vec![],
+                        main_class: None,
+                    };
+                    for require in requires {
+                        module.requires.push(Requires {
+                            name: module_name(class_file, require.require_index)?,
+                            flags: require.require_flags,
+                            compiled_version: optional(class_file, require.require_version_index)?,
+                        });
+                    }
+                    for export in exports {
+                        module.exports.push(Exports {
+                            package: package_name(class_file, export.export_index)?,
+                            flags: export.export_flags,
+                            targets: module_names(class_file, &export.export_to_index)?,
+                        });
+                    }
+                    for open in opens {
+                        module.opens.push(Opens {
+                            package: package_name(class_file, open.open_index)?,
+                            flags: open.open_flags,
+                            targets: module_names(class_file, &open.open_to_index)?,
+                        });
+                    }
+                    for index in uses {
+                        module.uses.push(class_name(class_file, *index)?);
+                    }
+                    for provide in provides {
+                        let mut providers = Vec::with_capacity(provide.provide_with_index.len());
+                        for index in &provide.provide_with_index {
+                            providers.push(class_name(class_file, *index)?);
+                        }
+                        module.provides.push(Provides {
+                            service: class_name(class_file, provide.provide_index)?,
+                            providers,
+                        });
+                    }
+                    descriptor = Some(module);
+                }
+                AttributeType::ModulePackages { package_index } => {
+                    for index in package_index {
+                        packages.push(package_name(class_file, *index)?);
+                    }
+                }
+                AttributeType::ModuleMainClass { main_class_index } => {
+                    main_class = Some(class_name(class_file, *main_class_index)?);
+                }
+                _ => {}
+            }
+        }
+        Ok(descriptor.map(|descriptor| ModuleDescriptor {
+            packages,
+            main_class,
+            ..descriptor
+        }))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.flags & ACC_OPEN != 0
+    }
+
+    /// The packages exported to every module.
+    pub fn exported_packages(&self) -> impl Iterator<Item = &str> {
+        self.exports
+            .iter()
+            .filter(|export| export.targets.is_empty())
+            .map(|export| export.package.as_str())
+    }
+}
+
+impl Requires {
+    pub fn is_transitive(&self) -> bool {
+        self.flags & ACC_TRANSITIVE != 0
+    }
+
+    /// Whether the module is required at compile time only, `requires static`.
+    pub fn is_static(&self) -> bool {
+        self.flags & ACC_STATIC_PHASE != 0
+    }
+
+    pub fn is_mandated(&self) -> bool {
+        self.flags & ACC_MANDATED != 0
+    }
+}
+
+fn constant<'c, 'a>(class_file: &'c ClassFile<'a>, index: u16) -> Result<&'c Constant<'a>, Error> {
+    match class_file.constant_pool.get(index as usize) {
+        None | Some(Constant::Placeholder) => Err(Error::InvalidConstantIndex(index as usize)),
+        Some(constant) => Ok(constant),
+    }
+}
+
+fn utf8(class_file: &ClassFile, index: u16) -> Result<String, Error> {
+    match constant(class_file, index)? {
+        Constant::Utf8(bytes) => mutf8::decode(bytes),
+        _ => Err(Error::MismatchConstantType),
+    }
+}
+
+// index 0 stands for no version
+fn optional(class_file: &ClassFile, index: u16) -> Result<Option<String>, Error> {
+    match index {
+        0 => Ok(None),
+        _ => utf8(class_file, index).map(Some),
+    }
+}
+
+fn module_name(class_file: &ClassFile, index: u16) -> Result<String, Error> {
+    match constant(class_file, index)? {
+        Constant::Module { name_index } => utf8(class_file, *name_index),
+        _ => Err(Error::MismatchConstantType),
+    }
+}
+
+fn module_names(class_file: &ClassFile, indices: &[u16]) -> Result<Vec<String>, Error> {
+    indices
+        .iter()
+        .map(|index| module_name(class_file, *index))
+        .collect()
+}
+
+fn package_name(class_file: &ClassFile, index: u16) -> Result<String, Error> {
+    match constant(class_file, index)? {
+        Constant::Package { name_index } => utf8(class_file, *name_index),
+        _ => Err(Error::MismatchConstantType),
+    }
+}
+
+fn class_name(class_file: &ClassFile, index: u16) -> Result<String, Error> {
+    match constant(class_file, index)? {
+        Constant::Class { name_index } => utf8(class_file, *name_index),
+        _ => Err(Error::MismatchConstantType),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::check;
+    use crate::module::{Exports, ModuleDescriptor, Opens, Provides, Requires, ACC_MANDATED};
+    use crate::{parse, parse_lazy, write, Attribute, AttributeType, ClassFile, Constant, Error};
+    use crate::{Export, Open, Provide, Require};
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    fn push(class_file: &mut ClassFile, constant: Constant<'static>) -> u16 {
+        let constant_pool = Arc::make_mut(&mut class_file.constant_pool);
+        constant_pool.push(constant);
+        (constant_pool.len() - 1) as u16
+    }
+
+    fn utf8(class_file: &mut ClassFile, s: &'static str) -> u16 {
+        push(class_file, Constant::Utf8(Cow::Borrowed(s.as_bytes())))
+    }
+
+    fn module(class_file: &mut ClassFile, name: &'static str) -> u16 {
+        let name_index = utf8(class_file, name);
+        push(class_file, Constant::Module { name_index })
+    }
+
+    fn package(class_file: &mut ClassFile, name: &'static str) -> u16 {
+        let name_index = utf8(class_file, name);
+        push(class_file, Constant::Package { name_index })
+    }
+
+    fn class(class_file: &mut ClassFile, name: &'static str) -> u16 {
+        let name_index = utf8(class_file, name);
+        push(class_file, Constant::Class { name_index })
+    }
+
+    fn attribute(
+        class_file: &mut ClassFile,
+        name: &'static str,
+        attribute_type: AttributeType<'static>,
+    ) {
+        let attribute_name_index = utf8(class_file, name);
+        class_file.attributes.push(Attribute {
+            attribute_name_index,
+            attribute_length: 0,
+            attribute_type,
+        });
+    }
+
+    // module com.example@1.0 {
+    //     requires transitive java.sql;
+    //     exports com.example.api;
+    //     exports com.example.spi to com.example.impl;
+    //     opens com.example.model;
+    //     uses java.sql.Driver;
+    //     provides java.sql.Driver with com.example.impl.ExampleDriver;
+    // }
+    fn module_info() -> ClassFile<'static> {
+        let bytes = std::fs::read("../data/jvm8/GaussTest.class").unwrap();
+        let mut class_file = parse(&bytes).unwrap().into_static();
+        class_file.major_version = 53;
+        class_file.access_flags = 0x8000;
+        class_file.this_class = class(&mut class_file, "module-info");
+        class_file.super_class = 0;
+        class_file.methods.clear();
+        class_file.attributes.clear();
+
+        let module_name_index = module(&mut class_file, "com.example");
+        let module_version_index = utf8(&mut class_file, "1.0");
+        let java_base = module(&mut class_file, "java.base");
+        let java_base_version = utf8(&mut class_file, "17");
+        let java_sql = module(&mut class_file, "java.sql");
+        let api = package(&mut class_file, "com/example/api");
+        let spi = package(&mut class_file, "com/example/spi");
+        let model = package(&mut class_file, "com/example/model");
+        let implementation = module(&mut class_file, "com.example.impl");
+        let driver = class(&mut class_file, "java/sql/Driver");
+        let example_driver = class(&mut class_file, "com/example/impl/ExampleDriver");
+        let module = AttributeType::Module {
+            module_name_index,
+            module_flags: 0,
+            module_version_index,
+            requires: vec![
+                Require {
+                    require_index: java_base,
+                    require_flags: ACC_MANDATED,
+                    require_version_index: java_base_version,
+                },
+                Require {
+                    require_index: java_sql,
+                    require_flags: 0x0020,
+                    require_version_index: 0,
+                },
+            ],
+            exports: vec![
+                Export {
+                    export_index: api,
+                    export_flags: 0,
+                    export_to_index: vec![],
+                },
+                Export {
+                    export_index: spi,
+                    export_flags: 0,
+                    export_to_index: vec![implementation],
+                },
+            ],
+            opens: vec![Open {
+                open_index: model,
+                open_flags: 0,
+                open_to_index: vec![],
+            }],
+            uses: vec![driver],
+            provides: vec![Provide {
+                provide_index: driver,
+                provide_with_index: vec![example_driver],
+            }],
+        };
+        attribute(&mut class_file, "Module", module);
+
+        let main_class_index = class(&mut class_file, "com/example/Main");
+        let main = package(&mut class_file, "com/example");
+        let package_index = vec![main, api, spi, model];
+        attribute(
+            &mut class_file,
+            "ModulePackages",
+            AttributeType::ModulePackages { package_index },
+        );
+        attribute(
+            &mut class_file,
+            "ModuleMainClass",
+            AttributeType::ModuleMainClass { main_class_index },
+        );
+        class_file
+    }
+
+    fn strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_read() {
+        let class_file = module_info();
+        check(&class_file).unwrap();
+        let bytes = write(&class_file).unwrap();
+        for class_file in [parse(&bytes).unwrap(), parse_lazy(&bytes).unwrap()] {
+            let module = ModuleDescriptor::read(&class_file).unwrap().unwrap();
+            assert_eq!(module.name, "com.example");
+            assert_eq!(module.version.as_deref(), Some("1.0"));
+            assert!(!module.is_open());
+            assert_eq!(
+                module.requires,
+                vec![
+                    Requires {
+                        name: "java.base".to_string(),
+                        flags: ACC_MANDATED,
+                        compiled_version: Some("17".to_string()),
+                    },
+                    Requires {
+                        name: "java.sql".to_string(),
+                        flags: 0x0020,
+                        compiled_version: None,
+                    },
+                ]
+            );
+            assert!(module.requires[0].is_mandated());
+            assert!(!module.requires[0].is_transitive());
+            assert!(module.requires[1].is_transitive());
+            assert!(!module.requires[1].is_static());
+            assert_eq!(
+                module.exports,
+                vec![
+                    Exports {
+                        package: "com/example/api".to_string(),
+                        flags: 0,
+                        targets: vec![],
+                    },
+                    Exports {
+                        package: "com/example/spi".to_string(),
+                        flags: 0,
+                        targets: strings(&["com.example.impl"]),
+                    },
+                ]
+            );
+            assert_eq!(
+                module.exported_packages().collect::<Vec<_>>(),
+                ["com/example/api"]
+            );
+            assert_eq!(
+                module.opens,
+                vec![Opens {
+                    package: "com/example/model".to_string(),
+                    flags: 0,
+                    targets: vec![],
+                }]
+            );
+            assert_eq!(module.uses, strings(&["java/sql/Driver"]));
+            assert_eq!(
+                module.provides,
+                vec![Provides {
+                    service: "java/sql/Driver".to_string(),
+                    providers: strings(&["com/example/impl/ExampleDriver"]),
+                }]
+            );
+            assert_eq!(
+                module.packages,
+                strings(&[
+                    "com/example",
+                    "com/example/api",
+                    "com/example/spi",
+                    "com/example/model"
+                ])
+            );
+            assert_eq!(module.main_class.as_deref(), Some("com/example/Main"));
+        }
+    }
+
+    #[test]
+    fn test_not_a_module() {
+        let bytes = std::fs::read("../data/jvm8/GaussTest.class").unwrap();
+        let class_file = parse(&bytes).unwrap();
+        assert_eq!(ModuleDescriptor::read(&class_file).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        // a module named by a class constant
+        let mut class_file = module_info();
+        let this_class = class_file.this_class;
+        if let AttributeType::Module {
+            module_name_index, ..
+        } = &mut class_file.attributes[0].attribute_type
+        {
+            *module_name_index = this_class;
+        }
+        assert!(matches!(
+            ModuleDescriptor::read(&class_file),
+            Err(Error::MismatchConstantType)
+        ));
+
+        let mut class_file = module_info();
+        if let AttributeType::ModuleMainClass { main_class_index } =
+            &mut class_file.attributes[2].attribute_type
+        {
+            *main_class_index = 0;
+        }
+        assert!(matches!(
+            ModuleDescriptor::read(&class_file),
+            Err(Error::InvalidConstantIndex(0))
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+//! Modified UTF-8, the encoding of `CONSTANT_Utf8_info`, JVMS 4.4.7.
+//!
+//! It differs from standard UTF-8 in two ways: the null character is encoded in
+//! two bytes, so no encoded string contains a zero byte, and supplementary
+//! characters are encoded as a surrogate pair of three bytes each, so there are
+//! no four byte forms.
+
+use crate::errors::Error;
+
+fn invalid(bytes: &[u8]) -> Error {
+    Error::InvalidString(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Decodes into UTF-16 code units, which may include unpaired surrogates like
+/// any Java string.
+pub fn decode_utf16(bytes: &[u8]) -> Result<Vec<u16>, Error> {
+    let continuation = |i: usize| match bytes.get(i) {
+        Some(b) if b & 0xc0 == 0x80 => Ok((b & 0x3f) as u16),
+        _ => Err(invalid(bytes)),
+    };
+    let mut chars = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i] as u16;
+        match b {
+            0x01..=0x7f => {
+                chars.push(b);
+                i += 1;
+            }
+            0xc0..=0xdf => {
+                chars.push((b & 0x1f) << 6 | continuation(i + 1)?);
+                i += 2;
+            }
+            0xe0..=0xef => {
+                chars.push((b & 0x0f) << 12 | continuation(i + 1)? << 6 | continuation(i + 2)?);
+                i += 3;
+            }
+            _ => return Err(invalid(bytes)),
+        }
+    }
+    Ok(chars)
+}
+
+/// Decodes into a Rust string, which cannot hold unpaired surrogates.
+pub fn decode(bytes: &[u8]) -> Result<String, Error> {
+    // without zero bytes and four byte forms, valid UTF-8 is the same in both
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        if !bytes.iter().any(|&b| b == 0 || b >= 0xf0) {
+            return Ok(s.to_string());
+        }
+    }
+    String::from_utf16(&decode_utf16(bytes)?).map_err(|_| invalid(bytes))
+}
+
+pub fn encode_utf16(chars: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(chars.len());
+    for &c in chars {
+        match c {
+            0x0001..=0x007f => bytes.push(c as u8),
+            0x0000 | 0x0080..=0x07ff => {
+                bytes.extend([0xc0 | (c >> 6) as u8, 0x80 | (c & 0x3f) as u8]);
+            }
+            _ => bytes.extend([
+                0xe0 | (c >> 12) as u8,
+                0x80 | (c >> 6 & 0x3f) as u8,
+                0x80 | (c & 0x3f) as u8,
+            ]),
+        }
+    }
+    bytes
+}
+
+pub fn encode(s: &str) -> Vec<u8> {
+    encode_utf16(&s.encode_utf16().collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mutf8::{decode, decode_utf16, encode, encode_utf16};
+    use crate::Error;
+
+    #[test]
+    fn test_round_trip() {
+        for s in [
+            "",
+            "java/lang/Object",
+            "a\0b",
+            "\u{7f}\u{80}",
+            "é€",
+            "\u{10400}x",
+        ] {
+            assert_eq!(decode(&encode(s)).unwrap(), s);
+        }
+        assert_eq!(encode("a\0b"), b"a\xc0\x80b");
+        assert_eq!(encode("\u{10400}"), b"\xed\xa0\x81\xed\xb0\x80");
+        assert_eq!(encode("é€"), "é€".as_bytes());
+
+        // an unpaired surrogate survives as UTF-16 but not as a Rust string
+        let bytes = encode_utf16(&[0x61, 0xd800]);
+        assert_eq!(bytes, b"a\xed\xa0\x80");
+        assert_eq!(decode_utf16(&bytes).unwrap(), [0x61, 0xd800]);
+        assert!(matches!(decode(&bytes), Err(Error::InvalidString(_))));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for bytes in [
+            &b"a\0"[..],
+            b"\xf0\x90\x90\x80",
+            b"\xc0",
+            b"\xe0\x80",
+            b"\xc0\x00",
+            b"\x80",
+        ] {
+            assert!(decode_utf16(bytes).is_err(), "{:?}", bytes);
+            assert!(decode(bytes).is_err(), "{:?}", bytes);
+        }
+    }
+}
@@ -0,0 +1,829 @@
+//! Serializes a `ClassFile` back to bytes, JVMS 4.
+//!
+//! Attribute lengths are computed from the attributes' contents, so a class file
+//! that was parsed and modified is written consistently. An unmodified one is
+//! written back byte for byte.
+
+use crate::attribute::{ElementValuePair, TypePathPair};
+use crate::errors::Error;
+use crate::{
+    Annotation, Attribute, AttributeType, ClassFile, CodeAttribute, Constant, Element,
+    ElementValue, StackMap, StackMapFrame, TargetInfo, TypeAnnotation, VerificationTypeInfo, MAGIC,
+};
+
+type Result<T = ()> = std::result::Result<T, Error>;
+
+pub fn write(class_file: &ClassFile) -> Result<Vec<u8>> {
+    let mut writer = Writer { bytes: vec![] };
+    writer.class_file(class_file)?;
+    Ok(writer.bytes)
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    fn u8_count(&mut self, len: usize, what: &'static str) -> Result {
+        self.u8(u8::try_from(len).map_err(|_| Error::TooLarge(what))?);
+        Ok(())
+    }
+
+    fn u16_count(&mut self, len: usize, what: &'static str) -> Result {
+        self.u16(u16::try_from(len).map_err(|_| Error::TooLarge(what))?);
+        Ok(())
+    }
+
+    fn u16s(&mut self, values: &[u16], what: &'static str) -> Result {
+        self.u16_count(values.len(), what)?;
+        values.iter().for_each(|value| self.u16(*value));
+        Ok(())
+    }
+
+    fn list<T>(
+        &mut self,
+        items: &[T],
+        what: &'static str,
+        mut f: impl FnMut(&mut Self, &T) -> Result,
+    ) -> Result {
+        self.u16_count(items.len(), what)?;
+        items.iter().try_for_each(|item| f(self, item))
+    }
+
+    fn class_file(&mut self, class_file: &ClassFile) -> Result {
+        self.bytes.extend(MAGIC);
+        self.u16(class_file.minor_version);
+        self.u16(class_file.major_version);
+        // index 0 and the slots after longs and doubles are placeholders
+        self.u16_count(class_file.constant_pool.len(), "constants")?;
+        class_file
+            .constant_pool
+            .iter()
+            .try_for_each(|constant| self.constant(constant))?;
+        self.u16(class_file.access_flags);
+        self.u16(class_file.this_class);
+        self.u16(class_file.super_class);
+        self.u16s(&class_file.interfaces, "interfaces")?;
+        self.list(&class_file.fields, "fields", |w, field| {
+            w.u16(field.access_flags);
+            w.u16(field.name_index);
+            w.u16(field.descriptor_index);
+            w.attributes(&field.attributes)
+        })?;
+        self.list(&class_file.methods, "methods", |w, method| {
+            w.u16(method.access_flags);
+            w.u16(method.name_index);
+            w.u16(method.descriptor_index);
+            w.attributes(&method.attributes)
+        })?;
+        self.attributes(&class_file.attributes)
+    }
+
+    fn constant(&mut self, constant: &Constant) -> Result {
+        match constant {
+            Constant::Placeholder => {}
+            Constant::Class { name_index } => {
+                self.u8(7);
+                self.u16(*name_index);
+            }
+            Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                self.u8(9);
+                self.u16(*class_index);
+                self.u16(*name_and_type_index);
+            }
+            Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                self.u8(10);
+                self.u16(*class_index);
+                self.u16(*name_and_type_index);
+            }
+            Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            } => {
+                self.u8(11);
+                self.u16(*class_index);
+                self.u16(*name_and_type_index);
+            }
+            Constant::String { string_index } => {
+                self.u8(8);
+                self.u16(*string_index);
+            }
+            Constant::Integer(value) => {
+                self.u8(3);
+                self.bytes.extend(value.to_be_bytes());
+            }
+            Constant::Float(value) => {
+                self.u8(4);
+                self.u32(value.to_bits());
+            }
+            Constant::Long(value) => {
+                self.u8(5);
+                self.bytes.extend(value.to_be_bytes());
+            }
+            Constant::Double(value) => {
+                self.u8(6);
+                self.bytes.extend(value.to_bits().to_be_bytes());
+            }
+            Constant::NameAndType {
+                name_index,
+                descriptor_index,
+            } => {
+                self.u8(12);
+                self.u16(*name_index);
+                self.u16(*descriptor_index);
+            }
+            Constant::Utf8(bytes) => {
+                self.u8(1);
+                self.u16_count(bytes.len(), "Utf8 bytes")?;
+                self.bytes.extend(bytes.iter());
+            }
+            Constant::MethodHandle {
+                reference_kind,
+                reference_index,
+            } => {
+                self.u8(15);
+                self.u8(*reference_kind);
+                self.u16(*reference_index);
+            }
+            Constant::MethodType { descriptor_index } => {
+                self.u8(16);
+                self.u16(*descriptor_index);
+            }
+            Constant::Dynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                self.u8(17);
+                self.u16(*bootstrap_method_attr_index);
+                self.u16(*name_and_type_index);
+            }
+            Constant::InvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                self.u8(18);
+                self.u16(*bootstrap_method_attr_index);
+                self.u16(*name_and_type_index);
+            }
+            Constant::Module { name_index } => {
+                self.u8(19);
+                self.u16(*name_index);
+            }
+            Constant::Package { name_index } => {
+                self.u8(20);
+                self.u16(*name_index);
+            }
+        }
+        Ok(())
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) -> Result {
+        self.list(attributes, "attributes", Self::attribute)
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) -> Result {
+        self.u16(attribute.attribute_name_index);
+        let start = self.bytes.len();
+        self.u32(0);
+        self.attribute_type(&attribute.attribute_type)?;
+        let length = u32::try_from(self.bytes.len() - start - 4)
+            .map_err(|_| Error::TooLarge("attribute bytes"))?;
+        self.bytes[start..start + 4].copy_from_slice(&length.to_be_bytes());
+        Ok(())
+    }
+
+    fn attribute_type(&mut self, attribute_type: &AttributeType) -> Result {
+        match attribute_type {
+            AttributeType::ConstantValue {
+                constant_value_index,
+            } => self.u16(*constant_value_index),
+            AttributeType::Code { code } => self.code(code)?,
+            AttributeType::StackMapTable { entries } => {
+                self.list(entries, "stack map frames", Self::stack_map)?
+            }
+            AttributeType::Exceptions {
+                exception_index_table,
+            } => self.u16s(exception_index_table, "exceptions")?,
+            AttributeType::InnerClasses { classes } => {
+                self.list(classes, "inner classes", |w, class| {
+                    w.u16(class.inner_class_info_index);
+                    w.u16(class.outer_class_info_index);
+                    w.u16(class.inner_name_index);
+                    w.u16(class.inner_class_access_flags);
+                    Ok(())
+                })?
+            }
+            AttributeType::EnclosingMethod {
+                class_index,
+                method_index,
+            } => {
+                self.u16(*class_index);
+                self.u16(*method_index);
+            }
+            AttributeType::Synthetic | AttributeType::Deprecated => {}
+            AttributeType::Signature { signature_index } => self.u16(*signature_index),
+            AttributeType::SourceFile { sourcefile_index } => self.u16(*sourcefile_index),
+            AttributeType::SourceDebugExtension { debug_extension } => {
+                self.bytes.extend(debug_extension)
+            }
+            AttributeType::LineNumberTable { line_number_table } => {
+                self.list(line_number_table, "line numbers", |w, line| {
+                    w.u16(line.start_pc);
+                    w.u16(line.line_number);
+                    Ok(())
+                })?
+            }
+            AttributeType::LocalVariableTable {
+                local_variable_table,
+            } => self.list(local_variable_table, "local variables", |w, local| {
+                w.u16(local.start_pc);
+                w.u16(local.length);
+                w.u16(local.name_index);
+                w.u16(local.descriptor_index);
+                w.u16(local.index);
+                Ok(())
+            })?,
+            AttributeType::LocalVariableTypeTable {
+                local_variable_type_table,
+            } => self.list(local_variable_type_table, "local variables", |w, local| {
+                w.u16(local.start_pc);
+                w.u16(local.length);
+                w.u16(local.name_index);
+                w.u16(local.signature_index);
+                w.u16(local.index);
+                Ok(())
+            })?,
+            AttributeType::RuntimeVisibleAnnotations { annotations }
+            | AttributeType::RuntimeInvisibleAnnotations { annotations } => {
+                self.list(annotations, "annotations", Self::annotation)?
+            }
+            AttributeType::RuntimeVisibleParameterAnnotations {
+                parameter_annotations,
+            }
+            | AttributeType::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations,
+            } => {
+                self.u8_count(parameter_annotations.len(), "parameters")?;
+                for parameter in parameter_annotations {
+                    self.list(&parameter.annotations, "annotations", Self::annotation)?;
+                }
+            }
+            AttributeType::RuntimeVisibleTypeAnnotations { annotations }
+            | AttributeType::RuntimeInvisibleTypeAnnotations { annotations } => {
+                self.list(annotations, "type annotations", Self::type_annotation)?
+            }
+            AttributeType::AnnotationDefault { default_value } => {
+                self.element_value(default_value)?
+            }
+            AttributeType::BootstrapMethods { bootstrap_methods } => {
+                self.list(bootstrap_methods, "bootstrap methods", |w, method| {
+                    w.u16(method.bootstrap_method_ref);
+                    w.u16s(&method.bootstrap_arguments, "bootstrap arguments")
+                })?
+            }
+            AttributeType::MethodParameters { parameters } => {
+                self.u8_count(parameters.len(), "parameters")?;
+                for parameter in parameters {
+                    self.u16(parameter.name_index);
+                    self.u16(parameter.access_flags);
+                }
+            }
+            AttributeType::Module {
+                module_name_index,
+                module_flags,
+                module_version_index,
+                requires,
+                exports,
+                opens,
+                uses,
+                provides,
+            } => {
+                self.u16(*module_name_index);
+                self.u16(*module_flags);
+                self.u16(*module_version_index);
+                self.list(requires, "requires", |w, require| {
+                    w.u16(require.require_index);
+                    w.u16(require.require_flags);
+                    w.u16(require.require_version_index);
+                    Ok(())
+                })?;
+                self.list(exports, "exports", |w, export| {
+                    w.u16(export.export_index);
+                    w.u16(export.export_flags);
+                    w.u16s(&export.export_to_index, "exports")
+                })?;
+                self.list(opens, "opens", |w, open| {
+                    w.u16(open.open_index);
+                    w.u16(open.open_flags);
+                    w.u16s(&open.open_to_index, "opens")
+                })?;
+                self.u16s(uses, "uses")?;
+                self.list(provides, "provides", |w, provide| {
+                    w.u16(provide.provide_index);
+                    w.u16s(&provide.provide_with_index, "provides")
+                })?;
+            }
+            AttributeType::ModulePackages { package_index } => {
+                self.u16s(package_index, "packages")?
+            }
+            AttributeType::ModuleMainClass { main_class_index } => self.u16(*main_class_index),
+            AttributeType::NestHost { host_class_index } => self.u16(*host_class_index),
+            AttributeType::NestMembers { classes } => self.u16s(classes, "nest members")?,
+            AttributeType::Record { components } => {
+                self.list(components, "record components", |w, component| {
+                    w.u16(component.name_index);
+                    w.u16(component.descriptor_index);
+                    w.attributes(&component.attributes)
+                })?
+            }
+            AttributeType::PermittedSubclasses { classes } => {
+                self.u16s(classes, "permitted subclasses")?
+            }
+            AttributeType::Unknown { data } | AttributeType::Lazy { data } => {
+                self.bytes.extend(data.iter())
+            }
+        }
+        Ok(())
+    }
+
+    fn code(&mut self, code: &CodeAttribute) -> Result {
+        self.u16(code.max_stack);
+        self.u16(code.max_locals);
+        self.u32(u32::try_from(code.code.len()).map_err(|_| Error::TooLarge("code bytes"))?);
+        self.bytes.extend(code.code.iter());
+        self.list(&code.exception_table, "exception handlers", |w, entry| {
+            w.u16(entry.start_pc);
+            w.u16(entry.end_pc);
+            w.u16(entry.handler_pc);
+            w.u16(entry.catch_type);
+            Ok(())
+        })?;
+        self.attributes(&code.attributes)
+    }
+
+    fn stack_map(&mut self, stack_map: &StackMap) -> Result {
+        let frame_type = stack_map.frame_type;
+        let mismatch = || Error::MismatchFrameType(frame_type, stack_map.frame.clone());
+        self.u8(frame_type);
+        match &stack_map.frame {
+            StackMapFrame::SameFrame if frame_type <= 63 => {}
+            StackMapFrame::SameLocals1StackItemFrame { stack }
+                if (64..=127).contains(&frame_type) =>
+            {
+                self.verification_type_info(stack)
+            }
+            StackMapFrame::SameLocals1StackItemFrameExtended {
+                offset_delta,
+                stack,
+            } if frame_type == 247 => {
+                self.u16(*offset_delta);
+                self.verification_type_info(stack);
+            }
+            StackMapFrame::ChopFrame { offset_delta } if (248..=250).contains(&frame_type) => {
+                self.u16(*offset_delta)
+            }
+            StackMapFrame::SameFrameExtended { offset_delta } if frame_type == 251 => {
+                self.u16(*offset_delta)
+            }
+            StackMapFrame::AppendFrame {
+                offset_delta,
+                locals,
+            } if (252..=254).contains(&frame_type) && locals.len() == frame_type as usize - 251 => {
+                self.u16(*offset_delta);
+                locals
+                    .iter()
+                    .for_each(|local| self.verification_type_info(local));
+            }
+            StackMapFrame::FullFrame {
+                offset_delta,
+                locals,
+                stack,
+            } if frame_type == 255 => {
+                self.u16(*offset_delta);
+                self.list(locals, "locals", |w, local| {
+                    w.verification_type_info(local);
+                    Ok(())
+                })?;
+                self.list(stack, "stack items", |w, item| {
+                    w.verification_type_info(item);
+                    Ok(())
+                })?;
+            }
+            _ => return Err(mismatch()),
+        }
+        Ok(())
+    }
+
+    fn verification_type_info(&mut self, info: &VerificationTypeInfo) {
+        match info {
+            VerificationTypeInfo::Top => self.u8(0),
+            VerificationTypeInfo::Integer => self.u8(1),
+            VerificationTypeInfo::Float => self.u8(2),
+            VerificationTypeInfo::Double => self.u8(3),
+            VerificationTypeInfo::Long => self.u8(4),
+            VerificationTypeInfo::Null => self.u8(5),
+            VerificationTypeInfo::UninitializedThis => self.u8(6),
+            VerificationTypeInfo::Object { cpool_index } => {
+                self.u8(7);
+                self.u16(*cpool_index);
+            }
+            VerificationTypeInfo::Uninitialized { offset } => {
+                self.u8(8);
+                self.u16(*offset);
+            }
+        }
+    }
+
+    fn annotation(&mut self, annotation: &Annotation) -> Result {
+        self.u16(annotation.type_index);
+        self.list(
+            &annotation.element_value_pairs,
+            "element values",
+            |w, pair: &ElementValuePair| {
+                w.u16(pair.element_name_index);
+                w.element_value(&pair.value)
+            },
+        )
+    }
+
+    fn element_value(&mut self, element_value: &ElementValue) -> Result {
+        self.u8(element_value.tag);
+        match &element_value.value {
+            Element::ConstValueIndex(index) | Element::ClassInfoIndex(index) => self.u16(*index),
+            Element::EnumConstValue {
+                type_name_index,
+                const_name_index,
+            } => {
+                self.u16(*type_name_index);
+                self.u16(*const_name_index);
+            }
+            Element::AnnotationValue(annotation) => self.annotation(annotation)?,
+            Element::ArrayValue(values) => {
+                self.list(values, "element values", Self::element_value)?
+            }
+        }
+        Ok(())
+    }
+
+    fn type_annotation(&mut self, annotation: &TypeAnnotation) -> Result {
+        self.u8(annotation.target_type);
+        match &annotation.target_info {
+            TargetInfo::TypeParameterTarget {
+                type_parameter_index,
+            } => self.u8(*type_parameter_index),
+            TargetInfo::SupertypeTarget { supertype_index } => self.u16(*supertype_index),
+            TargetInfo::TypeParameterBoundTarget {
+                type_parameter_index,
+                bound_index,
+            } => {
+                self.u8(*type_parameter_index);
+                self.u8(*bound_index);
+            }
+            TargetInfo::EmptyTarget => {}
+            TargetInfo::FormalParameterTarget {
+                formal_parameter_index,
+            } => self.u8(*formal_parameter_index),
+            TargetInfo::ThrowTarget { throws_type_index } => self.u16(*throws_type_index),
+            TargetInfo::LocalVarTarget(local_vars) => {
+                self.list(local_vars, "local variables", |w, local| {
+                    w.u16(local.start_pc);
+                    w.u16(local.length);
+                    w.u16(local.index);
+                    Ok(())
+                })?
+            }
+            TargetInfo::CatchTarget {
+                exception_table_index,
+            } => self.u16(*exception_table_index),
+            TargetInfo::OffsetTarget { offset } => self.u16(*offset),
+            TargetInfo::TypeArgumentTarget {
+                offset,
+                type_argument_index,
+            } => {
+                self.u16(*offset);
+                self.u8(*type_argument_index);
+            }
+        }
+        self.u8_count(annotation.type_path.path.len(), "type path entries")?;
+        for TypePathPair {
+            type_path_kind,
+            type_argument_index,
+        } in &annotation.type_path.path
+        {
+            self.u8(*type_path_kind);
+            self.u8(*type_argument_index);
+        }
+        self.u16(annotation.type_index);
+        self.list(
+            &annotation.element_value_pairs,
+            "element values",
+            |w, (name_index, value)| {
+                w.u16(*name_index);
+                w.element_value(value)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::attribute::{ElementValuePair, TypePathPair};
+    use crate::{
+        parse, write, Annotation, Attribute, AttributeType, ClassFile, CodeAttribute, Constant,
+        Element, ElementValue, Error, Export, LocalVar, Provide, RecordComponent, Require,
+        StackMap, StackMapFrame, TargetInfo, TypeAnnotation, TypePath, VerificationTypeInfo,
+    };
+    use std::borrow::Cow;
+    use std::io::Read;
+    use std::sync::Arc;
+
+    fn attribute(attribute_type: AttributeType) -> Attribute {
+        // the writer computes the length
+        Attribute {
+            attribute_name_index: 1,
+            attribute_length: 0,
+            attribute_type,
+        }
+    }
+
+    fn named(name_index: u16, attribute_type: AttributeType) -> Attribute {
+        Attribute {
+            attribute_name_index: name_index,
+            ..attribute(attribute_type)
+        }
+    }
+
+    #[test]
+    fn test_round_trip_rt_jar() {
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let mut classes = 0;
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if !file.name().ends_with(".class") {
+                continue;
+            }
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).unwrap();
+            let class_file = parse(&bytes).unwrap();
+            assert!(write(&class_file).unwrap() == bytes, "{}", file.name());
+            classes += 1;
+        }
+        assert!(classes > 6000);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let names = [
+            "Module",
+            "Record",
+            "PermittedSubclasses",
+            "NestHost",
+            "NestMembers",
+            "RuntimeVisibleTypeAnnotations",
+            "Code",
+            "StackMapTable",
+            "Custom",
+            "AnnotationDefault",
+        ];
+        let mut constant_pool = vec![Constant::Placeholder];
+        constant_pool.extend(
+            names
+                .iter()
+                .map(|name| Constant::Utf8(Cow::Borrowed(name.as_bytes()))),
+        );
+        constant_pool.push(Constant::Long(-1));
+        constant_pool.push(Constant::Placeholder);
+        constant_pool.push(Constant::Double(f64::NAN));
+        constant_pool.push(Constant::Placeholder);
+
+        let value = |tag: u8, value: Element| ElementValue { tag, value };
+        let type_annotation = TypeAnnotation {
+            target_type: 0x40,
+            target_info: TargetInfo::LocalVarTarget(vec![LocalVar {
+                start_pc: 0,
+                length: 1,
+                index: 0,
+            }]),
+            type_path: TypePath {
+                path: vec![TypePathPair {
+                    type_path_kind: 3,
+                    type_argument_index: 0,
+                }],
+            },
+            type_index: 1,
+            element_value_pairs: vec![(2, value(b'I', Element::ConstValueIndex(11)))],
+        };
+        let code = CodeAttribute {
+            max_stack: 1,
+            max_locals: 1,
+            code: Cow::Borrowed(&[0x03, 0xac]),
+            exception_table: vec![],
+            attributes: vec![
+                named(
+                    8,
+                    AttributeType::StackMapTable {
+                        entries: vec![StackMap {
+                            frame_type: 252,
+                            frame: StackMapFrame::AppendFrame {
+                                offset_delta: 0,
+                                locals: vec![VerificationTypeInfo::Object { cpool_index: 1 }],
+                            },
+                        }],
+                    },
+                ),
+                named(
+                    6,
+                    AttributeType::RuntimeVisibleTypeAnnotations {
+                        annotations: vec![type_annotation.clone(), type_annotation],
+                    },
+                ),
+            ],
+        };
+        let annotation = Annotation {
+            type_index: 1,
+            element_value_pairs: vec![ElementValuePair {
+                element_name_index: 2,
+                value: value(
+                    b'[',
+                    Element::ArrayValue(vec![
+                        value(
+                            b'e',
+                            Element::EnumConstValue {
+                                type_name_index: 1,
+                                const_name_index: 2,
+                            },
+                        ),
+                        value(b'c', Element::ClassInfoIndex(3)),
+                    ]),
+                ),
+            }],
+        };
+        let class_file = ClassFile {
+            minor_version: 0,
+            major_version: 61,
+            constant_pool: Arc::new(constant_pool),
+            access_flags: 0x8000,
+            this_class: 0,
+            super_class: 0,
+            interfaces: vec![],
+            fields: vec![],
+            methods: vec![crate::MethodInfo {
+                access_flags: 0,
+                name_index: 1,
+                descriptor_index: 2,
+                attributes: vec![
+                    named(7, AttributeType::Code { code }),
+                    named(
+                        10,
+                        AttributeType::AnnotationDefault {
+                            default_value: value(b'@', Element::AnnotationValue(annotation)),
+                        },
+                    ),
+                ],
+                code_attr_index: Some(0),
+            }],
+            attributes: vec![
+                attribute(AttributeType::Module {
+                    module_name_index: 1,
+                    module_flags: 0x20,
+                    module_version_index: 0,
+                    requires: vec![Require {
+                        require_index: 2,
+                        require_flags: 0x8000,
+                        require_version_index: 3,
+                    }],
+                    exports: vec![Export {
+                        export_index: 4,
+                        export_flags: 0,
+                        export_to_index: vec![5, 6],
+                    }],
+                    opens: vec![],
+                    uses: vec![7],
+                    provides: vec![Provide {
+                        provide_index: 8,
+                        provide_with_index: vec![9],
+                    }],
+                }),
+                named(
+                    2,
+                    AttributeType::Record {
+                        components: vec![RecordComponent {
+                            name_index: 1,
+                            descriptor_index: 2,
+                            attributes: vec![named(
+                                9,
+                                AttributeType::Unknown {
+                                    data: Cow::Borrowed(b"\x01\x02"),
+                                },
+                            )],
+                        }],
+                    },
+                ),
+                named(
+                    3,
+                    AttributeType::PermittedSubclasses {
+                        classes: vec![1, 2],
+                    },
+                ),
+                named(
+                    4,
+                    AttributeType::NestHost {
+                        host_class_index: 3,
+                    },
+                ),
+                named(5, AttributeType::NestMembers { classes: vec![] }),
+            ],
+        };
+        let bytes = write(&class_file).unwrap();
+        let parsed = parse(&bytes).unwrap();
+        assert_eq!(write(&parsed).unwrap(), bytes);
+
+        let code = parsed.methods[0].code_attribute().unwrap();
+        assert_eq!(code.code[..], [0x03, 0xac]);
+        match &code.attributes[1].attribute_type {
+            AttributeType::RuntimeVisibleTypeAnnotations { annotations } => {
+                assert_eq!(annotations.len(), 2)
+            }
+            other => panic!("{:?}", other),
+        }
+        match &parsed.attributes[1] {
+            Attribute {
+                attribute_length: 16,
+                attribute_type: AttributeType::Record { components },
+                ..
+            } => match &components[0].attributes[0].attribute_type {
+                AttributeType::Unknown { data } => assert_eq!(data[..], b"\x01\x02"[..]),
+                other => panic!("{:?}", other),
+            },
+            other => panic!("{:?}", other),
+        }
+        match parsed.constant_pool[13] {
+            Constant::Double(value) => assert!(value.is_nan()),
+            ref other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let class_file = ClassFile {
+            minor_version: 0,
+            major_version: 52,
+            constant_pool: Arc::new(vec![
+                Constant::Placeholder,
+                Constant::Utf8(Cow::Borrowed(b"StackMapTable")),
+            ]),
+            access_flags: 0,
+            this_class: 0,
+            super_class: 0,
+            interfaces: vec![0; 70000],
+            fields: vec![],
+            methods: vec![],
+            attributes: vec![],
+        };
+        assert!(matches!(
+            write(&class_file),
+            Err(Error::TooLarge("interfaces"))
+        ));
+
+        let frame = StackMap {
+            frame_type: 253,
+            frame: StackMapFrame::AppendFrame {
+                offset_delta: 0,
+                locals: vec![VerificationTypeInfo::Integer],
+            },
+        };
+        let class_file = ClassFile {
+            interfaces: vec![],
+            attributes: vec![attribute(AttributeType::StackMapTable {
+                entries: vec![frame],
+            })],
+            ..class_file
+        };
+        assert!(matches!(
+            write(&class_file),
+            Err(Error::MismatchFrameType(
+                253,
+                StackMapFrame::AppendFrame { .. }
+            ))
+        ));
+    }
+}
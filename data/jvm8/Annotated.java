@@ -0,0 +1,39 @@
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+
+@Annotated.Values(b = 1, c = 'c', d = 1.5, f = 2.5f, i = 3, j = 4L, s = 5, z = true,
+        string = "text", policy = RetentionPolicy.CLASS, type = String[].class,
+        primitive = void.class, nested = @Annotated.Invisible("inner"), array = {1, 2})
+@Deprecated
+public class Annotated {
+
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Values {
+        byte b();
+        char c();
+        double d();
+        float f();
+        int i();
+        long j();
+        short s();
+        boolean z();
+        String string();
+        RetentionPolicy policy();
+        Class<?> type();
+        Class<?> primitive();
+        Invisible nested();
+        int[] array();
+        String missing() default "default";
+    }
+
+    @interface Invisible {
+        String value();
+    }
+
+    @Invisible("field")
+    int field;
+
+    @Deprecated
+    public void method(@Invisible("first") int a, int b, @Deprecated @Invisible("third") int c) {
+    }
+}
@@ -96,29 +96,42 @@ pub fn symbolic_ref_instruction_derive(input: TokenStream) -> TokenStream {
 
 fn impl_symbol_ref(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let struct_ident = &input.ident;
+    // member refs name their owner in `class_name`, class refs in `name`
+    let has_class_name = match &input.data {
+        syn::Data::Struct(data) => data.fields.iter().any(|field| {
+            field
+                .ident
+                .as_ref()
+                .is_some_and(|ident| ident == "class_name")
+        }),
+        _ => false,
+    };
+    let class_name = if has_class_name {
+        quote! { self.class_name }
+    } else {
+        quote! { self.name }
+    };
     let ret = quote! {
         impl SymbolicRef for #struct_ident {
-            fn resolved_class_ref(&mut self) -> anyhow::Result<()> {
+            fn resolved_class_ref(&self) -> anyhow::Result<()> {
                 unsafe {
-                    let class = self.constant_pool.as_mut().class.as_mut();
-                    let class_loaded = class.loader.as_mut().load_class(&self.name.as_str())?;
+                    let class = self.constant_pool.as_ref().class.as_ref();
+                    let class_loaded = class.loader.as_ref().load_class(#class_name.as_str())?;
                     if !class_loaded.is_accessible_to(class) {
                         return Err(anyhow!("java.lang.IllegalAccessError"));
                     }
-                    self.class = Some(NonNull::from(class_loaded));
+                    let _ = self.class.set(NonNull::from(class_loaded));
                     Ok(())
                 }
             }
         }
 
         impl #struct_ident {
-            pub fn resolved_class(&mut self) -> anyhow::Result<NonNull<Class>> {
-                unsafe {
-                    if self.class.is_none() {
-                        self.resolved_class_ref()?;
-                    }
-                    Ok(self.class.unwrap().clone())
+            pub fn resolved_class(&self) -> anyhow::Result<NonNull<Class>> {
+                if self.class.get().is_none() {
+                    self.resolved_class_ref()?;
                 }
+                Ok(*self.class.get().unwrap())
             }
         }
     };
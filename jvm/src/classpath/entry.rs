@@ -20,7 +20,7 @@ pub const CLASS_SEPARATOR: &str = ".";
 pub const CLASS_EXTENSION: &str = "class";
 pub const JAR_EXTENSION: &str = "jar";
 
-pub trait Entry {
+pub trait Entry: Send + Sync {
     fn string(&self) -> &String;
     fn read_class(&self, class_name: &str) -> anyhow::Result<Vec<u8>>;
 }
@@ -65,8 +65,9 @@ impl Entry for DirEntry {
             PATH_SEPARATOR,
             class_name
         );
-        let file = std::fs::File::open(path.as_str())?;
-        let bytes: Vec<u8> = file.bytes().map(Result::unwrap).collect();
+        let mut file = std::fs::File::open(path.as_str())?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
         Ok(bytes)
     }
 }
@@ -206,9 +207,10 @@ mod tests {
             if file.name().ends_with(".class") {
                 let mut bytes = Vec::new();
                 file.read_to_end(&mut bytes).unwrap();
-                let (_, class_file) = classfile::parse(bytes.as_slice()).unwrap();
-                let raw = get_utf8(class_file.constant_pool, class_file.this_class as usize);
-                println!("{}", String::from_utf8_lossy(raw));
+                let class_file = classfile::parse_lazy(bytes.as_slice()).unwrap();
+                let raw =
+                    get_utf8(class_file.constant_pool, class_file.this_class as usize).unwrap();
+                println!("{}", String::from_utf8_lossy(&raw));
             }
         }
     }
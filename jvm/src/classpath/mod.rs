@@ -3,6 +3,14 @@ use std::path::{Path, PathBuf};
 
 mod entry;
 
+/// Where on the class path a class was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassSource {
+    Bootstrap,
+    Extension,
+    User,
+}
+
 pub struct ClassPath {
     pub bootstrap: Box<dyn Entry>,
     pub extension: Box<dyn Entry>,
@@ -21,6 +29,18 @@ impl ClassPath {
             Err(e) => panic!("{}", e),
         }
     }
+
+    pub fn read_class_from(&self, class_name: &str) -> anyhow::Result<(Vec<u8>, ClassSource)> {
+        let class_name = format!("{}.{}", class_name, CLASS_EXTENSION);
+        if let Ok(bytes) = self.bootstrap.read_class(class_name.as_str()) {
+            return Ok((bytes, ClassSource::Bootstrap));
+        }
+        if let Ok(bytes) = self.extension.read_class(class_name.as_str()) {
+            return Ok((bytes, ClassSource::Extension));
+        }
+        let bytes = self.user.read_class(class_name.as_str())?;
+        Ok((bytes, ClassSource::User))
+    }
 }
 
 fn parse_user_class_path(mut cp_opt: String) -> Box<dyn Entry> {
@@ -66,7 +86,7 @@ fn get_jre_dir(jre_opt: &str) -> anyhow::Result<PathBuf> {
         }
     }
 
-    return match std::env::var("JAVA_HOME") {
+    match std::env::var("JAVA_HOME") {
         Ok(path) => {
             let java_home = Path::new(&path);
             let path = java_home.join("jre");
@@ -79,7 +99,7 @@ fn get_jre_dir(jre_opt: &str) -> anyhow::Result<PathBuf> {
             }
         }
         Err(e) => Err(anyhow::anyhow!("{}", e)),
-    };
+    }
 }
 
 impl Entry for ClassPath {
@@ -88,21 +108,14 @@ impl Entry for ClassPath {
     }
 
     fn read_class(&self, class_name: &str) -> anyhow::Result<Vec<u8>> {
-        let class_name = format!("{}.{}", class_name, CLASS_EXTENSION);
-        if let Ok(bytes) = self.bootstrap.read_class(class_name.as_str()) {
-            return Ok(bytes);
-        }
-        if let Ok(bytes) = self.extension.read_class(class_name.as_str()) {
-            return Ok(bytes);
-        }
-        self.user.read_class(class_name.as_str())
+        self.read_class_from(class_name).map(|(bytes, _)| bytes)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::classpath::entry::Entry;
-    use crate::classpath::ClassPath;
+    use crate::classpath::{ClassPath, ClassSource};
 
     #[test]
     fn read_class() {
@@ -110,4 +123,13 @@ mod tests {
         let bytes = class_path.read_class("java/lang/Object").unwrap();
         assert_eq!(bytes[..4], [0xCA, 0xFE, 0xBA, 0xBE]);
     }
+
+    #[test]
+    fn read_class_from() {
+        let class_path = ClassPath::new("".to_string(), "../data/jvm8".to_string());
+        let (_, source) = class_path.read_class_from("java/lang/Object").unwrap();
+        assert_eq!(source, ClassSource::Bootstrap);
+        let (_, source) = class_path.read_class_from("User").unwrap();
+        assert_eq!(source, ClassSource::User);
+    }
 }
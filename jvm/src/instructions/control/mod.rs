@@ -1,2 +1,4 @@
 pub(crate) mod goto;
+pub(crate) mod r#return;
+pub(crate) mod subroutine;
 pub(crate) mod switch;
@@ -0,0 +1,29 @@
+use crate::instructions::{InstructionExecutor, InstructionReader};
+use crate::rtda::Frame;
+use jvm_macros::NoOperand;
+
+macro_rules! register_return {
+    ($(($inst:ident, $slots:expr)),*) => {
+        $(
+            #[derive(NoOperand, Debug)]
+            #[allow(non_camel_case_types)]
+            pub struct $inst;
+
+            impl InstructionExecutor for $inst {
+                fn execute(&self, frame: &mut Frame) {
+                    // the interpreter hands the value on top over to the caller
+                    frame.thread().lock().unwrap().set_returned($slots);
+                }
+            }
+        )*
+    };
+}
+
+register_return! {
+    (IRETURN, 1),
+    (FRETURN, 1),
+    (ARETURN, 1),
+    (LRETURN, 2),
+    (DRETURN, 2),
+    (RETURN, 0)
+}
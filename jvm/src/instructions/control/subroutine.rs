@@ -0,0 +1,40 @@
+use crate::instructions::{InstructionExecutor, InstructionReader};
+use crate::rtda::Frame;
+use bytes::Buf;
+use jvm_macros::{Branch, Index8};
+
+#[derive(Branch, Default, Debug)]
+#[allow(non_camel_case_types)]
+pub struct JSR {
+    offset: i32,
+}
+
+impl InstructionExecutor for JSR {
+    fn execute(&self, frame: &mut Frame) {
+        let return_address = frame.next_pc();
+        frame
+            .operand_stack_mut()
+            .push_return_address(return_address);
+        frame.branch(self.offset);
+    }
+}
+
+#[derive(Index8, Default, Debug)]
+#[allow(non_camel_case_types)]
+pub struct RET {
+    index: usize,
+}
+
+impl RET {
+    #[inline]
+    pub const fn new(index: usize) -> Self {
+        Self { index }
+    }
+}
+
+impl InstructionExecutor for RET {
+    fn execute(&self, frame: &mut Frame) {
+        let return_address = frame.local_vars().get_return_address(self.index);
+        frame.set_next_pc(return_address);
+    }
+}
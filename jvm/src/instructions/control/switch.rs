@@ -13,7 +13,7 @@ pub struct TABLE_SWITCH {
 
 impl<T: AsRef<[u8]>> InstructionReader<T> for TABLE_SWITCH {
     fn fetch_operands(&mut self, reader: &mut std::io::Cursor<T>) {
-        while reader.position() % 4 != 0 {
+        while !reader.position().is_multiple_of(4) {
             reader.get_u8();
         }
         self.default_offset = reader.get_i32();
@@ -53,7 +53,7 @@ where
     T: AsRef<[u8]> + std::convert::AsRef<[u8]>,
 {
     fn fetch_operands(&mut self, reader: &mut std::io::Cursor<T>) {
-        while reader.position() % 4 != 0 {
+        while !reader.position().is_multiple_of(4) {
             reader.get_u8();
         }
         self.default_offset = reader.get_i32();
@@ -0,0 +1,26 @@
+use crate::instructions::{InstructionExecutor, InstructionReader};
+use crate::rtda::Frame;
+use bytes::Buf;
+use std::io::Cursor;
+
+#[derive(Default, Debug)]
+#[allow(non_camel_case_types)]
+pub struct JSR_W {
+    offset: i32,
+}
+
+impl<T: AsRef<[u8]>> InstructionReader<T> for JSR_W {
+    fn fetch_operands(&mut self, reader: &mut Cursor<T>) {
+        self.offset = reader.get_i32();
+    }
+}
+
+impl InstructionExecutor for JSR_W {
+    fn execute(&self, frame: &mut Frame) {
+        let return_address = frame.next_pc();
+        frame
+            .operand_stack_mut()
+            .push_return_address(return_address);
+        frame.branch(self.offset);
+    }
+}
@@ -1,3 +1,4 @@
 pub(crate) mod goto_w;
 pub(crate) mod ifnull;
+pub(crate) mod jsr_w;
 pub(crate) mod wide;
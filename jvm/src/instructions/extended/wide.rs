@@ -1,17 +1,24 @@
 use crate::instructions::opcode::OpCode;
 use crate::instructions::{
-    Instruction, InstructionExecutor, InstructionReader, ALOAD, ASTORE, DLOAD, DSTORE, FLOAD,
-    FSTORE, IINC, ILOAD, ISTORE, LLOAD, LSTORE, NOP,
+    InstructionExecutor, InstructionReader, ALOAD, ASTORE, DLOAD, DSTORE, FLOAD, FSTORE, IINC,
+    ILOAD, ISTORE, LLOAD, LSTORE, NOP, RET,
 };
 use crate::rtda::Frame;
 use bytes::Buf;
+use std::fmt::Debug;
 use std::io::Cursor;
 
-pub struct WIDE<T> {
-    modified_instruction: Box<dyn Instruction<T>>,
+// the modified instruction has its operands already, so it need not be a reader
+trait ModifiedInstruction: InstructionExecutor + Debug {}
+
+impl<I: InstructionExecutor + Debug> ModifiedInstruction for I {}
+
+#[derive(Debug)]
+pub struct WIDE {
+    modified_instruction: Box<dyn ModifiedInstruction>,
 }
 
-impl<T: AsRef<[u8]>> Default for WIDE<T> {
+impl Default for WIDE {
     fn default() -> Self {
         Self {
             modified_instruction: Box::new(NOP {}),
@@ -19,7 +26,7 @@ impl<T: AsRef<[u8]>> Default for WIDE<T> {
     }
 }
 
-impl<T: AsRef<[u8]>> InstructionReader<T> for WIDE<T> {
+impl<T: AsRef<[u8]>> InstructionReader<T> for WIDE {
     fn fetch_operands(&mut self, reader: &mut Cursor<T>) {
         let opcode = reader.get_u8();
         match opcode.into() {
@@ -65,10 +72,14 @@ impl<T: AsRef<[u8]>> InstructionReader<T> for WIDE<T> {
             }
             OpCode::iinc => {
                 let index = reader.get_u16() as usize;
-                let r#const = reader.get_u16() as i32;
+                let r#const = reader.get_i16() as i32;
                 let inst = IINC::new(index, r#const);
                 self.modified_instruction = Box::new(inst);
             }
+            OpCode::ret => {
+                let inst = RET::new(reader.get_u16() as usize);
+                self.modified_instruction = Box::new(inst);
+            }
             _ => {
                 panic!("Unsupported opcode: {:02x}", opcode);
             }
@@ -76,7 +87,7 @@ impl<T: AsRef<[u8]>> InstructionReader<T> for WIDE<T> {
     }
 }
 
-impl<T> InstructionExecutor for WIDE<T> {
+impl InstructionExecutor for WIDE {
     fn execute(&self, frame: &mut Frame) {
         self.modified_instruction.execute(frame);
     }
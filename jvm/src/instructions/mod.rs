@@ -1,6 +1,6 @@
 #![allow(clippy::upper_case_acronyms)]
 use crate::rtda::Frame;
-use std::fmt::{Debug, Formatter};
+use std::fmt::Debug;
 use std::io::Cursor;
 
 mod comparisons;
@@ -39,6 +39,8 @@ pub use {
     },
     control::{
         goto::GOTO,
+        r#return::{ARETURN, DRETURN, FRETURN, IRETURN, LRETURN, RETURN},
+        subroutine::{JSR, RET},
         switch::{LOOKUP_SWITCH, TABLE_SWITCH},
     },
     conversions::{
@@ -50,6 +52,7 @@ pub use {
     extended::{
         goto_w::GOTO_W,
         ifnull::{IFNONNULL, IFNULL},
+        jsr_w::JSR_W,
         wide::WIDE,
     },
     loads::{
@@ -92,10 +95,12 @@ pub use {
         put_static::PUT_STATIC,
         check_cast::CHECK_CAST,
         instance_of::INSTANCE_OF,
+        invoke::{INVOKE_INTERFACE, INVOKE_SPECIAL, INVOKE_STATIC, INVOKE_VIRTUAL},
+        monitor::{MONITOR_ENTER, MONITOR_EXIT},
         new::NEW,
     },
 };
-use crate::instructions::opcode::OpCode;
+pub(crate) use crate::instructions::opcode::OpCode;
 
 pub trait InstructionReader<T>
 where
@@ -134,7 +139,9 @@ register_inst! {
     NOP,
     // control
     GOTO,
+    JSR, RET,
     LOOKUP_SWITCH, TABLE_SWITCH,
+    IRETURN, LRETURN, FRETURN, DRETURN, ARETURN, RETURN,
     // conversions
     D2F, D2I, D2L,
     F2D, F2I, F2L,
@@ -143,7 +150,8 @@ register_inst! {
     // extended
     GOTO_W,
     IFNONNULL, IFNULL,
-    // WIDE,
+    JSR_W,
+    WIDE,
     // loads
     ILOAD, ILOAD_0, ILOAD_1, ILOAD_2, ILOAD_3,
     ALOAD, ALOAD_0, ALOAD_1, ALOAD_2, ALOAD_3,
@@ -177,17 +185,11 @@ register_inst! {
     PUT_FIELD, GET_FIELD,
     PUT_STATIC, GET_STATIC,
     NEW,
-    CHECK_CAST, INSTANCE_OF
+    CHECK_CAST, INSTANCE_OF,
+    INVOKE_VIRTUAL, INVOKE_SPECIAL, INVOKE_STATIC, INVOKE_INTERFACE,
+    MONITOR_ENTER, MONITOR_EXIT
 }
 
-impl<T: AsRef<[u8]>> Debug for WIDE<T> {
-    fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
-        todo!()
-    }
-}
-
-impl<T: AsRef<[u8]>> Instruction<T> for WIDE<T> {}
-
 pub fn new_inst<T: AsRef<[u8]>>(opcode: u8) -> Box<dyn Instruction<T>> {
     match opcode.into() {
         OpCode::nop => Box::new(NOP {}),
@@ -358,24 +360,24 @@ pub fn new_inst<T: AsRef<[u8]>>(opcode: u8) -> Box<dyn Instruction<T>> {
         OpCode::if_acmpeq => Box::new(IF_ACMPEQ::default()),
         OpCode::if_acmpne => Box::new(IF_ACMPNE::default()),
         OpCode::goto => Box::new(GOTO::default()),
-        // OpCode::jsr => Box::new(JSR {}),
-        // OpCode::ret => Box::new(RET {}),
+        OpCode::jsr => Box::new(JSR::default()),
+        OpCode::ret => Box::new(RET::default()),
         OpCode::tableswitch => Box::new(TABLE_SWITCH::default()),
         OpCode::lookupswitch => Box::new(LOOKUP_SWITCH::default()),
-        // OpCode::ireturn => Box::new(IRETURN {}),
-        // OpCode::lreturn => Box::new(LRETURN {}),
-        // OpCode::freturn => Box::new(FRETURN {}),
-        // OpCode::dreturn => Box::new(DRETURN {}),
-        // OpCode::areturn => Box::new(ARETURN {}),
-        // OpCode::vreturn => Box::new(VRETURN {}),
+        OpCode::ireturn => Box::new(IRETURN {}),
+        OpCode::lreturn => Box::new(LRETURN {}),
+        OpCode::freturn => Box::new(FRETURN {}),
+        OpCode::dreturn => Box::new(DRETURN {}),
+        OpCode::areturn => Box::new(ARETURN {}),
+        OpCode::vreturn => Box::new(RETURN {}),
         OpCode::getstatic => Box::new(GET_STATIC::default()),
         OpCode::putstatic => Box::new(PUT_STATIC::default()),
         OpCode::getfield => Box::new(GET_FIELD::default()),
         OpCode::putfield => Box::new(PUT_FIELD::default()),
-        // OpCode::invokevirtual => Box::new(INVOKEVIRTUAL {}),
-        // OpCode::invokespecial => Box::new(INVOKESPECIAL {}),
-        // OpCode::invokestatic => Box::new(INVOKESTATIC {}),
-        // OpCode::invokeinterface => Box::new(INVOKEINTERFACE {}),
+        OpCode::invokevirtual => Box::new(INVOKE_VIRTUAL::default()),
+        OpCode::invokespecial => Box::new(INVOKE_SPECIAL::default()),
+        OpCode::invokestatic => Box::new(INVOKE_STATIC::default()),
+        OpCode::invokeinterface => Box::new(INVOKE_INTERFACE::default()),
         // OpCode::invokedynamic => Box::new(INVOKEDYNAMIC {}),
         OpCode::new => Box::new(NEW::default()),
         // OpCode::newarray => Box::new(NEWARRAY {}),
@@ -384,14 +386,14 @@ pub fn new_inst<T: AsRef<[u8]>>(opcode: u8) -> Box<dyn Instruction<T>> {
         // OpCode::athrow => Box::new(ATHROW {}),
         OpCode::checkcast => Box::new(CHECK_CAST::default()),
         OpCode::instanceof => Box::new(INSTANCE_OF::default()),
-        // OpCode::monitorenter => Box::new(MONITORENTER {}),
-        // OpCode::monitorexit => Box::new(MONITOREXIT {}),
-        // OpCode::wide => Box::new(WIDE::default()),
+        OpCode::monitorenter => Box::new(MONITOR_ENTER {}),
+        OpCode::monitorexit => Box::new(MONITOR_EXIT {}),
+        OpCode::wide => Box::new(WIDE::default()),
         // OpCode::multianewarray => Box::new(MULTIANEWARRAY {}),
         OpCode::ifnull => Box::new(IFNULL::default()),
         OpCode::ifnonnull => Box::new(IFNONNULL::default()),
         OpCode::goto_w => Box::new(GOTO_W::default()),
-        // OpCode::jsr_w => Box::new(JSR_W {}),
+        OpCode::jsr_w => Box::new(JSR_W::default()),
         // OpCode::breakpoint => Box::new(BREAKPOINT {}),
         // OpCode::impdep1 => Box::new(IMPDEP1 {}),
         // OpCode::impdep2 => Box::new(IMPDEP2 {}),
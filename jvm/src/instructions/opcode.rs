@@ -1,6 +1,6 @@
 #[repr(u8)]
 #[allow(non_camel_case_types)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
     nop = 0x00,
     aconst_null = 0x01,
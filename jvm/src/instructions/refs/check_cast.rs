@@ -17,8 +17,8 @@ impl InstructionExecutor for CHECK_CAST {
             return;
         }
         unsafe {
-            let mut constant_pool = frame.method().borrow_mut().class.as_mut().constant_pool;
-            if let Constant::Class(class_ref) = constant_pool.as_mut().get_mut(self.index) {
+            let constant_pool = frame.method().read().unwrap().class.as_ref().constant_pool;
+            if let Constant::Class(class_ref) = constant_pool.as_ref().get(self.index) {
                 let class = class_ref.resolved_class().unwrap();
                 if !(*object).is_instance_of(class) {
                     panic!("java.lang.ClassCastException");
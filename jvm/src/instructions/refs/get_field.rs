@@ -1,6 +1,7 @@
 use crate::instructions::{InstructionExecutor, InstructionReader};
 use crate::rtda::{Constant, Frame};
 use bytes::Buf;
+use classfile::descriptor::{BaseType, FieldType};
 use jvm_macros::Index16;
 
 #[derive(Debug, Default, Index16)]
@@ -12,50 +13,52 @@ pub struct GET_FIELD {
 impl InstructionExecutor for GET_FIELD {
     fn execute(&self, frame: &mut Frame) {
         let cur_method = frame.method();
-        let mut cur_class = cur_method.borrow().class;
+        let cur_class = cur_method.read().unwrap().class;
         unsafe {
-            let mut constant_pool = cur_class.as_mut().constant_pool;
-            if let Constant::FieldRef(field_ref) = constant_pool.as_mut().get_mut(self.index) {
+            let constant_pool = cur_class.as_ref().constant_pool;
+            if let Constant::FieldRef(field_ref) = constant_pool.as_ref().get(self.index) {
                 let field = field_ref.resolve_field();
-                if field.borrow().is_static() {
+                if field.read().unwrap().is_static() {
                     panic!("java.lang.IncompatibleClassChangeError");
                 }
                 let object = frame.operand_stack_mut().pop_ref();
                 if object.is_null() {
                     panic!("java.lang.NullPointerException");
                 }
-                let descriptor = field.borrow().descriptor.clone();
-                let slot_id = field.borrow().slot_id;
-                if let Some(char) = descriptor.chars().next() {
-                    match char {
-                        'Z' | 'B' | 'C' | 'S' | 'I' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_int((*object).fields.get_int(slot_id));
-                        }
-                        'F' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_float((*object).fields.get_float(slot_id));
-                        }
-                        'J' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_long((*object).fields.get_long(slot_id));
-                        }
-                        'D' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_double((*object).fields.get_double(slot_id));
-                        }
-                        'L' | '[' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_ref((*object).fields.get_ref(slot_id));
-                        }
-                        _ => {
-                            panic!("java.lang.IllegalAccessError");
-                        }
+                let field_type = field.read().unwrap().field_type.clone();
+                let slot_id = field.read().unwrap().slot_id;
+                let volatile = field.read().unwrap().is_volatile();
+                match field_type {
+                    FieldType::Base(
+                        BaseType::Boolean
+                        | BaseType::Byte
+                        | BaseType::Char
+                        | BaseType::Short
+                        | BaseType::Int,
+                    ) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_int((*object).fields.get_int(slot_id, volatile));
+                    }
+                    FieldType::Base(BaseType::Float) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_float((*object).fields.get_float(slot_id, volatile));
+                    }
+                    FieldType::Base(BaseType::Long) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_long((*object).fields.get_long(slot_id, volatile));
+                    }
+                    FieldType::Base(BaseType::Double) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_double((*object).fields.get_double(slot_id, volatile));
+                    }
+                    FieldType::Object(_) | FieldType::Array(_) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_ref((*object).fields.get_ref(slot_id, volatile));
                     }
                 }
             }
@@ -1,6 +1,7 @@
 use crate::instructions::{InstructionExecutor, InstructionReader};
 use crate::rtda::{Constant, Frame};
 use bytes::Buf;
+use classfile::descriptor::{BaseType, FieldType};
 use jvm_macros::Index16;
 
 #[derive(Debug, Default, Index16)]
@@ -12,40 +13,48 @@ pub struct GET_STATIC {
 impl InstructionExecutor for GET_STATIC {
     fn execute(&self, frame: &mut Frame) {
         unsafe {
-            let mut constant_pool = frame.method().borrow_mut().class.as_mut().constant_pool;
-            if let Constant::FieldRef(field_ref) = constant_pool.as_mut().get_mut(self.index) {
+            let constant_pool = frame.method().read().unwrap().class.as_ref().constant_pool;
+            if let Constant::FieldRef(field_ref) = constant_pool.as_ref().get(self.index) {
                 let field = field_ref.resolve_field();
-                let mut class = field.borrow_mut().class;
-                if !field.borrow().is_static() {
+                let class = field.read().unwrap().class;
+                if !field.read().unwrap().is_static() {
                     panic!("java.lang.IncompatibleClassChangeError");
                 }
-                let descriptor = field.borrow().descriptor.clone();
-                let slot_id = field.borrow().slot_id;
-                let slots = class.as_mut().static_vars_mut();
-                if let Some(char) = descriptor.chars().next() {
-                    match char {
-                        'Z' | 'B' | 'C' | 'S' | 'I' => {
-                            frame.operand_stack_mut().push_int(slots.get_int(slot_id));
-                        }
-                        'F' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_float(slots.get_float(slot_id));
-                        }
-                        'J' => {
-                            frame.operand_stack_mut().push_long(slots.get_long(slot_id));
-                        }
-                        'D' => {
-                            frame
-                                .operand_stack_mut()
-                                .push_double(slots.get_double(slot_id));
-                        }
-                        'L' | '[' => {
-                            frame.operand_stack_mut().push_ref(slots.get_ref(slot_id));
-                        }
-                        _ => {
-                            panic!("java.lang.ClassFormatError");
-                        }
+                let field_type = field.read().unwrap().field_type.clone();
+                let slot_id = field.read().unwrap().slot_id;
+                let volatile = field.read().unwrap().is_volatile();
+                let slots = class.as_ref().static_vars();
+                match field_type {
+                    FieldType::Base(
+                        BaseType::Boolean
+                        | BaseType::Byte
+                        | BaseType::Char
+                        | BaseType::Short
+                        | BaseType::Int,
+                    ) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_int(slots.get_int(slot_id, volatile));
+                    }
+                    FieldType::Base(BaseType::Float) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_float(slots.get_float(slot_id, volatile));
+                    }
+                    FieldType::Base(BaseType::Long) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_long(slots.get_long(slot_id, volatile));
+                    }
+                    FieldType::Base(BaseType::Double) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_double(slots.get_double(slot_id, volatile));
+                    }
+                    FieldType::Object(_) | FieldType::Array(_) => {
+                        frame
+                            .operand_stack_mut()
+                            .push_ref(slots.get_ref(slot_id, volatile));
                     }
                 }
             }
@@ -18,8 +18,8 @@ impl InstructionExecutor for INSTANCE_OF {
                 return;
             }
 
-            let mut constant_pool = frame.method().borrow().class.as_ref().constant_pool;
-            if let Constant::Class(class_ref) = constant_pool.as_mut().get_mut(self.index) {
+            let constant_pool = frame.method().read().unwrap().class.as_ref().constant_pool;
+            if let Constant::Class(class_ref) = constant_pool.as_ref().get(self.index) {
                 let class = class_ref.resolved_class().unwrap();
                 if (*object).is_instance_of(class) {
                     frame.operand_stack_mut().push_int(1);
@@ -0,0 +1,232 @@
+use crate::instructions::refs::monitor::monitor_enter;
+use crate::instructions::{InstructionExecutor, InstructionReader};
+use crate::native::{find_native_method, invoke_native};
+use crate::rtda::{Class, Constant, Frame, LockWord, Method, Object, Thread};
+use bytes::Buf;
+use classfile::descriptor::{BaseType, FieldType, MethodDescriptor};
+use jvm_macros::Index16;
+use std::io::Cursor;
+use std::ptr::NonNull;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Default, Index16)]
+#[allow(non_camel_case_types)]
+pub struct INVOKE_STATIC {
+    index: usize,
+}
+
+impl InstructionExecutor for INVOKE_STATIC {
+    fn execute(&self, frame: &mut Frame) {
+        let (_, method) = resolve_method(frame, self.index);
+        if !method.read().unwrap().is_static() {
+            panic!("java.lang.IncompatibleClassChangeError");
+        }
+        invoke_method(frame, method);
+    }
+}
+
+#[derive(Debug, Default, Index16)]
+#[allow(non_camel_case_types)]
+pub struct INVOKE_SPECIAL {
+    index: usize,
+}
+
+impl InstructionExecutor for INVOKE_SPECIAL {
+    fn execute(&self, frame: &mut Frame) {
+        let current_class = frame.method().read().unwrap().class;
+        let (resolved_class, resolved) = resolve_method(frame, self.index);
+        let (name, descriptor) = {
+            let resolved = resolved.read().unwrap();
+            if resolved.is_static() {
+                panic!("java.lang.IncompatibleClassChangeError");
+            }
+            (resolved.name.clone(), resolved.descriptor.clone())
+        };
+        let method = unsafe {
+            current_class
+                .as_ref()
+                .select_special_method(resolved_class, &name, &descriptor)
+        };
+        let method = method.unwrap_or_else(|| panic!("java.lang.AbstractMethodError"));
+        receiver(frame, &resolved);
+        invoke_method(frame, method);
+    }
+}
+
+#[derive(Debug, Default, Index16)]
+#[allow(non_camel_case_types)]
+pub struct INVOKE_VIRTUAL {
+    index: usize,
+}
+
+impl InstructionExecutor for INVOKE_VIRTUAL {
+    fn execute(&self, frame: &mut Frame) {
+        let (_, resolved) = resolve_method(frame, self.index);
+        let method = select_method(frame, resolved);
+        invoke_method(frame, method);
+    }
+}
+
+#[derive(Debug, Default)]
+#[allow(non_camel_case_types)]
+pub struct INVOKE_INTERFACE {
+    index: usize,
+}
+
+impl<T: AsRef<[u8]>> InstructionReader<T> for INVOKE_INTERFACE {
+    fn fetch_operands(&mut self, reader: &mut Cursor<T>) {
+        self.index = reader.get_u16() as usize;
+        // the count and the zero byte, the descriptor gives the argument slots
+        reader.get_u8();
+        reader.get_u8();
+    }
+}
+
+impl InstructionExecutor for INVOKE_INTERFACE {
+    fn execute(&self, frame: &mut Frame) {
+        let (interface, resolved) = resolve_method(frame, self.index);
+        let this = receiver(frame, &resolved);
+        if !unsafe { (*this).is_instance_of(interface) } {
+            panic!("java.lang.IncompatibleClassChangeError");
+        }
+        let method = select_method(frame, resolved);
+        invoke_method(frame, method);
+    }
+}
+
+// the class a method reference of the current class names, with the method it resolves to
+fn resolve_method(frame: &mut Frame, index: usize) -> (NonNull<Class>, Arc<RwLock<Method>>) {
+    let class = frame.method().read().unwrap().class;
+    unsafe {
+        let (method_ref, method) = match class.as_ref().constant_pool.as_ref().get(index) {
+            Constant::MethodRef(method_ref) => (method_ref, method_ref.resolve_method()),
+            Constant::InterfaceMethodRef(method_ref) => {
+                (method_ref, method_ref.resolve_interface_method())
+            }
+            _ => panic!("java.lang.IncompatibleClassChangeError"),
+        };
+        (method_ref.resolved_class().unwrap(), method)
+    }
+}
+
+// the object an instance method is invoked on, below its arguments
+fn receiver(frame: &mut Frame, method: &Arc<RwLock<Method>>) -> *mut Object {
+    let arg_slots = method.read().unwrap().arg_slot_count();
+    let this = frame.operand_stack().get_ref_from_top(arg_slots - 1);
+    if this.is_null() {
+        panic!("java.lang.NullPointerException");
+    }
+    this
+}
+
+// the method overriding `resolved` in the class of the receiver, JVMS 5.4.6
+fn select_method(frame: &mut Frame, resolved: Arc<RwLock<Method>>) -> Arc<RwLock<Method>> {
+    let this = receiver(frame, &resolved);
+    let resolved_ref = resolved.read().unwrap();
+    if resolved_ref.is_static() {
+        panic!("java.lang.IncompatibleClassChangeError");
+    }
+    if resolved_ref.is_private() {
+        drop(resolved_ref);
+        return resolved;
+    }
+    let method = unsafe {
+        (*this)
+            .class
+            .as_ref()
+            .select_method(&resolved_ref.name, &resolved_ref.descriptor)
+    };
+    method.unwrap_or_else(|| panic!("java.lang.AbstractMethodError"))
+}
+
+/// Passes the arguments on top of the operand stack to `method`, a native one
+/// runs right away and others once the current instruction completes.
+pub(crate) fn invoke_method(frame: &mut Frame, method: Arc<RwLock<Method>>) {
+    let method_ref = method.read().unwrap();
+    // a synchronized method locks before its arguments are taken, so a virtual
+    // thread parked on the lock retries the invoke with the operand stack intact
+    let monitor = match method_ref.is_synchronized() {
+        true => Some(synchronized_lock(frame, &method_ref)),
+        false => None,
+    };
+    if let Some(monitor) = monitor {
+        if !monitor_enter(frame, monitor) {
+            return;
+        }
+    }
+    let mut callee = Thread::new_frame(frame.thread(), method.clone());
+    let descriptor = MethodDescriptor::parse(&method_ref.descriptor)
+        .unwrap_or_else(|e| panic!("java.lang.ClassFormatError: {}", e));
+    pass_args(frame, &mut callee, &descriptor, method_ref.is_static());
+    // a registered native also replaces a method implemented in Java, an intrinsic
+    let class_name = unsafe { &method_ref.class.as_ref().name };
+    let native = find_native_method(class_name, &method_ref.name, &method_ref.descriptor);
+    if native.is_none() && method_ref.is_abstract() {
+        panic!("java.lang.AbstractMethodError");
+    }
+    if native.is_none() && method_ref.is_native() {
+        panic!(
+            "java.lang.UnsatisfiedLinkError: {}.{}{}",
+            class_name.replace('/', "."),
+            method_ref.name,
+            method_ref.descriptor
+        );
+    }
+    if let Some(native) = native {
+        drop(method_ref);
+        invoke_native(native, &mut callee);
+        if let Some(monitor) = monitor {
+            monitor.exit(frame.thread().lock().unwrap().id());
+        }
+        callee.return_to(frame, descriptor.return_slots());
+        return;
+    }
+    drop(method_ref);
+    if let Some(monitor) = monitor {
+        callee.set_monitor(monitor);
+    }
+    frame.thread().lock().unwrap().invoke(callee);
+}
+
+// the receiver's lock, or the class's for a static method
+fn synchronized_lock(frame: &mut Frame, method: &Method) -> &'static LockWord {
+    unsafe {
+        if method.is_static() {
+            return &method.class.as_ref().lock;
+        }
+        let this = frame
+            .operand_stack()
+            .get_ref_from_top(method.arg_slot_count() - 1);
+        if this.is_null() {
+            panic!("java.lang.NullPointerException");
+        }
+        &(*this).lock
+    }
+}
+
+fn pass_args(
+    caller: &mut Frame,
+    callee: &mut Frame,
+    descriptor: &MethodDescriptor,
+    is_static: bool,
+) {
+    let mut index = descriptor.parameter_slots() + usize::from(!is_static);
+    for parameter in descriptor.parameters.iter().rev() {
+        index -= parameter.slot_size();
+        match parameter {
+            // the halves swap places between the operand stack and the local variables
+            FieldType::Base(BaseType::Long | BaseType::Double) => {
+                let value = caller.operand_stack_mut().pop_long();
+                callee.local_vars_mut().set_long(index, value);
+            }
+            _ => {
+                let slot = caller.operand_stack_mut().pop_slot();
+                callee.local_vars_mut().set_slot(index, slot);
+            }
+        }
+    }
+    if !is_static {
+        let this = caller.operand_stack_mut().pop_slot();
+        callee.local_vars_mut().set_slot(0, this);
+    }
+}
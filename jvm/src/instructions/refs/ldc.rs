@@ -12,7 +12,7 @@ pub struct LDC {
 impl InstructionExecutor for LDC {
     fn execute(&self, frame: &mut Frame) {
         unsafe {
-            let constant_pool = frame.method().borrow().class.as_ref().constant_pool;
+            let constant_pool = frame.method().read().unwrap().class.as_ref().constant_pool;
             let constant = constant_pool.as_ref().get(self.index);
             match constant {
                 Constant::Integer(int) => {
@@ -38,7 +38,7 @@ pub struct LDC_W {
 impl InstructionExecutor for LDC_W {
     fn execute(&self, frame: &mut Frame) {
         unsafe {
-            let constant_pool = frame.method().borrow().class.as_ref().constant_pool;
+            let constant_pool = frame.method().read().unwrap().class.as_ref().constant_pool;
             let constant = constant_pool.as_ref().get(self.index);
             match constant {
                 Constant::Integer(int) => {
@@ -64,7 +64,7 @@ pub struct LDC2_W {
 impl InstructionExecutor for LDC2_W {
     fn execute(&self, frame: &mut Frame) {
         unsafe {
-            let constant_pool = frame.method().borrow().class.as_ref().constant_pool;
+            let constant_pool = frame.method().read().unwrap().class.as_ref().constant_pool;
             let constant = constant_pool.as_ref().get(self.index);
             match constant {
                 Constant::Long(long) => {
@@ -2,7 +2,9 @@ pub(crate) mod check_cast;
 pub(crate) mod get_field;
 pub(crate) mod get_static;
 pub(crate) mod instance_of;
+pub(crate) mod invoke;
 pub(crate) mod ldc;
+pub(crate) mod monitor;
 pub(crate) mod new;
 pub(crate) mod put_field;
 pub(crate) mod put_static;
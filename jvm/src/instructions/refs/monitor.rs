@@ -0,0 +1,56 @@
+use crate::instructions::{InstructionExecutor, InstructionReader};
+use crate::rtda::{safepoint, Frame, LockWord, Park};
+use jvm_macros::NoOperand;
+
+#[derive(NoOperand, Debug)]
+#[allow(non_camel_case_types)]
+pub struct MONITOR_ENTER;
+
+impl InstructionExecutor for MONITOR_ENTER {
+    fn execute(&self, frame: &mut Frame) {
+        let object = frame.operand_stack_mut().pop_ref();
+        if object.is_null() {
+            panic!("java.lang.NullPointerException");
+        }
+        if !monitor_enter(frame, unsafe { &(*object).lock }) {
+            frame.operand_stack_mut().push_ref(object);
+        }
+    }
+}
+
+/// Enters `lock` on behalf of the current thread. A virtual thread which finds
+/// it contended parks instead and returns `false`, the current instruction is
+/// executed again once it is rescheduled.
+pub(crate) fn monitor_enter(frame: &mut Frame, lock: &'static LockWord) -> bool {
+    let thread = frame.thread().lock().unwrap().java_thread().clone();
+    if lock.try_enter(thread.id()) {
+        return true;
+    }
+    if thread.is_virtual() {
+        // free the carrier and execute this instruction again once the lock is released
+        let pc = frame.thread().lock().unwrap().pc();
+        frame.set_next_pc(pc);
+        frame.thread().lock().unwrap().park(Park::Monitor(lock));
+        return false;
+    }
+    // only a contended enter may block, let a pause proceed meanwhile
+    safepoint().in_native(&thread, || lock.enter(thread.id()));
+    true
+}
+
+#[derive(NoOperand, Debug)]
+#[allow(non_camel_case_types)]
+pub struct MONITOR_EXIT;
+
+impl InstructionExecutor for MONITOR_EXIT {
+    fn execute(&self, frame: &mut Frame) {
+        let object = frame.operand_stack_mut().pop_ref();
+        if object.is_null() {
+            panic!("java.lang.NullPointerException");
+        }
+        let thread_id = frame.thread().lock().unwrap().id();
+        unsafe {
+            (*object).monitor_exit(thread_id);
+        }
+    }
+}
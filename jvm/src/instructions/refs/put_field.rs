@@ -1,6 +1,7 @@
 use crate::instructions::{InstructionExecutor, InstructionReader};
 use crate::rtda::{Constant, Frame};
 use bytes::Buf;
+use classfile::descriptor::{BaseType, FieldType};
 use jvm_macros::Index16;
 
 #[derive(Debug, Default, Index16)]
@@ -12,64 +13,69 @@ pub struct PUT_FIELD {
 impl InstructionExecutor for PUT_FIELD {
     fn execute(&self, frame: &mut Frame) {
         let cur_method = frame.method();
-        let mut cur_class = cur_method.borrow().class;
+        let cur_class = cur_method.read().unwrap().class;
         unsafe {
-            let mut constant_pool = cur_class.as_mut().constant_pool;
-            if let Constant::FieldRef(field_ref) = constant_pool.as_mut().get_mut(self.index) {
+            let constant_pool = cur_class.as_ref().constant_pool;
+            if let Constant::FieldRef(field_ref) = constant_pool.as_ref().get(self.index) {
                 let field = field_ref.resolve_field();
-                if field.borrow().is_static() {
+                if field.read().unwrap().is_static() {
                     panic!("java.lang.IncompatibleClassChangeError");
                 }
-                if field.borrow().is_final()
-                    && (cur_class != field.borrow().class || cur_method.borrow().name != "<init>")
+                if field.read().unwrap().is_final()
+                    && (cur_class != field.read().unwrap().class
+                        || cur_method.read().unwrap().name != "<init>")
                 {
                     panic!("java.lang.IllegalAccessError");
                 }
-                let descriptor = field.borrow().descriptor.clone();
-                let slot_id = field.borrow().slot_id;
-                if let Some(char) = descriptor.chars().next() {
-                    match char {
-                        'Z' | 'B' | 'C' | 'S' | 'I' => {
-                            let val = frame.operand_stack_mut().pop_int();
-                            let object = frame.operand_stack_mut().pop_ref();
-                            if object.is_null() {
-                                panic!("java.lang.NullPointerException");
-                            }
-                            (*object).fields.set_int(slot_id, val)
+                let field_type = field.read().unwrap().field_type.clone();
+                let slot_id = field.read().unwrap().slot_id;
+                let volatile = field.read().unwrap().is_volatile();
+                match field_type {
+                    FieldType::Base(
+                        BaseType::Boolean
+                        | BaseType::Byte
+                        | BaseType::Char
+                        | BaseType::Short
+                        | BaseType::Int,
+                    ) => {
+                        let val = frame.operand_stack_mut().pop_int();
+                        let object = frame.operand_stack_mut().pop_ref();
+                        if object.is_null() {
+                            panic!("java.lang.NullPointerException");
                         }
-                        'F' => {
-                            let val = frame.operand_stack_mut().pop_float();
-                            let object = frame.operand_stack_mut().pop_ref();
-                            if object.is_null() {
-                                panic!("java.lang.NullPointerException");
-                            }
-                            (*object).fields.set_float(slot_id, val)
+                        (*object).fields.set_int(slot_id, val, volatile)
+                    }
+                    FieldType::Base(BaseType::Float) => {
+                        let val = frame.operand_stack_mut().pop_float();
+                        let object = frame.operand_stack_mut().pop_ref();
+                        if object.is_null() {
+                            panic!("java.lang.NullPointerException");
                         }
-                        'J' => {
-                            let val = frame.operand_stack_mut().pop_long();
-                            let object = frame.operand_stack_mut().pop_ref();
-                            if object.is_null() {
-                                panic!("java.lang.NullPointerException");
-                            }
-                            (*object).fields.set_long(slot_id, val)
+                        (*object).fields.set_float(slot_id, val, volatile)
+                    }
+                    FieldType::Base(BaseType::Long) => {
+                        let val = frame.operand_stack_mut().pop_long();
+                        let object = frame.operand_stack_mut().pop_ref();
+                        if object.is_null() {
+                            panic!("java.lang.NullPointerException");
                         }
-                        'D' => {
-                            let val = frame.operand_stack_mut().pop_double();
-                            let object = frame.operand_stack_mut().pop_ref();
-                            if object.is_null() {
-                                panic!("java.lang.NullPointerException");
-                            }
-                            (*object).fields.set_double(slot_id, val)
+                        (*object).fields.set_long(slot_id, val, volatile)
+                    }
+                    FieldType::Base(BaseType::Double) => {
+                        let val = frame.operand_stack_mut().pop_double();
+                        let object = frame.operand_stack_mut().pop_ref();
+                        if object.is_null() {
+                            panic!("java.lang.NullPointerException");
                         }
-                        'L' | '[' => {
-                            let val = frame.operand_stack_mut().pop_ref();
-                            let object = frame.operand_stack_mut().pop_ref();
-                            if object.is_null() {
-                                panic!("java.lang.NullPointerException");
-                            }
-                            (*object).fields.set_ref(slot_id, val)
+                        (*object).fields.set_double(slot_id, val, volatile)
+                    }
+                    FieldType::Object(_) | FieldType::Array(_) => {
+                        let val = frame.operand_stack_mut().pop_ref();
+                        let object = frame.operand_stack_mut().pop_ref();
+                        if object.is_null() {
+                            panic!("java.lang.NullPointerException");
                         }
-                        _ => panic!("java.lang.ClassFormatError"),
+                        (*object).fields.set_ref(slot_id, val, volatile)
                     }
                 }
             }
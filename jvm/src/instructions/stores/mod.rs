@@ -15,7 +15,8 @@ macro_rules! register_store_fn {
 }
 
 register_store_fn! {
-    (astore, pop_ref, set_ref),
+    // moves the whole slot, a subroutine saves its return address with astore
+    (astore, pop_slot, set_slot),
     (dstore, pop_double, set_double),
     (fstore, pop_float, set_float),
     (istore, pop_int, set_int),
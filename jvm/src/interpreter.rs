@@ -1,44 +1,149 @@
 use crate::instructions::new_inst;
-use crate::rtda::{Class, Method, Thread};
+use crate::rtda::{freeze_final_fields, Class, Frame, LocalVars, Method, Park, Thread};
+use crate::vthread::Continuation;
 use bytes::Buf;
 
-use std::cell::RefCell;
+use std::fmt::Debug;
 use std::io::Cursor;
-use std::sync::Arc;
-
-pub fn interpret(method: Arc<RefCell<Method>>) {
-    if let Some(code) = method.borrow().code() {
-        let thread = Arc::new(RefCell::new(Thread::new()));
-        let frame = Thread::new_frame(thread.clone(), method.clone());
-        thread.borrow_mut().push_frame(frame);
-        loop_interpret(thread, &mut Cursor::new(code));
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+static TRACE: AtomicBool = AtomicBool::new(false);
+
+/// Whether each instruction is printed with the frame it executes in, as `-Xtrace`.
+pub fn set_trace(enabled: bool) {
+    TRACE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn interpret(method: Arc<RwLock<Method>>) {
+    interpret_with_args(method, |_| {});
+}
+
+/// Runs `method` on the calling host thread, `init_args` fills in its
+/// parameters, `this` included.
+pub fn interpret_with_args<F: FnOnce(&mut LocalVars)>(method: Arc<RwLock<Method>>, init_args: F) {
+    if let Some(mut interpreter) = Interpreter::new(method, init_args) {
+        while interpreter.step() {}
     }
 }
 
-fn loop_interpret<T: AsRef<[u8]>>(thread: Arc<RefCell<Thread>>, cursor: &mut Cursor<T>) {
-    let mut frame = thread.borrow_mut().pop_frame();
-    loop {
-        let pc = frame.next_pc();
-        thread.borrow_mut().set_pc(pc);
-        cursor.set_position(pc as u64);
-        let opcode = cursor.get_u8();
-        let mut inst = new_inst(opcode);
-        inst.fetch_operands(cursor);
-        frame.set_next_pc(cursor.position() as isize);
-        println!("pc: {} inst: {:?}", pc, inst);
-        println!(
-            "local_vars: {:?}, operand_stack: {:?}",
-            frame.local_vars(),
-            frame.operand_stack()
-        );
-        inst.execute(&mut frame);
+/// The frames of a thread being interpreted. The current frame is kept off the
+/// thread's stack, as its instructions lock the thread, while its callers wait
+/// on the stack.
+struct Interpreter {
+    thread: Arc<Mutex<Thread>>,
+    frame: Box<Frame>,
+    cursor: Cursor<Vec<u8>>,
+}
+
+impl Interpreter {
+    fn new<F: FnOnce(&mut LocalVars)>(method: Arc<RwLock<Method>>, init_args: F) -> Option<Self> {
+        let code = method.read().unwrap().code().map(<[u8]>::to_vec)?;
+        let thread = Arc::new(Mutex::new(Thread::new()));
+        let mut frame = Thread::new_frame(thread.clone(), method);
+        init_args(frame.local_vars_mut());
+        Some(Self {
+            thread,
+            frame: Box::new(frame),
+            cursor: Cursor::new(code),
+        })
+    }
+
+    /// Executes one instruction, returns `false` once the first method has returned.
+    fn step(&mut self) -> bool {
+        step(&self.thread, &mut self.frame, &mut self.cursor);
+        let mut thread = self.thread.lock().unwrap();
+        if let Some(mut callee) = thread.take_invoked() {
+            self.cursor = Cursor::new(code(&mut callee));
+            let caller = std::mem::replace(&mut *self.frame, callee);
+            thread.push_frame(caller);
+        } else if let Some(slots) = thread.take_returned() {
+            if self.frame.method().read().unwrap().name == "<init>" {
+                freeze_final_fields();
+            }
+            if let Some(monitor) = self.frame.monitor() {
+                monitor.exit(thread.id());
+            }
+            if thread.is_stack_empty() {
+                return false;
+            }
+            let mut caller = thread.pop_frame();
+            self.frame.return_to(&mut caller, slots);
+            self.cursor = Cursor::new(code(&mut caller));
+            self.frame = caller;
+        }
+        true
+    }
+}
+
+fn code(frame: &mut Frame) -> Vec<u8> {
+    let method = frame.method();
+    let code = method.read().unwrap().code().map(<[u8]>::to_vec);
+    code.unwrap_or_default()
+}
+
+fn step<T: AsRef<[u8]>>(thread: &Arc<Mutex<Thread>>, frame: &mut Frame, cursor: &mut Cursor<T>) {
+    let pc = frame.next_pc();
+    thread.lock().unwrap().set_pc(pc);
+    cursor.set_position(pc as u64);
+    let opcode = cursor.get_u8();
+    let mut inst = new_inst(opcode);
+    inst.fetch_operands(cursor);
+    frame.set_next_pc(cursor.position() as isize);
+    if TRACE.load(Ordering::Relaxed) {
+        print!("{}", trace(pc, &inst, frame));
+    }
+    inst.execute(frame);
+}
+
+fn trace(pc: isize, inst: &dyn Debug, frame: &Frame) -> String {
+    format!(
+        "pc: {} inst: {:?}\nlocal_vars: {:?}, operand_stack: {:?}\n",
+        pc,
+        inst,
+        frame.local_vars(),
+        frame.operand_stack()
+    )
+}
+
+/// An interpreted method suspended between two instructions, the body of a
+/// virtual thread.
+pub struct InterpretedContinuation(Interpreter);
+
+// only ever resumed by one carrier at a time
+unsafe impl Send for InterpretedContinuation {}
+
+impl InterpretedContinuation {
+    /// Must be created on the thread the continuation belongs to, i.e. with
+    /// it mounted, as its frames capture the current thread.
+    pub fn new<F: FnOnce(&mut LocalVars)>(
+        method: Arc<RwLock<Method>>,
+        init_args: F,
+    ) -> Option<Self> {
+        Interpreter::new(method, init_args).map(Self)
     }
 }
 
-fn get_main_method(class: &Class) -> Option<Arc<RefCell<Method>>> {
+impl Continuation for InterpretedContinuation {
+    fn resume(&mut self, pending: Option<anyhow::Error>) -> anyhow::Result<Option<Park>> {
+        // without exception handlers whatever the blocking point threw is uncaught
+        if let Some(e) = pending {
+            return Err(e);
+        }
+        while self.0.step() {
+            if let Some(park) = self.0.thread.lock().unwrap().take_park() {
+                return Ok(Some(park));
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub(crate) fn get_main_method(class: &Class) -> Option<Arc<RwLock<Method>>> {
     for method in class.methods.iter() {
-        if method.borrow().name.as_str() == "main"
-            && method.borrow().descriptor.as_str() == "([Ljava/lang/String;)V"
+        let method_ref = method.read().unwrap();
+        if method_ref.name.as_str() == "main"
+            && method_ref.descriptor.as_str() == "([Ljava/lang/String;)V"
         {
             return Some(method.clone());
         }
@@ -49,9 +154,90 @@ fn get_main_method(class: &Class) -> Option<Arc<RefCell<Method>>> {
 #[cfg(test)]
 mod tests {
     use crate::classpath::ClassPath;
+    use crate::instructions::new_inst;
     use crate::interpreter;
-    use crate::interpreter::get_main_method;
-    use crate::rtda::ClassLoader;
+    use crate::interpreter::{get_main_method, step, trace};
+    use crate::rtda::{ClassLoader, Frame, Method, Thread};
+    use std::io::Cursor;
+    use std::marker::PhantomData;
+    use std::ptr::NonNull;
+    use std::sync::{Arc, Mutex, RwLock};
+
+    fn run(code: &[u8], max_locals: usize, steps: usize) -> Frame {
+        let method = Arc::new(RwLock::new(Method {
+            access_flags: 0,
+            name: "m".to_string(),
+            descriptor: "()I".to_string(),
+            class: NonNull::dangling(),
+            max_stack: 2,
+            max_locals,
+            code: Some(code.to_vec()),
+            marker: PhantomData,
+        }));
+        let thread = Arc::new(Mutex::new(Thread::new()));
+        let mut frame = Thread::new_frame(thread.clone(), method);
+        let mut cursor = Cursor::new(code);
+        for _ in 0..steps {
+            step(&thread, &mut frame, &mut cursor);
+        }
+        frame
+    }
+
+    #[test]
+    fn test_subroutine() {
+        // iconst_1, istore_1, jsr 7, iload_1, ireturn, astore_2, iinc 1 5, ret 2
+        let code = [
+            0x04, 0x3c, 0xa8, 0x00, 0x05, 0x1b, 0xac, 0x4d, 0x84, 0x01, 0x05, 0xa9, 0x02,
+        ];
+        let mut frame = run(&code, 3, 7);
+        assert_eq!(frame.next_pc(), 6);
+        assert_eq!(frame.local_vars().get_return_address(2), 5);
+        assert_eq!(frame.operand_stack_mut().pop_int(), 6);
+
+        // the same with jsr_w 7
+        let code = [
+            0x04, 0x3c, 0xc9, 0x00, 0x00, 0x00, 0x07, 0x1b, 0xac, 0x4d, 0x84, 0x01, 0x05, 0xa9,
+            0x02,
+        ];
+        let mut frame = run(&code, 3, 7);
+        assert_eq!(frame.next_pc(), 8);
+        assert_eq!(frame.operand_stack_mut().pop_int(), 6);
+    }
+
+    #[test]
+    fn test_wide() {
+        // sipush 1000, wide istore 300, wide iinc 300 -2, wide iload 300
+        let code = [
+            0x11, 0x03, 0xe8, 0xc4, 0x36, 0x01, 0x2c, 0xc4, 0x84, 0x01, 0x2c, 0xff, 0xfe, 0xc4,
+            0x15, 0x01, 0x2c,
+        ];
+        let mut frame = run(&code, 301, 4);
+        assert_eq!(frame.local_vars().get_int(300), 998);
+        assert_eq!(frame.operand_stack_mut().pop_int(), 998);
+
+        let mut inst = new_inst(0xc4);
+        inst.fetch_operands(&mut Cursor::new(&code[8..]));
+        assert_eq!(
+            format!("{:?}", inst),
+            "WIDE { modified_instruction: IINC { index: 300, const: -2 } }"
+        );
+        let trace = trace(7, &inst, &frame);
+        assert!(trace.starts_with(
+            "pc: 7 inst: WIDE { modified_instruction: IINC { index: 300, const: -2 } }\nlocal_vars: "
+        ));
+    }
+
+    #[test]
+    fn test_wide_ret() {
+        // jsr 7, return, ..., wide astore 299, wide ret 299
+        let code = [
+            0xa8, 0x00, 0x07, 0xb1, 0x00, 0x00, 0x00, 0xc4, 0x3a, 0x01, 0x2b, 0xc4, 0xa9, 0x01,
+            0x2b,
+        ];
+        let mut frame = run(&code, 300, 3);
+        assert_eq!(frame.local_vars().get_return_address(299), 3);
+        assert_eq!(frame.next_pc(), 3);
+    }
 
     #[test]
     #[should_panic]
@@ -0,0 +1,366 @@
+//! A `javap` work-alike, run as `jvm javap [options] <classes>`.
+
+use crate::classpath::ClassPath;
+use crate::javap::printer::ClassPrinter;
+use anyhow::anyhow;
+use std::path::Path;
+
+mod printer;
+mod types;
+mod writer;
+
+const USAGE: &str = "Usage: jvm javap <options> <classes>
+where possible options include:
+  -v  -verbose             Print additional information
+  -l                       Print line number and local variable tables
+  -public                  Show only public classes and members
+  -protected               Show protected/public classes and members
+  -package                 Show package/protected/public classes
+                           and members (default)
+  -p  -private             Show all classes and members
+  -c                       Disassemble the code
+  -s                       Print internal type signatures
+  -constants               Show final constants
+  -cp <path>               Specify where to find user class files
+  -classpath <path>        Specify where to find user class files
+  --class-path <path>      Specify where to find user class files
+
+<classes> are class names, .class files, or jar members given as
+<file>.jar!/<member> or jar:file:<file>.jar!/<member>.";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Protected,
+    #[default]
+    Package,
+    Private,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub verbose: bool,
+    pub code: bool,
+    pub lines: bool,
+    pub descriptors: bool,
+    pub constants: bool,
+    pub access: Access,
+    pub class_path: String,
+}
+
+impl Options {
+    /// Parses the options in `args`, returning them with the class names.
+    pub fn parse(args: &[String]) -> anyhow::Result<(Options, Vec<String>)> {
+        let mut options = Options::default();
+        let mut classes = vec![];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-v" | "-verbose" => {
+                    options.verbose = true;
+                    options.descriptors = true;
+                }
+                "-c" => options.code = true,
+                "-l" => options.lines = true,
+                "-s" => options.descriptors = true,
+                "-constants" => options.constants = true,
+                "-public" => options.access = Access::Public,
+                "-protected" => options.access = Access::Protected,
+                "-package" => options.access = Access::Package,
+                "-p" | "-private" => options.access = Access::Private,
+                "-cp" | "-classpath" | "--class-path" => match args.next() {
+                    Some(path) => options.class_path = path.clone(),
+                    None => return Err(anyhow!("{} requires an argument", arg)),
+                },
+                _ if arg.starts_with('-') => return Err(anyhow!("invalid flag: {}", arg)),
+                _ => classes.push(arg.clone()),
+            }
+        }
+        Ok((options, classes))
+    }
+
+    /// Whether a member or inner class with `access_flags` is shown.
+    pub fn shows(&self, access_flags: u16) -> bool {
+        const ACC_PUBLIC: u16 = 0x0001;
+        const ACC_PRIVATE: u16 = 0x0002;
+        const ACC_PROTECTED: u16 = 0x0004;
+        match self.access {
+            Access::Public => access_flags & ACC_PUBLIC != 0,
+            Access::Protected => access_flags & (ACC_PUBLIC | ACC_PROTECTED) != 0,
+            Access::Package => access_flags & ACC_PRIVATE == 0,
+            Access::Private => true,
+        }
+    }
+}
+
+/// Runs `javap` with the arguments after `javap` and returns the exit code.
+pub fn main(args: &[String]) -> i32 {
+    let (options, classes) = match Options::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    if classes.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+    let mut class_path = None;
+    let mut code = 0;
+    for class in &classes {
+        let result = read_class(class, &options.class_path, &mut class_path)
+            .and_then(|(bytes, location)| javap(&bytes, location.as_deref(), &options));
+        match result {
+            Ok(output) => print!("{}", output),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+/// Prints the class file in `bytes`, found at `location` if it came from a
+/// file.
+pub fn javap(bytes: &[u8], location: Option<&str>, options: &Options) -> anyhow::Result<String> {
+    let class_file = classfile::parse(bytes)?;
+    Ok(ClassPrinter::new(&class_file, options).write(location))
+}
+
+/// Reads `class` from a jar member, a `.class` file or the class path given
+/// by `cp`, with the location of the file it was read from. The class path is
+/// only opened when needed.
+pub fn read_class(
+    class: &str,
+    cp: &str,
+    class_path: &mut Option<ClassPath>,
+) -> anyhow::Result<(Vec<u8>, Option<String>)> {
+    let jar_member = class.strip_prefix("jar:file:").unwrap_or(class);
+    if let Some((jar, member)) = jar_member.split_once("!/") {
+        let jar = std::fs::canonicalize(jar)?;
+        let entry = crate::classpath::new_entry(jar.to_string_lossy().to_string())?;
+        let bytes = entry.read_class(member)?;
+        return Ok((
+            bytes,
+            Some(format!("jar:file:{}!/{}", jar.display(), member)),
+        ));
+    }
+
+    let path = Path::new(class);
+    if class.ends_with(".class") && path.is_file() {
+        let bytes = std::fs::read(path)?;
+        let location = std::fs::canonicalize(path)?;
+        return Ok((bytes, Some(location.display().to_string())));
+    }
+
+    let class_path =
+        class_path.get_or_insert_with(|| ClassPath::new(String::new(), cp.to_string()));
+    let name = class.replace('.', "/");
+    match class_path.read_class_from(&name) {
+        Ok((bytes, _)) => Ok((bytes, None)),
+        Err(_) => Err(anyhow!("class not found: {}", class)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn options(args: &[&str]) -> Options {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        Options::parse(&args).unwrap().0
+    }
+
+    #[test]
+    fn test_options() {
+        let args = ["-c", "-cp", "lib", "-p", "java.lang.Object", "Foo.class"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+        let (options, classes) = Options::parse(&args).unwrap();
+        assert!(options.code && !options.verbose);
+        assert_eq!(options.class_path, "lib");
+        assert_eq!(options.access, Access::Private);
+        assert_eq!(classes, ["java.lang.Object", "Foo.class"]);
+        assert!(options.shows(0x0002));
+        assert!(!self::options(&[]).shows(0x0002));
+        assert!(!self::options(&["-public"]).shows(0x0004));
+        assert!(self::options(&["-protected"]).shows(0x0004));
+        assert!(Options::parse(&["-cp".to_string()]).is_err());
+        assert!(Options::parse(&["-x".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_verbose() {
+        let bytes = std::fs::read("../data/jvm8/GaussTest.class").unwrap();
+        let output = javap(&bytes, Some("GaussTest.class"), &options(&["-v", "-p"])).unwrap();
+        assert_eq!(
+            output,
+            r#"Classfile GaussTest.class
+  Compiled from "GaussTest.java"
+public class GaussTest
+  minor version: 0
+  major version: 52
+  flags: (0x0021) ACC_PUBLIC, ACC_SUPER
+  this_class: #19                         // GaussTest
+  super_class: #2                         // java/lang/Object
+  interfaces: 0, fields: 0, methods: 2, attributes: 1
+Constant pool:
+   #1 = Methodref          #2.#3          // java/lang/Object."<init>":()V
+   #2 = Class              #4             // java/lang/Object
+   #3 = NameAndType        #5:#6          // "<init>":()V
+   #4 = Utf8               java/lang/Object
+   #5 = Utf8               <init>
+   #6 = Utf8               ()V
+   #7 = Fieldref           #8.#9          // java/lang/System.out:Ljava/io/PrintStream;
+   #8 = Class              #10            // java/lang/System
+   #9 = NameAndType        #11:#12        // out:Ljava/io/PrintStream;
+  #10 = Utf8               java/lang/System
+  #11 = Utf8               out
+  #12 = Utf8               Ljava/io/PrintStream;
+  #13 = Methodref          #14.#15        // java/io/PrintStream.println:(I)V
+  #14 = Class              #16            // java/io/PrintStream
+  #15 = NameAndType        #17:#18        // println:(I)V
+  #16 = Utf8               java/io/PrintStream
+  #17 = Utf8               println
+  #18 = Utf8               (I)V
+  #19 = Class              #20            // GaussTest
+  #20 = Utf8               GaussTest
+  #21 = Utf8               Code
+  #22 = Utf8               LineNumberTable
+  #23 = Utf8               main
+  #24 = Utf8               ([Ljava/lang/String;)V
+  #25 = Utf8               StackMapTable
+  #26 = Utf8               SourceFile
+  #27 = Utf8               GaussTest.java
+{
+  public GaussTest();
+    descriptor: ()V
+    flags: (0x0001) ACC_PUBLIC
+    Code:
+      stack=1, locals=1, args_size=1
+         0: aload_0
+         1: invokespecial #1                  // Method java/lang/Object."<init>":()V
+         4: return
+      LineNumberTable:
+        line 1: 0
+
+  public static void main(java.lang.String[]);
+    descriptor: ([Ljava/lang/String;)V
+    flags: (0x0009) ACC_PUBLIC, ACC_STATIC
+    Code:
+      stack=2, locals=3, args_size=1
+         0: iconst_0
+         1: istore_1
+         2: iconst_1
+         3: istore_2
+         4: iload_2
+         5: bipush        100
+         7: if_icmpgt     20
+        10: iload_1
+        11: iload_2
+        12: iadd
+        13: istore_1
+        14: iinc          2, 1
+        17: goto          4
+        20: getstatic     #7                  // Field java/lang/System.out:Ljava/io/PrintStream;
+        23: iload_1
+        24: invokevirtual #13                 // Method java/io/PrintStream.println:(I)V
+        27: return
+      LineNumberTable:
+        line 3: 0
+        line 4: 2
+        line 5: 10
+        line 4: 14
+        line 7: 20
+        line 8: 27
+      StackMapTable: number_of_entries = 2
+        frame_type = 253 /* append */
+          offset_delta = 4
+          locals = [ int, int ]
+        frame_type = 250 /* chop */
+          offset_delta = 15
+}
+SourceFile: "GaussTest.java"
+"#
+        );
+    }
+
+    #[test]
+    fn test_descriptors() {
+        let bytes = std::fs::read("../data/jvm8/User.class").unwrap();
+        let output = javap(&bytes, None, &options(&["-p", "-s"])).unwrap();
+        assert_eq!(
+            output,
+            r#"Compiled from "User.java"
+public class User {
+  private java.lang.String name;
+    descriptor: Ljava/lang/String;
+  private int age;
+    descriptor: I
+  private double weight;
+    descriptor: D
+  public User();
+    descriptor: ()V
+
+  public java.lang.String getName();
+    descriptor: ()Ljava/lang/String;
+
+  public void setName(java.lang.String);
+    descriptor: (Ljava/lang/String;)V
+
+  public int getAge();
+    descriptor: ()I
+
+  public void setAge(int);
+    descriptor: (I)V
+
+  public double getWeight();
+    descriptor: ()D
+
+  public void setWeight(double);
+    descriptor: (D)V
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_rt_jar() {
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        let options = options(&["-v", "-p"]);
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if file.name().starts_with("java/util/") && file.name().ends_with(".class") {
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes).unwrap();
+                let output = javap(&bytes, None, &options).unwrap();
+                assert!(output.contains("Constant pool:"), "{}", file.name());
+                assert!(output.ends_with("}\n") || output.contains("\nSourceFile: "));
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_class() {
+        let options = Options::default();
+        let (bytes, location) = read_class("../data/jvm8/User.class", "", &mut None).unwrap();
+        assert_eq!(classfile::parse(&bytes).unwrap().methods.len(), 7);
+        assert!(location.unwrap().ends_with("/data/jvm8/User.class"));
+
+        let (bytes, location) =
+            read_class("../data/jvm8/rt.jar!/java/lang/Void.class", "", &mut None).unwrap();
+        let output = javap(&bytes, None, &options).unwrap();
+        assert!(
+            output.starts_with("Compiled from \"Void.java\"\npublic final class java.lang.Void {")
+        );
+        assert!(location
+            .unwrap()
+            .ends_with("/data/jvm8/rt.jar!/java/lang/Void.class"));
+    }
+}
@@ -0,0 +1,1669 @@
+use crate::javap::types::{
+    class_signature, field_type, java_name, parameter_types, signature_type, type_parameters,
+};
+use crate::javap::writer::LineWriter;
+use crate::javap::Options;
+use classfile::bytecode::{constant_value, describe_constant, disassemble};
+use classfile::descriptor::{
+    ClassSignature, FieldType, MethodDescriptor, MethodSignature, TypeSignature,
+};
+use classfile::{
+    get_str, Annotation, Attribute, AttributeType, ClassFile, CodeAttribute, Constant, Element,
+    ElementValue, FieldInfo, MethodInfo, StackMap, StackMapFrame, TargetInfo, TypeAnnotation,
+    VerificationTypeInfo,
+};
+
+const ACC_PUBLIC: u16 = 0x0001;
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_PROTECTED: u16 = 0x0004;
+const ACC_STATIC: u16 = 0x0008;
+const ACC_FINAL: u16 = 0x0010;
+const ACC_SYNCHRONIZED: u16 = 0x0020;
+const ACC_VOLATILE: u16 = 0x0040;
+const ACC_VARARGS: u16 = 0x0080;
+const ACC_NATIVE: u16 = 0x0100;
+const ACC_INTERFACE: u16 = 0x0200;
+const ACC_ABSTRACT: u16 = 0x0400;
+const ACC_STRICT: u16 = 0x0800;
+const ACC_SYNTHETIC: u16 = 0x1000;
+const ACC_MANDATED: u16 = 0x8000;
+const ACC_MODULE: u16 = 0x8000;
+
+// Module flags
+const ACC_OPEN: u16 = 0x0020;
+const ACC_TRANSITIVE: u16 = 0x0020;
+const ACC_STATIC_PHASE: u16 = 0x0040;
+
+/// Interface methods other than abstract, static and private ones are
+/// `default` from this version on.
+const DEFAULT_METHODS_MAJOR_VERSION: u16 = 52;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Class,
+    InnerClass,
+    Field,
+    Method,
+}
+
+const CLASS_MODIFIERS: &[u16] = &[ACC_PUBLIC, ACC_FINAL, ACC_ABSTRACT];
+const INNER_CLASS_MODIFIERS: &[u16] = &[
+    ACC_PUBLIC,
+    ACC_PRIVATE,
+    ACC_PROTECTED,
+    ACC_STATIC,
+    ACC_FINAL,
+    ACC_ABSTRACT,
+];
+const FIELD_MODIFIERS: &[u16] = &[
+    ACC_PUBLIC,
+    ACC_PRIVATE,
+    ACC_PROTECTED,
+    ACC_STATIC,
+    ACC_FINAL,
+    ACC_VOLATILE,
+    0x0080,
+];
+const METHOD_MODIFIERS: &[u16] = &[
+    ACC_PUBLIC,
+    ACC_PRIVATE,
+    ACC_PROTECTED,
+    ACC_STATIC,
+    ACC_FINAL,
+    ACC_SYNCHRONIZED,
+    ACC_NATIVE,
+    ACC_ABSTRACT,
+    ACC_STRICT,
+];
+
+const CLASS_FLAGS: &[u16] = &[
+    0x0001, 0x0010, 0x0020, 0x0200, 0x0400, 0x1000, 0x2000, 0x4000, 0x8000,
+];
+const FIELD_FLAGS: &[u16] = &[
+    0x0001, 0x0002, 0x0004, 0x0008, 0x0010, 0x0040, 0x0080, 0x1000, 0x4000,
+];
+const METHOD_FLAGS: &[u16] = &[
+    0x0001, 0x0002, 0x0004, 0x0008, 0x0010, 0x0020, 0x0040, 0x0080, 0x0100, 0x0400, 0x0800, 0x1000,
+];
+
+fn modifier(flag: u16, kind: Kind) -> Option<&'static str> {
+    Some(match flag {
+        ACC_PUBLIC => "public",
+        ACC_PRIVATE => "private",
+        ACC_PROTECTED => "protected",
+        ACC_STATIC => "static",
+        ACC_FINAL => "final",
+        ACC_SYNCHRONIZED => "synchronized",
+        ACC_VOLATILE => "volatile",
+        0x0080 if kind == Kind::Field => "transient",
+        ACC_NATIVE => "native",
+        ACC_ABSTRACT => "abstract",
+        ACC_STRICT => "strictfp",
+        _ => return None,
+    })
+}
+
+fn modifiers(flags: u16, expected: &[u16], kind: Kind) -> Vec<&'static str> {
+    expected
+        .iter()
+        .filter(|&&flag| flags & flag != 0)
+        .filter_map(|&flag| modifier(flag, kind))
+        .collect()
+}
+
+fn flag_name(flag: u16, kind: Kind) -> &'static str {
+    match flag {
+        0x0001 => "ACC_PUBLIC",
+        0x0002 => "ACC_PRIVATE",
+        0x0004 => "ACC_PROTECTED",
+        0x0008 => "ACC_STATIC",
+        0x0010 => "ACC_FINAL",
+        0x0020 if kind == Kind::Class => "ACC_SUPER",
+        0x0020 => "ACC_SYNCHRONIZED",
+        0x0040 if kind == Kind::Field => "ACC_VOLATILE",
+        0x0040 => "ACC_BRIDGE",
+        0x0080 if kind == Kind::Field => "ACC_TRANSIENT",
+        0x0080 => "ACC_VARARGS",
+        0x0100 => "ACC_NATIVE",
+        0x0200 => "ACC_INTERFACE",
+        0x0400 => "ACC_ABSTRACT",
+        0x0800 => "ACC_STRICT",
+        0x1000 => "ACC_SYNTHETIC",
+        0x2000 => "ACC_ANNOTATION",
+        0x4000 => "ACC_ENUM",
+        _ if kind == Kind::Class => "ACC_MODULE",
+        _ => "ACC_MANDATED",
+    }
+}
+
+/// `(0x0021) ACC_PUBLIC, ACC_SUPER`, with unexpected flags in hex.
+fn flags(flags: u16, expected: &[u16], kind: Kind) -> String {
+    let mut names = vec![];
+    let mut rest = flags;
+    for &flag in expected {
+        if rest & flag != 0 {
+            names.push(flag_name(flag, kind).to_string());
+            rest &= !flag;
+        }
+    }
+    while rest != 0 {
+        let bit = 1 << (15 - rest.leading_zeros());
+        names.push(format!("0x{:x}", bit));
+        rest &= !bit;
+    }
+    format!("(0x{:04x}) {}", flags, names.join(", "))
+}
+
+// the kinds of constants in the constant pool listing
+fn tag_name(constant: &Constant) -> &'static str {
+    match constant {
+        Constant::Placeholder => "",
+        Constant::Class { .. } => "Class",
+        Constant::FieldRef { .. } => "Fieldref",
+        Constant::MethodRef { .. } => "Methodref",
+        Constant::InterfaceMethodRef { .. } => "InterfaceMethodref",
+        Constant::String { .. } => "String",
+        Constant::Integer(_) => "Integer",
+        Constant::Float(_) => "Float",
+        Constant::Long(_) => "Long",
+        Constant::Double(_) => "Double",
+        Constant::NameAndType { .. } => "NameAndType",
+        Constant::Utf8(_) => "Utf8",
+        Constant::MethodHandle { .. } => "MethodHandle",
+        Constant::MethodType { .. } => "MethodType",
+        Constant::Dynamic { .. } => "Dynamic",
+        Constant::InvokeDynamic { .. } => "InvokeDynamic",
+        Constant::Module { .. } => "Module",
+        Constant::Package { .. } => "Package",
+    }
+}
+
+fn target_type_name(target_type: u8) -> &'static str {
+    match target_type {
+        0x00 => "CLASS_TYPE_PARAMETER",
+        0x01 => "METHOD_TYPE_PARAMETER",
+        0x10 => "CLASS_EXTENDS",
+        0x11 => "CLASS_TYPE_PARAMETER_BOUND",
+        0x12 => "METHOD_TYPE_PARAMETER_BOUND",
+        0x13 => "FIELD",
+        0x14 => "METHOD_RETURN",
+        0x15 => "METHOD_RECEIVER",
+        0x16 => "METHOD_FORMAL_PARAMETER",
+        0x17 => "THROWS",
+        0x40 => "LOCAL_VARIABLE",
+        0x41 => "RESOURCE_VARIABLE",
+        0x42 => "EXCEPTION_PARAMETER",
+        0x43 => "INSTANCEOF",
+        0x44 => "NEW",
+        0x45 => "CONSTRUCTOR_REFERENCE",
+        0x46 => "METHOD_REFERENCE",
+        0x47 => "CAST",
+        0x48 => "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT",
+        0x49 => "METHOD_INVOCATION_TYPE_ARGUMENT",
+        0x4a => "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT",
+        0x4b => "METHOD_REFERENCE_TYPE_ARGUMENT",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Writes a class file the way `javap` does with the same options.
+pub struct ClassPrinter<'a> {
+    class_file: &'a ClassFile<'a>,
+    options: &'a Options,
+    out: LineWriter,
+}
+
+impl<'a> ClassPrinter<'a> {
+    pub fn new(class_file: &'a ClassFile<'a>, options: &'a Options) -> Self {
+        ClassPrinter {
+            class_file,
+            options,
+            out: LineWriter::default(),
+        }
+    }
+
+    fn utf8(&self, index: u16) -> String {
+        get_str(self.class_file.constant_pool.clone(), index as usize)
+            .unwrap_or_else(|_| format!("#{}", index))
+    }
+
+    fn value(&self, index: u16) -> String {
+        constant_value(self.class_file, index as usize)
+    }
+
+    fn describe(&self, index: u16) -> String {
+        describe_constant(self.class_file, index as usize)
+    }
+
+    fn is_interface(&self) -> bool {
+        self.class_file.access_flags & ACC_INTERFACE != 0
+    }
+
+    fn class_name(&self) -> String {
+        java_name(&self.utf8(self.class_file.this_class))
+    }
+
+    fn signature(&self, attributes: &[Attribute]) -> Option<String> {
+        attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute_type {
+                AttributeType::Signature { signature_index } => Some(self.utf8(*signature_index)),
+                _ => None,
+            })
+    }
+
+    pub fn write(mut self, location: Option<&str>) -> String {
+        let class_file = self.class_file;
+        let verbose = self.options.verbose;
+        if verbose {
+            if let Some(location) = location {
+                self.out.println(format!("Classfile {}", location));
+            }
+            self.out.indent();
+        }
+        for attribute in &class_file.attributes {
+            if let AttributeType::SourceFile { sourcefile_index } = attribute.attribute_type {
+                let source_file = self.utf8(sourcefile_index);
+                self.out
+                    .println(format!("Compiled from \"{}\"", source_file));
+            }
+        }
+        if verbose {
+            self.out.dedent();
+        }
+
+        let access_flags = class_file.access_flags;
+        let modifier_flags = match self.is_interface() {
+            true => access_flags & !ACC_ABSTRACT,
+            false => access_flags,
+        };
+        for modifier in modifiers(modifier_flags, CLASS_MODIFIERS, Kind::Class) {
+            self.out.print(format!("{} ", modifier));
+        }
+        if access_flags & ACC_MODULE != 0 {
+            self.module_header();
+        } else {
+            match self.is_interface() {
+                true => self.out.print("interface "),
+                false => self.out.print("class "),
+            }
+            self.out.print(self.class_name());
+        }
+
+        match self
+            .signature(&class_file.attributes)
+            .map(|signature| ClassSignature::parse(&signature))
+        {
+            Some(Ok(signature)) => {
+                let s = class_signature(&signature, self.is_interface(), verbose);
+                self.out.print(s);
+            }
+            _ => {
+                if !self.is_interface() && class_file.super_class != 0 {
+                    let super_class = java_name(&self.utf8(class_file.super_class));
+                    if super_class != "java.lang.Object" {
+                        self.out.print(format!(" extends {}", super_class));
+                    }
+                }
+                for (i, interface) in class_file.interfaces.iter().enumerate() {
+                    let separator = match (i, self.is_interface()) {
+                        (0, false) => " implements ",
+                        (0, true) => " extends ",
+                        _ => ",",
+                    };
+                    let name = java_name(&self.utf8(*interface));
+                    self.out.print(format!("{}{}", separator, name));
+                }
+            }
+        }
+
+        if verbose {
+            self.out.newline();
+            self.out.indent();
+            self.out
+                .println(format!("minor version: {}", class_file.minor_version));
+            self.out
+                .println(format!("major version: {}", class_file.major_version));
+            self.out.println(format!(
+                "flags: {}",
+                flags(access_flags, CLASS_FLAGS, Kind::Class)
+            ));
+            for (label, index) in [
+                ("this_class", class_file.this_class),
+                ("super_class", class_file.super_class),
+            ] {
+                self.out.print(format!("{}: #{}", label, index));
+                if index != 0 {
+                    self.out.tab();
+                    self.out.print(format!("// {}", self.value(index)));
+                }
+                self.out.newline();
+            }
+            self.out.println(format!(
+                "interfaces: {}, fields: {}, methods: {}, attributes: {}",
+                class_file.interfaces.len(),
+                class_file.fields.len(),
+                class_file.methods.len(),
+                class_file.attributes.len()
+            ));
+            self.out.dedent();
+            self.constant_pool();
+        } else {
+            self.out.print(" ");
+        }
+
+        self.out.println("{");
+        self.out.indent();
+        if access_flags & ACC_MODULE != 0 && !verbose {
+            self.module_directives();
+        }
+        for field in &class_file.fields {
+            self.field(field);
+        }
+        for method in &class_file.methods {
+            self.method(method);
+        }
+        self.out.set_pending_newline(false);
+        self.out.dedent();
+        self.out.println("}");
+
+        if verbose {
+            for attribute in &class_file.attributes {
+                self.attribute(attribute, None);
+            }
+        }
+        self.out.finish()
+    }
+
+    fn module_header(&mut self) {
+        let module = self.class_file.attributes.iter().find_map(|attribute| {
+            match &attribute.attribute_type {
+                AttributeType::Module {
+                    module_name_index,
+                    module_flags,
+                    module_version_index,
+                    ..
+                } => Some((*module_name_index, *module_flags, *module_version_index)),
+                _ => None,
+            }
+        });
+        match module {
+            Some((name_index, module_flags, version_index)) => {
+                if module_flags & ACC_OPEN != 0 {
+                    self.out.print("open ");
+                }
+                self.out.print("module ");
+                self.out.print(java_name(&self.utf8(name_index)));
+                if version_index != 0 {
+                    self.out.print(format!("@{}", self.utf8(version_index)));
+                }
+            }
+            None => {
+                self.out.print("class ");
+                self.out.print(self.class_name());
+            }
+        }
+    }
+
+    fn module_directives(&mut self) {
+        let attribute = self
+            .class_file
+            .attributes
+            .iter()
+            .find(|attribute| matches!(attribute.attribute_type, AttributeType::Module { .. }));
+        let Some(AttributeType::Module {
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+            ..
+        }) = attribute.map(|attribute| &attribute.attribute_type)
+        else {
+            return;
+        };
+        for require in requires {
+            self.out.print("requires");
+            if require.require_flags & ACC_STATIC_PHASE != 0 {
+                self.out.print(" static");
+            }
+            if require.require_flags & ACC_TRANSITIVE != 0 {
+                self.out.print(" transitive");
+            }
+            self.out.println(format!(
+                " {};",
+                java_name(&self.utf8(require.require_index))
+            ));
+        }
+        for export in exports {
+            self.package_directive("exports", export.export_index, &export.export_to_index);
+        }
+        for open in opens {
+            self.package_directive("opens", open.open_index, &open.open_to_index);
+        }
+        for &index in uses {
+            self.out
+                .println(format!("uses {};", java_name(&self.utf8(index))));
+        }
+        for provide in provides {
+            self.out.println(format!(
+                "provides  {} with",
+                java_name(&self.utf8(provide.provide_index))
+            ));
+            self.out.indent();
+            self.names(&provide.provide_with_index);
+            self.out.dedent();
+        }
+    }
+
+    fn package_directive(&mut self, directive: &str, package: u16, to: &[u16]) {
+        self.out
+            .print(format!("{} {}", directive, java_name(&self.utf8(package))));
+        if to.is_empty() {
+            self.out.println(";");
+            return;
+        }
+        self.out.println(" to");
+        self.out.indent();
+        self.names(to);
+        self.out.dedent();
+    }
+
+    // one name per line, separated by commas and ending in a semicolon
+    fn names(&mut self, indices: &[u16]) {
+        for (i, &index) in indices.iter().enumerate() {
+            let separator = match i == indices.len() - 1 {
+                true => ";",
+                false => ",",
+            };
+            self.out
+                .println(format!("{}{}", java_name(&self.utf8(index)), separator));
+        }
+    }
+
+    fn constant_pool(&mut self) {
+        let cp = self.class_file.constant_pool.clone();
+        self.out.println("Constant pool:");
+        self.out.indent();
+        let width = cp.len().to_string().len() + 1;
+        for (index, constant) in cp.iter().enumerate() {
+            if let Constant::Placeholder = constant {
+                continue;
+            }
+            self.out.print(format!(
+                "{:>width$} = {:<18} ",
+                format!("#{}", index),
+                tag_name(constant),
+                width = width
+            ));
+            let operands = match constant {
+                Constant::Class { name_index: index }
+                | Constant::String {
+                    string_index: index,
+                }
+                | Constant::MethodType {
+                    descriptor_index: index,
+                }
+                | Constant::Module { name_index: index }
+                | Constant::Package { name_index: index } => Some(format!("#{}", index)),
+                Constant::FieldRef {
+                    class_index,
+                    name_and_type_index,
+                }
+                | Constant::MethodRef {
+                    class_index,
+                    name_and_type_index,
+                }
+                | Constant::InterfaceMethodRef {
+                    class_index,
+                    name_and_type_index,
+                } => Some(format!("#{}.#{}", class_index, name_and_type_index)),
+                Constant::NameAndType {
+                    name_index,
+                    descriptor_index,
+                } => Some(format!("#{}:#{}", name_index, descriptor_index)),
+                Constant::MethodHandle {
+                    reference_kind,
+                    reference_index,
+                } => Some(format!("{}:#{}", reference_kind, reference_index)),
+                Constant::Dynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                }
+                | Constant::InvokeDynamic {
+                    bootstrap_method_attr_index,
+                    name_and_type_index,
+                } => Some(format!(
+                    "#{}:#{}",
+                    bootstrap_method_attr_index, name_and_type_index
+                )),
+                _ => None,
+            };
+            let value = constant_value(self.class_file, index);
+            match operands {
+                Some(operands) => {
+                    self.out.print(operands);
+                    self.out.tab();
+                    match constant {
+                        Constant::MethodType { .. } => self.out.println(format!("//  {}", value)),
+                        _ => self.out.println(format!("// {}", value)),
+                    }
+                }
+                None => self.out.println(value),
+            }
+        }
+        self.out.dedent();
+    }
+
+    fn field(&mut self, field: &FieldInfo) {
+        if !self.options.shows(field.access_flags) {
+            return;
+        }
+        let options = self.options;
+        for modifier in modifiers(field.access_flags, FIELD_MODIFIERS, Kind::Field) {
+            self.out.print(format!("{} ", modifier));
+        }
+        let descriptor = self.utf8(field.descriptor_index);
+        let signature = self
+            .signature(&field.attributes)
+            .and_then(|signature| TypeSignature::parse_field(&signature).ok());
+        let field_type = match signature {
+            Some(signature) => java_name(&signature_type(&signature)),
+            None => FieldType::parse(&descriptor)
+                .map(|descriptor| field_type(&descriptor))
+                .unwrap_or_else(|_| descriptor.clone()),
+        };
+        self.out.print(field_type);
+        self.out.print(" ");
+        self.out.print(self.utf8(field.name_index));
+        if options.constants {
+            for attribute in &field.attributes {
+                if let AttributeType::ConstantValue {
+                    constant_value_index,
+                } = attribute.attribute_type
+                {
+                    let value = self.field_constant(&descriptor, constant_value_index);
+                    self.out.print(format!(" = {}", value));
+                }
+            }
+        }
+        self.out.print(";");
+        self.out.newline();
+
+        self.out.indent();
+        if options.descriptors {
+            self.out.println(format!("descriptor: {}", descriptor));
+        }
+        if options.verbose {
+            self.out.println(format!(
+                "flags: {}",
+                flags(field.access_flags, FIELD_FLAGS, Kind::Field)
+            ));
+            for attribute in &field.attributes {
+                self.attribute(attribute, None);
+            }
+        }
+        self.out.dedent();
+        if options.verbose || options.code || options.lines {
+            self.out.newline();
+        }
+    }
+
+    // the value of a constant field as Java source would write it
+    fn field_constant(&self, descriptor: &str, index: u16) -> String {
+        let escape = |c: char, quote: char| match c {
+            ' '..='~' if c != quote && c != '\\' => c.to_string(),
+            '\u{8}' => "\\b".to_string(),
+            '\n' => "\\n".to_string(),
+            '\t' => "\\t".to_string(),
+            '\u{c}' => "\\f".to_string(),
+            '\r' => "\\r".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\'' => "\\'".to_string(),
+            '"' => "\\\"".to_string(),
+            c => {
+                let mut units = [0; 2];
+                c.encode_utf16(&mut units)
+                    .iter()
+                    .map(|unit| format!("\\u{:04x}", unit))
+                    .collect()
+            }
+        };
+        match self.class_file.constant_pool.get(index as usize) {
+            Some(Constant::Integer(value)) => match descriptor {
+                "C" => {
+                    let c = char::from_u32(*value as u16 as u32).unwrap_or('\u{fffd}');
+                    format!("'{}'", escape(c, '\''))
+                }
+                "Z" => (*value == 1).to_string(),
+                _ => value.to_string(),
+            },
+            Some(Constant::String { string_index }) => {
+                let s: String = self
+                    .utf8(*string_index)
+                    .chars()
+                    .map(|c| escape(c, '"'))
+                    .collect();
+                format!("\"{}\"", s)
+            }
+            Some(_) => self.value(index),
+            None => format!("#{}", index),
+        }
+    }
+
+    fn method(&mut self, method: &MethodInfo) {
+        if !self.options.shows(method.access_flags) {
+            return;
+        }
+        let options = self.options;
+        let access_flags = method.access_flags;
+        let name = self.utf8(method.name_index);
+        let descriptor = self.utf8(method.descriptor_index);
+        let signature = self
+            .signature(&method.attributes)
+            .and_then(|signature| MethodSignature::parse(&signature).ok());
+
+        let mut modifiers = modifiers(access_flags, METHOD_MODIFIERS, Kind::Method);
+        if self.is_interface()
+            && access_flags & ACC_ABSTRACT == 0
+            && name != "<clinit>"
+            && self.class_file.major_version >= DEFAULT_METHODS_MAJOR_VERSION
+            && access_flags & (ACC_STATIC | ACC_PRIVATE) == 0
+        {
+            modifiers.push("default");
+        }
+        for modifier in modifiers {
+            self.out.print(format!("{} ", modifier));
+        }
+
+        let (parameters, return_type) = match &signature {
+            Some(signature) => {
+                if !signature.type_parameters.is_empty() {
+                    let type_parameters =
+                        type_parameters(&signature.type_parameters, options.verbose);
+                    self.out.print(format!("<{}> ", type_parameters));
+                }
+                let parameters = signature
+                    .parameters
+                    .iter()
+                    .map(|parameter| java_name(&signature_type(parameter)))
+                    .collect::<Vec<_>>();
+                let return_type = match &signature.return_type {
+                    Some(return_type) => java_name(&signature_type(return_type)),
+                    None => "void".to_string(),
+                };
+                (parameters, return_type)
+            }
+            None => match MethodDescriptor::parse(&descriptor) {
+                Ok(descriptor) => {
+                    let parameters = descriptor.parameters.iter().map(field_type).collect();
+                    let return_type = match &descriptor.return_type {
+                        Some(return_type) => field_type(return_type),
+                        None => "void".to_string(),
+                    };
+                    (parameters, return_type)
+                }
+                Err(_) => (vec![descriptor.clone()], "?".to_string()),
+            },
+        };
+        let parameters = parameter_types(&parameters, access_flags & ACC_VARARGS != 0);
+        match name.as_str() {
+            "<init>" => self
+                .out
+                .print(format!("{}{}", self.class_name(), parameters)),
+            "<clinit>" => self.out.print("{}"),
+            _ => self
+                .out
+                .print(format!("{} {}{}", return_type, name, parameters)),
+        }
+
+        for attribute in &method.attributes {
+            if let AttributeType::Exceptions {
+                exception_index_table,
+            } = &attribute.attribute_type
+            {
+                self.out.print(" throws ");
+                let throws = match &signature {
+                    Some(signature) if !signature.throws.is_empty() => signature
+                        .throws
+                        .iter()
+                        .map(signature_type)
+                        .collect::<Vec<_>>(),
+                    _ => exception_index_table
+                        .iter()
+                        .map(|&index| java_name(&self.utf8(index)))
+                        .collect(),
+                };
+                self.out.print(throws.join(", "));
+            }
+        }
+        self.out.println(";");
+
+        self.out.indent();
+        if options.descriptors {
+            self.out.println(format!("descriptor: {}", descriptor));
+        }
+        if options.verbose {
+            self.out.println(format!(
+                "flags: {}",
+                flags(access_flags, METHOD_FLAGS, Kind::Method)
+            ));
+            for attribute in &method.attributes {
+                self.attribute(attribute, Some(method));
+            }
+        } else if let Some(code) = method.code_attribute() {
+            if options.code {
+                self.out.println("Code:");
+                self.instructions(code);
+                self.exception_table(code);
+            }
+            if options.lines {
+                for attribute in &code.attributes {
+                    if let AttributeType::LineNumberTable { .. }
+                    | AttributeType::LocalVariableTable { .. } = attribute.attribute_type
+                    {
+                        self.attribute(attribute, Some(method));
+                    }
+                }
+            }
+        }
+        self.out.dedent();
+        self.out.set_pending_newline(
+            options.code || options.verbose || options.descriptors || options.lines,
+        );
+    }
+
+    fn code(&mut self, code: &CodeAttribute, method: Option<&MethodInfo>) {
+        self.out.println("Code:");
+        self.out.indent();
+        let args_size = match method {
+            Some(method) => match MethodDescriptor::parse(&self.utf8(method.descriptor_index)) {
+                Ok(descriptor) => {
+                    let this = (method.access_flags & ACC_STATIC == 0) as usize;
+                    (descriptor.parameters.len() + this).to_string()
+                }
+                Err(e) => e.to_string(),
+            },
+            None => "?".to_string(),
+        };
+        self.out.println(format!(
+            "stack={}, locals={}, args_size={}",
+            code.max_stack, code.max_locals, args_size
+        ));
+        self.instructions(code);
+        self.exception_table(code);
+        for attribute in &code.attributes {
+            self.attribute(attribute, method);
+        }
+        self.out.dedent();
+    }
+
+    fn instructions(&mut self, code: &CodeAttribute) {
+        match disassemble(self.class_file, &code.code) {
+            Ok(instructions) => {
+                for line in instructions.lines() {
+                    self.out.println(line);
+                }
+            }
+            Err(e) => self.out.println(format!("Error: {}", e)),
+        }
+    }
+
+    fn exception_table(&mut self, code: &CodeAttribute) {
+        if code.exception_table.is_empty() {
+            return;
+        }
+        self.out.println("Exception table:");
+        self.out.indent();
+        self.out.println(" from    to  target type");
+        for exception in &code.exception_table {
+            self.out.print(format!(
+                " {:5} {:5} {:5}",
+                exception.start_pc, exception.end_pc, exception.handler_pc
+            ));
+            self.out.print("   ");
+            match exception.catch_type {
+                0 => self.out.println("any"),
+                catch_type => self
+                    .out
+                    .println(format!("Class {}", self.value(catch_type))),
+            }
+        }
+        self.out.dedent();
+    }
+
+    fn attribute(&mut self, attribute: &Attribute, method: Option<&MethodInfo>) {
+        match &attribute.attribute_type {
+            AttributeType::ConstantValue {
+                constant_value_index,
+            } => {
+                self.out.println(format!(
+                    "ConstantValue: {}",
+                    self.describe(*constant_value_index)
+                ));
+            }
+            AttributeType::Code { code } => self.code(code, method),
+            AttributeType::StackMapTable { entries } => self.stack_map_table(entries),
+            AttributeType::Exceptions {
+                exception_index_table,
+            } => {
+                self.out.println("Exceptions:");
+                self.out.indent();
+                let exceptions = exception_index_table
+                    .iter()
+                    .map(|&index| java_name(&self.utf8(index)))
+                    .collect::<Vec<_>>();
+                self.out
+                    .println(format!("throws {}", exceptions.join(", ")));
+                self.out.dedent();
+            }
+            AttributeType::InnerClasses { classes } => {
+                let mut first = true;
+                for class in classes {
+                    let access_flags = class.inner_class_access_flags;
+                    if !self.options.shows(access_flags) {
+                        continue;
+                    }
+                    if first {
+                        self.out.println("InnerClasses:");
+                        self.out.indent();
+                        first = false;
+                    }
+                    let modifier_flags = match access_flags & ACC_INTERFACE != 0 {
+                        true => access_flags & !ACC_ABSTRACT,
+                        false => access_flags,
+                    };
+                    for modifier in
+                        modifiers(modifier_flags, INNER_CLASS_MODIFIERS, Kind::InnerClass)
+                    {
+                        self.out.print(format!("{} ", modifier));
+                    }
+                    if class.inner_name_index != 0 {
+                        self.out.print(format!("#{}= ", class.inner_name_index));
+                    }
+                    self.out.print(format!("#{}", class.inner_class_info_index));
+                    if class.outer_class_info_index != 0 {
+                        self.out
+                            .print(format!(" of #{}", class.outer_class_info_index));
+                    }
+                    self.out.print(";");
+                    self.out.tab();
+                    self.out.print("// ");
+                    if class.inner_name_index != 0 {
+                        self.out
+                            .print(format!("{}=", self.utf8(class.inner_name_index)));
+                    }
+                    self.out.print(self.describe(class.inner_class_info_index));
+                    if class.outer_class_info_index != 0 {
+                        self.out.print(format!(
+                            " of {}",
+                            self.describe(class.outer_class_info_index)
+                        ));
+                    }
+                    self.out.newline();
+                }
+                if !first {
+                    self.out.dedent();
+                }
+            }
+            AttributeType::EnclosingMethod {
+                class_index,
+                method_index,
+            } => {
+                self.out.print(format!(
+                    "EnclosingMethod: #{}.#{}",
+                    class_index, method_index
+                ));
+                self.out.tab();
+                self.out
+                    .print(format!("// {}", java_name(&self.utf8(*class_index))));
+                if let Some(Constant::NameAndType { name_index, .. }) =
+                    self.class_file.constant_pool.get(*method_index as usize)
+                {
+                    self.out.print(format!(".{}", self.utf8(*name_index)));
+                }
+                self.out.newline();
+            }
+            AttributeType::Synthetic => self.out.println("Synthetic: true"),
+            AttributeType::Signature { signature_index } => {
+                self.out.print(format!("Signature: #{}", signature_index));
+                self.out.tab();
+                self.out
+                    .println(format!("// {}", self.utf8(*signature_index)));
+            }
+            AttributeType::SourceFile { sourcefile_index } => {
+                self.out
+                    .println(format!("SourceFile: \"{}\"", self.utf8(*sourcefile_index)));
+            }
+            AttributeType::SourceDebugExtension { debug_extension } => {
+                self.out.println("SourceDebugExtension:");
+                self.out.indent();
+                let text = String::from_utf8_lossy(debug_extension);
+                let mut lines = text.split(['\r', '\n']).collect::<Vec<_>>();
+                while lines.last() == Some(&"") {
+                    lines.pop();
+                }
+                for (i, line) in lines.iter().enumerate() {
+                    if i == 0 || !line.is_empty() {
+                        self.out.println(line);
+                    }
+                }
+                self.out.dedent();
+            }
+            AttributeType::LineNumberTable { line_number_table } => {
+                self.out.println("LineNumberTable:");
+                self.out.indent();
+                for line in line_number_table {
+                    self.out
+                        .println(format!("line {}: {}", line.line_number, line.start_pc));
+                }
+                self.out.dedent();
+            }
+            AttributeType::LocalVariableTable {
+                local_variable_table,
+            } => {
+                let variables = local_variable_table
+                    .iter()
+                    .map(|v| {
+                        (
+                            v.start_pc,
+                            v.length,
+                            v.index,
+                            v.name_index,
+                            v.descriptor_index,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                self.local_variables("LocalVariableTable:", &variables);
+            }
+            AttributeType::LocalVariableTypeTable {
+                local_variable_type_table,
+            } => {
+                let variables = local_variable_type_table
+                    .iter()
+                    .map(|v| {
+                        (
+                            v.start_pc,
+                            v.length,
+                            v.index,
+                            v.name_index,
+                            v.signature_index,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                self.local_variables("LocalVariableTypeTable:", &variables);
+            }
+            AttributeType::Deprecated => self.out.println("Deprecated: true"),
+            AttributeType::RuntimeVisibleAnnotations { annotations } => {
+                self.annotations("RuntimeVisibleAnnotations:", annotations)
+            }
+            AttributeType::RuntimeInvisibleAnnotations { annotations } => {
+                self.annotations("RuntimeInvisibleAnnotations:", annotations)
+            }
+            AttributeType::RuntimeVisibleParameterAnnotations {
+                parameter_annotations,
+            } => {
+                let parameters = parameter_annotations
+                    .iter()
+                    .map(|parameter| &parameter.annotations[..])
+                    .collect::<Vec<_>>();
+                self.parameter_annotations("RuntimeVisibleParameterAnnotations:", &parameters)
+            }
+            AttributeType::RuntimeInvisibleParameterAnnotations {
+                parameter_annotations,
+            } => {
+                let parameters = parameter_annotations
+                    .iter()
+                    .map(|parameter| &parameter.annotations[..])
+                    .collect::<Vec<_>>();
+                self.parameter_annotations("RuntimeInvisibleParameterAnnotations:", &parameters)
+            }
+            AttributeType::RuntimeVisibleTypeAnnotations { annotations } => {
+                self.type_annotations("RuntimeVisibleTypeAnnotations:", annotations)
+            }
+            AttributeType::RuntimeInvisibleTypeAnnotations { annotations } => {
+                self.type_annotations("RuntimeInvisibleTypeAnnotations:", annotations)
+            }
+            AttributeType::AnnotationDefault { default_value } => {
+                self.out.println("AnnotationDefault:");
+                self.out.indent();
+                self.out.print("default_value: ");
+                self.element_value(default_value, false);
+                self.out.newline();
+                self.out.indent();
+                self.element_value(default_value, true);
+                self.out.dedent();
+                self.out.dedent();
+                self.out.newline();
+            }
+            AttributeType::BootstrapMethods { bootstrap_methods } => {
+                self.out.println("BootstrapMethods:");
+                for (i, method) in bootstrap_methods.iter().enumerate() {
+                    self.out.indent();
+                    self.out
+                        .print(format!("{}: #{} ", i, method.bootstrap_method_ref));
+                    self.out.println(self.value(method.bootstrap_method_ref));
+                    self.out.indent();
+                    self.out.println("Method arguments:");
+                    self.out.indent();
+                    for &argument in &method.bootstrap_arguments {
+                        self.out
+                            .println(format!("#{} {}", argument, self.value(argument)));
+                    }
+                    self.out.dedent();
+                    self.out.dedent();
+                    self.out.dedent();
+                }
+            }
+            AttributeType::MethodParameters { parameters } => {
+                self.out.println("MethodParameters:");
+                self.out.indent();
+                self.out.println(format!("{:<31}{}", "Name", "Flags"));
+                for parameter in parameters {
+                    let name = match parameter.name_index {
+                        0 => "<no name>".to_string(),
+                        index => self.value(index),
+                    };
+                    let flags = parameter.access_flags;
+                    let flags = [
+                        (ACC_FINAL, "final "),
+                        (ACC_MANDATED, "mandated "),
+                        (ACC_SYNTHETIC, "synthetic"),
+                    ]
+                    .iter()
+                    .filter(|(flag, _)| flags & flag != 0)
+                    .map(|(_, name)| *name)
+                    .collect::<String>();
+                    self.out.println(format!("{:<31}{}", name, flags));
+                }
+                self.out.dedent();
+            }
+            AttributeType::Module {
+                module_name_index,
+                module_flags,
+                module_version_index,
+                requires,
+                exports,
+                opens,
+                uses,
+                provides,
+            } => {
+                self.out.println("Module:");
+                self.out.indent();
+                self.out
+                    .print(format!("#{},{:x}", module_name_index, module_flags));
+                self.out.tab();
+                self.out
+                    .print(format!("// {}", self.value(*module_name_index)));
+                self.module_flags(
+                    *module_flags,
+                    &[
+                        (ACC_OPEN, "ACC_OPEN"),
+                        (ACC_MANDATED, "ACC_MANDATED"),
+                        (ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+                    ],
+                );
+                self.out.newline();
+                self.optional_constant(*module_version_index);
+
+                self.table_header(requires.len(), "requires");
+                for require in requires {
+                    self.out.print(format!(
+                        "#{},{:x}",
+                        require.require_index, require.require_flags
+                    ));
+                    self.out.tab();
+                    self.out
+                        .print(format!("// {}", self.value(require.require_index)));
+                    self.module_flags(
+                        require.require_flags,
+                        &[
+                            (ACC_TRANSITIVE, "ACC_TRANSITIVE"),
+                            (ACC_STATIC_PHASE, "ACC_STATIC_PHASE"),
+                            (ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+                            (ACC_MANDATED, "ACC_MANDATED"),
+                        ],
+                    );
+                    self.out.newline();
+                    self.optional_constant(require.require_version_index);
+                }
+                self.out.dedent();
+
+                self.table_header(exports.len(), "exports");
+                for export in exports {
+                    self.export_or_open(
+                        export.export_index,
+                        export.export_flags,
+                        &export.export_to_index,
+                    );
+                }
+                self.out.dedent();
+
+                self.table_header(opens.len(), "opens");
+                for open in opens {
+                    self.export_or_open(open.open_index, open.open_flags, &open.open_to_index);
+                }
+                self.out.dedent();
+
+                self.table_header(uses.len(), "uses");
+                for &index in uses {
+                    self.out.print(format!("#{}", index));
+                    self.out.tab();
+                    self.out.println(format!("// {}", self.value(index)));
+                }
+                self.out.dedent();
+
+                self.table_header(provides.len(), "provides");
+                for provide in provides {
+                    self.out.print(format!("#{}", provide.provide_index));
+                    self.out.tab();
+                    self.out
+                        .print(format!("// {}", self.value(provide.provide_index)));
+                    self.out
+                        .println(format!(" with ... {}", provide.provide_with_index.len()));
+                    self.out.indent();
+                    for &index in &provide.provide_with_index {
+                        self.out.print(format!("#{}", index));
+                        self.out.tab();
+                        self.out
+                            .println(format!("// ... with {}", self.value(index)));
+                    }
+                    self.out.dedent();
+                }
+                self.out.dedent();
+                self.out.dedent();
+            }
+            AttributeType::ModulePackages { package_index } => {
+                self.out.println("ModulePackages: ");
+                self.out.indent();
+                for &index in package_index {
+                    self.out.print(format!("#{}", index));
+                    self.out.tab();
+                    self.out
+                        .println(format!("// {}", java_name(&self.utf8(index))));
+                }
+                self.out.dedent();
+            }
+            AttributeType::ModuleMainClass { main_class_index } => {
+                self.out
+                    .print(format!("ModuleMainClass: #{}", main_class_index));
+                self.out.tab();
+                self.out
+                    .println(format!("// {}", java_name(&self.utf8(*main_class_index))));
+            }
+            AttributeType::NestHost { host_class_index } => {
+                self.out
+                    .println(format!("NestHost: {}", self.describe(*host_class_index)));
+            }
+            AttributeType::NestMembers { classes } => self.class_list("NestMembers:", classes),
+            AttributeType::PermittedSubclasses { classes } => {
+                self.class_list("PermittedSubclasses:", classes)
+            }
+            AttributeType::Record { components } => {
+                self.out.println("Record:");
+                self.out.indent();
+                for component in components {
+                    let descriptor = self.utf8(component.descriptor_index);
+                    let signature = self
+                        .signature(&component.attributes)
+                        .and_then(|signature| TypeSignature::parse_field(&signature).ok());
+                    let component_type = match signature {
+                        Some(signature) => java_name(&signature_type(&signature)),
+                        None => FieldType::parse(&descriptor)
+                            .map(|descriptor| field_type(&descriptor))
+                            .unwrap_or_else(|_| descriptor.clone()),
+                    };
+                    self.out.print(format!(
+                        "{} {};",
+                        component_type,
+                        self.utf8(component.name_index)
+                    ));
+                    self.out.newline();
+                    self.out.indent();
+                    if self.options.descriptors {
+                        self.out.println(format!("descriptor: {}", descriptor));
+                    }
+                    if self.options.verbose {
+                        for attribute in &component.attributes {
+                            self.attribute(attribute, None);
+                        }
+                        self.out.newline();
+                    }
+                    self.out.dedent();
+                }
+                self.out.dedent();
+            }
+            AttributeType::Lazy { data } => {
+                match attribute.decode(self.class_file.constant_pool.clone()) {
+                    Ok(attribute) => self.attribute(&attribute, method),
+                    Err(_) => self.attribute(
+                        &Attribute {
+                            attribute_name_index: attribute.attribute_name_index,
+                            attribute_length: attribute.attribute_length,
+                            attribute_type: AttributeType::Unknown { data: data.clone() },
+                        },
+                        method,
+                    ),
+                }
+            }
+            AttributeType::Unknown { data } => {
+                self.out.print("  ");
+                self.out.print(self.utf8(attribute.attribute_name_index));
+                self.out.print(": ");
+                self.out.println(format!("length = 0x{:X}", data.len()));
+                self.out.print("   ");
+                for (i, byte) in data.iter().enumerate() {
+                    self.out.print(format!("{:02X}", byte));
+                    match (i + 1) % 16 {
+                        0 => {
+                            self.out.newline();
+                            self.out.print("   ");
+                        }
+                        _ => self.out.print(" "),
+                    }
+                }
+                self.out.newline();
+            }
+        }
+    }
+
+    fn module_flags(&mut self, flags: u16, names: &[(u16, &str)]) {
+        for (flag, name) in names {
+            if flags & flag != 0 {
+                self.out.print(format!(" {}", name));
+            }
+        }
+    }
+
+    fn optional_constant(&mut self, index: u16) {
+        self.out.print(format!("#{}", index));
+        if index != 0 {
+            self.out.tab();
+            self.out.print(format!("// {}", self.value(index)));
+        }
+        self.out.newline();
+    }
+
+    // leaves the table indented
+    fn table_header(&mut self, length: usize, name: &str) {
+        self.out.print(length.to_string());
+        self.out.tab();
+        self.out.println(format!("// {}", name));
+        self.out.indent();
+    }
+
+    fn export_or_open(&mut self, index: u16, flags: u16, to: &[u16]) {
+        self.out.print(format!("#{},{:x}", index, flags));
+        self.out.tab();
+        self.out.print(format!("// {}", self.value(index)));
+        self.module_flags(
+            flags,
+            &[
+                (ACC_MANDATED, "ACC_MANDATED"),
+                (ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+            ],
+        );
+        if to.is_empty() {
+            self.out.newline();
+            return;
+        }
+        self.out.println(format!(" to ... {}", to.len()));
+        self.out.indent();
+        for &index in to {
+            self.out.print(format!("#{}", index));
+            self.out.tab();
+            self.out.println(format!("// ... to {}", self.value(index)));
+        }
+        self.out.dedent();
+    }
+
+    fn class_list(&mut self, header: &str, classes: &[u16]) {
+        self.out.println(header);
+        self.out.indent();
+        for &class in classes {
+            self.out.println(self.value(class));
+        }
+        self.out.dedent();
+    }
+
+    fn local_variables(&mut self, header: &str, variables: &[(u16, u16, u16, u16, u16)]) {
+        self.out.println(header);
+        self.out.indent();
+        self.out.println("Start  Length  Slot  Name   Signature");
+        for &(start_pc, length, index, name_index, signature_index) in variables {
+            self.out.println(format!(
+                "{:5} {:7} {:5} {:>5}   {}",
+                start_pc,
+                length,
+                index,
+                self.value(name_index),
+                self.value(signature_index)
+            ));
+        }
+        self.out.dedent();
+    }
+
+    fn stack_map_table(&mut self, entries: &[StackMap]) {
+        self.out.println(format!(
+            "StackMapTable: number_of_entries = {}",
+            entries.len()
+        ));
+        self.out.indent();
+        for entry in entries {
+            let header = |kind: &str| format!("frame_type = {} /* {} */", entry.frame_type, kind);
+            match &entry.frame {
+                StackMapFrame::SameFrame => self.out.println(header("same")),
+                StackMapFrame::SameLocals1StackItemFrame { stack } => {
+                    self.out.println(header("same_locals_1_stack_item"));
+                    self.out.indent();
+                    self.verification_types("stack", std::slice::from_ref(stack));
+                    self.out.dedent();
+                }
+                StackMapFrame::SameLocals1StackItemFrameExtended {
+                    offset_delta,
+                    stack,
+                } => {
+                    self.out
+                        .println(header("same_locals_1_stack_item_frame_extended"));
+                    self.out.indent();
+                    self.out.println(format!("offset_delta = {}", offset_delta));
+                    self.verification_types("stack", std::slice::from_ref(stack));
+                    self.out.dedent();
+                }
+                StackMapFrame::ChopFrame { offset_delta } => {
+                    self.out.println(header("chop"));
+                    self.out.indent();
+                    self.out.println(format!("offset_delta = {}", offset_delta));
+                    self.out.dedent();
+                }
+                StackMapFrame::SameFrameExtended { offset_delta } => {
+                    self.out.println(header("same_frame_extended"));
+                    self.out.indent();
+                    self.out.println(format!("offset_delta = {}", offset_delta));
+                    self.out.dedent();
+                }
+                StackMapFrame::AppendFrame {
+                    offset_delta,
+                    locals,
+                } => {
+                    self.out.println(header("append"));
+                    self.out.indent();
+                    self.out.println(format!("offset_delta = {}", offset_delta));
+                    self.verification_types("locals", locals);
+                    self.out.dedent();
+                }
+                StackMapFrame::FullFrame {
+                    offset_delta,
+                    locals,
+                    stack,
+                } => {
+                    self.out.println(header("full_frame"));
+                    self.out.indent();
+                    self.out.println(format!("offset_delta = {}", offset_delta));
+                    self.verification_types("locals", locals);
+                    self.verification_types("stack", stack);
+                    self.out.dedent();
+                }
+            }
+        }
+        self.out.dedent();
+    }
+
+    fn verification_types(&mut self, name: &str, types: &[VerificationTypeInfo]) {
+        self.out.print(format!("{} = [", name));
+        for (i, info) in types.iter().enumerate() {
+            let s = match info {
+                VerificationTypeInfo::Top => "top".to_string(),
+                VerificationTypeInfo::Integer => "int".to_string(),
+                VerificationTypeInfo::Float => "float".to_string(),
+                VerificationTypeInfo::Long => "long".to_string(),
+                VerificationTypeInfo::Double => "double".to_string(),
+                VerificationTypeInfo::Null => "null".to_string(),
+                VerificationTypeInfo::UninitializedThis => "this".to_string(),
+                VerificationTypeInfo::Object { cpool_index } => self.describe(*cpool_index),
+                VerificationTypeInfo::Uninitialized { offset } => {
+                    format!("uninitialized {}", offset)
+                }
+            };
+            self.out.print(format!(" {}", s));
+            match i == types.len() - 1 {
+                true => self.out.print(" "),
+                false => self.out.print(","),
+            }
+        }
+        self.out.println("]");
+    }
+
+    fn annotations(&mut self, header: &str, annotations: &[Annotation]) {
+        self.out.println(header);
+        self.out.indent();
+        for (i, annotation) in annotations.iter().enumerate() {
+            let pairs = annotation
+                .element_value_pairs
+                .iter()
+                .map(|pair| (pair.element_name_index, &pair.value))
+                .collect::<Vec<_>>();
+            self.out.print(format!("{}: ", i));
+            self.annotation(annotation.type_index, &pairs, false);
+            self.out.newline();
+            self.out.indent();
+            self.annotation(annotation.type_index, &pairs, true);
+            self.out.newline();
+            self.out.dedent();
+        }
+        self.out.dedent();
+    }
+
+    fn parameter_annotations(&mut self, header: &str, parameters: &[&[Annotation]]) {
+        self.out.println(header);
+        self.out.indent();
+        for (parameter, annotations) in parameters.iter().enumerate() {
+            self.out.println(format!("parameter {}: ", parameter));
+            self.out.indent();
+            for (i, annotation) in annotations.iter().enumerate() {
+                let pairs = annotation
+                    .element_value_pairs
+                    .iter()
+                    .map(|pair| (pair.element_name_index, &pair.value))
+                    .collect::<Vec<_>>();
+                self.out.print(format!("{}: ", i));
+                self.annotation(annotation.type_index, &pairs, false);
+                self.out.newline();
+                self.out.indent();
+                self.annotation(annotation.type_index, &pairs, true);
+                self.out.newline();
+                self.out.dedent();
+            }
+            self.out.dedent();
+        }
+        self.out.dedent();
+    }
+
+    fn type_annotations(&mut self, header: &str, annotations: &[TypeAnnotation]) {
+        self.out.println(header);
+        self.out.indent();
+        for (i, annotation) in annotations.iter().enumerate() {
+            self.out.print(format!("{}: ", i));
+            self.type_annotation(annotation, false);
+            self.out.newline();
+            self.out.indent();
+            self.type_annotation(annotation, true);
+            self.out.newline();
+            self.out.dedent();
+        }
+        self.out.dedent();
+    }
+
+    fn type_annotation(&mut self, annotation: &TypeAnnotation, resolve: bool) {
+        let pairs = annotation
+            .element_value_pairs
+            .iter()
+            .map(|(name, value)| (*name, value))
+            .collect::<Vec<_>>();
+        self.annotation(annotation.type_index, &pairs, resolve);
+        if resolve {
+            return;
+        }
+        self.out.print(": ");
+        self.out.print(target_type_name(annotation.target_type));
+        let position = match &annotation.target_info {
+            TargetInfo::TypeParameterTarget {
+                type_parameter_index,
+            } => format!(", param_index={}", type_parameter_index),
+            TargetInfo::SupertypeTarget { supertype_index } => {
+                format!(", type_index={}", supertype_index)
+            }
+            TargetInfo::TypeParameterBoundTarget {
+                type_parameter_index,
+                bound_index,
+            } => format!(
+                ", param_index={}, bound_index={}",
+                type_parameter_index, bound_index
+            ),
+            TargetInfo::EmptyTarget => String::new(),
+            TargetInfo::FormalParameterTarget {
+                formal_parameter_index,
+            } => format!(", param_index={}", formal_parameter_index),
+            TargetInfo::ThrowTarget { throws_type_index } => {
+                format!(", type_index={}", throws_type_index)
+            }
+            TargetInfo::LocalVarTarget(table) => {
+                let entries = table
+                    .iter()
+                    .map(|entry| {
+                        format!(
+                            "start_pc={}, length={}, index={}",
+                            entry.start_pc, entry.length, entry.index
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                format!(", {{{}}}", entries.join("; "))
+            }
+            TargetInfo::CatchTarget {
+                exception_table_index,
+            } => format!(", exception_index={}", exception_table_index),
+            TargetInfo::OffsetTarget { offset } => format!(", offset={}", offset),
+            TargetInfo::TypeArgumentTarget {
+                offset,
+                type_argument_index,
+            } => format!(", offset={}, type_index={}", offset, type_argument_index),
+        };
+        self.out.print(position);
+        if !annotation.type_path.path.is_empty() {
+            let location = annotation
+                .type_path
+                .path
+                .iter()
+                .map(|entry| match entry.type_path_kind {
+                    0 => "ARRAY".to_string(),
+                    1 => "INNER_TYPE".to_string(),
+                    2 => "WILDCARD".to_string(),
+                    _ => format!("TYPE_ARGUMENT({})", entry.type_argument_index),
+                })
+                .collect::<Vec<_>>();
+            self.out
+                .print(format!(", location=[{}]", location.join(", ")));
+        }
+    }
+
+    /// `#12(#13=e#14.#15)`, or with `resolve` the annotation with its
+    /// elements on separate lines.
+    fn annotation(&mut self, type_index: u16, pairs: &[(u16, &ElementValue)], resolve: bool) {
+        let type_name = match FieldType::parse(&self.utf8(type_index)) {
+            Ok(descriptor) if resolve => field_type(&descriptor),
+            _ => format!("#{}", type_index),
+        };
+        self.out.print(type_name);
+        if resolve {
+            if !pairs.is_empty() {
+                self.out.println("(");
+                self.out.indent();
+            }
+            for (name, value) in pairs {
+                self.out.print(format!("{}=", self.value(*name)));
+                self.element_value(value, true);
+                self.out.newline();
+            }
+            if !pairs.is_empty() {
+                self.out.dedent();
+                self.out.print(")");
+            }
+        } else {
+            self.out.print("(");
+            for (i, (name, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    self.out.print(",");
+                }
+                self.out.print(format!("#{}=", name));
+                self.element_value(value, false);
+            }
+            self.out.print(")");
+        }
+    }
+
+    fn element_value(&mut self, element_value: &ElementValue, resolve: bool) {
+        let tag = element_value.tag as char;
+        match &element_value.value {
+            Element::ConstValueIndex(index) if resolve => {
+                let index = *index;
+                let value = match tag {
+                    'B' => format!("(byte) {}", self.value(index)),
+                    'C' => match self.class_file.constant_pool.get(index as usize) {
+                        Some(Constant::Integer(value)) => {
+                            let c = char::from_u32(*value as u16 as u32).unwrap_or('\u{fffd}');
+                            format!("'{}'", c)
+                        }
+                        _ => format!("'#{}'", index),
+                    },
+                    'S' => format!("(short) {}", self.value(index)),
+                    'Z' => match self.class_file.constant_pool.get(index as usize) {
+                        Some(Constant::Integer(0)) => "false".to_string(),
+                        Some(Constant::Integer(1)) => "true".to_string(),
+                        _ => format!("#{}", index),
+                    },
+                    's' => format!("\"{}\"", self.value(index)),
+                    'D' | 'F' | 'I' | 'J' => self.value(index),
+                    _ => format!("{}#{}", tag, index),
+                };
+                self.out.print(value);
+            }
+            Element::ConstValueIndex(index) => self.out.print(format!("{}#{}", tag, index)),
+            Element::EnumConstValue {
+                type_name_index,
+                const_name_index,
+            } => match resolve {
+                true => self.out.print(format!(
+                    "{}.{}",
+                    self.value(*type_name_index),
+                    self.value(*const_name_index)
+                )),
+                false => self
+                    .out
+                    .print(format!("{}#{}.#{}", tag, type_name_index, const_name_index)),
+            },
+            Element::ClassInfoIndex(index) => match resolve {
+                true => self.out.print(format!("class {}", self.value(*index))),
+                false => self.out.print(format!("{}#{}", tag, index)),
+            },
+            Element::AnnotationValue(annotation) => {
+                self.out.print(tag.to_string());
+                let pairs = annotation
+                    .element_value_pairs
+                    .iter()
+                    .map(|pair| (pair.element_name_index, &pair.value))
+                    .collect::<Vec<_>>();
+                self.annotation(annotation.type_index, &pairs, resolve);
+            }
+            Element::ArrayValue(values) => {
+                self.out.print("[");
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        self.out.print(",");
+                    }
+                    self.element_value(value, resolve);
+                }
+                self.out.print("]");
+            }
+        }
+    }
+}
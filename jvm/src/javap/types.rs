@@ -0,0 +1,145 @@
+use classfile::descriptor::{
+    ClassSignature, ClassTypeSignature, FieldType, TypeArgument, TypeParameter, TypeSignature,
+};
+
+pub fn java_name(name: &str) -> String {
+    name.replace('/', ".")
+}
+
+/// A field type as Java source spells it, e.g. `java.lang.String[]`.
+pub fn field_type(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Base(base) => base.keyword().to_string(),
+        FieldType::Object(name) => java_name(name),
+        FieldType::Array(component) => format!("{}[]", self::field_type(component)),
+    }
+}
+
+/// A generic type with binary class names, e.g. `java/util/List<? extends T>[]`.
+pub fn signature_type(signature: &TypeSignature) -> String {
+    match signature {
+        TypeSignature::Base(base) => base.keyword().to_string(),
+        TypeSignature::Class(class) => class_type(class),
+        TypeSignature::TypeVariable(name) => name.clone(),
+        TypeSignature::Array(component) => format!("{}[]", signature_type(component)),
+    }
+}
+
+fn class_type(class: &ClassTypeSignature) -> String {
+    let classes = class
+        .classes
+        .iter()
+        .map(|class| match class.type_arguments.is_empty() {
+            true => class.name.clone(),
+            false => format!("{}<{}>", class.name, type_arguments(&class.type_arguments)),
+        })
+        .collect::<Vec<_>>();
+    format!("{}{}", class.package, classes.join("."))
+}
+
+fn type_arguments(arguments: &[TypeArgument]) -> String {
+    arguments
+        .iter()
+        .map(|argument| match argument {
+            TypeArgument::Any => "?".to_string(),
+            TypeArgument::Exact(signature) => signature_type(signature),
+            TypeArgument::Extends(signature) => format!("? extends {}", signature_type(signature)),
+            TypeArgument::Super(signature) => format!("? super {}", signature_type(signature)),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn is_object(signature: &TypeSignature) -> bool {
+    match signature {
+        TypeSignature::Class(class) => is_object_class(class),
+        _ => false,
+    }
+}
+
+fn is_object_class(class: &ClassTypeSignature) -> bool {
+    class.package == "java/lang/"
+        && class.classes.len() == 1
+        && class.classes[0].name == "Object"
+        && class.classes[0].type_arguments.is_empty()
+}
+
+/// `T extends java.lang.Comparable<? super T>, U`, leaving out `Object` bounds
+/// unless `verbose`.
+pub fn type_parameters(parameters: &[TypeParameter], verbose: bool) -> String {
+    let parameters = parameters
+        .iter()
+        .map(|parameter| {
+            let mut s = parameter.name.clone();
+            let mut separator = " extends ";
+            if let Some(bound) = &parameter.class_bound {
+                if verbose || !is_object(bound) {
+                    s.push_str(separator);
+                    s.push_str(&signature_type(bound));
+                    separator = " & ";
+                }
+            }
+            for bound in &parameter.interface_bounds {
+                s.push_str(separator);
+                s.push_str(&signature_type(bound));
+                separator = " & ";
+            }
+            s
+        })
+        .collect::<Vec<_>>();
+    java_name(&parameters.join(", "))
+}
+
+/// What follows the class name in its declaration, e.g.
+/// `<E> extends java.util.AbstractList<E> implements java.util.List<E>`.
+pub fn class_signature(signature: &ClassSignature, is_interface: bool, verbose: bool) -> String {
+    let join = |classes: &[ClassTypeSignature]| {
+        classes
+            .iter()
+            .map(class_type)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut s = String::new();
+    if signature.type_parameters.is_empty() && signature.interfaces.is_empty() {
+        // read as the signature of a single type, as javap does
+        if verbose || !is_object_class(&signature.super_class) {
+            s.push_str(" extends ");
+            s.push_str(&class_type(&signature.super_class));
+        }
+        return java_name(&s);
+    }
+    if !signature.type_parameters.is_empty() {
+        s.push('<');
+        s.push_str(&type_parameters(&signature.type_parameters, verbose));
+        s.push('>');
+    }
+    if is_interface {
+        if !signature.interfaces.is_empty() {
+            s.push_str(" extends ");
+            s.push_str(&join(&signature.interfaces));
+        }
+    } else {
+        if verbose || !is_object_class(&signature.super_class) {
+            s.push_str(" extends ");
+            s.push_str(&class_type(&signature.super_class));
+        }
+        if !signature.interfaces.is_empty() {
+            s.push_str(" implements ");
+            s.push_str(&join(&signature.interfaces));
+        }
+    }
+    java_name(&s)
+}
+
+/// `(int, java.lang.String...)`, with the last array of a varargs method
+/// written as `...`.
+pub fn parameter_types(types: &[String], varargs: bool) -> String {
+    let parameters = format!("({})", types.join(", "));
+    match parameters.rfind("[]") {
+        Some(i) if varargs && i > 0 => {
+            format!("{}...{}", &parameters[..i], &parameters[i + 2..])
+        }
+        _ => parameters,
+    }
+}
@@ -0,0 +1,79 @@
+/// Lines laid out the way `javap` does: spaces are held back until something
+/// follows them on the line, so no line ends in a space, and `tab` moves to
+/// the next comment column.
+#[derive(Default)]
+pub struct LineWriter {
+    out: String,
+    buffer: String,
+    indent: usize,
+    pending_spaces: usize,
+    pending_newline: bool,
+}
+
+const INDENT_WIDTH: usize = 2;
+const TAB_COLUMN: usize = 40;
+
+impl LineWriter {
+    pub fn print(&mut self, s: impl AsRef<str>) {
+        if self.pending_newline {
+            self.newline();
+            self.pending_newline = false;
+        }
+        for c in s.as_ref().chars() {
+            match c {
+                ' ' => self.pending_spaces += 1,
+                '\n' => self.newline(),
+                c => {
+                    if self.buffer.is_empty() {
+                        self.buffer
+                            .push_str(&" ".repeat(self.indent * INDENT_WIDTH));
+                    }
+                    self.buffer.push_str(&" ".repeat(self.pending_spaces));
+                    self.pending_spaces = 0;
+                    self.buffer.push(c);
+                }
+            }
+        }
+    }
+
+    pub fn println(&mut self, s: impl AsRef<str>) {
+        self.print(s);
+        self.newline();
+    }
+
+    pub fn newline(&mut self) {
+        self.pending_spaces = 0;
+        self.out.push_str(&self.buffer);
+        self.out.push('\n');
+        self.buffer.clear();
+    }
+
+    /// Ends the line before anything else is printed, if anything is.
+    pub fn set_pending_newline(&mut self, pending: bool) {
+        self.pending_newline = pending;
+    }
+
+    pub fn tab(&mut self) {
+        let column = self.indent * INDENT_WIDTH + TAB_COLUMN;
+        let length = self.buffer.chars().count();
+        self.pending_spaces += match column <= length {
+            true => 1,
+            false => column - length,
+        };
+    }
+
+    pub fn indent(&mut self) {
+        self.indent += 1;
+    }
+
+    pub fn dedent(&mut self) {
+        self.indent -= 1;
+    }
+
+    pub fn finish(mut self) -> String {
+        if !self.buffer.is_empty() {
+            self.newline();
+        }
+        self.out
+    }
+}
@@ -0,0 +1,259 @@
+//! Dumps class files as JSON, run as `jvm json [options] <classes or jars>`.
+
+use crate::javap::read_class;
+use anyhow::anyhow;
+use classfile::bytecode::constant_value;
+use classfile::{mutf8, ClassFile};
+use serde_json::{json, Map, Value};
+use std::io::{Read, Write};
+use std::path::Path;
+
+const USAGE: &str = "Usage: jvm json <options> <classes or jars>
+where possible options include:
+  --pretty                 Indent the JSON
+  -cp <path>               Specify where to find user class files
+  -classpath <path>        Specify where to find user class files
+  --class-path <path>      Specify where to find user class files
+
+A jar is dumped as an object from its class file names to their JSON.";
+
+// fields and element values holding constant pool indices, resolved inline
+const CONSTANT_INDICES: &[&str] = &[
+    "attribute_name_index",
+    "bootstrap_arguments",
+    "bootstrap_method_ref",
+    "catch_type",
+    "class_index",
+    "classes",
+    "const_name_index",
+    "constant_value_index",
+    "cpool_index",
+    "descriptor_index",
+    "element_name_index",
+    "exception_index_table",
+    "export_index",
+    "export_to_index",
+    "host_class_index",
+    "inner_class_info_index",
+    "inner_name_index",
+    "interfaces",
+    "main_class_index",
+    "method_index",
+    "module_name_index",
+    "module_version_index",
+    "name_and_type_index",
+    "name_index",
+    "open_index",
+    "open_to_index",
+    "outer_class_info_index",
+    "package_index",
+    "provide_index",
+    "provide_with_index",
+    "reference_index",
+    "require_index",
+    "require_version_index",
+    "signature_index",
+    "sourcefile_index",
+    "string_index",
+    "super_class",
+    "this_class",
+    "type_index",
+    "type_name_index",
+    "uses",
+    "ClassInfoIndex",
+    "ConstValueIndex",
+];
+
+/// Runs `json` with the arguments after `json` and returns the exit code.
+pub fn main(args: &[String]) -> i32 {
+    let mut pretty = false;
+    let mut cp = String::new();
+    let mut classes = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pretty" => pretty = true,
+            "-cp" | "-classpath" | "--class-path" => match args.next() {
+                Some(path) => cp = path.clone(),
+                None => {
+                    eprintln!("Error: {} requires an argument", arg);
+                    return 2;
+                }
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Error: invalid flag: {}", arg);
+                eprintln!("{}", USAGE);
+                return 2;
+            }
+            _ => classes.push(arg),
+        }
+    }
+    if classes.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let mut class_path = None;
+    let mut code = 0;
+    let stdout = std::io::stdout();
+    for class in classes {
+        let mut out = stdout.lock();
+        let result = match class.ends_with(".jar") && Path::new(class).is_file() {
+            true => dump_jar(class, pretty, &mut out),
+            false => read_class(class, &cp, &mut class_path)
+                .and_then(|(bytes, _)| dump(&bytes))
+                .and_then(|value| write_value(&value, pretty, &mut out)),
+        };
+        if let Err(e) = result.and_then(|_| Ok(writeln!(out)?)) {
+            eprintln!("Error: {}", e);
+            code = 1;
+        }
+    }
+    code
+}
+
+/// The class file in `bytes` as JSON, with constant pool indices replaced by
+/// `{"index": i, "value": "..."}` and `Utf8` constants decoded.
+pub fn dump(bytes: &[u8]) -> anyhow::Result<Value> {
+    let class_file = classfile::parse(bytes)?;
+    let mut value = serde_json::to_value(&class_file)?;
+    resolve(&class_file, &mut value);
+    Ok(value)
+}
+
+fn dump_jar(path: &str, pretty: bool, out: &mut impl Write) -> anyhow::Result<()> {
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    write!(out, "{{")?;
+    let mut first = true;
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if !file.name().ends_with(".class") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let value = dump(&bytes).map_err(|e| anyhow!("{}: {}", file.name(), e))?;
+        if !first {
+            write!(out, ",")?;
+        }
+        first = false;
+        write!(out, "{}:", Value::from(file.name()))?;
+        write_value(&value, pretty, out)?;
+    }
+    write!(out, "}}")?;
+    Ok(())
+}
+
+fn write_value(value: &Value, pretty: bool, out: &mut impl Write) -> anyhow::Result<()> {
+    match pretty {
+        true => serde_json::to_writer_pretty(out, value)?,
+        false => serde_json::to_writer(out, value)?,
+    }
+    Ok(())
+}
+
+fn resolve(class_file: &ClassFile, value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match (key.as_str(), &mut *value) {
+                    ("Utf8", Value::Array(bytes)) => {
+                        let bytes = bytes
+                            .iter()
+                            .filter_map(Value::as_u64)
+                            .map(|byte| byte as u8)
+                            .collect::<Vec<_>>();
+                        *value = match mutf8::decode(&bytes) {
+                            Ok(s) => Value::from(s),
+                            Err(_) => Value::from(String::from_utf8_lossy(&bytes).into_owned()),
+                        };
+                    }
+                    (key, Value::Number(_)) if CONSTANT_INDICES.contains(&key) => {
+                        *value = constant(class_file, value);
+                    }
+                    (key, Value::Array(indices)) if CONSTANT_INDICES.contains(&key) => {
+                        for index in indices {
+                            match index {
+                                Value::Number(_) => *index = constant(class_file, index),
+                                _ => resolve(class_file, index),
+                            }
+                        }
+                    }
+                    _ => resolve(class_file, value),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                resolve(class_file, value);
+            }
+        }
+        _ => {}
+    }
+}
+
+// index 0 stands for no constant, e.g. the super class of java.lang.Object
+fn constant(class_file: &ClassFile, index: &Value) -> Value {
+    match index.as_u64() {
+        Some(0) => Value::Null,
+        Some(index) => {
+            let mut object = Map::new();
+            object.insert("index".to_string(), json!(index));
+            let value = constant_value(class_file, index as usize);
+            object.insert("value".to_string(), json!(value));
+            Value::Object(object)
+        }
+        None => index.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump() {
+        let bytes = std::fs::read("../data/jvm8/GaussTest.class").unwrap();
+        let value = dump(&bytes).unwrap();
+        assert_eq!(value["major_version"], 52);
+        assert_eq!(
+            value["this_class"],
+            json!({"index": 19, "value": "GaussTest"})
+        );
+        assert_eq!(value["constant_pool"][0], "Placeholder");
+        assert_eq!(
+            value["constant_pool"][4],
+            json!({"Utf8": "java/lang/Object"})
+        );
+        assert_eq!(
+            value["constant_pool"][1]["MethodRef"]["name_and_type_index"]["value"],
+            "\"<init>\":()V"
+        );
+
+        let main = &value["methods"][1];
+        assert_eq!(main["name_index"]["value"], "main");
+        assert_eq!(main["descriptor_index"]["value"], "([Ljava/lang/String;)V");
+        let code = &main["attributes"][0];
+        assert_eq!(code["attribute_name_index"]["value"], "Code");
+        assert_eq!(code["attribute_type"]["Code"]["code"]["max_locals"], 3);
+
+        let source_file = &value["attributes"][0]["attribute_type"]["SourceFile"];
+        assert_eq!(source_file["sourcefile_index"]["value"], "GaussTest.java");
+    }
+
+    #[test]
+    fn test_dump_jar() {
+        let file = std::fs::File::open("../data/jvm8/rt.jar").unwrap();
+        let mut zip = zip::ZipArchive::new(file).unwrap();
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).unwrap();
+            if file.name().starts_with("java/lang/") && file.name().ends_with(".class") {
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes).unwrap();
+                let value = dump(&bytes).unwrap();
+                let name = file.name().trim_end_matches(".class");
+                assert_eq!(value["this_class"]["value"], name);
+            }
+        }
+    }
+}
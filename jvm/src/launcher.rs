@@ -0,0 +1,622 @@
+//! Runs the `main` method of a class, `jvm [options] <main class> [args...]`.
+
+use crate::classpath::ClassPath;
+use crate::interpreter::{self, get_main_method};
+use crate::rtda::{set_current, threads, ClassLoader};
+use anyhow::anyhow;
+use std::panic::{self, AssertUnwindSafe};
+
+const USAGE: &str = "Usage: jvm [options] <main class> [args...]
+           (to execute a class)
+   or  jvm javap <options> <classes>
+   or  jvm json <options> <classes or jars>
+where options include:
+  -cp <path>               Specify where to find user class files
+  -classpath <path>        Specify where to find user class files
+  --class-path <path>      Specify where to find user class files
+  -Xjre <dir>              Specify where to find the JRE class files
+  --enable-preview         Allow classes to depend on preview features
+  -Xtrace                  Print each instruction executed with its frame";
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub class_path: String,
+    pub jre: String,
+    pub enable_preview: bool,
+    pub trace: bool,
+}
+
+impl Options {
+    /// Parses the options in `args` up to the main class, returning them with
+    /// the main class and the arguments after it.
+    pub fn parse(args: &[String]) -> anyhow::Result<(Options, String, Vec<String>)> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-cp" | "-classpath" | "--class-path" => match args.next() {
+                    Some(path) => options.class_path = path.clone(),
+                    None => return Err(anyhow!("{} requires an argument", arg)),
+                },
+                "-Xjre" => match args.next() {
+                    Some(path) => options.jre = path.clone(),
+                    None => return Err(anyhow!("{} requires an argument", arg)),
+                },
+                "--enable-preview" => options.enable_preview = true,
+                "-Xtrace" => options.trace = true,
+                _ if arg.starts_with('-') => return Err(anyhow!("invalid flag: {}", arg)),
+                _ => return Ok((options, arg.clone(), args.cloned().collect())),
+            }
+        }
+        Err(anyhow!("no main class given"))
+    }
+}
+
+/// Runs the main class named in `args` and returns the exit code.
+pub fn main(args: &[String]) -> i32 {
+    let (options, main_class, _args) = match Options::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    interpreter::set_trace(options.trace);
+    let class_path = ClassPath::new(options.jre, options.class_path);
+    // classes are never unloaded, the loader lives as long as the VM
+    let mut class_loader = ClassLoader::new(class_path);
+    class_loader.set_enable_preview(options.enable_preview);
+    let class_loader = Box::leak(Box::new(class_loader));
+    match launch(class_loader, &main_class) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// Runs `main` of `main_class` on the calling host thread, then waits for the
+/// non-daemon threads it started before the VM may exit. Returns `false` if
+/// `main` threw.
+pub fn launch(class_loader: &'static ClassLoader, main_class: &str) -> anyhow::Result<bool> {
+    let class = class_loader
+        .load_class(&main_class.replace('.', "/"))
+        .map_err(|e| anyhow!("Could not find or load main class {}: {}", main_class, e))?;
+    let main = get_main_method(class)
+        .ok_or_else(|| anyhow!("Main method not found in class {}", main_class))?;
+    let thread = threads().attach_current();
+    // there are no arrays or strings yet, so `args` is passed as null
+    let completed = panic::catch_unwind(AssertUnwindSafe(|| interpreter::interpret(main))).is_ok();
+    // like DestroyJavaVM the main thread terminates first, then waits for the others
+    threads().remove(&thread);
+    set_current(None);
+    threads().wait_for_non_daemon_threads();
+    Ok(completed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::classpath::{ClassPath, Entry};
+    use crate::launcher::{launch, Options};
+    use crate::rtda::{safepoint, threads, ClassLoader};
+    use classfile::builder::{ClassBuilder, ACC_PRIVATE, ACC_PUBLIC, ACC_STATIC};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    const ACC_SYNCHRONIZED: u16 = 0x0020;
+    const ACC_NATIVE: u16 = 0x0100;
+
+    // a JRE of the real java.lang.Object, and java.lang.Thread and
+    // java.lang.VirtualThread reduced to their natives and intrinsics
+    fn jre(dir: &Path) {
+        let lang = dir.join("jre").join("lib").join("java").join("lang");
+        std::fs::create_dir_all(&lang).unwrap();
+        let object = ClassPath::new(String::new(), String::new())
+            .read_class("java/lang/Object")
+            .unwrap();
+        std::fs::write(lang.join("Object.class"), object).unwrap();
+        let thread = ClassBuilder::new("java/lang/Thread")
+            .field(ACC_PRIVATE, "daemon", "Z")
+            .default_constructor()
+            .method("setDaemon", "(Z)V", |code| {
+                code.aload(0)
+                    .iload(1)
+                    .putfield("java/lang/Thread", "daemon", "Z")
+                    .return_()
+            })
+            .method("start", "()V", |code| {
+                code.aload(0)
+                    .invokespecial("java/lang/Thread", "start0", "()V")
+                    .return_()
+            })
+            .method("run", "()V", |code| code.return_())
+            .method("interrupt", "()V", |code| {
+                code.aload(0)
+                    .invokespecial("java/lang/Thread", "interrupt0", "()V")
+                    .return_()
+            })
+            .method_with_flags(ACC_PUBLIC | ACC_SYNCHRONIZED, "join", "()V", |code| {
+                let check = code.new_label();
+                let done = code.new_label();
+                code.bind(check)
+                    .aload(0)
+                    .invokevirtual("java/lang/Thread", "isAlive", "()Z")
+                    .ifeq(done)
+                    .aload(0)
+                    .lconst(0)
+                    .invokevirtual("java/lang/Object", "wait", "(J)V")
+                    .goto(check)
+                    .bind(done)
+                    .return_()
+            })
+            .abstract_method(ACC_PRIVATE | ACC_NATIVE, "start0", "()V")
+            .abstract_method(ACC_PRIVATE | ACC_NATIVE, "interrupt0", "()V")
+            .abstract_method(ACC_PUBLIC | ACC_STATIC | ACC_NATIVE, "sleep", "(J)V")
+            .abstract_method(ACC_PUBLIC | ACC_NATIVE, "isAlive", "()Z")
+            .build()
+            .unwrap();
+        std::fs::write(lang.join("Thread.class"), thread).unwrap();
+        let virtual_thread = ClassBuilder::new("java/lang/VirtualThread")
+            .super_class("java/lang/Thread")
+            .default_constructor()
+            // implemented in Java by the class library, replaced by an intrinsic
+            .method("start", "()V", |code| code.return_())
+            .build()
+            .unwrap();
+        std::fs::write(lang.join("VirtualThread.class"), virtual_thread).unwrap();
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("jvm-launcher-{}-{}", std::process::id(), name))
+    }
+
+    fn class_loader(name: &str, classes: Vec<(&str, Vec<u8>)>) -> &'static ClassLoader {
+        let dir = dir(name);
+        jre(&dir);
+        let user = dir.join("classes");
+        std::fs::create_dir_all(&user).unwrap();
+        for (name, bytes) in classes {
+            std::fs::write(user.join(format!("{}.class", name)), bytes).unwrap();
+        }
+        let class_path = ClassPath::new(
+            dir.join("jre").to_str().unwrap().to_string(),
+            user.to_str().unwrap().to_string(),
+        );
+        Box::leak(Box::new(ClassLoader::new(class_path)))
+    }
+
+    fn static_int(class_loader: &ClassLoader, class: &str, name: &str) -> i32 {
+        let class = class_loader.load_class(class).unwrap();
+        let slot_id = class
+            .look_up_field(name, "Z")
+            .unwrap()
+            .read()
+            .unwrap()
+            .slot_id;
+        class.static_vars().get_int(slot_id, true)
+    }
+
+    fn wait_until<F: Fn() -> bool>(condition: F) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !condition() {
+            assert!(Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // `main` starts a worker which sets `Main.started`, sleeps for `millis` and
+    // then sets `Main.done`
+    fn start_worker(name: &str, daemon: bool, millis: i64) -> &'static ClassLoader {
+        let worker = ClassBuilder::new("Worker")
+            .super_class("java/lang/Thread")
+            .default_constructor()
+            .method("run", "()V", |code| {
+                code.iconst(1)
+                    .putstatic("Main", "started", "Z")
+                    .lconst(millis)
+                    .invokestatic("java/lang/Thread", "sleep", "(J)V")
+                    .iconst(1)
+                    .putstatic("Main", "done", "Z")
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        let main = ClassBuilder::new("Main")
+            .field(ACC_PUBLIC | ACC_STATIC, "started", "Z")
+            .field(ACC_PUBLIC | ACC_STATIC, "done", "Z")
+            .method_with_flags(
+                ACC_PUBLIC | ACC_STATIC,
+                "main",
+                "([Ljava/lang/String;)V",
+                |code| {
+                    code.new_instance("Worker")
+                        .dup()
+                        .invokespecial("Worker", "<init>", "()V")
+                        .astore(1)
+                        .aload(1)
+                        .iconst(daemon as i32)
+                        .invokevirtual("Worker", "setDaemon", "(Z)V")
+                        .aload(1)
+                        .invokevirtual("Worker", "start", "()V")
+                        .return_()
+                },
+            )
+            .build()
+            .unwrap();
+        class_loader(name, vec![("Worker", worker), ("Main", main)])
+    }
+
+    #[test]
+    fn test_wait_notify_and_join() {
+        // a worker which sets `done` in a synchronized method, notifies the main
+        // thread waiting for it and stays alive a while longer
+        let worker = ClassBuilder::new("Worker")
+            .super_class("java/lang/Thread")
+            .default_constructor()
+            .method_with_flags(ACC_PUBLIC | ACC_SYNCHRONIZED, "signal", "()V", |code| {
+                code.iconst(1)
+                    .putstatic("Main", "done", "Z")
+                    .aload(0)
+                    .invokevirtual("java/lang/Object", "notifyAll", "()V")
+                    .return_()
+            })
+            .method("run", "()V", |code| {
+                code.lconst(50)
+                    .invokestatic("java/lang/Thread", "sleep", "(J)V")
+                    .aload(0)
+                    .invokevirtual("Worker", "signal", "()V")
+                    .lconst(1000)
+                    .invokestatic("java/lang/Thread", "sleep", "(J)V")
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        // a broken notify or join makes main time out rather than hang
+        let main = ClassBuilder::new("Main")
+            .field(ACC_PUBLIC | ACC_STATIC, "done", "Z")
+            .field(ACC_PUBLIC | ACC_STATIC, "notified", "Z")
+            .field(ACC_PUBLIC | ACC_STATIC, "alive", "Z")
+            .method_with_flags(
+                ACC_PUBLIC | ACC_STATIC,
+                "main",
+                "([Ljava/lang/String;)V",
+                |code| {
+                    let notified = code.new_label();
+                    code.new_instance("Worker")
+                        .dup()
+                        .invokespecial("Worker", "<init>", "()V")
+                        .astore(1)
+                        .aload(1)
+                        .invokevirtual("Worker", "start", "()V")
+                        // synchronized (worker) {
+                        //     if (!done) worker.wait(10_000);
+                        //     notified = done && worker.isAlive();
+                        // }
+                        .aload(1)
+                        .monitorenter()
+                        .getstatic("Main", "done", "Z")
+                        .ifne(notified)
+                        .aload(1)
+                        .lconst(10_000)
+                        .invokevirtual("java/lang/Object", "wait", "(J)V")
+                        .bind(notified)
+                        .getstatic("Main", "done", "Z")
+                        .aload(1)
+                        .invokevirtual("Worker", "isAlive", "()Z")
+                        .iand()
+                        .putstatic("Main", "notified", "Z")
+                        .aload(1)
+                        .monitorexit()
+                        // worker.join(); alive = worker.isAlive();
+                        .aload(1)
+                        .invokevirtual("Worker", "join", "()V")
+                        .aload(1)
+                        .invokevirtual("Worker", "isAlive", "()Z")
+                        .putstatic("Main", "alive", "Z")
+                        .return_()
+                },
+            )
+            .build()
+            .unwrap();
+        let class_loader = class_loader("join", vec![("Worker", worker), ("Main", main)]);
+        let start = Instant::now();
+        assert!(launch(class_loader, "Main").unwrap());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        // woken by notifyAll, not by the worker terminating
+        assert_eq!(static_int(class_loader, "Main", "notified"), 1);
+        assert_eq!(static_int(class_loader, "Main", "alive"), 0);
+        std::fs::remove_dir_all(dir("join")).unwrap();
+    }
+
+    #[test]
+    fn test_virtual_thread_parks_on_contended_monitor() {
+        let worker = ClassBuilder::new("Worker")
+            .super_class("java/lang/VirtualThread")
+            .default_constructor()
+            .method_with_flags(ACC_PUBLIC | ACC_SYNCHRONIZED, "signal", "()V", |code| {
+                code.iconst(1).putstatic("Main", "done", "Z").return_()
+            })
+            .method("run", "()V", |code| {
+                code.aload(0)
+                    .invokevirtual("Worker", "signal", "()V")
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        let main = ClassBuilder::new("Main")
+            .field(ACC_PUBLIC | ACC_STATIC, "done", "Z")
+            .field(ACC_PUBLIC | ACC_STATIC, "early", "Z")
+            .field(ACC_PUBLIC | ACC_STATIC, "alive", "Z")
+            .method_with_flags(
+                ACC_PUBLIC | ACC_STATIC,
+                "main",
+                "([Ljava/lang/String;)V",
+                |code| {
+                    // synchronized (worker) {
+                    //     worker.start();
+                    //     Thread.sleep(200);
+                    //     early = done;
+                    // }
+                    // worker.join(); alive = worker.isAlive();
+                    code.new_instance("Worker")
+                        .dup()
+                        .invokespecial("Worker", "<init>", "()V")
+                        .astore(1)
+                        .aload(1)
+                        .monitorenter()
+                        .aload(1)
+                        .invokevirtual("Worker", "start", "()V")
+                        .lconst(200)
+                        .invokestatic("java/lang/Thread", "sleep", "(J)V")
+                        .getstatic("Main", "done", "Z")
+                        .putstatic("Main", "early", "Z")
+                        .aload(1)
+                        .monitorexit()
+                        .aload(1)
+                        .invokevirtual("Worker", "join", "()V")
+                        .aload(1)
+                        .invokevirtual("Worker", "isAlive", "()Z")
+                        .putstatic("Main", "alive", "Z")
+                        .return_()
+                },
+            )
+            .build()
+            .unwrap();
+        let class_loader = class_loader("monitor", vec![("Worker", worker), ("Main", main)]);
+        let start = Instant::now();
+        assert!(launch(class_loader, "Main").unwrap());
+        assert!(start.elapsed() < Duration::from_secs(10));
+        // the virtual worker entered `signal` only once main released it
+        assert_eq!(static_int(class_loader, "Main", "early"), 0);
+        assert_eq!(static_int(class_loader, "Main", "done"), 1);
+        assert_eq!(static_int(class_loader, "Main", "alive"), 0);
+        std::fs::remove_dir_all(dir("monitor")).unwrap();
+    }
+
+    #[test]
+    fn test_virtual_threads_wait_off_their_carriers() {
+        // more waiters than carriers, which all wait at once only if waiting
+        // frees the carrier
+        let waiters = 2 * std::thread::available_parallelism().map_or(1, usize::from) as i32;
+        let waiter = ClassBuilder::new("Waiter")
+            .super_class("java/lang/VirtualThread")
+            .default_constructor()
+            .method("run", "()V", |code| {
+                // synchronized (lock) { count++; lock.notifyAll(); lock.wait(10_000); }
+                code.getstatic("Main", "lock", "Ljava/lang/Object;")
+                    .monitorenter()
+                    .getstatic("Main", "count", "I")
+                    .iconst(1)
+                    .iadd()
+                    .putstatic("Main", "count", "I")
+                    .getstatic("Main", "lock", "Ljava/lang/Object;")
+                    .invokevirtual("java/lang/Object", "notifyAll", "()V")
+                    .getstatic("Main", "lock", "Ljava/lang/Object;")
+                    .lconst(10_000)
+                    .invokevirtual("java/lang/Object", "wait", "(J)V")
+                    .getstatic("Main", "lock", "Ljava/lang/Object;")
+                    .monitorexit()
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        let main = ClassBuilder::new("Main")
+            .field(ACC_PUBLIC | ACC_STATIC, "lock", "Ljava/lang/Object;")
+            .field(ACC_PUBLIC | ACC_STATIC, "count", "I")
+            .field(ACC_PUBLIC | ACC_STATIC, "arrived", "I")
+            .method_with_flags(
+                ACC_PUBLIC | ACC_STATIC,
+                "main",
+                "([Ljava/lang/String;)V",
+                |code| {
+                    let (start, started, wait, ready) = (
+                        code.new_label(),
+                        code.new_label(),
+                        code.new_label(),
+                        code.new_label(),
+                    );
+                    // for (int i = 0; i < waiters; i++) new Waiter().start();
+                    code.new_instance("java/lang/Object")
+                        .dup()
+                        .invokespecial("java/lang/Object", "<init>", "()V")
+                        .putstatic("Main", "lock", "Ljava/lang/Object;")
+                        .iconst(0)
+                        .istore(1)
+                        .bind(start)
+                        .iload(1)
+                        .iconst(waiters)
+                        .if_icmpge(started)
+                        .new_instance("Waiter")
+                        .dup()
+                        .invokespecial("Waiter", "<init>", "()V")
+                        .invokevirtual("Waiter", "start", "()V")
+                        .iinc(1, 1)
+                        .goto(start)
+                        // synchronized (lock) {
+                        //     for (int i = 0; count < waiters && i < 100; i++) lock.wait(100);
+                        //     arrived = count;
+                        //     lock.notifyAll();
+                        // }
+                        .bind(started)
+                        .getstatic("Main", "lock", "Ljava/lang/Object;")
+                        .monitorenter()
+                        .iconst(0)
+                        .istore(1)
+                        .bind(wait)
+                        .getstatic("Main", "count", "I")
+                        .iconst(waiters)
+                        .if_icmpge(ready)
+                        .iload(1)
+                        .iconst(100)
+                        .if_icmpge(ready)
+                        .iinc(1, 1)
+                        .getstatic("Main", "lock", "Ljava/lang/Object;")
+                        .lconst(100)
+                        .invokevirtual("java/lang/Object", "wait", "(J)V")
+                        .goto(wait)
+                        .bind(ready)
+                        .getstatic("Main", "count", "I")
+                        .putstatic("Main", "arrived", "I")
+                        .getstatic("Main", "lock", "Ljava/lang/Object;")
+                        .invokevirtual("java/lang/Object", "notifyAll", "()V")
+                        .getstatic("Main", "lock", "Ljava/lang/Object;")
+                        .monitorexit()
+                        .return_()
+                },
+            )
+            .build()
+            .unwrap();
+        let class_loader = class_loader("wait", vec![("Waiter", waiter), ("Main", main)]);
+        assert!(launch(class_loader, "Main").unwrap());
+        let class = class_loader.load_class("Main").unwrap();
+        let slot_id = class
+            .look_up_field("arrived", "I")
+            .unwrap()
+            .read()
+            .unwrap()
+            .slot_id;
+        assert_eq!(class.static_vars().get_int(slot_id, true), waiters);
+        std::fs::remove_dir_all(dir("wait")).unwrap();
+    }
+
+    #[test]
+    fn test_interrupt_virtual_thread_sleep() {
+        let worker = ClassBuilder::new("Worker")
+            .super_class("java/lang/VirtualThread")
+            .default_constructor()
+            .method("run", "()V", |code| {
+                code.lconst(60_000)
+                    .invokestatic("java/lang/Thread", "sleep", "(J)V")
+                    .iconst(1)
+                    .putstatic("Main", "done", "Z")
+                    .return_()
+            })
+            .build()
+            .unwrap();
+        let main = ClassBuilder::new("Main")
+            .field(ACC_PUBLIC | ACC_STATIC, "done", "Z")
+            .method_with_flags(
+                ACC_PUBLIC | ACC_STATIC,
+                "main",
+                "([Ljava/lang/String;)V",
+                |code| {
+                    code.new_instance("Worker")
+                        .dup()
+                        .invokespecial("Worker", "<init>", "()V")
+                        .astore(1)
+                        .aload(1)
+                        .invokevirtual("Worker", "start", "()V")
+                        .lconst(50)
+                        .invokestatic("java/lang/Thread", "sleep", "(J)V")
+                        .aload(1)
+                        .invokevirtual("Worker", "interrupt", "()V")
+                        .aload(1)
+                        .invokevirtual("Worker", "join", "()V")
+                        .return_()
+                },
+            )
+            .build()
+            .unwrap();
+        let class_loader = class_loader("interrupt", vec![("Worker", worker), ("Main", main)]);
+        let start = Instant::now();
+        assert!(launch(class_loader, "Main").unwrap());
+        // the sleep threw InterruptedException, which ended the worker
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(static_int(class_loader, "Main", "done"), 0);
+        std::fs::remove_dir_all(dir("interrupt")).unwrap();
+    }
+
+    #[test]
+    fn test_options() {
+        let args = [
+            "-cp",
+            "lib",
+            "--enable-preview",
+            "-Xtrace",
+            "-Xjre",
+            "jre",
+            "Main",
+            "-cp",
+            "x",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+        let (options, main_class, args) = Options::parse(&args).unwrap();
+        assert_eq!(options.class_path, "lib");
+        assert_eq!(options.jre, "jre");
+        assert!(options.enable_preview);
+        assert!(options.trace);
+        assert_eq!(main_class, "Main");
+        assert_eq!(args, ["-cp", "x"]);
+        let (options, _, _) = Options::parse(&["Main".to_string()]).unwrap();
+        assert!(!options.enable_preview && !options.trace);
+        assert!(Options::parse(&["-cp".to_string()]).is_err());
+        assert!(Options::parse(&["-x".to_string()]).is_err());
+        assert!(Options::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_exit_waits_for_non_daemon_threads() {
+        let class_loader = start_worker("non-daemon", false, 100);
+        let start = Instant::now();
+        assert!(launch(class_loader, "Main").unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(static_int(class_loader, "Main", "done"), 1);
+        std::fs::remove_dir_all(dir("non-daemon")).unwrap();
+    }
+
+    #[test]
+    fn test_exit_leaves_daemon_threads() {
+        let class_loader = start_worker("daemon", true, 60_000);
+        assert!(launch(class_loader, "Main").unwrap());
+        assert_eq!(static_int(class_loader, "Main", "done"), 0);
+        std::fs::remove_dir_all(dir("daemon")).unwrap();
+    }
+
+    #[test]
+    fn test_safepoint_while_in_native() {
+        let class_loader = start_worker("safepoint", true, 500);
+        assert!(launch(class_loader, "Main").unwrap());
+        wait_until(|| static_int(class_loader, "Main", "started") == 1);
+        // the worker is sleeping in a native method, which counts as safe
+        let guard = safepoint().request(threads());
+        std::thread::sleep(Duration::from_secs(1));
+        // and it parks on its way back into Java while the pause lasts
+        assert_eq!(static_int(class_loader, "Main", "done"), 0);
+        drop(guard);
+        wait_until(|| static_int(class_loader, "Main", "done") == 1);
+        std::fs::remove_dir_all(dir("safepoint")).unwrap();
+    }
+
+    #[test]
+    fn test_main_class_not_found() {
+        let class_loader = class_loader("not-found", vec![]);
+        let error = launch(class_loader, "Missing").unwrap_err().to_string();
+        assert!(error.starts_with("Could not find or load main class Missing"));
+        std::fs::remove_dir_all(dir("not-found")).unwrap();
+    }
+}
@@ -3,9 +3,22 @@ mod classpath;
 #[allow(dead_code)]
 mod instructions;
 mod interpreter;
+mod javap;
+mod json;
+mod launcher;
+mod native;
 #[allow(dead_code)]
 mod rtda;
+mod verifier;
+mod vthread;
 
 fn main() {
-    println!("Hello, jvm!");
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("javap") {
+        std::process::exit(javap::main(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some("json") {
+        std::process::exit(json::main(&args[2..]));
+    }
+    std::process::exit(launcher::main(&args[1..]));
 }
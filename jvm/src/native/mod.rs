@@ -0,0 +1,61 @@
+use crate::rtda::{current, safepoint, Frame};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+mod object;
+mod thread;
+
+pub type NativeMethod = fn(&mut Frame);
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+#[derive(Default)]
+pub struct Registry(HashMap<String, NativeMethod>);
+
+impl Registry {
+    pub fn register(
+        &mut self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+        method: NativeMethod,
+    ) {
+        self.0
+            .insert(key(class_name, method_name, descriptor), method);
+    }
+
+    pub fn find(
+        &self,
+        class_name: &str,
+        method_name: &str,
+        descriptor: &str,
+    ) -> Option<NativeMethod> {
+        self.0
+            .get(&key(class_name, method_name, descriptor))
+            .copied()
+    }
+}
+
+fn key(class_name: &str, method_name: &str, descriptor: &str) -> String {
+    format!("{}~{}~{}", class_name, method_name, descriptor)
+}
+
+pub fn find_native_method(
+    class_name: &str,
+    method_name: &str,
+    descriptor: &str,
+) -> Option<NativeMethod> {
+    REGISTRY
+        .get_or_init(|| {
+            let mut registry = Registry::default();
+            object::init(&mut registry);
+            thread::init(&mut registry);
+            registry
+        })
+        .find(class_name, method_name, descriptor)
+}
+
+/// Calls a native method, the thread counts as safe while it runs.
+pub fn invoke_native(method: NativeMethod, frame: &mut Frame) {
+    safepoint().in_native(&current(), || method(frame));
+}
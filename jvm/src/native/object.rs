@@ -0,0 +1,48 @@
+use crate::native::Registry;
+use crate::rtda::{current, Frame, LockWord, Park};
+use std::time::Duration;
+
+const OBJECT: &str = "java/lang/Object";
+
+pub(crate) fn init(registry: &mut Registry) {
+    registry.register(OBJECT, "wait", "(J)V", wait);
+    // jdk 17 keeps `wait(long)` in Java around a private native
+    registry.register(OBJECT, "wait0", "(J)V", wait);
+    registry.register(OBJECT, "notify", "()V", notify);
+    registry.register(OBJECT, "notifyAll", "()V", notify_all);
+}
+
+// public final native void wait(long timeout) throws InterruptedException;
+fn wait(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    let millis = frame.local_vars().get_long(1);
+    if millis < 0 {
+        panic!("java.lang.IllegalArgumentException: timeout value is negative");
+    }
+    let timeout = (millis > 0).then(|| Duration::from_millis(millis as u64));
+    let thread = current();
+    if thread.is_virtual() {
+        // checked here so that it is thrown before the thread parks
+        let lock: &'static LockWord = unsafe { &(*this).lock };
+        if !lock.is_held_by(thread.id()) {
+            panic!("java.lang.IllegalMonitorStateException");
+        }
+        // frees the carrier, the monitor is reentered on the blocking pool
+        let call = Box::new(move || lock.wait(&thread, timeout));
+        frame.thread().lock().unwrap().park(Park::Blocking(call));
+    } else if let Err(e) = unsafe { (*this).wait(&thread, timeout) } {
+        panic!("{}", e);
+    }
+}
+
+// public final native void notify();
+fn notify(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    unsafe { (*this).notify(current().id()) };
+}
+
+// public final native void notifyAll();
+fn notify_all(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    unsafe { (*this).notify_all(current().id()) };
+}
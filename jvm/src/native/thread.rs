@@ -0,0 +1,157 @@
+use crate::interpreter::{self, InterpretedContinuation};
+use crate::native::Registry;
+use crate::rtda::{current, threads, Frame, Object, Park};
+use crate::vthread::scheduler;
+use std::time::Duration;
+
+const THREAD: &str = "java/lang/Thread";
+const VIRTUAL_THREAD: &str = "java/lang/VirtualThread";
+
+pub(crate) fn init(registry: &mut Registry) {
+    registry.register(THREAD, "start0", "()V", start0);
+    registry.register(
+        THREAD,
+        "currentThread",
+        "()Ljava/lang/Thread;",
+        current_thread,
+    );
+    registry.register(THREAD, "sleep", "(J)V", sleep);
+    registry.register(THREAD, "yield", "()V", r#yield);
+    registry.register(THREAD, "interrupt0", "()V", interrupt0);
+    registry.register(THREAD, "isInterrupted", "(Z)Z", is_interrupted);
+    registry.register(THREAD, "isAlive", "()Z", is_alive);
+    registry.register(THREAD, "holdsLock", "(Ljava/lang/Object;)Z", holds_lock);
+    registry.register(THREAD, "setPriority0", "(I)V", set_priority0);
+    // intrinsics replacing the ForkJoinPool submission of the class library, they
+    // are not native there. `Thread.ofVirtual().start` ends up in the first and
+    // `newVirtualThreadPerTaskExecutor` in the second
+    registry.register(VIRTUAL_THREAD, "start", "()V", start_virtual);
+    registry.register(
+        VIRTUAL_THREAD,
+        "start",
+        "(Ljdk/internal/vm/ThreadContainer;)V",
+        start_virtual,
+    );
+}
+
+// the heap is shared by all threads, the reference only crosses over to the new one
+struct ThreadObject(*mut Object);
+
+unsafe impl Send for ThreadObject {}
+
+// private native void start0();
+fn start0(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    if threads().find(this).is_some() {
+        panic!("java.lang.IllegalThreadStateException");
+    }
+    let (run, daemon) = unsafe {
+        let class = (*this).class.as_ref();
+        let run = class
+            .look_up_method("run", "()V")
+            .unwrap_or_else(|| panic!("java.lang.AbstractMethodError"));
+        let daemon = class.look_up_field("daemon", "Z").is_some_and(|field| {
+            let slot_id = field.read().unwrap().slot_id;
+            slot_id < (*this).fields.len() && (*this).fields.get_int(slot_id, false) != 0
+        });
+        (run, daemon)
+    };
+    let object = ThreadObject(this);
+    let started = threads().spawn(daemon, this, move || {
+        let object = object;
+        interpreter::interpret_with_args(run, |args| args.set_ref(0, object.0));
+    });
+    if started.is_err() {
+        panic!("java.lang.OutOfMemoryError: unable to create new native thread");
+    }
+}
+
+// void start();
+// void start(ThreadContainer container);
+fn start_virtual(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    if threads().find(this).is_some() {
+        panic!("java.lang.IllegalThreadStateException");
+    }
+    let run = unsafe {
+        (*this)
+            .class
+            .as_ref()
+            .look_up_method("run", "()V")
+            .unwrap_or_else(|| panic!("java.lang.AbstractMethodError"))
+    };
+    let object = ThreadObject(this);
+    scheduler().spawn(threads(), this, move || {
+        let object = object;
+        InterpretedContinuation::new(run, |args| args.set_ref(0, object.0))
+    });
+}
+
+// public static native Thread currentThread();
+fn current_thread(frame: &mut Frame) {
+    frame.operand_stack_mut().push_ref(current().object());
+}
+
+// public static native void sleep(long millis) throws InterruptedException;
+fn sleep(frame: &mut Frame) {
+    let millis = frame.local_vars().get_long(0);
+    if millis < 0 {
+        panic!("java.lang.IllegalArgumentException: timeout value is negative");
+    }
+    let duration = Duration::from_millis(millis as u64);
+    let thread = current();
+    if thread.is_virtual() {
+        frame.thread().lock().unwrap().park(Park::Sleep(duration));
+    } else if let Err(e) = thread.sleep(duration) {
+        panic!("{}", e);
+    }
+}
+
+// public static native void yield();
+fn r#yield(frame: &mut Frame) {
+    if current().is_virtual() {
+        frame.thread().lock().unwrap().park(Park::Yield);
+    } else {
+        std::thread::yield_now();
+    }
+}
+
+// private native void interrupt0();
+fn interrupt0(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    if let Some(thread) = threads().find(this) {
+        thread.interrupt();
+    }
+}
+
+// private native boolean isInterrupted(boolean ClearInterrupted);
+fn is_interrupted(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    let clear = frame.local_vars().get_int(1) != 0;
+    let interrupted = threads()
+        .find(this)
+        .is_some_and(|thread| thread.is_interrupted(clear));
+    frame.operand_stack_mut().push_int(interrupted as i32);
+}
+
+// public final native boolean isAlive();
+fn is_alive(frame: &mut Frame) {
+    let this = frame.local_vars().get_ref(0);
+    let alive = threads().find(this).is_some_and(|thread| thread.is_alive());
+    frame.operand_stack_mut().push_int(alive as i32);
+}
+
+// public static native boolean holdsLock(Object obj);
+fn holds_lock(frame: &mut Frame) {
+    let object = frame.local_vars().get_ref(0);
+    if object.is_null() {
+        panic!("java.lang.NullPointerException");
+    }
+    let holds = unsafe { (*object).lock.is_held_by(current().id()) };
+    frame.operand_stack_mut().push_int(holds as i32);
+}
+
+// private native void setPriority0(int newPriority);
+fn set_priority0(_frame: &mut Frame) {
+    // host thread priorities are left to the OS scheduler
+}
@@ -1,13 +1,14 @@
 use crate::rtda::heap::access_flags::AccessFlag;
 use crate::rtda::heap::class_loader::ClassLoader;
-use crate::rtda::heap::constant_pool::{Constant, ConstantPool};
+use crate::rtda::heap::constant_pool::{get_str, Constant, ConstantPool};
 use crate::rtda::heap::field::{new_fields, Field};
+use crate::rtda::heap::field_slots::FieldSlots;
 use crate::rtda::heap::method::{new_methods, Method};
-use crate::rtda::{LocalVars, Slot};
-use classfile::{get_str, ClassFile};
-use std::cell::RefCell;
+use crate::rtda::heap::object::LockWord;
+use classfile::descriptor::{BaseType, FieldType};
+use classfile::ClassFile;
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 #[derive(Debug)]
 pub struct Class {
@@ -17,18 +18,26 @@ pub struct Class {
     pub super_class_name: Option<String>,
     pub interface_names: Vec<String>,
     pub constant_pool: NonNull<ConstantPool>,
-    pub fields: Vec<Arc<RefCell<Field>>>,
-    pub methods: Vec<Arc<RefCell<Method>>>,
+    pub fields: Vec<Arc<RwLock<Field>>>,
+    pub methods: Vec<Arc<RwLock<Method>>>,
     pub loader: NonNull<ClassLoader>,
     pub super_class: Option<NonNull<Class>>,
     pub interfaces: Vec<NonNull<Class>>,
     pub instance_slot_count: usize,
     pub static_slot_count: usize,
-    static_vars: LocalVars,
+    static_vars: FieldSlots,
+    // stands in for the monitor of the class's java.lang.Class object, which
+    // static synchronized methods lock
+    pub(crate) lock: LockWord,
 }
 
+// classes are leaked once defined and only mutated while being linked,
+// static variables are atomic slots
+unsafe impl Send for Class {}
+unsafe impl Sync for Class {}
+
 impl Class {
-    pub fn new(class_file: &ClassFile) -> Class {
+    pub fn new(class_file: &ClassFile) -> Box<Class> {
         let access_flags = class_file.access_flags;
         // initialize constant pool
         let constant_pool = ConstantPool::new(class_file.constant_pool.clone());
@@ -56,12 +65,12 @@ impl Class {
             ));
         }
 
-        let mut class = Self {
+        let mut class = Box::new(Self {
             access_flags,
             name,
             super_class_name,
             interface_names,
-            constant_pool: Box::leak(Box::new(constant_pool)).into(),
+            constant_pool: Box::leak(constant_pool).into(),
             loader: NonNull::dangling(),
             fields: Vec::with_capacity(class_file.fields.len()),
             methods: Vec::with_capacity(class_file.methods.len()),
@@ -69,34 +78,35 @@ impl Class {
             interfaces: Vec::with_capacity(class_file.interfaces.len()),
             instance_slot_count: 0,
             static_slot_count: 0,
-            static_vars: LocalVars::new(0),
-        };
+            static_vars: FieldSlots::new(0),
+            lock: LockWord::new(),
+        });
 
-        let class_ptr = NonNull::from(&mut class);
+        let class_ptr = NonNull::from(&mut *class);
         unsafe {
             class.constant_pool.as_mut().class = class_ptr;
         }
         // initialize fields
         let fields = new_fields(&mut class, &class_file.fields);
         for field in fields {
-            class.fields.push(Arc::new(RefCell::new(field)));
+            class.fields.push(Arc::new(RwLock::new(field)));
         }
 
         // initialize methods
         let methods = new_methods(&mut class, &class_file.methods);
         for method in methods {
-            class.methods.push(Arc::new(RefCell::new(method)));
+            class.methods.push(Arc::new(RwLock::new(method)));
         }
         class
     }
 
     pub fn calc_instance_field_slot_ids(&mut self) {
         let mut slot_id: usize = 0;
-        if self.super_class.is_some() {
-            slot_id = unsafe { self.super_class.unwrap().as_ref().instance_slot_count };
+        if let Some(super_class) = self.super_class {
+            slot_id = unsafe { super_class.as_ref().instance_slot_count };
         }
         for field in self.fields.iter_mut() {
-            let mut field = field.borrow_mut();
+            let mut field = field.write().unwrap();
             if !field.is_static() {
                 field.slot_id = slot_id;
                 slot_id += 1;
@@ -111,7 +121,7 @@ impl Class {
     pub fn calc_static_field_slot_ids(&mut self) {
         let mut slot_id: usize = 0;
         for field in self.fields.iter_mut() {
-            let mut field = field.borrow_mut();
+            let mut field = field.write().unwrap();
             if field.is_static() {
                 field.slot_id = slot_id;
                 slot_id += 1;
@@ -124,56 +134,56 @@ impl Class {
     }
 
     pub fn alloc_init_static_vars(&mut self) {
-        self.static_vars.0.resize(
-            self.static_slot_count,
-            Slot {
-                num: 0,
-                r#ref: std::ptr::null_mut(),
-            },
-        );
+        self.static_vars = FieldSlots::new(self.static_slot_count);
+        let static_vars = &self.static_vars;
         for field in self.fields.iter_mut().as_ref() {
-            let field = field.borrow();
+            let field = field.read().unwrap();
             if field.is_static() && field.is_final() && field.const_value_index > 0 {
-                match field.descriptor.as_str() {
-                    "Z" | "B" | "C" | "S" | "I" => {
+                match field.field_type {
+                    FieldType::Base(
+                        BaseType::Boolean
+                        | BaseType::Byte
+                        | BaseType::Char
+                        | BaseType::Short
+                        | BaseType::Int,
+                    ) => {
                         if let Constant::Integer(int) = unsafe {
                             self.constant_pool
                                 .as_ref()
                                 .get(field.const_value_index as usize)
                         } {
-                            self.static_vars.set_int(field.slot_id, *int);
+                            static_vars.set_int(field.slot_id, *int, false);
                         }
                     }
-                    "J" => {
+                    FieldType::Base(BaseType::Long) => {
                         if let Constant::Long(long) = unsafe {
                             self.constant_pool
                                 .as_ref()
                                 .get(field.const_value_index as usize)
                         } {
-                            self.static_vars.set_long(field.slot_id, *long);
+                            static_vars.set_long(field.slot_id, *long, false);
                         }
                     }
-                    "F" => {
+                    FieldType::Base(BaseType::Float) => {
                         if let Constant::Float(float) = unsafe {
                             self.constant_pool
                                 .as_ref()
                                 .get(field.const_value_index as usize)
                         } {
-                            self.static_vars.set_float(field.slot_id, *float);
+                            static_vars.set_float(field.slot_id, *float, false);
                         }
                     }
-                    "D" => {
+                    FieldType::Base(BaseType::Double) => {
                         if let Constant::Double(double) = unsafe {
                             self.constant_pool
                                 .as_ref()
                                 .get(field.const_value_index as usize)
                         } {
-                            self.static_vars.set_double(field.slot_id, *double);
+                            static_vars.set_double(field.slot_id, *double, false);
                         }
                     }
-                    _ => {
-                        unimplemented!("please implement me")
-                    }
+                    // string constants wait for java.lang.String objects, left null for now
+                    _ => {}
                 }
             }
         }
@@ -196,10 +206,6 @@ impl Class {
         self.access_flags & AccessFlag::ACC_FINAL.bits() != 0
     }
 
-    pub fn is_super(&self) -> bool {
-        self.access_flags & AccessFlag::ACC_SUPER.bits() != 0
-    }
-
     pub fn is_interface(&self) -> bool {
         self.access_flags & AccessFlag::ACC_INTERFACE.bits() != 0
     }
@@ -231,7 +237,7 @@ impl Class {
                 if child.super_class.unwrap() == class {
                     return true;
                 }
-                child = unsafe { self.super_class.unwrap().as_ref() };
+                child = unsafe { child.super_class.unwrap().as_ref() };
             } else {
                 return false;
             }
@@ -287,9 +293,10 @@ impl Class {
         None
     }
 
-    pub fn look_up_field(&self, name: &str, descriptor: &str) -> Option<Arc<RefCell<Field>>> {
+    pub fn look_up_field(&self, name: &str, descriptor: &str) -> Option<Arc<RwLock<Field>>> {
         for field in self.fields.iter() {
-            if field.borrow().name == name && field.borrow().descriptor == descriptor {
+            if field.read().unwrap().name == name && field.read().unwrap().descriptor == descriptor
+            {
                 return Some(field.clone());
             }
         }
@@ -299,43 +306,208 @@ impl Class {
                     return Some(field);
                 }
             }
-            if self.super_class.is_some() {
-                return self
-                    .super_class
-                    .unwrap()
+            if let Some(super_class) = self.super_class {
+                return super_class.as_ref().look_up_field(name, descriptor);
+            }
+        }
+        None
+    }
+
+    pub fn look_up_method(&self, name: &str, descriptor: &str) -> Option<Arc<RwLock<Method>>> {
+        for method in self.methods.iter() {
+            if method.read().unwrap().name == name
+                && method.read().unwrap().descriptor == descriptor
+            {
+                return Some(method.clone());
+            }
+        }
+        unsafe {
+            if let Some(super_class) = self.super_class {
+                return super_class.as_ref().look_up_method(name, descriptor);
+            }
+        }
+        None
+    }
+
+    /// Looks a method up in this interface, its superinterfaces and then
+    /// `java.lang.Object`, JVMS 5.4.3.4.
+    pub fn look_up_interface_method(
+        &self,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<Arc<RwLock<Method>>> {
+        self.look_up_super_interface_method(name, descriptor)
+            .or_else(|| unsafe { self.super_class?.as_ref().look_up_method(name, descriptor) })
+    }
+
+    fn look_up_super_interface_method(
+        &self,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<Arc<RwLock<Method>>> {
+        for method in self.methods.iter() {
+            let method_ref = method.read().unwrap();
+            if method_ref.name == name && method_ref.descriptor == descriptor {
+                return Some(method.clone());
+            }
+        }
+        unsafe {
+            self.interfaces.iter().find_map(|interface| {
+                interface
+                    .as_ref()
+                    .look_up_super_interface_method(name, descriptor)
+            })
+        }
+    }
+
+    /// The method `invokevirtual` and `invokeinterface` run for an object of this
+    /// class, JVMS 5.4.6: the class and its superclasses first, then a default
+    /// method of the interfaces they implement.
+    pub fn select_method(&self, name: &str, descriptor: &str) -> Option<Arc<RwLock<Method>>> {
+        let mut class = Some(self);
+        while let Some(current) = class {
+            for method in current.methods.iter() {
+                let method_ref = method.read().unwrap();
+                if method_ref.name == name
+                    && method_ref.descriptor == descriptor
+                    && !method_ref.is_static()
+                {
+                    return Some(method.clone());
+                }
+            }
+            class = current
+                .super_class
+                .map(|super_class| unsafe { super_class.as_ref() });
+        }
+        let mut class = Some(self);
+        while let Some(current) = class {
+            let method = current.interfaces.iter().find_map(|interface| unsafe {
+                interface
                     .as_ref()
-                    .look_up_field(name, descriptor);
+                    .look_up_super_interface_method(name, descriptor)
+                    .filter(|method| !method.read().unwrap().is_abstract())
+            });
+            if method.is_some() {
+                return method;
             }
+            class = current
+                .super_class
+                .map(|super_class| unsafe { super_class.as_ref() });
         }
         None
     }
 
-    pub fn static_vars_mut(&mut self) -> &mut LocalVars {
-        &mut self.static_vars
+    /// The method `invokespecial` in this class invokes, given the class its method
+    /// reference resolved to (JVMS 6.5). Methods of a superclass other than `<init>`
+    /// are looked up from the direct superclass. That depends on ACC_SUPER, which
+    /// Java SE 8 and above consider set in every class file whatever its version
+    /// (JVMS 4.1), so neither the flag nor the version is checked.
+    pub fn select_special_method(
+        &self,
+        resolved_class: NonNull<Class>,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<Arc<RwLock<Method>>> {
+        unsafe {
+            let resolved = resolved_class.as_ref();
+            let class = match self.super_class {
+                Some(super_class)
+                    if name != "<init>"
+                        && !resolved.is_interface()
+                        && self.is_sub_class_of(resolved_class) =>
+                {
+                    super_class.as_ref()
+                }
+                _ => resolved,
+            };
+            class.look_up_method(name, descriptor)
+        }
+    }
+
+    pub fn static_vars(&self) -> &FieldSlots {
+        &self.static_vars
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::classpath::{ClassPath, Entry};
+    use crate::rtda::heap::access_flags::AccessFlag;
     use crate::rtda::heap::class::Class;
+    use crate::rtda::heap::class_loader::ClassLoader;
+    use crate::rtda::heap::method::Method;
+    use std::ptr::NonNull;
+    use std::sync::{Arc, RwLock};
 
     #[test]
     fn test_read_class() {
         let class_path = ClassPath::new("".to_string(), "../data/jvm8".to_string());
         if let Ok(class_bytes) = class_path.read_class("User") {
-            if let Ok((_, ref class_file)) = classfile::parse(class_bytes.as_slice()) {
+            if let Ok(ref class_file) = classfile::parse(class_bytes.as_slice()) {
                 let class = Class::new(class_file);
                 assert_eq!(class.name, "User");
                 assert_eq!(class.super_class_name.unwrap(), "java/lang/Object");
                 assert_eq!(class.fields.len(), 3);
                 for field in class.fields.iter().as_ref() {
-                    println!("field: {}", field.borrow().name);
+                    println!("field: {}", field.read().unwrap().name);
                 }
                 for method in class.methods.iter().as_ref() {
-                    println!("method: {}", method.borrow().name);
+                    println!("method: {}", method.read().unwrap().name);
                 }
             }
         }
     }
+
+    #[test]
+    fn test_is_sub_class_of() {
+        let class_path = ClassPath::new("".to_string(), "../data/jvm8".to_string());
+        let class_loader = ClassLoader::new(class_path);
+        let class = |name| NonNull::from(class_loader.load_class(name).unwrap());
+        let array_list = unsafe { class("java/util/ArrayList").as_ref() };
+        // walks past the direct superclass instead of looping on it
+        assert!(array_list.is_sub_class_of(class("java/util/AbstractList")));
+        assert!(array_list.is_sub_class_of(class("java/util/AbstractCollection")));
+        assert!(array_list.is_sub_class_of(class("java/lang/Object")));
+        assert!(!array_list.is_sub_class_of(class("java/util/HashMap")));
+        assert!(!array_list.is_sub_class_of(class("java/util/ArrayList")));
+    }
+
+    #[test]
+    fn test_select_special_method() {
+        let class_path = ClassPath::new("".to_string(), "../data/jvm8".to_string());
+        let class_loader = ClassLoader::new(class_path);
+        let abstract_collection = class_loader
+            .load_class("java/util/AbstractCollection")
+            .unwrap();
+        let abstract_collection = NonNull::from(abstract_collection);
+        let owner = |method: Arc<RwLock<Method>>| unsafe {
+            method.read().unwrap().class.as_ref().name.clone()
+        };
+
+        let data = class_loader
+            .class_path()
+            .read_class("java/util/ArrayList")
+            .unwrap();
+        let mut class_file = classfile::parse(&data).unwrap();
+        let mut array_list = Class::new(&class_file);
+        let abstract_list = class_loader.load_class("java/util/AbstractList").unwrap();
+        array_list.super_class = Some(NonNull::from(abstract_list));
+        // AbstractCollection.equals resolves to Object.equals, but AbstractList overrides it
+        let equals = |class: &Class| {
+            let method =
+                class.select_special_method(abstract_collection, "equals", "(Ljava/lang/Object;)Z");
+            owner(method.unwrap())
+        };
+        assert_eq!(equals(&array_list), "java/util/AbstractList");
+        let init = array_list.select_special_method(abstract_collection, "<init>", "()V");
+        assert_eq!(owner(init.unwrap()), "java/util/AbstractCollection");
+
+        // JVMS 4.1: in Java SE 8 and above, the JVM considers the ACC_SUPER flag
+        // to be set in every class file, regardless of its value or the version
+        class_file.access_flags &= !AccessFlag::ACC_SUPER.bits();
+        class_file.major_version = 51;
+        let mut old_array_list = Class::new(&class_file);
+        old_array_list.super_class = Some(NonNull::from(abstract_list));
+        assert_eq!(equals(&old_array_list), "java/util/AbstractList");
+    }
 }
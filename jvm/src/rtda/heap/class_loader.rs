@@ -1,80 +1,391 @@
-use crate::classpath::{ClassPath, Entry};
+use crate::classpath::{ClassPath, ClassSource};
 use crate::rtda::heap::class::Class;
+use crate::verifier::{inline_class_subroutines, verify_class, with_inlined_code, ClassHierarchy};
 use anyhow::anyhow;
+use classfile::ClassFile;
 use dashmap::DashMap;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
 use std::ptr::NonNull;
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, ThreadId};
 
 const OBJECT_CLASS_NAME: &str = "java/lang/Object";
 
+// class file versions of Java 1.1 through Java 17
+const MIN_MAJOR_VERSION: u16 = 45;
+const MAX_MAJOR_VERSION: u16 = 61;
+// the minor version of classes using preview features, valid from Java 12 on
+const PREVIEW_MINOR_VERSION: u16 = 0xffff;
+const PREVIEW_MAJOR_VERSION: u16 = 56;
+
 pub struct ClassLoader {
     class_path: ClassPath,
     pub class_map: DashMap<String, NonNull<Class>>,
+    // classes being loaded, by name, until they are linked and published in `class_map`
+    loading: Mutex<HashMap<String, Pending>>,
+    loaded: Condvar,
+    inline_subroutines: bool,
+    major_versions: RangeInclusive<u16>,
+    enable_preview: bool,
+}
+
+// a class being loaded, owned by the thread defining or linking it
+struct Pending {
+    thread: Option<ThreadId>,
+    defined: Option<Defined>,
+}
+
+impl Pending {
+    fn new(thread: ThreadId) -> Self {
+        Pending {
+            thread: Some(thread),
+            defined: None,
+        }
+    }
+}
+
+// a user class which is defined but not linked yet, with the bytes to verify
+struct Defined {
+    class: NonNull<Class>,
+    data: Vec<u8>,
 }
 
+// loaded classes are leaked and never move, so handing out pointers to them across threads is fine
+unsafe impl Send for ClassLoader {}
+unsafe impl Sync for ClassLoader {}
+
 impl ClassLoader {
     pub fn new(class_path: ClassPath) -> Self {
         ClassLoader {
             class_path,
             class_map: DashMap::new(),
+            loading: Mutex::new(HashMap::new()),
+            loaded: Condvar::new(),
+            inline_subroutines: false,
+            major_versions: MIN_MAJOR_VERSION..=MAX_MAJOR_VERSION,
+            enable_preview: false,
+        }
+    }
+
+    /// Whether `jsr` and `ret` are replaced by copies of their subroutines when a
+    /// class is loaded, so that neither the verifier nor the interpreter sees them.
+    pub fn set_inline_subroutines(&mut self, inline: bool) {
+        self.inline_subroutines = inline;
+    }
+
+    /// The class file major versions that can be loaded, any other throws
+    /// `UnsupportedClassVersionError`. Preview features are those of the last one.
+    pub fn set_major_versions(&mut self, versions: RangeInclusive<u16>) {
+        self.major_versions = versions;
+    }
+
+    /// Whether classes using preview features may be loaded, as with `--enable-preview`.
+    pub fn set_enable_preview(&mut self, enable: bool) {
+        self.enable_preview = enable;
+    }
+
+    /// Loads and links the class, only linked classes are handed out. Other threads
+    /// loading the same class wait for it, the loading thread itself sees it while
+    /// linking it.
+    pub fn load_class(&self, name: &str) -> anyhow::Result<&Class> {
+        if let Some(class) = self.class_map.get(name) {
+            return Ok(unsafe { class.as_ref() });
         }
+        let current = thread::current().id();
+        let mut loading = self.loading.lock().unwrap();
+        let defined = loop {
+            if let Some(class) = self.class_map.get(name) {
+                return Ok(unsafe { class.as_ref() });
+            }
+            match loading.get_mut(name) {
+                None => {
+                    loading.insert(name.to_string(), Pending::new(current));
+                    break None;
+                }
+                Some(pending) if pending.thread == Some(current) => {
+                    return match &pending.defined {
+                        Some(defined) => Ok(unsafe { defined.class.as_ref() }),
+                        None => Err(anyhow!("java.lang.ClassCircularityError: {}", name)),
+                    };
+                }
+                // defined to look at the hierarchy, but not linked yet
+                Some(pending) if pending.thread.is_none() => {
+                    pending.thread = Some(current);
+                    break pending.defined.as_ref().map(|defined| defined.class);
+                }
+                Some(_) => loading = self.loaded.wait(loading).unwrap(),
+            }
+        };
+        drop(loading);
+
+        let class = match defined {
+            Some(class) => Ok(class),
+            None => self.define(name),
+        };
+        let class = class.and_then(|class| self.link(name, class).map(|()| class));
+        // a class failing to link is leaked rather than freed, as classes defined
+        // meanwhile may refer to it
+        self.finish(name, class.as_ref().ok().copied());
+        class.map(|class| unsafe { class.as_ref() })
     }
 
-    pub fn load_class(&self, name: &str) -> anyhow::Result<&mut Class> {
-        if self.class_map.contains_key(name) {
-            let class = unsafe { self.class_map.get_mut(name).unwrap().as_mut() };
+    // Loads the class far enough to look at its hierarchy, which is all that defining
+    // a subclass or verifying another class needs. Never waits for a class to be
+    // linked, so threads verifying classes that refer to each other don't deadlock.
+    fn load_defined_class(&self, name: &str) -> anyhow::Result<&Class> {
+        let current = thread::current().id();
+        let mut loading = self.loading.lock().unwrap();
+        loop {
+            if let Some(class) = self.class_map.get(name) {
+                return Ok(unsafe { class.as_ref() });
+            }
+            match loading.get(name) {
+                None => {
+                    loading.insert(name.to_string(), Pending::new(current));
+                    break;
+                }
+                Some(Pending {
+                    defined: Some(defined),
+                    ..
+                }) => return Ok(unsafe { defined.class.as_ref() }),
+                Some(pending) if pending.thread == Some(current) => {
+                    return Err(anyhow!("java.lang.ClassCircularityError: {}", name));
+                }
+                Some(_) => loading = self.loaded.wait(loading).unwrap(),
+            }
+        }
+        drop(loading);
+
+        match self.define(name) {
+            Ok(class) => {
+                let mut loading = self.loading.lock().unwrap();
+                if let Some(pending) = loading.get_mut(name) {
+                    pending.thread = None;
+                }
+                drop(loading);
+                self.loaded.notify_all();
+                Ok(unsafe { class.as_ref() })
+            }
+            Err(e) => {
+                self.finish(name, None);
+                Err(e)
+            }
+        }
+    }
+
+    // boot and extension classes are trusted, as HotSpot does by default, and are
+    // published as soon as they are defined
+    fn define(&self, name: &str) -> anyhow::Result<NonNull<Class>> {
+        let (data, source) = self.class_path.read_class_from(name)?;
+        let class_file = self.parse_class_file(data.as_slice())?;
+        let class =
+            self.inlining_subroutines(class_file, |class_file| self.define_class_file(class_file))?;
+        let class = NonNull::from(Box::leak(class));
+        if source != ClassSource::User {
+            self.finish(name, Some(class));
             return Ok(class);
         }
-        let data = self.read_class(name)?;
-        let class = self.define_class(data.as_slice())?;
-        self.class_map
-            .insert(name.to_string(), Box::leak(Box::new(class)).into());
-        let class = unsafe { self.class_map.get_mut(name).unwrap().as_mut() };
+        let mut loading = self.loading.lock().unwrap();
+        if let Some(pending) = loading.get_mut(name) {
+            pending.defined = Some(Defined { class, data });
+        }
+        drop(loading);
+        self.loaded.notify_all();
         Ok(class)
     }
 
-    fn read_class(&self, name: &str) -> anyhow::Result<Vec<u8>> {
-        self.class_path.read_class(name)
+    // superclasses and interfaces are linked first (JVMS 5.4)
+    fn link(&self, name: &str, class: NonNull<Class>) -> anyhow::Result<()> {
+        let data = match self.loading.lock().unwrap().get(name) {
+            Some(Pending {
+                defined: Some(defined),
+                ..
+            }) => defined.data.clone(),
+            // a trusted class, already published
+            _ => return Ok(()),
+        };
+        let class = unsafe { class.as_ref() };
+        if let Some(super_class_name) = &class.super_class_name {
+            self.load_class(super_class_name)?;
+        }
+        for interface_name in class.interface_names.iter() {
+            self.load_class(interface_name)?;
+        }
+        let class_file = self.parse_class_file(data.as_slice())?;
+        self.inlining_subroutines(class_file, |class_file| self.link_class(class_file))
+    }
+
+    // hands `f` the class file with its subroutines inlined if asked to, the class
+    // is both defined and verified from it
+    fn inlining_subroutines<T>(
+        &self,
+        class_file: ClassFile,
+        f: impl FnOnce(&ClassFile) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if !self.inline_subroutines {
+            return f(&class_file);
+        }
+        let inlined = inline_class_subroutines(&class_file)?;
+        f(&with_inlined_code(&class_file, &inlined))
+    }
+
+    // publishes a linked class, or forgets one that failed to load, and wakes the waiters
+    fn finish(&self, name: &str, class: Option<NonNull<Class>>) {
+        let mut loading = self.loading.lock().unwrap();
+        loading.remove(name);
+        if let Some(class) = class {
+            self.class_map.insert(name.to_string(), class);
+        }
+        drop(loading);
+        self.loaded.notify_all();
+    }
+
+    pub fn class_path(&self) -> &ClassPath {
+        &self.class_path
+    }
+
+    pub fn define_class(&self, data: &[u8]) -> anyhow::Result<Box<Class>> {
+        self.define_class_file(&self.parse_class_file(data)?)
+    }
+
+    // a class file that cannot be parsed, has an unsupported version or fails the
+    // format checks of JVMS 4.8
+    fn parse_class_file<'a>(&self, data: &'a [u8]) -> anyhow::Result<ClassFile<'a>> {
+        let class_file =
+            classfile::parse(data).map_err(|e| anyhow!("java.lang.ClassFormatError: {}", e))?;
+        self.check_version(&class_file)
+            .map_err(|e| anyhow!("java.lang.UnsupportedClassVersionError: {}", e))?;
+        classfile::format::check(&class_file)
+            .map_err(|e| anyhow!("java.lang.ClassFormatError: {}", e))?;
+        Ok(class_file)
+    }
+
+    // the same checks and messages as HotSpot
+    fn check_version(&self, class_file: &ClassFile) -> Result<(), String> {
+        let (major, minor) = (class_file.major_version, class_file.minor_version);
+        let name = classfile::get_str(
+            class_file.constant_pool.clone(),
+            class_file.this_class as usize,
+        )
+        .unwrap_or_else(|_| "class".to_string());
+        let max = *self.major_versions.end();
+        if major > max {
+            return Err(format!(
+                "{} has been compiled by a more recent version of the Java Runtime \
+                 (class file version {}.{}), this version of the Java Runtime only \
+                 recognizes class file versions up to {}.0",
+                name, major, minor, max
+            ));
+        }
+        if major < *self.major_versions.start() {
+            return Err(format!(
+                "{} (class file version {}.{}) was compiled with an invalid major version",
+                name, major, minor
+            ));
+        }
+        if major < PREVIEW_MAJOR_VERSION {
+            return Ok(());
+        }
+        match minor {
+            0 => Ok(()),
+            PREVIEW_MINOR_VERSION if major != max => Err(format!(
+                "{} (class file version {}.{}) was compiled with preview features that are \
+                 unsupported. This version of the Java Runtime only recognizes preview \
+                 features for class file version {}.{}",
+                name, major, minor, max, PREVIEW_MINOR_VERSION
+            )),
+            PREVIEW_MINOR_VERSION if !self.enable_preview => Err(format!(
+                "Preview features are not enabled for {} (class file version {}.{}). \
+                 Try running with '--enable-preview'",
+                name, major, minor
+            )),
+            PREVIEW_MINOR_VERSION => Ok(()),
+            _ => Err(format!(
+                "{} (class file version {}.{}) was compiled with an invalid non-zero minor version",
+                name, major, minor
+            )),
+        }
     }
 
-    pub fn define_class(&self, data: &[u8]) -> anyhow::Result<Class> {
-        let mut class = parse_class(data)?;
+    fn define_class_file(&self, class_file: &ClassFile) -> anyhow::Result<Box<Class>> {
+        let mut class = Class::new(class_file);
         if class.name != OBJECT_CLASS_NAME {
-            let super_class = self.load_class(class.super_class_name.as_ref().unwrap())?;
+            let super_class = self.load_defined_class(class.super_class_name.as_ref().unwrap())?;
             class.super_class = Some(NonNull::from(super_class));
         }
         let interface_count = class.interface_names.len();
         if interface_count > 0 {
             for idx in 0..interface_count {
-                let class_ref = self.load_class(&class.interface_names[idx])?;
+                let class_ref = self.load_defined_class(&class.interface_names[idx])?;
                 class.interfaces.push(NonNull::from(class_ref));
             }
         }
         class.loader = NonNull::from(self);
+        // prepared right away, so trusted classes which are never verified get their slots
+        class.calc_instance_field_slot_ids();
+        class.calc_static_field_slot_ids();
+        class.alloc_init_static_vars();
         Ok(class)
     }
-}
 
-fn parse_class(data: &[u8]) -> anyhow::Result<Class> {
-    match classfile::parse(data) {
-        Ok((_, ref class_file)) => Ok(Class::new(class_file)),
-        Err(e) => Err(anyhow!("parse class error: {}", e)),
+    fn link_class(&self, class_file: &ClassFile) -> anyhow::Result<()> {
+        verify_class(class_file, self)?;
+        Ok(())
     }
 }
 
-fn link_class(class: &Class) {
-    verify_class(class);
-    prepare_class(class);
+impl ClassHierarchy for ClassLoader {
+    fn super_class(&self, class: &str) -> Option<String> {
+        if class.starts_with('[') {
+            return Some(OBJECT_CLASS_NAME.to_string());
+        }
+        self.load_defined_class(class)
+            .ok()?
+            .super_class_name
+            .clone()
+    }
+
+    fn is_interface(&self, class: &str) -> bool {
+        self.load_defined_class(class)
+            .is_ok_and(|class| class.is_interface())
+    }
+
+    fn protected_member_owner(&self, class: &str, name: &str, descriptor: &str) -> Option<String> {
+        let class = self.load_defined_class(class).ok()?;
+        let (protected, owner) = if descriptor.starts_with('(') {
+            let method = class.look_up_method(name, descriptor)?;
+            let method = method.read().unwrap();
+            (method.is_protected(), method.class)
+        } else {
+            let field = class.look_up_field(name, descriptor)?;
+            let field = field.read().unwrap();
+            (field.is_protected(), field.class)
+        };
+        protected.then(|| unsafe { owner.as_ref() }.name.clone())
+    }
 }
 
-fn verify_class(_class: &Class) {}
+// computing stack maps merges classes through the same hierarchy
+impl classfile::frames::ClassHierarchy for ClassLoader {
+    fn super_class(&self, class: &str) -> Option<String> {
+        ClassHierarchy::super_class(self, class)
+    }
 
-fn prepare_class(_class: &Class) {}
+    fn is_interface(&self, class: &str) -> bool {
+        ClassHierarchy::is_interface(self, class)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use crate::classpath::{ClassPath, Entry};
     use crate::rtda::heap::class::Class;
     use crate::rtda::heap::class_loader::ClassLoader;
+    use crate::rtda::heap::constant_pool::Constant;
+    use classfile::builder::{ClassBuilder, ACC_STATIC};
+    use classfile::bytecode::decode_all;
+    use classfile::AttributeType;
     use std::ptr::NonNull;
 
     fn class_loader_init() -> ClassLoader {
@@ -90,11 +401,11 @@ mod tests {
         assert_eq!(class.super_class_name.as_ref().unwrap(), "java/lang/Object");
         assert_eq!(class.fields.len(), 3);
         for field in class.fields.iter().as_ref() {
-            println!("field: {}", field.borrow().name);
+            println!("field: {}", field.read().unwrap().name);
         }
 
         for method in class.methods.iter().as_ref() {
-            println!("method: {}", method.borrow().name);
+            println!("method: {}", method.read().unwrap().name);
         }
 
         let super_class = unsafe { class.super_class.unwrap().as_ref() };
@@ -102,11 +413,11 @@ mod tests {
         assert_eq!(super_class.super_class_name, None);
         assert_eq!(super_class.fields.len(), 0);
         for field in super_class.fields.iter().as_ref() {
-            println!("field: {}", field.borrow().name);
+            println!("field: {}", field.read().unwrap().name);
         }
 
         for method in super_class.methods.iter().as_ref() {
-            println!("method: {}", method.borrow().name);
+            println!("method: {}", method.read().unwrap().name);
         }
     }
 
@@ -120,10 +431,218 @@ mod tests {
 
         let class_path = ClassPath::new("".to_string(), "../data/jvm8".to_string());
         if let Ok(class_bytes) = class_path.read_class("java/lang/Object") {
-            if let Ok((_, ref class_file)) = classfile::parse(class_bytes.as_slice()) {
+            if let Ok(ref class_file) = classfile::parse(class_bytes.as_slice()) {
                 let class_inner = Class::new(class_file);
-                assert_eq!(class.is_sub_class_of(NonNull::from(&class_inner)), false);
+                assert!(!class.is_sub_class_of(NonNull::from(class_inner.as_ref())));
             }
         }
     }
+
+    #[test]
+    fn test_modified_utf8() {
+        let class_path = ClassPath::new("".to_string(), "../data/jvm8".to_string());
+        // string literals with supplementary characters, embedded nulls and
+        // unpaired surrogates
+        for name in ["java/lang/CharacterData00", "sun/nio/cs/GB18030"] {
+            let class_bytes = class_path.read_class(name).unwrap();
+            let class_file = classfile::parse(class_bytes.as_slice()).unwrap();
+            let class = Class::new(&class_file);
+            assert_eq!(class.name, name);
+        }
+
+        let class_bytes = class_path.read_class("sun/nio/cs/GB18030").unwrap();
+        let class_file = classfile::parse(class_bytes.as_slice()).unwrap();
+        let class = Class::new(&class_file);
+        let constant_pool = unsafe { class.constant_pool.as_ref() };
+        let unpaired = (0..constant_pool.len()).any(|i| match constant_pool.get(i) {
+            Constant::String(chars) => String::from_utf16(chars).is_err(),
+            _ => false,
+        });
+        assert!(unpaired);
+    }
+
+    #[test]
+    fn test_inline_subroutines() {
+        // static int run() { int i = 1; try { return i; } finally { i += 5; } }
+        // as javac 1.4 compiled it:
+        // iconst_1, istore_1, jsr 7, iload_1, ireturn, astore_2, iinc 1 5, ret 2
+        let jsr = [
+            0x04, 0x3c, 0xa8, 0x00, 0x05, 0x1b, 0xac, 0x4d, 0x84, 0x01, 0x05, 0xa9, 0x02,
+        ];
+        let data = ClassBuilder::new("Subroutine")
+            .version(49, 0)
+            .method_with_flags(ACC_STATIC, "run", "()I", |code| code.iconst(1).ireturn())
+            .build()
+            .unwrap();
+        let mut class_file = classfile::parse(&data).unwrap();
+        let method = &mut class_file.methods[0];
+        let index = method.code_attr_index.unwrap();
+        if let AttributeType::Code { code } = &mut method.attributes[index].attribute_type {
+            code.max_stack = 2;
+            code.max_locals = 3;
+            code.code = jsr.to_vec().into();
+            code.attributes.clear();
+        }
+        let data = classfile::write(&class_file).unwrap();
+        let dir = std::env::temp_dir().join(format!("jvm-subroutines-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Subroutine.class"), data).unwrap();
+        let class_loader = |inline| {
+            let class_path = ClassPath::new("".to_string(), dir.to_str().unwrap().to_string());
+            let mut class_loader = ClassLoader::new(class_path);
+            class_loader.set_inline_subroutines(inline);
+            class_loader
+        };
+        let opcodes = |class_loader: &ClassLoader| {
+            let class = class_loader.load_class("Subroutine").unwrap();
+            let method = class.methods[0].read().unwrap();
+            decode_all(method.code().unwrap())
+                .unwrap()
+                .iter()
+                .map(|instruction| instruction.opcode)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(opcodes(&class_loader(false)).contains(&0xa8));
+        // the class executes the inlined code that was verified
+        let inlined = opcodes(&class_loader(true));
+        assert!(!inlined.contains(&0xa8) && !inlined.contains(&0xa9));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_class_format_error() {
+        let class_loader = class_loader_init();
+        let data = class_loader.class_path().read_class("GaussTest").unwrap();
+        let error = class_loader
+            .define_class(&data[..data.len() - 1])
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("java.lang.ClassFormatError: unexpected end of input"));
+
+        // a static constructor
+        let mut class_file = classfile::parse(&data).unwrap();
+        class_file.methods[0].access_flags = 0x0009;
+        let data = classfile::write(&class_file).unwrap();
+        let error = class_loader.define_class(&data).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "java.lang.ClassFormatError: illegal method modifiers 0x0009 in method #0"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_class_version() {
+        let mut class_loader = class_loader_init();
+        let data = class_loader.class_path().read_class("GaussTest").unwrap();
+        let mut class_file = classfile::parse(&data).unwrap();
+        // StackMapTable needs version 50, the format checker would reject older versions
+        for method in class_file.methods.iter_mut() {
+            for attribute in method.attributes.iter_mut() {
+                if let AttributeType::Code { code } = &mut attribute.attribute_type {
+                    code.attributes.retain(|attribute| {
+                        !matches!(
+                            attribute.attribute_type,
+                            AttributeType::StackMapTable { .. }
+                        )
+                    });
+                }
+            }
+        }
+        let mut define = |class_loader: &ClassLoader, major, minor| {
+            class_file.major_version = major;
+            class_file.minor_version = minor;
+            let data = classfile::write(&class_file).unwrap();
+            class_loader.define_class(&data).map(|_| ())
+        };
+        let error = |result: anyhow::Result<()>| result.err().unwrap().to_string();
+
+        assert!(define(&class_loader, 45, 3).is_ok());
+        assert!(define(&class_loader, 55, 1).is_ok());
+        assert!(define(&class_loader, 61, 0).is_ok());
+        assert_eq!(
+            error(define(&class_loader, 62, 0)),
+            "java.lang.UnsupportedClassVersionError: GaussTest has been compiled by a more \
+             recent version of the Java Runtime (class file version 62.0), this version of \
+             the Java Runtime only recognizes class file versions up to 61.0"
+        );
+        assert_eq!(
+            error(define(&class_loader, 44, 0)),
+            "java.lang.UnsupportedClassVersionError: GaussTest (class file version 44.0) \
+             was compiled with an invalid major version"
+        );
+        assert_eq!(
+            error(define(&class_loader, 56, 1)),
+            "java.lang.UnsupportedClassVersionError: GaussTest (class file version 56.1) \
+             was compiled with an invalid non-zero minor version"
+        );
+        assert_eq!(
+            error(define(&class_loader, 61, 0xffff)),
+            "java.lang.UnsupportedClassVersionError: Preview features are not enabled for \
+             GaussTest (class file version 61.65535). Try running with '--enable-preview'"
+        );
+        assert_eq!(
+            error(define(&class_loader, 60, 0xffff)),
+            "java.lang.UnsupportedClassVersionError: GaussTest (class file version 60.65535) \
+             was compiled with preview features that are unsupported. This version of the \
+             Java Runtime only recognizes preview features for class file version 61.65535"
+        );
+
+        class_loader.set_enable_preview(true);
+        assert!(define(&class_loader, 61, 0xffff).is_ok());
+        assert!(define(&class_loader, 60, 0xffff).is_err());
+
+        class_loader.set_major_versions(45..=52);
+        assert!(define(&class_loader, 52, 0).is_ok());
+        assert!(error(define(&class_loader, 53, 0)).ends_with("versions up to 52.0"));
+    }
+
+    #[test]
+    fn test_load_class_concurrently() {
+        let class_loader = class_loader_init();
+        let classes: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let class = class_loader.load_class("GaussTest").unwrap();
+                        class as *const Class as usize
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        // defined and verified once, every thread gets the same class
+        assert!(classes.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn test_verify_before_publishing() {
+        let dir = std::env::temp_dir().join(format!("jvm-class-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bad = ClassBuilder::new("Bad")
+            .default_constructor()
+            .method_with_flags(ACC_STATIC, "get", "()I", |code| code.fconst(1.0).ireturn())
+            .build()
+            .unwrap();
+        std::fs::write(dir.join("Bad.class"), bad).unwrap();
+        let sub = ClassBuilder::new("Sub").super_class("Bad").build().unwrap();
+        std::fs::write(dir.join("Sub.class"), sub).unwrap();
+        let class_path = ClassPath::new("".to_string(), dir.to_str().unwrap().to_string());
+        let class_loader = ClassLoader::new(class_path);
+
+        let error = class_loader.load_class("Bad").err().unwrap().to_string();
+        assert!(error.starts_with("java.lang.VerifyError"), "{}", error);
+        assert!(class_loader.class_map.get("Bad").is_none());
+        // a failed class is not handed out on a second try either
+        assert!(class_loader.load_class("Bad").is_err());
+        // nor is a subclass, as the superclass is linked first
+        assert!(class_loader.load_class("Sub").is_err());
+        assert!(class_loader.class_map.get("Sub").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
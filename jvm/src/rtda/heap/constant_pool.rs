@@ -2,12 +2,15 @@ use crate::rtda::heap::class::Class;
 use crate::rtda::heap::field::Field;
 use crate::rtda::heap::method::Method;
 use anyhow::anyhow;
-use classfile::{get_str, ConstantPoolRef};
+use classfile::{mutf8, ConstantPoolRef};
 use jvm_macros::SymbolRef;
-use std::cell::RefCell;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock, RwLock};
+
+pub(crate) fn get_str(cp: ConstantPoolRef, index: usize) -> String {
+    classfile::get_str(cp, index).unwrap_or_else(|e| panic!("java.lang.ClassFormatError: {}", e))
+}
 
 #[derive(Debug, Clone)]
 pub enum Constant {
@@ -16,7 +19,8 @@ pub enum Constant {
     Float(f32),
     Long(i64),
     Double(f64),
-    String(String),
+    // the UTF-16 code units of a string literal, which may contain unpaired surrogates
+    String(Vec<u16>),
     Utf8(Vec<u8>),
     Class(ClassRef),
     FieldRef(FieldRef),
@@ -30,14 +34,14 @@ pub enum Constant {
 }
 
 pub trait SymbolicRef {
-    fn resolved_class_ref(&mut self) -> anyhow::Result<()>;
+    fn resolved_class_ref(&self) -> anyhow::Result<()>;
 }
 
 #[derive(Debug, Clone, SymbolRef)]
 pub struct ClassRef {
     pub name: String,
     constant_pool: NonNull<ConstantPool>,
-    class: Option<NonNull<Class>>,
+    class: OnceLock<NonNull<Class>>,
     _marker: PhantomData<Box<Class>>,
 }
 
@@ -47,46 +51,89 @@ pub struct MethodRef {
     pub descriptor: String,
     pub class_name: String,
     constant_pool: NonNull<ConstantPool>,
-    class: Option<NonNull<Class>>,
-    method: Option<NonNull<Method>>,
+    class: OnceLock<NonNull<Class>>,
+    method: OnceLock<Arc<RwLock<Method>>>,
     _marker: PhantomData<Box<Class>>,
 }
 
 pub type InterfaceMethodRef = MethodRef;
 
+impl MethodRef {
+    /// Resolves a `CONSTANT_Methodref`, JVMS 5.4.3.3.
+    pub fn resolve_method(&self) -> Arc<RwLock<Method>> {
+        self.resolve(false)
+    }
+
+    /// Resolves a `CONSTANT_InterfaceMethodref`, JVMS 5.4.3.4.
+    pub fn resolve_interface_method(&self) -> Arc<RwLock<Method>> {
+        self.resolve(true)
+    }
+
+    // resolution is idempotent, if two threads race the first result is kept
+    fn resolve(&self, interface: bool) -> Arc<RwLock<Method>> {
+        if let Some(method) = self.method.get() {
+            return method.clone();
+        }
+        unsafe {
+            let cp_class = self.constant_pool.as_ref().class.as_ref();
+            let class = self
+                .resolved_class()
+                .unwrap_or_else(|e| panic!("{}", e))
+                .as_ref();
+            if class.is_interface() != interface {
+                panic!("java.lang.IncompatibleClassChangeError");
+            }
+            let method = match interface {
+                true => class.look_up_interface_method(&self.name, &self.descriptor),
+                false => class.look_up_method(&self.name, &self.descriptor),
+            };
+            let method = match method {
+                Some(method) => method,
+                None => panic!("java.lang.NoSuchMethodError: {}.{}", class.name, self.name),
+            };
+            if !method.read().unwrap().is_accessible_to(cp_class) {
+                panic!("java.lang.IllegalAccessError");
+            }
+            self.method.get_or_init(|| method).clone()
+        }
+    }
+}
+
 #[derive(Debug, Clone, SymbolRef)]
 pub struct FieldRef {
     pub name: String,
     pub descriptor: String,
     pub class_name: String,
     pub constant_pool: NonNull<ConstantPool>,
-    class: Option<NonNull<Class>>,
-    field: Option<Arc<RefCell<Field>>>,
+    class: OnceLock<NonNull<Class>>,
+    field: OnceLock<Arc<RwLock<Field>>>,
     _marker: PhantomData<Box<Class>>,
 }
 
 impl FieldRef {
-    pub fn resolve_field(&mut self) -> Arc<RefCell<Field>> {
-        if self.field.is_none() {
+    pub fn resolve_field(&self) -> Arc<RwLock<Field>> {
+        if self.field.get().is_none() {
             let _ = self.resolve_field_ref();
         }
-        self.field.clone().unwrap()
+        self.field.get().unwrap().clone()
     }
 
-    pub fn resolve_field_ref(&mut self) -> anyhow::Result<()> {
+    // resolution is idempotent, if two threads race the first result is kept
+    pub fn resolve_field_ref(&self) -> anyhow::Result<()> {
         unsafe {
             let cp_class = self.constant_pool.as_ref().class.as_ref();
             let self_class = self.resolved_class()?;
             let field = self_class
                 .as_ref()
                 .look_up_field(self.name.as_str(), self.descriptor.as_str());
-            if field.is_none() {
-                panic!("java.lang.NoSuchFieldError");
-            }
-            if !field.clone().unwrap().borrow().is_accessible_to(cp_class) {
+            let field = match field {
+                Some(field) => field,
+                None => panic!("java.lang.NoSuchFieldError"),
+            };
+            if !field.read().unwrap().is_accessible_to(cp_class) {
                 panic!("java.lang.IllegalAccessError");
             }
-            self.field = field;
+            let _ = self.field.set(field);
             Ok(())
         }
     }
@@ -99,13 +146,18 @@ pub struct ConstantPool {
     _marker: PhantomData<Box<Class>>,
 }
 
+// the pool is immutable once built, symbolic refs cache their resolution in `OnceLock`s
+unsafe impl Send for ConstantPool {}
+unsafe impl Sync for ConstantPool {}
+
 impl ConstantPool {
-    pub fn new(cp: ConstantPoolRef) -> Self {
-        let mut constant_pool = Self {
+    // boxed so the symbolic refs can point back at a stable address
+    pub fn new(cp: ConstantPoolRef) -> Box<Self> {
+        let mut constant_pool = Box::new(Self {
             class: NonNull::dangling(),
             consts: Vec::with_capacity(cp.len()),
             _marker: PhantomData,
-        };
+        });
         for constant in cp.iter() {
             match constant {
                 classfile::Constant::Integer(i) => {
@@ -121,10 +173,10 @@ impl ConstantPool {
                     constant_pool.consts.push(Constant::Double(*d));
                 }
                 classfile::Constant::String { string_index } => {
-                    constant_pool.consts.push(Constant::String(get_str(
-                        cp.clone(),
-                        *string_index as usize,
-                    )));
+                    let utf8 = classfile::get_utf8(cp.clone(), *string_index as usize)
+                        .and_then(|utf8| mutf8::decode_utf16(&utf8))
+                        .unwrap_or_else(|e| panic!("java.lang.ClassFormatError: {}", e));
+                    constant_pool.consts.push(Constant::String(utf8));
                 }
                 classfile::Constant::Utf8(utf8) => {
                     constant_pool.consts.push(Constant::Utf8(utf8.to_vec()));
@@ -156,8 +208,8 @@ impl ConstantPool {
                 classfile::Constant::Class { name_index } => {
                     let constant = Constant::Class(ClassRef {
                         name: get_str(cp.clone(), *name_index as usize),
-                        constant_pool: NonNull::from(&mut constant_pool),
-                        class: None,
+                        constant_pool: NonNull::from(&mut *constant_pool),
+                        class: OnceLock::new(),
                         _marker: PhantomData,
                     });
                     constant_pool.consts.push(constant);
@@ -175,9 +227,9 @@ impl ConstantPool {
                             name: get_str(cp.clone(), *name_index as usize),
                             descriptor: get_str(cp.clone(), *descriptor_index as usize),
                             class_name: get_str(cp.clone(), *class_index as usize),
-                            constant_pool: NonNull::from(&mut constant_pool),
-                            class: None,
-                            field: None,
+                            constant_pool: NonNull::from(&mut *constant_pool),
+                            class: OnceLock::new(),
+                            field: OnceLock::new(),
                             _marker: PhantomData,
                         });
                         constant_pool.consts.push(constant);
@@ -196,9 +248,9 @@ impl ConstantPool {
                             name: get_str(cp.clone(), *name_index as usize),
                             descriptor: get_str(cp.clone(), *descriptor_index as usize),
                             class_name: get_str(cp.clone(), *class_index as usize),
-                            constant_pool: NonNull::from(&mut constant_pool),
-                            class: None,
-                            method: None,
+                            constant_pool: NonNull::from(&mut *constant_pool),
+                            class: OnceLock::new(),
+                            method: OnceLock::new(),
                             _marker: PhantomData,
                         });
                         constant_pool.consts.push(constant);
@@ -218,9 +270,9 @@ impl ConstantPool {
                                 name: get_str(cp.clone(), *name_index as usize),
                                 descriptor: get_str(cp.clone(), *descriptor_index as usize),
                                 class_name: get_str(cp.clone(), *class_index as usize),
-                                constant_pool: NonNull::from(&mut constant_pool),
-                                class: None,
-                                method: None,
+                                constant_pool: NonNull::from(&mut *constant_pool),
+                                class: OnceLock::new(),
+                                method: OnceLock::new(),
                                 _marker: PhantomData,
                             });
                         constant_pool.consts.push(interface_method_ref);
@@ -246,9 +298,11 @@ impl ConstantPool {
         &mut self.consts[index]
     }
 
-    pub fn get_utf8(&self, index: usize) -> Vec<u8> {
-        match self.get(index as usize) {
-            Constant::Utf8(utf8) => utf8.clone(),
+    pub fn get_str(&self, index: usize) -> String {
+        match self.get(index) {
+            Constant::Utf8(utf8) => {
+                mutf8::decode(utf8).unwrap_or_else(|e| panic!("java.lang.ClassFormatError: {}", e))
+            }
             _ => panic!("java.lang.ClassFormatError"),
         }
     }
@@ -1,5 +1,6 @@
 use crate::rtda::heap::access_flags::AccessFlag;
 use crate::rtda::heap::class::Class;
+use classfile::descriptor::{BaseType, FieldType};
 use classfile::{AttributeType, FieldInfo};
 use std::marker::PhantomData;
 use std::ptr::NonNull;
@@ -9,12 +10,16 @@ pub struct Field {
     pub access_flags: u16,
     pub name: String,
     pub descriptor: String,
+    pub field_type: FieldType,
     pub const_value_index: u16,
     pub slot_id: usize,
     pub class: NonNull<Class>,
     marker: PhantomData<Box<Class>>,
 }
 
+unsafe impl Send for Field {}
+unsafe impl Sync for Field {}
+
 impl Field {
     pub fn new(class: &mut Class, field_info: &FieldInfo) -> Self {
         let mut const_value_index = 0;
@@ -30,18 +35,21 @@ impl Field {
             class
                 .constant_pool
                 .as_ref()
-                .get_utf8(field_info.name_index as usize)
+                .get_str(field_info.name_index as usize)
         };
         let descriptor = unsafe {
             class
                 .constant_pool
                 .as_ref()
-                .get_utf8(field_info.descriptor_index as usize)
+                .get_str(field_info.descriptor_index as usize)
         };
+        let field_type = FieldType::parse(&descriptor)
+            .unwrap_or_else(|e| panic!("java.lang.ClassFormatError: {}", e));
         Self {
             access_flags: field_info.access_flags,
-            name: String::from_utf8(name).unwrap(),
-            descriptor: String::from_utf8(descriptor).unwrap(),
+            name,
+            descriptor,
+            field_type,
             const_value_index,
             slot_id: 0,
             class: NonNull::from(class),
@@ -82,11 +90,11 @@ impl Field {
     }
 
     pub fn is_long(&self) -> bool {
-        self.descriptor == "J"
+        self.field_type == FieldType::Base(BaseType::Long)
     }
 
     pub fn is_double(&self) -> bool {
-        self.descriptor == "D"
+        self.field_type == FieldType::Base(BaseType::Double)
     }
 
     pub fn is_accessible_to(&self, class: &Class) -> bool {
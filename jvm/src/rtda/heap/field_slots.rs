@@ -0,0 +1,180 @@
+use crate::rtda::Object;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+// Instance and static field storage, shared by every thread.
+//
+// A slot is a 64-bit atomic, a long or double is kept whole in the first of
+// its two slots so it can never be torn. Plain accesses are relaxed and
+// volatile ones sequentially consistent, as the JMM requires of volatiles.
+// References are loaded with acquire, pairing with `freeze_final_fields` so
+// whoever reads a reference to a constructed object sees its final fields.
+pub struct FieldSlots(Box<[AtomicU64]>);
+
+impl FieldSlots {
+    pub fn new(slot_count: usize) -> Self {
+        Self((0..slot_count).map(|_| AtomicU64::new(0)).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get_int(&self, index: usize, volatile: bool) -> i32 {
+        self.load(index, load_order(volatile)) as i32
+    }
+
+    pub fn set_int(&self, index: usize, value: i32, volatile: bool) {
+        self.store(index, value as u32 as u64, store_order(volatile));
+    }
+
+    pub fn get_float(&self, index: usize, volatile: bool) -> f32 {
+        f32::from_bits(self.load(index, load_order(volatile)) as u32)
+    }
+
+    pub fn set_float(&self, index: usize, value: f32, volatile: bool) {
+        self.store(index, value.to_bits() as u64, store_order(volatile));
+    }
+
+    pub fn get_long(&self, index: usize, volatile: bool) -> i64 {
+        self.load(index, load_order(volatile)) as i64
+    }
+
+    pub fn set_long(&self, index: usize, value: i64, volatile: bool) {
+        self.store(index, value as u64, store_order(volatile));
+    }
+
+    pub fn get_double(&self, index: usize, volatile: bool) -> f64 {
+        f64::from_bits(self.load(index, load_order(volatile)))
+    }
+
+    pub fn set_double(&self, index: usize, value: f64, volatile: bool) {
+        self.store(index, value.to_bits(), store_order(volatile));
+    }
+
+    pub fn get_ref(&self, index: usize, volatile: bool) -> *mut Object {
+        let order = if volatile {
+            Ordering::SeqCst
+        } else {
+            Ordering::Acquire
+        };
+        self.load(index, order) as usize as *mut Object
+    }
+
+    pub fn set_ref(&self, index: usize, value: *mut Object, volatile: bool) {
+        self.store(index, value as usize as u64, store_order(volatile));
+    }
+
+    fn load(&self, index: usize, order: Ordering) -> u64 {
+        self.0[index].load(order)
+    }
+
+    fn store(&self, index: usize, value: u64, order: Ordering) {
+        self.0[index].store(value, order);
+    }
+}
+
+/// Issued when a constructor returns, orders its stores before any later
+/// store that publishes the object.
+pub fn freeze_final_fields() {
+    fence(Ordering::Release);
+}
+
+fn load_order(volatile: bool) -> Ordering {
+    if volatile {
+        Ordering::SeqCst
+    } else {
+        Ordering::Relaxed
+    }
+}
+
+fn store_order(volatile: bool) -> Ordering {
+    if volatile {
+        Ordering::SeqCst
+    } else {
+        Ordering::Relaxed
+    }
+}
+
+// a copied object starts out with the current field values
+impl Clone for FieldSlots {
+    fn clone(&self) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|slot| AtomicU64::new(slot.load(Ordering::Relaxed)))
+                .collect(),
+        )
+    }
+}
+
+impl Debug for FieldSlots {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|slot| slot.load(Ordering::Relaxed)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtda::heap::field_slots::FieldSlots;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_field_slots() {
+        let slots = FieldSlots::new(8);
+        slots.set_int(0, -100, false);
+        slots.set_long(1, -2997924580, false);
+        slots.set_float(3, std::f32::consts::PI, true);
+        slots.set_double(4, std::f64::consts::E, true);
+        slots.set_ref(6, 0x1000 as *mut _, false);
+        assert_eq!(slots.get_int(0, false), -100);
+        assert_eq!(slots.get_long(1, true), -2997924580);
+        assert_eq!(slots.get_float(3, false), std::f32::consts::PI);
+        assert_eq!(slots.get_double(4, false), std::f64::consts::E);
+        assert_eq!(slots.get_ref(6, true), 0x1000 as *mut _);
+        assert_eq!(slots.get_int(7, false), 0);
+    }
+
+    #[test]
+    fn test_long_is_never_torn() {
+        let slots = Arc::new(FieldSlots::new(2));
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let slots = slots.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    slots.set_long(0, -1, false);
+                    slots.set_long(0, 0, false);
+                }
+            })
+        };
+        for _ in 0..100_000 {
+            let value = slots.get_long(0, false);
+            assert!(value == 0 || value == -1, "torn read: {:#x}", value);
+        }
+        stop.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_volatile_store_load_ordering() {
+        // Dekker: with both flags volatile at least one thread sees the other's store
+        for _ in 0..1000 {
+            let slots = Arc::new(FieldSlots::new(2));
+            let other = slots.clone();
+            let handle = thread::spawn(move || {
+                other.set_int(0, 1, true);
+                other.get_int(1, true)
+            });
+            slots.set_int(1, 1, true);
+            let seen_by_main = slots.get_int(0, true);
+            let seen_by_other = handle.join().unwrap();
+            assert!(seen_by_main == 1 || seen_by_other == 1);
+        }
+    }
+}
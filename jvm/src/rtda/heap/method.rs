@@ -1,5 +1,6 @@
 use crate::rtda::heap::access_flags::AccessFlag;
 use crate::rtda::heap::class::Class;
+use classfile::descriptor::MethodDescriptor;
 use classfile::MethodInfo;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
@@ -16,24 +17,27 @@ pub struct Method {
     pub(crate) marker: PhantomData<Box<Class>>,
 }
 
+unsafe impl Send for Method {}
+unsafe impl Sync for Method {}
+
 impl Method {
     pub fn new(class: &mut Class, method_info: &classfile::MethodInfo) -> Self {
         let name = unsafe {
             class
                 .constant_pool
                 .as_ref()
-                .get_utf8(method_info.name_index as usize)
+                .get_str(method_info.name_index as usize)
         };
         let descriptor = unsafe {
             class
                 .constant_pool
                 .as_ref()
-                .get_utf8(method_info.descriptor_index as usize)
+                .get_str(method_info.descriptor_index as usize)
         };
         let mut method = Method {
             access_flags: method_info.access_flags,
-            name: String::from_utf8(name).unwrap(),
-            descriptor: String::from_utf8(descriptor).unwrap(),
+            name,
+            descriptor,
             class: NonNull::from(class),
             max_stack: 0,
             max_locals: 0,
@@ -118,6 +122,13 @@ impl Method {
         self.max_stack
     }
 
+    /// The local variable slots the arguments take, `this` included.
+    pub fn arg_slot_count(&self) -> usize {
+        let parameters = MethodDescriptor::parse(&self.descriptor)
+            .map_or(0, |descriptor| descriptor.parameter_slots());
+        parameters + usize::from(!self.is_static())
+    }
+
     pub fn code(&self) -> Option<&[u8]> {
         self.code.as_deref()
     }
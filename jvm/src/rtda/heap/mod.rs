@@ -3,11 +3,15 @@ mod class;
 mod class_loader;
 mod constant_pool;
 mod field;
+pub(super) mod field_slots;
 mod method;
+mod monitor;
 mod object;
 
 pub use class::Class;
 pub use class_loader::ClassLoader;
-pub use constant_pool::{Constant, ConstantPool};
+pub use constant_pool::Constant;
+pub use field_slots::freeze_final_fields;
 pub use method::Method;
-pub use object::Object;
+pub use monitor::{Monitor, NO_OWNER};
+pub use object::{LockWord, Object};
@@ -0,0 +1,485 @@
+use crate::rtda::heap::object::LockWord;
+use crate::rtda::JavaThread;
+use anyhow::anyhow;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+pub const NO_OWNER: usize = 0;
+
+// inflated monitors are never freed, idle ones are kept here and reused
+static FREE_MONITORS: Mutex<Vec<&'static Monitor>> = Mutex::new(Vec::new());
+
+#[derive(Debug)]
+struct MonitorState {
+    owner: usize,
+    count: usize,
+    // threads blocked on entry, including notified waiters yet to re-enter
+    contenders: usize,
+    // ids of the threads in `wait`, in arrival order
+    wait_set: VecDeque<usize>,
+}
+
+#[derive(Debug)]
+pub struct Monitor {
+    state: Mutex<MonitorState>,
+    entry: Condvar,
+    waiting: Condvar,
+    // wakes the virtual threads parked on entry whenever the monitor is released
+    released: Notify,
+}
+
+impl Monitor {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(MonitorState {
+                owner: NO_OWNER,
+                count: 0,
+                contenders: 0,
+                wait_set: VecDeque::new(),
+            }),
+            entry: Condvar::new(),
+            waiting: Condvar::new(),
+            released: Notify::new(),
+        }
+    }
+
+    pub(crate) fn acquire(owner: usize, count: usize) -> &'static Monitor {
+        let monitor = FREE_MONITORS
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| Box::leak(Box::new(Monitor::new())));
+        let mut state = monitor.state.lock().unwrap();
+        state.owner = owner;
+        state.count = count;
+        drop(state);
+        monitor
+    }
+
+    pub(crate) fn release(&'static self) {
+        let mut state = self.state.lock().unwrap();
+        state.owner = NO_OWNER;
+        state.count = 0;
+        if state.contenders > 0 {
+            self.entry.notify_all();
+        }
+        drop(state);
+        FREE_MONITORS.lock().unwrap().push(self);
+        self.released.notify_waiters();
+    }
+
+    pub fn owner(&self) -> usize {
+        self.state.lock().unwrap().owner
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.state.lock().unwrap().count
+    }
+
+    /// Notified each time the monitor is released, a virtual thread awaits it
+    /// rather than blocking its carrier on entry.
+    pub fn released(&self) -> &Notify {
+        &self.released
+    }
+
+    /// Returns `false` if the monitor was deflated from `lock` before it
+    /// could be entered, in which case the caller must retry on the lock word.
+    pub(crate) fn enter(&'static self, thread_id: usize, lock: &LockWord) -> bool {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            // checked first, a recycled monitor may be owned by this thread
+            // on behalf of another object
+            if !lock.is_inflated_to(self) {
+                // the wakeup may have been meant for a contender of the new object
+                if state.contenders > 0 {
+                    self.entry.notify_one();
+                }
+                return false;
+            }
+            if state.owner == thread_id {
+                state.count += 1;
+                return true;
+            }
+            if state.owner == NO_OWNER {
+                state.owner = thread_id;
+                state.count = 1;
+                return true;
+            }
+            state.contenders += 1;
+            state = self.entry.wait(state).unwrap();
+            state.contenders -= 1;
+        }
+    }
+
+    /// Like `enter` without blocking, `None` means the monitor was deflated.
+    pub(crate) fn try_enter(&'static self, thread_id: usize, lock: &LockWord) -> Option<bool> {
+        let mut state = self.state.lock().unwrap();
+        if !lock.is_inflated_to(self) {
+            if state.contenders > 0 {
+                self.entry.notify_one();
+            }
+            return None;
+        }
+        if state.owner == thread_id {
+            state.count += 1;
+            return Some(true);
+        }
+        if state.owner != NO_OWNER {
+            return Some(false);
+        }
+        state.owner = thread_id;
+        state.count = 1;
+        Some(true)
+    }
+
+    pub(crate) fn exit(&'static self, thread_id: usize, lock: &LockWord) {
+        let mut state = self.state.lock().unwrap();
+        if state.owner != thread_id {
+            panic!("java.lang.IllegalMonitorStateException");
+        }
+        state.count -= 1;
+        if state.count > 0 {
+            return;
+        }
+        state.owner = NO_OWNER;
+        if state.contenders == 0 && state.wait_set.is_empty() {
+            // nobody is queued on the monitor, hand the object back to thin locking
+            lock.deflate();
+            drop(state);
+            FREE_MONITORS.lock().unwrap().push(self);
+        } else {
+            let has_contenders = state.contenders > 0;
+            drop(state);
+            if has_contenders {
+                self.entry.notify_one();
+            }
+        }
+        self.released.notify_waiters();
+    }
+
+    pub(crate) fn wait(
+        &'static self,
+        thread: &JavaThread,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        let thread_id = thread.id();
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state.lock().unwrap();
+        if state.owner != thread_id {
+            panic!("java.lang.IllegalMonitorStateException");
+        }
+        if thread.is_interrupted(true) {
+            return Err(anyhow!("java.lang.InterruptedException"));
+        }
+        // give the monitor up entirely, however many times it was entered
+        let count = state.count;
+        state.owner = NO_OWNER;
+        state.count = 0;
+        state.wait_set.push_back(thread_id);
+        if state.contenders > 0 {
+            self.entry.notify_one();
+        }
+        self.released.notify_waiters();
+        thread.set_waiting_on(Some(self));
+        let interrupted = loop {
+            if !state.wait_set.contains(&thread_id) {
+                // notified, `notify` already counted us as a contender
+                break false;
+            }
+            let interrupted = thread.is_interrupted(true);
+            let now = Instant::now();
+            let timed_out = deadline.is_some_and(|deadline| now >= deadline);
+            if interrupted || timed_out {
+                state.wait_set.retain(|&id| id != thread_id);
+                state.contenders += 1;
+                break interrupted;
+            }
+            state = match deadline {
+                Some(deadline) => self.waiting.wait_timeout(state, deadline - now).unwrap().0,
+                None => self.waiting.wait(state).unwrap(),
+            };
+        };
+        thread.set_waiting_on(None);
+        while state.owner != NO_OWNER {
+            state = self.entry.wait(state).unwrap();
+        }
+        state.owner = thread_id;
+        state.count = count;
+        state.contenders -= 1;
+        if interrupted {
+            return Err(anyhow!("java.lang.InterruptedException"));
+        }
+        Ok(())
+    }
+
+    /// Moves one waiter, or all of them, from the wait set to the entry queue.
+    pub(crate) fn notify(&self, thread_id: usize, all: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.owner != thread_id {
+            panic!("java.lang.IllegalMonitorStateException");
+        }
+        let woken = if all {
+            state.wait_set.len()
+        } else {
+            state.wait_set.len().min(1)
+        };
+        if woken > 0 {
+            state.wait_set.drain(..woken);
+            state.contenders += woken;
+            self.waiting.notify_all();
+        }
+    }
+
+    // wakes the wait set so an interrupted waiter can notice its interrupt
+    pub(crate) fn interrupt_waiters(&self) {
+        let _state = self.state.lock().unwrap();
+        self.waiting.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtda::current;
+    use crate::rtda::heap::object::{LockState, LockWord};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_thin_lock_recursion() {
+        let lock = LockWord::new();
+        lock.enter(1);
+        lock.enter(1);
+        lock.enter(1);
+        assert!(matches!(
+            lock.state(),
+            LockState::Thin {
+                owner: 1,
+                recursions: 2
+            }
+        ));
+        lock.exit(1);
+        lock.exit(1);
+        assert!(lock.is_held_by(1));
+        lock.exit(1);
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_recursion_overflow_inflates() {
+        let lock = LockWord::new();
+        for _ in 0..1000 {
+            lock.enter(7);
+        }
+        match lock.state() {
+            LockState::Inflated(monitor) => {
+                assert_eq!(monitor.owner(), 7);
+                assert_eq!(monitor.entry_count(), 1000);
+            }
+            state => panic!("expected inflated lock, got {:?}", state),
+        }
+        for _ in 0..1000 {
+            lock.exit(7);
+        }
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_inflate_by_owner() {
+        let lock = LockWord::new();
+        lock.enter(3);
+        lock.enter(3);
+        let monitor = lock.inflate(3);
+        assert_eq!(monitor.owner(), 3);
+        assert_eq!(monitor.entry_count(), 2);
+        lock.exit(3);
+        lock.exit(3);
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_stale_monitor_reference() {
+        // a monitor owned by thread 4 for `lock`, reached through `other`
+        // as if it had been recycled while a stale reference was held
+        let lock = LockWord::new();
+        let other = LockWord::new();
+        lock.enter(4);
+        let monitor = lock.inflate(4);
+        assert!(!monitor.enter(4, &other));
+        assert_eq!(monitor.try_enter(4, &other), None);
+        assert_eq!(monitor.entry_count(), 1);
+        assert!(matches!(other.state(), LockState::Unlocked));
+        lock.exit(4);
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    #[should_panic(expected = "java.lang.IllegalMonitorStateException")]
+    fn test_exit_not_owner() {
+        let lock = LockWord::new();
+        lock.enter(1);
+        lock.exit(2);
+    }
+
+    #[test]
+    fn test_contended_lock() {
+        let lock = Arc::new(LockWord::new());
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handles = (1..=8)
+            .map(|thread_id| {
+                let lock = lock.clone();
+                let counter = counter.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        lock.enter(thread_id);
+                        lock.enter(thread_id);
+                        // a non-atomic read-modify-write, only correct under the lock
+                        let value = counter.load(Ordering::Relaxed);
+                        thread::yield_now();
+                        counter.store(value + 1, Ordering::Relaxed);
+                        lock.exit(thread_id);
+                        lock.exit(thread_id);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(counter.load(Ordering::Relaxed), 8000);
+        // the last owner out deflates the monitor
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_wait_notify() {
+        let lock = Arc::new(LockWord::new());
+        let queue = Arc::new(AtomicUsize::new(0));
+        let consumer = {
+            let lock = lock.clone();
+            let queue = queue.clone();
+            thread::spawn(move || {
+                let thread = current();
+                let mut consumed = 0;
+                while consumed < 100 {
+                    lock.enter(thread.id());
+                    while queue.load(Ordering::Relaxed) == 0 {
+                        lock.wait(&thread, None).unwrap();
+                    }
+                    queue.fetch_sub(1, Ordering::Relaxed);
+                    consumed += 1;
+                    lock.notify_all(thread.id());
+                    lock.exit(thread.id());
+                }
+            })
+        };
+        let thread = current();
+        for _ in 0..100 {
+            lock.enter(thread.id());
+            // a bounded queue of one element
+            while queue.load(Ordering::Relaxed) == 1 {
+                lock.wait(&thread, None).unwrap();
+            }
+            queue.fetch_add(1, Ordering::Relaxed);
+            lock.notify_all(thread.id());
+            lock.exit(thread.id());
+        }
+        consumer.join().unwrap();
+        assert_eq!(queue.load(Ordering::Relaxed), 0);
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_wait_keeps_recursion_count() {
+        let lock = LockWord::new();
+        let thread = current();
+        lock.enter(thread.id());
+        lock.enter(thread.id());
+        let start = Instant::now();
+        lock.wait(&thread, Some(Duration::from_millis(20))).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        match lock.state() {
+            LockState::Inflated(monitor) => {
+                assert_eq!(monitor.owner(), thread.id());
+                assert_eq!(monitor.entry_count(), 2);
+            }
+            state => panic!("expected inflated lock, got {:?}", state),
+        }
+        lock.exit(thread.id());
+        lock.exit(thread.id());
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_notify_all_wakes_every_waiter() {
+        let lock = Arc::new(LockWord::new());
+        let ready = Arc::new(AtomicUsize::new(0));
+        let handles = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                let ready = ready.clone();
+                thread::spawn(move || {
+                    let thread = current();
+                    lock.enter(thread.id());
+                    ready.fetch_add(1, Ordering::Relaxed);
+                    lock.wait(&thread, None).unwrap();
+                    lock.exit(thread.id());
+                })
+            })
+            .collect::<Vec<_>>();
+        let thread = current();
+        loop {
+            lock.enter(thread.id());
+            // every waiter entered the wait set before releasing the lock
+            if ready.load(Ordering::Relaxed) == 4 {
+                lock.notify_all(thread.id());
+                lock.exit(thread.id());
+                break;
+            }
+            lock.exit(thread.id());
+            thread::yield_now();
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(matches!(lock.state(), LockState::Unlocked));
+    }
+
+    #[test]
+    fn test_interrupt_wakes_wait() {
+        let lock = Arc::new(LockWord::new());
+        let (tx, rx) = mpsc::channel();
+        let waiter = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                let thread = current();
+                lock.enter(thread.id());
+                tx.send(thread.clone()).unwrap();
+                let waited = lock.wait(&thread, None);
+                // the lock is re-acquired before the exception is thrown
+                assert!(lock.is_held_by(thread.id()));
+                lock.exit(thread.id());
+                assert!(!thread.is_interrupted(false));
+                waited.map_err(|e| e.to_string())
+            })
+        };
+        let waiting = rx.recv().unwrap();
+        let thread = current();
+        // once we get the lock the waiter is in the wait set
+        lock.enter(thread.id());
+        waiting.interrupt();
+        lock.exit(thread.id());
+        let waited = waiter.join().unwrap();
+        assert_eq!(waited.unwrap_err(), "java.lang.InterruptedException");
+    }
+
+    #[test]
+    #[should_panic(expected = "java.lang.IllegalMonitorStateException")]
+    fn test_notify_not_owner() {
+        let lock = LockWord::new();
+        lock.notify(current().id());
+    }
+}
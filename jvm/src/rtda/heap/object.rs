@@ -1,12 +1,278 @@
 use crate::rtda::heap::class::Class;
-use crate::rtda::LocalVars;
+use crate::rtda::heap::field_slots::FieldSlots;
+use crate::rtda::heap::monitor::Monitor;
+use crate::rtda::JavaThread;
+use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// lock word layout, from the low bits up:
+//
+//   unlocked: [ 0 .................................... | 00 ]
+//   thin:     [ owner thread id | recursions (8 bits) | 01 ]
+//   inflated: [ address of the Monitor ................ | 10 ]
+const TAG_MASK: usize = 0b11;
+const TAG_UNLOCKED: usize = 0b00;
+const TAG_THIN: usize = 0b01;
+const TAG_INFLATED: usize = 0b10;
+const RECURSION_SHIFT: u32 = 2;
+const RECURSION_BITS: u32 = 8;
+const RECURSION_MAX: usize = (1 << RECURSION_BITS) - 1;
+const OWNER_SHIFT: u32 = RECURSION_SHIFT + RECURSION_BITS;
+// how many times a contender re-reads a thin lock before inflating it
+const SPIN_LIMIT: usize = 64;
+
+#[derive(Debug)]
+pub enum LockState {
+    Unlocked,
+    Thin { owner: usize, recursions: usize },
+    Inflated(&'static Monitor),
+}
+
+pub struct LockWord(AtomicUsize);
+
+impl LockWord {
+    pub const fn new() -> Self {
+        Self(AtomicUsize::new(TAG_UNLOCKED))
+    }
+
+    pub fn state(&self) -> LockState {
+        decode(self.0.load(Ordering::Acquire))
+    }
+
+    pub fn enter(&self, thread_id: usize) {
+        let mut spins = 0;
+        loop {
+            let word = self.0.load(Ordering::Acquire);
+            match decode(word) {
+                LockState::Unlocked => {
+                    if self.cas(word, thin(thread_id, 0)) {
+                        return;
+                    }
+                }
+                LockState::Thin { owner, recursions } if owner == thread_id => {
+                    if recursions < RECURSION_MAX {
+                        if self.cas(word, thin(thread_id, recursions + 1)) {
+                            return;
+                        }
+                    } else {
+                        self.try_inflate(word, owner, recursions);
+                    }
+                }
+                LockState::Thin { owner, recursions } => {
+                    if spins < SPIN_LIMIT {
+                        spins += 1;
+                        std::thread::yield_now();
+                    } else {
+                        self.try_inflate(word, owner, recursions);
+                    }
+                }
+                LockState::Inflated(monitor) => {
+                    if monitor.enter(thread_id, self) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Enters the lock only if that does not block, the fast path before a
+    /// blocking `enter` and all a virtual thread tries before it parks.
+    pub fn try_enter(&self, thread_id: usize) -> bool {
+        loop {
+            let word = self.0.load(Ordering::Acquire);
+            match decode(word) {
+                LockState::Unlocked => {
+                    if self.cas(word, thin(thread_id, 0)) {
+                        return true;
+                    }
+                }
+                LockState::Thin { owner, recursions } if owner == thread_id => {
+                    if recursions < RECURSION_MAX {
+                        if self.cas(word, thin(thread_id, recursions + 1)) {
+                            return true;
+                        }
+                    } else {
+                        self.try_inflate(word, owner, recursions);
+                    }
+                }
+                LockState::Thin { .. } => return false,
+                LockState::Inflated(monitor) => {
+                    if let Some(entered) = monitor.try_enter(thread_id, self) {
+                        return entered;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn exit(&self, thread_id: usize) {
+        loop {
+            let word = self.0.load(Ordering::Acquire);
+            match decode(word) {
+                LockState::Thin { owner, recursions } if owner == thread_id => {
+                    let new_word = if recursions > 0 {
+                        thin(thread_id, recursions - 1)
+                    } else {
+                        TAG_UNLOCKED
+                    };
+                    // fails only if a contender inflated the lock in the meantime
+                    if self.cas(word, new_word) {
+                        return;
+                    }
+                }
+                LockState::Inflated(monitor) => {
+                    monitor.exit(thread_id, self);
+                    return;
+                }
+                _ => panic!("java.lang.IllegalMonitorStateException"),
+            }
+        }
+    }
+
+    /// The monitor a virtual thread that failed `try_enter` waits on until it
+    /// is released, a thin lock is inflated on behalf of its owner. `None`
+    /// means the lock was released meanwhile and can be entered again.
+    pub fn contended_monitor(&self, thread_id: usize) -> Option<&'static Monitor> {
+        loop {
+            let word = self.0.load(Ordering::Acquire);
+            match decode(word) {
+                LockState::Thin { owner, recursions } if owner != thread_id => {
+                    self.try_inflate(word, owner, recursions);
+                }
+                LockState::Inflated(monitor) => return Some(monitor),
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn is_held_by(&self, thread_id: usize) -> bool {
+        match self.state() {
+            LockState::Unlocked => false,
+            LockState::Thin { owner, .. } => owner == thread_id,
+            LockState::Inflated(monitor) => monitor.owner() == thread_id,
+        }
+    }
+
+    /// Inflates a lock held by `thread_id`, which needs a fat monitor to `wait` on.
+    pub fn inflate(&self, thread_id: usize) -> &'static Monitor {
+        loop {
+            let word = self.0.load(Ordering::Acquire);
+            match decode(word) {
+                LockState::Thin { owner, recursions } if owner == thread_id => {
+                    self.try_inflate(word, owner, recursions);
+                }
+                LockState::Inflated(monitor) if monitor.owner() == thread_id => {
+                    return monitor;
+                }
+                _ => panic!("java.lang.IllegalMonitorStateException"),
+            }
+        }
+    }
+
+    /// Releases the lock and waits until notified, interrupted or, given a
+    /// `timeout`, until it elapses, then re-acquires it with the same
+    /// recursion count.
+    ///
+    /// Java permits spurious wakeups and callers must re-check their condition
+    /// in a loop. This implementation does not wake spuriously: a return is
+    /// always caused by `notify`/`notifyAll`, the timeout, or an interrupt,
+    /// which clears the interrupt status and yields `InterruptedException`.
+    pub fn wait(&self, thread: &JavaThread, timeout: Option<Duration>) -> anyhow::Result<()> {
+        self.inflate(thread.id()).wait(thread, timeout)
+    }
+
+    pub fn notify(&self, thread_id: usize) {
+        self.notify_waiters(thread_id, false);
+    }
+
+    pub fn notify_all(&self, thread_id: usize) {
+        self.notify_waiters(thread_id, true);
+    }
+
+    fn notify_waiters(&self, thread_id: usize, all: bool) {
+        match self.state() {
+            // waiting inflates the lock, so a thin lock has nobody to notify
+            LockState::Thin { owner, .. } if owner == thread_id => {}
+            LockState::Inflated(monitor) => monitor.notify(thread_id, all),
+            _ => panic!("java.lang.IllegalMonitorStateException"),
+        }
+    }
+
+    pub(crate) fn is_inflated_to(&self, monitor: &'static Monitor) -> bool {
+        self.0.load(Ordering::Acquire) == inflated(monitor)
+    }
+
+    // only called by the owner of the inflated monitor while holding its state lock
+    pub(crate) fn deflate(&self) {
+        self.0.store(TAG_UNLOCKED, Ordering::Release);
+    }
+
+    fn try_inflate(&self, word: usize, owner: usize, recursions: usize) {
+        let monitor = Monitor::acquire(owner, recursions + 1);
+        if !self.cas(word, inflated(monitor)) {
+            monitor.release();
+        }
+    }
+
+    fn cas(&self, current: usize, new: usize) -> bool {
+        self.0
+            .compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+}
+
+impl Default for LockWord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a copied object gets a fresh header
+impl Clone for LockWord {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for LockWord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.state() {
+            LockState::Unlocked => write!(f, "unlocked"),
+            LockState::Thin { owner, recursions } => {
+                write!(f, "thin(owner: {}, recursions: {})", owner, recursions)
+            }
+            LockState::Inflated(monitor) => write!(f, "inflated({:p})", monitor),
+        }
+    }
+}
+
+fn thin(owner: usize, recursions: usize) -> usize {
+    (owner << OWNER_SHIFT) | (recursions << RECURSION_SHIFT) | TAG_THIN
+}
+
+fn inflated(monitor: &'static Monitor) -> usize {
+    monitor as *const Monitor as usize | TAG_INFLATED
+}
+
+fn decode(word: usize) -> LockState {
+    match word & TAG_MASK {
+        TAG_THIN => LockState::Thin {
+            owner: word >> OWNER_SHIFT,
+            recursions: (word >> RECURSION_SHIFT) & RECURSION_MAX,
+        },
+        TAG_INFLATED => LockState::Inflated(unsafe { &*((word & !TAG_MASK) as *const Monitor) }),
+        _ => LockState::Unlocked,
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Object {
+    pub(crate) lock: LockWord,
     pub(crate) class: NonNull<Class>,
-    pub(crate) fields: LocalVars,
+    pub(crate) fields: FieldSlots,
     pub(crate) marker: PhantomData<Box<Class>>,
 }
 
@@ -14,8 +280,9 @@ impl Object {
     pub fn new(class: NonNull<Class>) -> Self {
         unsafe {
             Self {
+                lock: LockWord::new(),
                 class,
-                fields: LocalVars(Vec::with_capacity(class.as_ref().instance_slot_count)),
+                fields: FieldSlots::new(class.as_ref().instance_slot_count),
                 marker: PhantomData,
             }
         }
@@ -24,4 +291,28 @@ impl Object {
     pub fn is_instance_of(&self, class: NonNull<Class>) -> bool {
         unsafe { class.as_ref().is_assignable_from(self.class) }
     }
+
+    pub fn monitor_enter(&self, thread_id: usize) {
+        self.lock.enter(thread_id);
+    }
+
+    pub fn try_monitor_enter(&self, thread_id: usize) -> bool {
+        self.lock.try_enter(thread_id)
+    }
+
+    pub fn monitor_exit(&self, thread_id: usize) {
+        self.lock.exit(thread_id);
+    }
+
+    pub fn wait(&self, thread: &JavaThread, timeout: Option<Duration>) -> anyhow::Result<()> {
+        self.lock.wait(thread, timeout)
+    }
+
+    pub fn notify(&self, thread_id: usize) {
+        self.lock.notify(thread_id);
+    }
+
+    pub fn notify_all(&self, thread_id: usize) {
+        self.lock.notify_all(thread_id);
+    }
 }
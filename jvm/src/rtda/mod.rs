@@ -1,13 +1,14 @@
-use std::cell::RefCell;
 use std::ptr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock};
 
 mod heap;
+mod safepoint;
 mod thread;
 
-pub use crate::rtda::heap::{Class, ClassLoader, Constant, ConstantPool, Method};
-pub use crate::rtda::thread::Thread;
-pub use heap::Object;
+pub use crate::rtda::heap::{Class, ClassLoader, Constant, Method};
+pub use crate::rtda::safepoint::{safepoint, ThreadState};
+pub use crate::rtda::thread::{current, set_current, threads, JavaThread, Park, Thread, Threads};
+pub use heap::{freeze_final_fields, LockWord, Object, NO_OWNER};
 
 #[derive(Debug)]
 pub struct Stack {
@@ -46,6 +47,10 @@ impl Stack {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.top.is_none()
+    }
+
     pub fn peek(&self) -> Option<&Frame> {
         if self.top.is_none() {
             panic!("jvm stack is empty");
@@ -60,20 +65,31 @@ pub struct Frame {
     lower: Option<Box<Frame>>,
     local_vars: LocalVars,
     operand_stack: OperandStack,
-    thread: Arc<RefCell<Thread>>,
-    method: Arc<RefCell<Method>>,
+    thread: Arc<Mutex<Thread>>,
+    method: Arc<RwLock<Method>>,
     next_pc: isize,
+    // held while a synchronized method runs
+    monitor: Option<&'static LockWord>,
 }
 
 impl Frame {
-    pub fn new(thread: Arc<RefCell<Thread>>, method: Arc<RefCell<Method>>) -> Frame {
+    pub fn new(thread: Arc<Mutex<Thread>>, method: Arc<RwLock<Method>>) -> Frame {
+        let (max_locals, max_stack) = {
+            let method = method.read().unwrap();
+            match method.is_native() {
+                // a native frame only holds the arguments and the return value
+                true => (method.arg_slot_count(), 2),
+                false => (method.max_locals(), method.max_stack()),
+            }
+        };
         Frame {
             lower: None,
-            local_vars: LocalVars::new(method.clone().borrow().max_locals()),
-            operand_stack: OperandStack::new(method.clone().borrow().max_stack()),
+            local_vars: LocalVars::new(max_locals),
+            operand_stack: OperandStack::new(max_stack),
             thread,
             method,
             next_pc: 0,
+            monitor: None,
         }
     }
 
@@ -101,21 +117,48 @@ impl Frame {
         self.next_pc = next_pc;
     }
 
-    pub fn thread(&mut self) -> Arc<RefCell<Thread>> {
+    pub fn monitor(&self) -> Option<&'static LockWord> {
+        self.monitor
+    }
+
+    pub fn set_monitor(&mut self, monitor: &'static LockWord) {
+        self.monitor = Some(monitor);
+    }
+
+    pub fn thread(&mut self) -> Arc<Mutex<Thread>> {
         self.thread.clone()
     }
 
-    pub fn method(&mut self) -> Arc<RefCell<Method>> {
+    pub fn method(&mut self) -> Arc<RwLock<Method>> {
         self.method.clone()
     }
 
+    /// Moves the top `slots` of the operand stack, a return value, onto the caller's.
+    pub fn return_to(&mut self, caller: &mut Frame, slots: usize) {
+        let values = self
+            .operand_stack
+            .slots
+            .split_off(self.operand_stack.size - slots);
+        self.operand_stack.size -= slots;
+        values
+            .into_iter()
+            .for_each(|slot| caller.operand_stack.push_slot(slot));
+    }
+
     pub fn branch(&mut self, offset: i32) {
-        let pc = self.thread.borrow_mut().pc();
+        // backward branches are safepoint polls, so loops can be stopped
+        if offset <= 0 && safepoint().is_requested() {
+            let thread = self.thread.lock().unwrap().java_thread().clone();
+            safepoint().poll(&thread);
+        }
+        let pc = self.thread.lock().unwrap().pc();
         let next_pc = pc + offset as isize;
         self.set_next_pc(next_pc);
     }
 }
 
+/// `num` holds an int, a float's bits, half of a long or double, or the
+/// returnAddress pushed by `jsr`.
 #[derive(Debug, Clone)]
 pub struct Slot {
     num: i32,
@@ -183,6 +226,14 @@ impl LocalVars {
     pub fn get_ref(&self, index: usize) -> *mut Object {
         self.0[index].r#ref
     }
+
+    pub fn get_return_address(&self, index: usize) -> isize {
+        self.0[index].num as isize
+    }
+
+    pub fn set_slot(&mut self, index: usize, slot: Slot) {
+        self.0[index] = slot;
+    }
 }
 
 #[derive(Debug)]
@@ -262,6 +313,10 @@ impl OperandStack {
         value
     }
 
+    pub fn push_return_address(&mut self, pc: isize) {
+        self.push_int(pc as i32);
+    }
+
     pub fn push_slot(&mut self, slot: Slot) {
         self.slots.push(slot);
         self.size += 1;
@@ -271,22 +326,28 @@ impl OperandStack {
         self.size -= 1;
         self.slots.pop().unwrap()
     }
+
+    /// The reference `n` slots below the top, e.g. the receiver of a call whose
+    /// arguments take `n` slots.
+    pub fn get_ref_from_top(&self, n: usize) -> *mut Object {
+        self.slots[self.size - 1 - n].r#ref
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::rtda::heap::field_slots::FieldSlots;
     use crate::rtda::heap::Object;
     use crate::rtda::{Frame, LocalVars, Method, OperandStack, Slot, Stack, Thread};
-    use std::cell::RefCell;
     use std::marker::PhantomData;
     use std::ptr;
     use std::ptr::NonNull;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex, RwLock};
 
     fn stack_init() -> Stack {
         let mut stack = Stack::new(10);
-        let thread = Arc::new(RefCell::new(Thread::new()));
-        let method = Arc::new(RefCell::new(Method {
+        let thread = Arc::new(Mutex::new(Thread::new()));
+        let method = Arc::new(RwLock::new(Method {
             access_flags: 0,
             name: "me".to_string(),
             descriptor: "you".to_string(),
@@ -300,7 +361,7 @@ mod tests {
             let mut local_vars = vec![];
             for j in 0..i {
                 let slot = Slot {
-                    num: j as i32,
+                    num: j,
                     r#ref: ptr::null_mut(),
                 };
                 local_vars.push(slot);
@@ -312,6 +373,7 @@ mod tests {
                 thread: thread.clone(),
                 method: method.clone(),
                 next_pc: 0,
+                monitor: None,
             };
             stack.push(frame);
         }
@@ -356,8 +418,9 @@ mod tests {
         local_var.set_float(6, std::f64::consts::PI as f32);
         local_var.set_double(7, std::f64::consts::E);
         let object = &mut Object {
+            lock: Default::default(),
             class: NonNull::dangling(),
-            fields: LocalVars(Vec::new()),
+            fields: FieldSlots::new(0),
             marker: PhantomData,
         } as *mut Object;
         local_var.set_ref(9, object);
@@ -380,8 +443,9 @@ mod tests {
         operand_stack.push_float(std::f64::consts::PI as f32);
         operand_stack.push_double(std::f64::consts::E);
         let object = &mut Object {
+            lock: Default::default(),
             class: NonNull::dangling(),
-            fields: LocalVars(Vec::new()),
+            fields: FieldSlots::new(0),
             marker: PhantomData,
         } as *mut Object;
         operand_stack.push_ref(object);
@@ -0,0 +1,266 @@
+use crate::rtda::thread::{current, JavaThread, Threads};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+// A thread running Java code may be halfway through touching the heap or its
+// own frames, so a stop-the-world operation has to wait until it polls, at a
+// backward branch or a method return, and parks. Threads in native code or
+// blocked in the runtime are already at a known point: they count as safe
+// without polling and park on their way back into Java.
+
+static SAFEPOINT: Safepoint = Safepoint::new();
+
+pub fn safepoint() -> &'static Safepoint {
+    &SAFEPOINT
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ThreadState {
+    InJava,
+    InNative,
+    AtSafepoint,
+}
+
+#[derive(Debug)]
+pub struct ThreadStateCell(AtomicU8);
+
+impl ThreadStateCell {
+    pub const fn new(state: ThreadState) -> Self {
+        Self(AtomicU8::new(state as u8))
+    }
+
+    pub fn get(&self) -> ThreadState {
+        match self.0.load(Ordering::SeqCst) {
+            0 => ThreadState::InJava,
+            1 => ThreadState::InNative,
+            _ => ThreadState::AtSafepoint,
+        }
+    }
+
+    // sequentially consistent, paired with the requester setting `requested`
+    // before it reads the states: one of the two always sees the other
+    pub fn set(&self, state: ThreadState) {
+        self.0.store(state as u8, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+pub struct Safepoint {
+    requested: AtomicBool,
+    // held by the requester for the whole pause, so requests are serialized
+    operation: Mutex<()>,
+    lock: Mutex<()>,
+    parked: Condvar,
+    released: Condvar,
+}
+
+impl Safepoint {
+    pub const fn new() -> Self {
+        Self {
+            requested: AtomicBool::new(false),
+            operation: Mutex::new(()),
+            lock: Mutex::new(()),
+            parked: Condvar::new(),
+            released: Condvar::new(),
+        }
+    }
+
+    /// The flag polled by interpreted code, compiled code is expected to
+    /// load it directly at the same places.
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    pub fn poll(&self, thread: &JavaThread) {
+        if self.is_requested() {
+            self.park(thread);
+        }
+    }
+
+    /// Runs `f`, a native method or a call that may block, with `thread`
+    /// counted as safe, then parks it if a pause began in the meantime.
+    pub fn in_native<R, F: FnOnce() -> R>(&self, thread: &JavaThread, f: F) -> R {
+        if thread.safepoint_state() != ThreadState::InJava {
+            return f();
+        }
+        thread.set_safepoint_state(ThreadState::InNative);
+        if self.is_requested() {
+            let _lock = self.lock.lock().unwrap();
+            self.parked.notify_all();
+        }
+        let result = f();
+        self.enter_java(thread);
+        result
+    }
+
+    pub fn enter_java(&self, thread: &JavaThread) {
+        thread.set_safepoint_state(ThreadState::InJava);
+        self.poll(thread);
+    }
+
+    /// Stops every thread in `threads` but the caller at a safe point and keeps
+    /// them there until the returned guard is dropped.
+    pub fn request<'a>(&'a self, threads: &Threads) -> SafepointGuard<'a> {
+        let current = current();
+        // a concurrent requester may be waiting for us to get safe
+        let operation = self.in_native(&current, || self.operation.lock().unwrap());
+        self.requested.store(true, Ordering::SeqCst);
+        let mut lock = self.lock.lock().unwrap();
+        loop {
+            let mut all_safe = true;
+            threads.for_each(|thread| {
+                if thread.id() != current.id() && thread.safepoint_state() == ThreadState::InJava {
+                    all_safe = false;
+                }
+            });
+            if all_safe {
+                break;
+            }
+            // threads may also get safe by leaving Java without a signal, re-check periodically
+            lock = self
+                .parked
+                .wait_timeout(lock, Duration::from_millis(1))
+                .unwrap()
+                .0;
+        }
+        SafepointGuard {
+            safepoint: self,
+            _operation: operation,
+        }
+    }
+
+    fn park(&self, thread: &JavaThread) {
+        let mut lock = self.lock.lock().unwrap();
+        thread.set_safepoint_state(ThreadState::AtSafepoint);
+        self.parked.notify_all();
+        while self.is_requested() {
+            lock = self.released.wait(lock).unwrap();
+        }
+        thread.set_safepoint_state(ThreadState::InJava);
+    }
+}
+
+impl Default for Safepoint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[must_use = "the pause ends when the guard is dropped"]
+pub struct SafepointGuard<'a> {
+    safepoint: &'a Safepoint,
+    _operation: MutexGuard<'a, ()>,
+}
+
+impl Drop for SafepointGuard<'_> {
+    fn drop(&mut self) {
+        let _lock = self.safepoint.lock.lock().unwrap();
+        self.safepoint.requested.store(false, Ordering::SeqCst);
+        self.safepoint.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtda::safepoint::{safepoint, Safepoint, ThreadState};
+    use crate::rtda::thread::{current, Threads};
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    // threads started by `Threads::spawn` enter Java through the global safepoint
+    fn fixtures() -> (&'static Safepoint, &'static Threads) {
+        (safepoint(), Box::leak(Box::new(Threads::new())))
+    }
+
+    #[test]
+    fn test_pause_stops_polling_threads() {
+        let (safepoint, threads) = fixtures();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handles = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                let stop = stop.clone();
+                threads
+                    .spawn(false, ptr::null_mut(), move || {
+                        let thread = current();
+                        while !stop.load(Ordering::Relaxed) {
+                            counter.fetch_add(1, Ordering::Relaxed);
+                            safepoint.poll(&thread);
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..3 {
+            let guard = safepoint.request(threads);
+            threads.for_each(|thread| {
+                // parked, or not yet started
+                assert_ne!(thread.safepoint_state(), ThreadState::InJava);
+            });
+            let paused = counter.load(Ordering::Relaxed);
+            thread::sleep(Duration::from_millis(10));
+            assert_eq!(counter.load(Ordering::Relaxed), paused);
+            drop(guard);
+            thread::sleep(Duration::from_millis(1));
+        }
+        stop.store(true, Ordering::Relaxed);
+        for handle in handles {
+            assert!(handle.join(Some(Duration::from_secs(10))));
+        }
+    }
+
+    #[test]
+    fn test_native_thread_is_safe() {
+        let (safepoint, threads) = fixtures();
+        let returned = Arc::new(AtomicBool::new(false));
+        let thread_returned = returned.clone();
+        let handle = threads
+            .spawn(false, ptr::null_mut(), move || {
+                let thread = current();
+                safepoint.in_native(&thread, || thread::sleep(Duration::from_millis(50)));
+                // parks on the way back into Java while the pause lasts
+                thread_returned.store(true, Ordering::Relaxed);
+            })
+            .unwrap();
+        // ends right away even though the thread never polls while sleeping
+        let guard = safepoint.request(threads);
+        thread::sleep(Duration::from_millis(100));
+        assert!(!returned.load(Ordering::Relaxed));
+        assert_eq!(handle.safepoint_state(), ThreadState::AtSafepoint);
+        drop(guard);
+        assert!(handle.join(Some(Duration::from_secs(10))));
+        assert!(returned.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_concurrent_requests() {
+        let (safepoint, threads) = fixtures();
+        let pauses = Arc::new(AtomicUsize::new(0));
+        let handles = (0..4)
+            .map(|_| {
+                let pauses = pauses.clone();
+                threads
+                    .spawn(false, ptr::null_mut(), move || {
+                        let thread = current();
+                        for _ in 0..20 {
+                            safepoint.poll(&thread);
+                            let guard = safepoint.request(threads);
+                            pauses.fetch_add(1, Ordering::Relaxed);
+                            drop(guard);
+                        }
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert!(handle.join(Some(Duration::from_secs(10))));
+        }
+        assert_eq!(pauses.load(Ordering::Relaxed), 80);
+    }
+}
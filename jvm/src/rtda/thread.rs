@@ -1,19 +1,74 @@
-use crate::rtda::{Frame, Method, Stack};
+use crate::rtda::heap::Monitor;
+use crate::rtda::safepoint::{safepoint, ThreadState, ThreadStateCell};
+use crate::rtda::{Frame, LockWord, Method, Object, Stack};
+use anyhow::anyhow;
+use dashmap::DashMap;
 use std::cell::RefCell;
-use std::sync::Arc;
+use std::fmt::{Debug, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+// 0 is reserved for "no owner" in lock words and monitors
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(1);
+
+static THREADS: OnceLock<Threads> = OnceLock::new();
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<JavaThread>>> = const { RefCell::new(None) };
+}
+
+/// Why a virtual thread gives up its carrier between two instructions.
+pub enum Park {
+    /// reschedule right away
+    Yield,
+    Sleep(Duration),
+    /// a contended monitor enter, retried once the monitor is released
+    Monitor(&'static LockWord),
+    /// a blocking call run off the carrier threads, an error is thrown into
+    /// the continuation once it resumes
+    Blocking(Box<dyn FnOnce() -> anyhow::Result<()> + Send>),
+}
+
+impl Debug for Park {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Park::Yield => write!(f, "Yield"),
+            Park::Sleep(duration) => write!(f, "Sleep({:?})", duration),
+            Park::Monitor(lock) => write!(f, "Monitor({:?})", lock),
+            Park::Blocking(_) => write!(f, "Blocking"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Thread {
+    java_thread: Arc<JavaThread>,
     pc: isize,
     stack: Option<Stack>,
+    park: Option<Park>,
+    invoked: Option<Frame>,
+    // the slots the return value takes
+    returned: Option<usize>,
 }
 
+// a thread's frames are only ever touched by the host thread running them,
+// the object references they hold point into the shared heap
+unsafe impl Send for Thread {}
+
 impl Thread {
     pub fn new() -> Thread {
         Thread {
+            java_thread: current(),
             pc: 0,
             // TODO: add -Xss to set stack size
             stack: Some(Stack::new(1024)),
+            park: None,
+            invoked: None,
+            returned: None,
         }
     }
 
@@ -22,13 +77,27 @@ impl Thread {
     }
 
     pub fn pop_frame(&mut self) -> Box<Frame> {
+        // method returns are safepoint polls
+        safepoint().poll(&self.java_thread);
         self.stack.as_mut().unwrap().pop().unwrap()
     }
 
+    pub fn is_stack_empty(&self) -> bool {
+        self.stack.as_ref().is_none_or(Stack::is_empty)
+    }
+
     pub fn current_frame(&self) -> Option<&Frame> {
         self.stack.as_ref().and_then(Stack::peek)
     }
 
+    pub fn id(&self) -> usize {
+        self.java_thread.id()
+    }
+
+    pub fn java_thread(&self) -> &Arc<JavaThread> {
+        &self.java_thread
+    }
+
     pub fn pc(&self) -> isize {
         self.pc
     }
@@ -37,7 +106,433 @@ impl Thread {
         self.pc = pc;
     }
 
-    pub fn new_frame(thread: Arc<RefCell<Thread>>, method: Arc<RefCell<Method>>) -> Frame {
+    /// Asks for a virtual thread to be unmounted once the current instruction completes.
+    pub fn park(&mut self, park: Park) {
+        self.park = Some(park);
+    }
+
+    pub fn take_park(&mut self) -> Option<Park> {
+        self.park.take()
+    }
+
+    /// Asks for `frame` to run once the current instruction completes, its
+    /// caller resumes after it returns.
+    pub fn invoke(&mut self, frame: Frame) {
+        self.invoked = Some(frame);
+    }
+
+    pub fn take_invoked(&mut self) -> Option<Frame> {
+        self.invoked.take()
+    }
+
+    /// Asks for the current frame to be popped once the current instruction
+    /// completes, with the top `slots` of its operand stack as the return value.
+    pub fn set_returned(&mut self, slots: usize) {
+        self.returned = Some(slots);
+    }
+
+    pub fn take_returned(&mut self) -> Option<usize> {
+        self.returned.take()
+    }
+
+    pub fn new_frame(thread: Arc<Mutex<Thread>>, method: Arc<RwLock<Method>>) -> Frame {
         Frame::new(thread, method)
     }
 }
+
+#[derive(Debug)]
+struct LifecycleState {
+    alive: bool,
+    interrupted: bool,
+    // the monitor whose wait set the thread is in
+    waiting_on: Option<&'static Monitor>,
+}
+
+/// The host thread behind a `java.lang.Thread`.
+#[derive(Debug)]
+pub struct JavaThread {
+    id: usize,
+    daemon: bool,
+    r#virtual: bool,
+    object: AtomicPtr<Object>,
+    state: Mutex<LifecycleState>,
+    // signalled on interrupt and on termination
+    event: Condvar,
+    // wakes a parked virtual thread on interrupt
+    unparker: Notify,
+    safepoint_state: ThreadStateCell,
+}
+
+impl JavaThread {
+    fn new(daemon: bool, object: *mut Object) -> Self {
+        Self {
+            id: NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed),
+            daemon,
+            r#virtual: false,
+            object: AtomicPtr::new(object),
+            state: Mutex::new(LifecycleState {
+                alive: true,
+                interrupted: false,
+                waiting_on: None,
+            }),
+            event: Condvar::new(),
+            unparker: Notify::new(),
+            safepoint_state: ThreadStateCell::new(ThreadState::InJava),
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn safepoint_state(&self) -> ThreadState {
+        self.safepoint_state.get()
+    }
+
+    pub(crate) fn set_safepoint_state(&self, state: ThreadState) {
+        self.safepoint_state.set(state);
+    }
+
+    pub fn is_daemon(&self) -> bool {
+        self.daemon
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.r#virtual
+    }
+
+    pub fn unparker(&self) -> &Notify {
+        &self.unparker
+    }
+
+    pub fn object(&self) -> *mut Object {
+        self.object.load(Ordering::Acquire)
+    }
+
+    pub fn set_object(&self, object: *mut Object) {
+        self.object.store(object, Ordering::Release);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.state.lock().unwrap().alive
+    }
+
+    /// Sets the interrupt status and wakes the thread if it is sleeping or
+    /// in `Object.wait`, which then throws `InterruptedException`.
+    pub fn interrupt(&self) {
+        let waiting_on = {
+            let mut state = self.state.lock().unwrap();
+            state.interrupted = true;
+            self.event.notify_all();
+            state.waiting_on
+        };
+        self.unparker.notify_one();
+        // the waiter holds the monitor's lock from publishing `waiting_on` until it
+        // blocks, so this wakeup cannot slip in before it starts waiting
+        if let Some(monitor) = waiting_on {
+            monitor.interrupt_waiters();
+        }
+    }
+
+    pub(crate) fn set_waiting_on(&self, monitor: Option<&'static Monitor>) {
+        self.state.lock().unwrap().waiting_on = monitor;
+    }
+
+    pub fn is_interrupted(&self, clear: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let interrupted = state.interrupted;
+        if clear {
+            state.interrupted = false;
+        }
+        interrupted
+    }
+
+    // only called by the thread itself
+    pub fn sleep(&self, duration: Duration) -> anyhow::Result<()> {
+        let deadline = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.interrupted {
+                state.interrupted = false;
+                return Err(anyhow!("java.lang.InterruptedException: sleep interrupted"));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            state = self.event.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Waits for the thread to terminate, returns `false` if `timeout` elapsed first.
+    pub fn join(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state.lock().unwrap();
+        while state.alive {
+            state = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    self.event.wait_timeout(state, deadline - now).unwrap().0
+                }
+                None => self.event.wait(state).unwrap(),
+            };
+        }
+        true
+    }
+
+    fn terminate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.alive = false;
+        self.event.notify_all();
+    }
+}
+
+/// The java thread running on the calling host thread. Host threads not
+/// started through `Threads::spawn` get an unregistered non-daemon one.
+pub fn current() -> Arc<JavaThread> {
+    CURRENT.with(|current| {
+        current
+            .borrow_mut()
+            .get_or_insert_with(|| Arc::new(JavaThread::new(false, ptr::null_mut())))
+            .clone()
+    })
+}
+
+/// Makes `thread` the current one of the calling host thread, as a carrier
+/// does when it mounts a virtual thread, and returns the one it replaces.
+pub fn set_current(thread: Option<Arc<JavaThread>>) -> Option<Arc<JavaThread>> {
+    CURRENT.with(|current| std::mem::replace(&mut *current.borrow_mut(), thread))
+}
+
+pub fn threads() -> &'static Threads {
+    THREADS.get_or_init(Threads::new)
+}
+
+#[derive(Debug)]
+pub struct Threads {
+    threads: DashMap<usize, Arc<JavaThread>>,
+    non_daemon_count: Mutex<usize>,
+    non_daemon_exited: Condvar,
+}
+
+impl Threads {
+    pub fn new() -> Self {
+        Self {
+            threads: DashMap::new(),
+            non_daemon_count: Mutex::new(0),
+            non_daemon_exited: Condvar::new(),
+        }
+    }
+
+    pub fn spawn<F>(
+        &'static self,
+        daemon: bool,
+        object: *mut Object,
+        run: F,
+    ) -> anyhow::Result<Arc<JavaThread>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let thread = Arc::new(JavaThread::new(daemon, object));
+        // not running Java code until the host thread is up
+        thread.set_safepoint_state(ThreadState::InNative);
+        self.add(thread.clone());
+        let java_thread = thread.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("Thread-{}", thread.id()))
+            .spawn(move || {
+                CURRENT.with(|current| *current.borrow_mut() = Some(java_thread.clone()));
+                safepoint().enter_java(&java_thread);
+                // an uncaught exception only terminates its own thread
+                let _ = panic::catch_unwind(AssertUnwindSafe(run));
+                self.remove(&java_thread);
+            });
+        if let Err(e) = spawned {
+            self.remove(&thread);
+            return Err(e.into());
+        }
+        Ok(thread)
+    }
+
+    /// Registers a virtual thread, it is scheduled by the caller and counts as
+    /// safe whenever it is not mounted. Virtual threads are always daemons.
+    pub fn add_virtual(&self, object: *mut Object) -> Arc<JavaThread> {
+        let mut thread = JavaThread::new(true, object);
+        thread.r#virtual = true;
+        thread.set_safepoint_state(ThreadState::InNative);
+        let thread = Arc::new(thread);
+        self.add(thread.clone());
+        thread
+    }
+
+    /// Registers the calling host thread, e.g. the one running `main`.
+    pub fn attach_current(&self) -> Arc<JavaThread> {
+        let thread = current();
+        if !self.threads.contains_key(&thread.id()) {
+            self.add(thread.clone());
+        }
+        thread
+    }
+
+    pub fn find(&self, object: *mut Object) -> Option<Arc<JavaThread>> {
+        self.threads
+            .iter()
+            .find(|thread| thread.object() == object)
+            .map(|thread| thread.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.threads.len()
+    }
+
+    pub fn for_each<F: FnMut(&Arc<JavaThread>)>(&self, mut f: F) {
+        self.threads.iter().for_each(|thread| f(thread.value()));
+    }
+
+    /// Blocks until every non-daemon thread but the caller has terminated,
+    /// after which the VM may exit and take the daemon threads with it.
+    pub fn wait_for_non_daemon_threads(&self) {
+        let current = current();
+        let own = usize::from(!current.is_daemon() && self.threads.contains_key(&current.id()));
+        let mut count = self.non_daemon_count.lock().unwrap();
+        while *count > own {
+            count = self.non_daemon_exited.wait(count).unwrap();
+        }
+    }
+
+    fn add(&self, thread: Arc<JavaThread>) {
+        if !thread.is_daemon() {
+            *self.non_daemon_count.lock().unwrap() += 1;
+        }
+        self.threads.insert(thread.id(), thread);
+    }
+
+    pub(crate) fn remove(&self, thread: &Arc<JavaThread>) {
+        thread.set_safepoint_state(ThreadState::InNative);
+        self.threads.remove(&thread.id());
+        thread.terminate();
+        // Thread.join waits on the Thread object until isAlive turns false
+        let object = thread.object();
+        if !object.is_null() {
+            unsafe {
+                (*object).monitor_enter(thread.id());
+                (*object).notify_all(thread.id());
+                (*object).monitor_exit(thread.id());
+            }
+        }
+        if !thread.is_daemon() {
+            let mut count = self.non_daemon_count.lock().unwrap();
+            *count -= 1;
+            self.non_daemon_exited.notify_all();
+        }
+    }
+}
+
+impl Default for Threads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtda::thread::{current, Threads};
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn threads() -> &'static Threads {
+        Box::leak(Box::new(Threads::new()))
+    }
+
+    #[test]
+    fn test_spawn_and_join() {
+        let threads = threads();
+        let (tx, rx) = mpsc::channel();
+        let thread = threads
+            .spawn(false, ptr::null_mut(), move || {
+                tx.send(current().id()).unwrap();
+            })
+            .unwrap();
+        assert!(thread.join(None));
+        assert!(!thread.is_alive());
+        assert_eq!(rx.recv().unwrap(), thread.id());
+        assert_ne!(thread.id(), current().id());
+        assert_eq!(threads.len(), 0);
+    }
+
+    #[test]
+    fn test_uncaught_exception_terminates_thread() {
+        let threads = threads();
+        let thread = threads
+            .spawn(false, ptr::null_mut(), || {
+                panic!("java.lang.RuntimeException")
+            })
+            .unwrap();
+        assert!(thread.join(Some(Duration::from_secs(10))));
+        threads.wait_for_non_daemon_threads();
+    }
+
+    #[test]
+    fn test_interrupt_sleep() {
+        let threads = threads();
+        let (tx, rx) = mpsc::channel();
+        let thread = threads
+            .spawn(false, ptr::null_mut(), move || {
+                let slept = current().sleep(Duration::from_secs(60));
+                tx.send(slept.map_err(|e| e.to_string())).unwrap();
+            })
+            .unwrap();
+        assert!(!thread.join(Some(Duration::from_millis(20))));
+        thread.interrupt();
+        let slept = rx.recv().unwrap();
+        assert!(slept
+            .unwrap_err()
+            .starts_with("java.lang.InterruptedException"));
+        // the interrupt status is cleared by the exception
+        assert!(!thread.is_interrupted(false));
+        assert!(thread.join(None));
+    }
+
+    #[test]
+    fn test_sleep_with_pending_interrupt() {
+        let thread = current();
+        thread.interrupt();
+        assert!(thread.sleep(Duration::from_secs(60)).is_err());
+        assert!(thread.sleep(Duration::from_millis(1)).is_ok());
+    }
+
+    #[test]
+    fn test_wait_for_non_daemon_threads() {
+        let threads = threads();
+        let done = Arc::new(AtomicBool::new(false));
+        let release = Arc::new(AtomicBool::new(false));
+        let daemon_release = release.clone();
+        let daemon = threads
+            .spawn(true, ptr::null_mut(), move || {
+                while !daemon_release.load(Ordering::Acquire) {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            })
+            .unwrap();
+        let worker_done = done.clone();
+        threads
+            .spawn(false, ptr::null_mut(), move || {
+                std::thread::sleep(Duration::from_millis(50));
+                worker_done.store(true, Ordering::Release);
+            })
+            .unwrap();
+        threads.attach_current();
+        threads.wait_for_non_daemon_threads();
+        assert!(done.load(Ordering::Acquire));
+        // the daemon does not hold up exit
+        assert!(daemon.is_alive());
+        release.store(true, Ordering::Release);
+        assert!(daemon.join(None));
+    }
+}
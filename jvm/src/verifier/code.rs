@@ -0,0 +1,413 @@
+use crate::instructions::OpCode;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// A local variable index, implicit for the `xload_n`/`xstore_n` forms.
+    Local(u16),
+    Iinc {
+        index: u16,
+        value: i16,
+    },
+    Int(i32),
+    /// A constant pool index.
+    Index(u16),
+    InvokeInterface {
+        index: u16,
+        count: u8,
+    },
+    /// The absolute offset a branch jumps to.
+    Branch(usize),
+    TableSwitch {
+        default: usize,
+        targets: Vec<usize>,
+    },
+    LookupSwitch {
+        default: usize,
+        pairs: Vec<(i32, usize)>,
+    },
+    NewArray(u8),
+    MultiANewArray {
+        index: u16,
+        dimensions: u8,
+    },
+}
+
+/// A decoded instruction, the opcode of a `wide` instruction is the one it modifies.
+#[derive(Debug, Clone)]
+pub struct Insn {
+    pub offset: usize,
+    pub length: usize,
+    pub opcode: OpCode,
+    pub operand: Operand,
+}
+
+impl Insn {
+    /// The locals a store or `iinc` writes.
+    pub fn stored_locals(&self) -> Option<Range<usize>> {
+        let size = match self.opcode {
+            OpCode::istore
+            | OpCode::fstore
+            | OpCode::astore
+            | OpCode::iinc
+            | OpCode::istore_0
+            | OpCode::istore_1
+            | OpCode::istore_2
+            | OpCode::istore_3
+            | OpCode::fstore_0
+            | OpCode::fstore_1
+            | OpCode::fstore_2
+            | OpCode::fstore_3
+            | OpCode::astore_0
+            | OpCode::astore_1
+            | OpCode::astore_2
+            | OpCode::astore_3 => 1,
+            OpCode::lstore
+            | OpCode::dstore
+            | OpCode::lstore_0
+            | OpCode::lstore_1
+            | OpCode::lstore_2
+            | OpCode::lstore_3
+            | OpCode::dstore_0
+            | OpCode::dstore_1
+            | OpCode::dstore_2
+            | OpCode::dstore_3 => 2,
+            _ => return None,
+        };
+        match self.operand {
+            Operand::Local(index) | Operand::Iinc { index, .. } => {
+                Some(index as usize..index as usize + size)
+            }
+            _ => None,
+        }
+    }
+
+    /// The offsets this instruction may jump to, other than the next one.
+    pub fn branch_targets(&self) -> Vec<usize> {
+        match &self.operand {
+            Operand::Branch(target) => vec![*target],
+            Operand::TableSwitch { default, targets } => {
+                let mut all = vec![*default];
+                all.extend(targets);
+                all
+            }
+            Operand::LookupSwitch { default, pairs } => {
+                let mut all = vec![*default];
+                all.extend(pairs.iter().map(|(_, target)| *target));
+                all
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// Decodes a whole `code` array, reporting the offset of the first malformed instruction.
+pub fn decode_all(code: &[u8]) -> Result<Vec<Insn>, (usize, String)> {
+    if code.is_empty() || code.len() > u16::MAX as usize {
+        return Err((0, format!("Invalid code length {}", code.len())));
+    }
+    let mut insns = vec![];
+    let mut offset = 0;
+    while offset < code.len() {
+        let insn = decode(code, offset).map_err(|e| (offset, e))?;
+        offset += insn.length;
+        insns.push(insn);
+    }
+    Ok(insns)
+}
+
+pub fn decode(code: &[u8], offset: usize) -> Result<Insn, String> {
+    let mut reader = Reader { code, pos: offset };
+    let byte = reader.u8()?;
+    // 0xca breakpoint and the implementation-dependent opcodes may not appear in class files
+    if byte > OpCode::jsr_w as u8 {
+        return Err(format!("Bad instruction: {:#04x}", byte));
+    }
+    let opcode = OpCode::from(byte);
+    let operand = match opcode {
+        OpCode::bipush => Operand::Int(reader.u8()? as i8 as i32),
+        OpCode::sipush => Operand::Int(reader.u16()? as i16 as i32),
+        OpCode::ldc => Operand::Index(reader.u8()? as u16),
+        OpCode::ldc_w
+        | OpCode::ldc2_w
+        | OpCode::getstatic
+        | OpCode::putstatic
+        | OpCode::getfield
+        | OpCode::putfield
+        | OpCode::invokevirtual
+        | OpCode::invokespecial
+        | OpCode::invokestatic
+        | OpCode::new
+        | OpCode::anewarray
+        | OpCode::checkcast
+        | OpCode::instanceof => Operand::Index(reader.u16()?),
+        OpCode::iload
+        | OpCode::lload
+        | OpCode::fload
+        | OpCode::dload
+        | OpCode::aload
+        | OpCode::istore
+        | OpCode::lstore
+        | OpCode::fstore
+        | OpCode::dstore
+        | OpCode::astore
+        | OpCode::ret => Operand::Local(reader.u8()? as u16),
+        OpCode::iload_0
+        | OpCode::iload_1
+        | OpCode::iload_2
+        | OpCode::iload_3
+        | OpCode::lload_0
+        | OpCode::lload_1
+        | OpCode::lload_2
+        | OpCode::lload_3
+        | OpCode::fload_0
+        | OpCode::fload_1
+        | OpCode::fload_2
+        | OpCode::fload_3
+        | OpCode::dload_0
+        | OpCode::dload_1
+        | OpCode::dload_2
+        | OpCode::dload_3
+        | OpCode::aload_0
+        | OpCode::aload_1
+        | OpCode::aload_2
+        | OpCode::aload_3 => Operand::Local(implicit_local(byte, OpCode::iload_0)),
+        OpCode::istore_0
+        | OpCode::istore_1
+        | OpCode::istore_2
+        | OpCode::istore_3
+        | OpCode::lstore_0
+        | OpCode::lstore_1
+        | OpCode::lstore_2
+        | OpCode::lstore_3
+        | OpCode::fstore_0
+        | OpCode::fstore_1
+        | OpCode::fstore_2
+        | OpCode::fstore_3
+        | OpCode::dstore_0
+        | OpCode::dstore_1
+        | OpCode::dstore_2
+        | OpCode::dstore_3
+        | OpCode::astore_0
+        | OpCode::astore_1
+        | OpCode::astore_2
+        | OpCode::astore_3 => Operand::Local(implicit_local(byte, OpCode::istore_0)),
+        OpCode::iinc => Operand::Iinc {
+            index: reader.u8()? as u16,
+            value: reader.u8()? as i8 as i16,
+        },
+        OpCode::ifeq
+        | OpCode::ifne
+        | OpCode::iflt
+        | OpCode::ifge
+        | OpCode::ifgt
+        | OpCode::ifle
+        | OpCode::if_icmpeq
+        | OpCode::if_icmpne
+        | OpCode::if_icmplt
+        | OpCode::if_icmpge
+        | OpCode::if_icmpgt
+        | OpCode::if_icmple
+        | OpCode::if_acmpeq
+        | OpCode::if_acmpne
+        | OpCode::goto
+        | OpCode::jsr
+        | OpCode::ifnull
+        | OpCode::ifnonnull => Operand::Branch(target(code, offset, reader.u16()? as i16 as i32)?),
+        OpCode::goto_w | OpCode::jsr_w => Operand::Branch(target(code, offset, reader.i32()?)?),
+        OpCode::tableswitch => {
+            reader.align()?;
+            let default = target(code, offset, reader.i32()?)?;
+            let low = reader.i32()?;
+            let high = reader.i32()?;
+            if low > high {
+                return Err(format!("Bad tableswitch range {}..{}", low, high));
+            }
+            let count = (high as i64 - low as i64 + 1) as usize;
+            if count > code.len() / 4 {
+                return Err("Bad tableswitch length".to_string());
+            }
+            let targets = (0..count)
+                .map(|_| target(code, offset, reader.i32()?))
+                .collect::<Result<_, _>>()?;
+            Operand::TableSwitch { default, targets }
+        }
+        OpCode::lookupswitch => {
+            reader.align()?;
+            let default = target(code, offset, reader.i32()?)?;
+            let count = reader.i32()?;
+            if count < 0 || count as usize > code.len() / 8 {
+                return Err("Bad lookupswitch length".to_string());
+            }
+            let mut pairs: Vec<(i32, usize)> = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let key = reader.i32()?;
+                if pairs.last().is_some_and(|(last, _)| *last >= key) {
+                    return Err("Bad lookupswitch sort order".to_string());
+                }
+                pairs.push((key, target(code, offset, reader.i32()?)?));
+            }
+            Operand::LookupSwitch { default, pairs }
+        }
+        OpCode::invokeinterface => {
+            let index = reader.u16()?;
+            let count = reader.u8()?;
+            if count == 0 || reader.u8()? != 0 {
+                return Err("Bad invokeinterface operands".to_string());
+            }
+            Operand::InvokeInterface { index, count }
+        }
+        OpCode::invokedynamic => {
+            let index = reader.u16()?;
+            if reader.u16()? != 0 {
+                return Err("Bad invokedynamic operands".to_string());
+            }
+            Operand::Index(index)
+        }
+        OpCode::newarray => Operand::NewArray(reader.u8()?),
+        OpCode::multianewarray => Operand::MultiANewArray {
+            index: reader.u16()?,
+            dimensions: reader.u8()?,
+        },
+        OpCode::wide => {
+            let modified = reader.u8()?;
+            let opcode = if modified <= OpCode::jsr_w as u8 {
+                OpCode::from(modified)
+            } else {
+                OpCode::wide
+            };
+            let operand = match opcode {
+                OpCode::iload
+                | OpCode::lload
+                | OpCode::fload
+                | OpCode::dload
+                | OpCode::aload
+                | OpCode::istore
+                | OpCode::lstore
+                | OpCode::fstore
+                | OpCode::dstore
+                | OpCode::astore
+                | OpCode::ret => Operand::Local(reader.u16()?),
+                OpCode::iinc => Operand::Iinc {
+                    index: reader.u16()?,
+                    value: reader.u16()? as i16,
+                },
+                _ => return Err(format!("Bad wide instruction: {:#04x}", modified)),
+            };
+            return Ok(Insn {
+                offset,
+                length: reader.pos - offset,
+                opcode,
+                operand,
+            });
+        }
+        _ => Operand::None,
+    };
+    Ok(Insn {
+        offset,
+        length: reader.pos - offset,
+        opcode,
+        operand,
+    })
+}
+
+// the xload_n and xstore_n opcodes come in groups of four per type
+fn implicit_local(byte: u8, first: OpCode) -> u16 {
+    ((byte - first as u8) % 4) as u16
+}
+
+fn target(code: &[u8], offset: usize, delta: i32) -> Result<usize, String> {
+    let target = offset as i64 + delta as i64;
+    if target < 0 || target >= code.len() as i64 {
+        return Err(format!("Illegal target of jump or branch {}", target));
+    }
+    Ok(target as usize)
+}
+
+struct Reader<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .code
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| "Instruction extends past the end of code".to_string())?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.bytes()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.bytes()?))
+    }
+
+    // switch operands start at the next multiple of four
+    fn align(&mut self) -> Result<(), String> {
+        while !self.pos.is_multiple_of(4) {
+            self.u8()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instructions::OpCode;
+    use crate::verifier::code::{decode, decode_all, Operand};
+
+    #[test]
+    fn test_decode() {
+        // iload_2, wide iinc 300 -2, goto -4, nop
+        let code = [
+            0x1c, 0xc4, 0x84, 0x01, 0x2c, 0xff, 0xfe, 0xa7, 0xff, 0xf9, 0x00,
+        ];
+        let insns = decode_all(&code).unwrap();
+        assert_eq!(insns.len(), 4);
+        assert_eq!(insns[0].operand, Operand::Local(2));
+        assert_eq!(insns[1].opcode, OpCode::iinc);
+        assert_eq!(insns[1].length, 6);
+        assert_eq!(
+            insns[1].operand,
+            Operand::Iinc {
+                index: 300,
+                value: -2
+            }
+        );
+        assert_eq!(insns[2].operand, Operand::Branch(0));
+        assert_eq!(insns[2].branch_targets(), vec![0]);
+    }
+
+    #[test]
+    fn test_decode_switch() {
+        // nop, tableswitch padded to offset 4, default +13, 1..=1 -> +12
+        let code = [
+            0x00, 0xaa, 0, 0, 0, 0, 0, 12, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 16, 0xb1,
+        ];
+        let insn = decode(&code, 1).unwrap();
+        assert_eq!(insn.length, 19);
+        assert_eq!(insn.branch_targets(), vec![13, 17]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(decode_all(&[]).is_err());
+        assert_eq!(decode_all(&[0x00, 0xca]).unwrap_err().0, 1);
+        // branch out of the code
+        assert!(decode_all(&[0xa7, 0x00, 0x10]).is_err());
+        // truncated operand
+        assert!(decode_all(&[0x11, 0x00]).is_err());
+        // wide may not modify bipush
+        assert!(decode_all(&[0xc4, 0x10, 0x00, 0x00]).is_err());
+    }
+}
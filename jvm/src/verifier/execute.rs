@@ -0,0 +1,744 @@
+use crate::instructions::OpCode;
+use crate::verifier::code::{Insn, Operand};
+use crate::verifier::types::{from_field_type, parse_method_descriptor, Frame, VType};
+use crate::verifier::{class_name, name_and_type, ClassHierarchy, Context};
+use classfile::Constant;
+
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// Applies the effect of `insn` to `frame`, returning whether execution can
+/// continue with the next instruction. `jsr` and `ret` are left to the caller.
+pub fn execute(ctx: &Context, frame: &mut Frame, insn: &Insn) -> Result<bool, String> {
+    let h = ctx.hierarchy;
+    let local = || match insn.operand {
+        Operand::Local(index) | Operand::Iinc { index, .. } => index as usize,
+        _ => unreachable!(),
+    };
+    match insn.opcode {
+        OpCode::nop => {}
+        OpCode::aconst_null => frame.push(VType::Null)?,
+        OpCode::iconst_m1
+        | OpCode::iconst_0
+        | OpCode::iconst_1
+        | OpCode::iconst_2
+        | OpCode::iconst_3
+        | OpCode::iconst_4
+        | OpCode::iconst_5
+        | OpCode::bipush
+        | OpCode::sipush => frame.push(VType::Integer)?,
+        OpCode::lconst_0 | OpCode::lconst_1 => frame.push(VType::Long)?,
+        OpCode::fconst_0 | OpCode::fconst_1 | OpCode::fconst_2 => frame.push(VType::Float)?,
+        OpCode::dconst_0 | OpCode::dconst_1 => frame.push(VType::Double)?,
+        OpCode::ldc | OpCode::ldc_w | OpCode::ldc2_w => {
+            let vtype = constant_type(ctx, index(insn))?;
+            if vtype.is_category2() != (insn.opcode == OpCode::ldc2_w) {
+                return Err(format!("Bad constant type {} for {:?}", vtype, insn.opcode));
+            }
+            frame.push(vtype)?;
+        }
+
+        OpCode::iload | OpCode::iload_0 | OpCode::iload_1 | OpCode::iload_2 | OpCode::iload_3 => {
+            frame.push(frame.load(local(), &VType::Integer, h)?)?
+        }
+        OpCode::lload | OpCode::lload_0 | OpCode::lload_1 | OpCode::lload_2 | OpCode::lload_3 => {
+            frame.push(frame.load(local(), &VType::Long, h)?)?
+        }
+        OpCode::fload | OpCode::fload_0 | OpCode::fload_1 | OpCode::fload_2 | OpCode::fload_3 => {
+            frame.push(frame.load(local(), &VType::Float, h)?)?
+        }
+        OpCode::dload | OpCode::dload_0 | OpCode::dload_1 | OpCode::dload_2 | OpCode::dload_3 => {
+            frame.push(frame.load(local(), &VType::Double, h)?)?
+        }
+        OpCode::aload | OpCode::aload_0 | OpCode::aload_1 | OpCode::aload_2 | OpCode::aload_3 => {
+            let vtype = frame.local(local())?.clone();
+            if !vtype.is_reference() {
+                return Err(format!(
+                    "Bad local variable type: expected a reference, found {}",
+                    vtype
+                ));
+            }
+            frame.push(vtype)?;
+        }
+        OpCode::iaload => array_load(frame, &["[I"], VType::Integer)?,
+        OpCode::laload => array_load(frame, &["[J"], VType::Long)?,
+        OpCode::faload => array_load(frame, &["[F"], VType::Float)?,
+        OpCode::daload => array_load(frame, &["[D"], VType::Double)?,
+        OpCode::baload => array_load(frame, &["[B", "[Z"], VType::Integer)?,
+        OpCode::caload => array_load(frame, &["[C"], VType::Integer)?,
+        OpCode::saload => array_load(frame, &["[S"], VType::Integer)?,
+        OpCode::aaload => {
+            frame.pop_type(&VType::Integer, h)?;
+            let array = frame.pop_array()?;
+            match array.component() {
+                Some(component) if component.is_reference() => frame.push(component)?,
+                None => frame.push(VType::Null)?,
+                _ => {
+                    return Err(format!(
+                        "Bad type on operand stack: {} is not an array of references",
+                        array
+                    ))
+                }
+            }
+        }
+
+        OpCode::istore
+        | OpCode::istore_0
+        | OpCode::istore_1
+        | OpCode::istore_2
+        | OpCode::istore_3 => store(frame, local(), &VType::Integer, h)?,
+        OpCode::lstore
+        | OpCode::lstore_0
+        | OpCode::lstore_1
+        | OpCode::lstore_2
+        | OpCode::lstore_3 => store(frame, local(), &VType::Long, h)?,
+        OpCode::fstore
+        | OpCode::fstore_0
+        | OpCode::fstore_1
+        | OpCode::fstore_2
+        | OpCode::fstore_3 => store(frame, local(), &VType::Float, h)?,
+        OpCode::dstore
+        | OpCode::dstore_0
+        | OpCode::dstore_1
+        | OpCode::dstore_2
+        | OpCode::dstore_3 => store(frame, local(), &VType::Double, h)?,
+        OpCode::astore
+        | OpCode::astore_0
+        | OpCode::astore_1
+        | OpCode::astore_2
+        | OpCode::astore_3 => {
+            // a subroutine saves its return address with astore
+            let vtype = frame.pop()?;
+            if !vtype.is_reference() && !matches!(vtype, VType::ReturnAddress(_)) {
+                return Err(format!(
+                    "Bad type on operand stack: expected a reference, found {}",
+                    vtype
+                ));
+            }
+            frame.store(local(), vtype)?;
+        }
+        OpCode::iastore => array_store(frame, &["[I"], VType::Integer, ctx)?,
+        OpCode::lastore => array_store(frame, &["[J"], VType::Long, ctx)?,
+        OpCode::fastore => array_store(frame, &["[F"], VType::Float, ctx)?,
+        OpCode::dastore => array_store(frame, &["[D"], VType::Double, ctx)?,
+        OpCode::bastore => array_store(frame, &["[B", "[Z"], VType::Integer, ctx)?,
+        OpCode::castore => array_store(frame, &["[C"], VType::Integer, ctx)?,
+        OpCode::sastore => array_store(frame, &["[S"], VType::Integer, ctx)?,
+        OpCode::aastore => {
+            // the component type is checked at run time
+            frame.pop_reference()?;
+            frame.pop_type(&VType::Integer, h)?;
+            let array = frame.pop_array()?;
+            if array
+                .component()
+                .is_some_and(|component| !component.is_reference())
+            {
+                return Err(format!(
+                    "Bad type on operand stack: {} is not an array of references",
+                    array
+                ));
+            }
+        }
+
+        OpCode::pop => {
+            pop_category1(frame)?;
+        }
+        OpCode::pop2 => {
+            if !frame.pop()?.is_category2() {
+                pop_category1(frame)?;
+            }
+        }
+        OpCode::dup => {
+            let v1 = pop_category1(frame)?;
+            push_all(frame, [&v1, &v1])?;
+        }
+        OpCode::dup_x1 => {
+            let v1 = pop_category1(frame)?;
+            let v2 = pop_category1(frame)?;
+            push_all(frame, [&v1, &v2, &v1])?;
+        }
+        OpCode::dup_x2 => {
+            let v1 = pop_category1(frame)?;
+            let v2 = frame.pop()?;
+            if v2.is_category2() {
+                push_all(frame, [&v1, &v2, &v1])?;
+            } else {
+                let v3 = pop_category1(frame)?;
+                push_all(frame, [&v1, &v3, &v2, &v1])?;
+            }
+        }
+        OpCode::dup2 => {
+            let v1 = frame.pop()?;
+            if v1.is_category2() {
+                push_all(frame, [&v1, &v1])?;
+            } else {
+                let v2 = pop_category1(frame)?;
+                push_all(frame, [&v2, &v1, &v2, &v1])?;
+            }
+        }
+        OpCode::dup2_x1 => {
+            let v1 = frame.pop()?;
+            if v1.is_category2() {
+                let v2 = pop_category1(frame)?;
+                push_all(frame, [&v1, &v2, &v1])?;
+            } else {
+                let v2 = pop_category1(frame)?;
+                let v3 = pop_category1(frame)?;
+                push_all(frame, [&v2, &v1, &v3, &v2, &v1])?;
+            }
+        }
+        OpCode::dup2_x2 => {
+            let v1 = frame.pop()?;
+            if v1.is_category2() {
+                let v2 = frame.pop()?;
+                if v2.is_category2() {
+                    push_all(frame, [&v1, &v2, &v1])?;
+                } else {
+                    let v3 = pop_category1(frame)?;
+                    push_all(frame, [&v1, &v3, &v2, &v1])?;
+                }
+            } else {
+                let v2 = pop_category1(frame)?;
+                let v3 = frame.pop()?;
+                if v3.is_category2() {
+                    push_all(frame, [&v2, &v1, &v3, &v2, &v1])?;
+                } else {
+                    let v4 = pop_category1(frame)?;
+                    push_all(frame, [&v2, &v1, &v4, &v3, &v2, &v1])?;
+                }
+            }
+        }
+        OpCode::swap => {
+            let v1 = pop_category1(frame)?;
+            let v2 = pop_category1(frame)?;
+            push_all(frame, [&v1, &v2])?;
+        }
+
+        OpCode::iadd
+        | OpCode::isub
+        | OpCode::imul
+        | OpCode::idiv
+        | OpCode::irem
+        | OpCode::ishl
+        | OpCode::ishr
+        | OpCode::iushr
+        | OpCode::iand
+        | OpCode::ior
+        | OpCode::ixor => binary(frame, &VType::Integer, &VType::Integer, VType::Integer, h)?,
+        OpCode::ladd
+        | OpCode::lsub
+        | OpCode::lmul
+        | OpCode::ldiv
+        | OpCode::lrem
+        | OpCode::land
+        | OpCode::lor
+        | OpCode::lxor => binary(frame, &VType::Long, &VType::Long, VType::Long, h)?,
+        OpCode::lshl | OpCode::lshr | OpCode::lushr => {
+            binary(frame, &VType::Long, &VType::Integer, VType::Long, h)?
+        }
+        OpCode::fadd | OpCode::fsub | OpCode::fmul | OpCode::fdiv | OpCode::frem => {
+            binary(frame, &VType::Float, &VType::Float, VType::Float, h)?
+        }
+        OpCode::dadd | OpCode::dsub | OpCode::dmul | OpCode::ddiv | OpCode::drem => {
+            binary(frame, &VType::Double, &VType::Double, VType::Double, h)?
+        }
+        OpCode::ineg | OpCode::i2b | OpCode::i2c | OpCode::i2s => {
+            unary(frame, &VType::Integer, VType::Integer, h)?
+        }
+        OpCode::lneg => unary(frame, &VType::Long, VType::Long, h)?,
+        OpCode::fneg => unary(frame, &VType::Float, VType::Float, h)?,
+        OpCode::dneg => unary(frame, &VType::Double, VType::Double, h)?,
+        OpCode::iinc => {
+            frame.load(local(), &VType::Integer, h)?;
+        }
+        OpCode::i2l => unary(frame, &VType::Integer, VType::Long, h)?,
+        OpCode::i2f => unary(frame, &VType::Integer, VType::Float, h)?,
+        OpCode::i2d => unary(frame, &VType::Integer, VType::Double, h)?,
+        OpCode::l2i => unary(frame, &VType::Long, VType::Integer, h)?,
+        OpCode::l2f => unary(frame, &VType::Long, VType::Float, h)?,
+        OpCode::l2d => unary(frame, &VType::Long, VType::Double, h)?,
+        OpCode::f2i => unary(frame, &VType::Float, VType::Integer, h)?,
+        OpCode::f2l => unary(frame, &VType::Float, VType::Long, h)?,
+        OpCode::f2d => unary(frame, &VType::Float, VType::Double, h)?,
+        OpCode::d2i => unary(frame, &VType::Double, VType::Integer, h)?,
+        OpCode::d2l => unary(frame, &VType::Double, VType::Long, h)?,
+        OpCode::d2f => unary(frame, &VType::Double, VType::Float, h)?,
+
+        OpCode::lcmp => binary(frame, &VType::Long, &VType::Long, VType::Integer, h)?,
+        OpCode::fcmpl | OpCode::fcmpg => {
+            binary(frame, &VType::Float, &VType::Float, VType::Integer, h)?
+        }
+        OpCode::dcmpl | OpCode::dcmpg => {
+            binary(frame, &VType::Double, &VType::Double, VType::Integer, h)?
+        }
+        OpCode::ifeq | OpCode::ifne | OpCode::iflt | OpCode::ifge | OpCode::ifgt | OpCode::ifle => {
+            frame.pop_type(&VType::Integer, h)?;
+        }
+        OpCode::if_icmpeq
+        | OpCode::if_icmpne
+        | OpCode::if_icmplt
+        | OpCode::if_icmpge
+        | OpCode::if_icmpgt
+        | OpCode::if_icmple => {
+            frame.pop_type(&VType::Integer, h)?;
+            frame.pop_type(&VType::Integer, h)?;
+        }
+        OpCode::if_acmpeq | OpCode::if_acmpne => {
+            pop_any_reference(frame)?;
+            pop_any_reference(frame)?;
+        }
+        OpCode::ifnull | OpCode::ifnonnull => {
+            pop_any_reference(frame)?;
+        }
+        OpCode::goto | OpCode::goto_w => return Ok(false),
+        OpCode::tableswitch | OpCode::lookupswitch => {
+            frame.pop_type(&VType::Integer, h)?;
+            return Ok(false);
+        }
+        OpCode::ireturn => return return_value(ctx, frame, &VType::Integer),
+        OpCode::lreturn => return return_value(ctx, frame, &VType::Long),
+        OpCode::freturn => return return_value(ctx, frame, &VType::Float),
+        OpCode::dreturn => return return_value(ctx, frame, &VType::Double),
+        OpCode::areturn => return return_value(ctx, frame, &VType::object("java/lang/Object")),
+        OpCode::vreturn => {
+            if ctx.return_type.is_some() {
+                return Err("Method expects a return value".to_string());
+            }
+            if frame.flag_this_uninit {
+                return Err("Constructor must call super() or this() before return".to_string());
+            }
+            return Ok(false);
+        }
+        OpCode::athrow => {
+            frame.pop_type(&VType::object(THROWABLE), h)?;
+            return Ok(false);
+        }
+
+        OpCode::getstatic | OpCode::putstatic | OpCode::getfield | OpCode::putfield => {
+            field_access(ctx, frame, insn)?
+        }
+        OpCode::invokevirtual
+        | OpCode::invokespecial
+        | OpCode::invokestatic
+        | OpCode::invokeinterface
+        | OpCode::invokedynamic => invoke(ctx, frame, insn)?,
+        OpCode::new => {
+            let class = class_name(ctx.cp, index(insn))?;
+            if class.starts_with('[') {
+                return Err(format!("Illegal new instruction for array class {}", class));
+            }
+            let vtype = VType::Uninitialized(insn.offset as u16);
+            // a loop may execute the same new again, the earlier object is lost
+            frame.initialize(&vtype, &VType::Top);
+            frame.push(vtype)?;
+        }
+        OpCode::newarray => {
+            let descriptor = match insn.operand {
+                Operand::NewArray(4) => "[Z",
+                Operand::NewArray(5) => "[C",
+                Operand::NewArray(6) => "[F",
+                Operand::NewArray(7) => "[D",
+                Operand::NewArray(8) => "[B",
+                Operand::NewArray(9) => "[S",
+                Operand::NewArray(10) => "[I",
+                Operand::NewArray(11) => "[J",
+                _ => return Err("Illegal newarray type".to_string()),
+            };
+            frame.pop_type(&VType::Integer, h)?;
+            frame.push(VType::object(descriptor))?;
+        }
+        OpCode::anewarray => {
+            let class = class_name(ctx.cp, index(insn))?;
+            let array = if class.starts_with('[') {
+                format!("[{}", class)
+            } else {
+                format!("[L{};", class)
+            };
+            if array.bytes().take_while(|b| *b == b'[').count() > 255 {
+                return Err("Array with too many dimensions".to_string());
+            }
+            frame.pop_type(&VType::Integer, h)?;
+            frame.push(VType::Reference(array))?;
+        }
+        OpCode::arraylength => {
+            frame.pop_array()?;
+            frame.push(VType::Integer)?;
+        }
+        OpCode::checkcast => {
+            let class = class_name(ctx.cp, index(insn))?;
+            frame.pop_reference()?;
+            frame.push(VType::Reference(class))?;
+        }
+        OpCode::instanceof => {
+            class_name(ctx.cp, index(insn))?;
+            frame.pop_reference()?;
+            frame.push(VType::Integer)?;
+        }
+        OpCode::monitorenter | OpCode::monitorexit => {
+            frame.pop_reference()?;
+        }
+        OpCode::multianewarray => {
+            let (index, dimensions) = match insn.operand {
+                Operand::MultiANewArray { index, dimensions } => (index, dimensions as usize),
+                _ => unreachable!(),
+            };
+            let class = class_name(ctx.cp, index)?;
+            if dimensions == 0 || class.bytes().take_while(|b| *b == b'[').count() < dimensions {
+                return Err(format!("Bad dimensions {} for {}", dimensions, class));
+            }
+            for _ in 0..dimensions {
+                frame.pop_type(&VType::Integer, h)?;
+            }
+            frame.push(VType::Reference(class))?;
+        }
+        OpCode::jsr | OpCode::jsr_w | OpCode::ret => {
+            return Err(format!(
+                "{:?} is not allowed in a class file verified by type checking",
+                insn.opcode
+            ))
+        }
+        opcode => return Err(format!("Bad instruction: {:?}", opcode)),
+    }
+    Ok(true)
+}
+
+fn index(insn: &Insn) -> u16 {
+    match insn.operand {
+        Operand::Index(index) => index,
+        _ => unreachable!(),
+    }
+}
+
+fn constant_type(ctx: &Context, index: u16) -> Result<VType, String> {
+    let vtype = match ctx.cp.get(index as usize) {
+        Some(Constant::Integer(_)) => VType::Integer,
+        Some(Constant::Float(_)) => VType::Float,
+        Some(Constant::Long(_)) => VType::Long,
+        Some(Constant::Double(_)) => VType::Double,
+        Some(Constant::String { .. }) => VType::object("java/lang/String"),
+        Some(Constant::Class { .. }) if ctx.major_version >= 49 => VType::object("java/lang/Class"),
+        Some(Constant::MethodType { .. }) if ctx.major_version >= 51 => {
+            VType::object("java/lang/invoke/MethodType")
+        }
+        Some(Constant::MethodHandle { .. }) if ctx.major_version >= 51 => {
+            VType::object("java/lang/invoke/MethodHandle")
+        }
+        Some(Constant::Dynamic {
+            name_and_type_index,
+            ..
+        }) if ctx.major_version >= 55 => {
+            let (_, descriptor) = name_and_type(ctx.cp, *name_and_type_index)?;
+            from_field_type(&descriptor)
+                .ok_or_else(|| format!("Bad field descriptor {}", descriptor))?
+        }
+        _ => return Err(format!("Illegal constant pool index {} for ldc", index)),
+    };
+    Ok(vtype)
+}
+
+fn store(
+    frame: &mut Frame,
+    index: usize,
+    vtype: &VType,
+    h: &dyn ClassHierarchy,
+) -> Result<(), String> {
+    let value = frame.pop_type(vtype, h)?;
+    frame.store(index, value)
+}
+
+fn array_load(frame: &mut Frame, arrays: &[&str], element: VType) -> Result<(), String> {
+    if frame.pop()? != VType::Integer {
+        return Err("Bad type on operand stack: expected an integer index".to_string());
+    }
+    check_array(frame.pop_array()?, arrays)?;
+    frame.push(element)
+}
+
+fn array_store(
+    frame: &mut Frame,
+    arrays: &[&str],
+    element: VType,
+    ctx: &Context,
+) -> Result<(), String> {
+    frame.pop_type(&element, ctx.hierarchy)?;
+    frame.pop_type(&VType::Integer, ctx.hierarchy)?;
+    check_array(frame.pop_array()?, arrays)
+}
+
+fn check_array(array: VType, arrays: &[&str]) -> Result<(), String> {
+    match &array {
+        VType::Null => Ok(()),
+        VType::Reference(name) if arrays.contains(&name.as_str()) => Ok(()),
+        _ => Err(format!(
+            "Bad type on operand stack: expected {}, found {}",
+            arrays[0], array
+        )),
+    }
+}
+
+fn pop_category1(frame: &mut Frame) -> Result<VType, String> {
+    let vtype = frame.pop()?;
+    if vtype.is_category2() {
+        return Err(format!(
+            "Bad type on operand stack: {} is a category 2 value",
+            vtype
+        ));
+    }
+    Ok(vtype)
+}
+
+fn pop_any_reference(frame: &mut Frame) -> Result<VType, String> {
+    let vtype = frame.pop()?;
+    if !vtype.is_reference() {
+        return Err(format!(
+            "Bad type on operand stack: expected a reference, found {}",
+            vtype
+        ));
+    }
+    Ok(vtype)
+}
+
+fn push_all<const N: usize>(frame: &mut Frame, values: [&VType; N]) -> Result<(), String> {
+    for value in values {
+        frame.push(value.clone())?;
+    }
+    Ok(())
+}
+
+fn unary(
+    frame: &mut Frame,
+    operand: &VType,
+    result: VType,
+    h: &dyn ClassHierarchy,
+) -> Result<(), String> {
+    frame.pop_type(operand, h)?;
+    frame.push(result)
+}
+
+fn binary(
+    frame: &mut Frame,
+    left: &VType,
+    right: &VType,
+    result: VType,
+    h: &dyn ClassHierarchy,
+) -> Result<(), String> {
+    frame.pop_type(right, h)?;
+    frame.pop_type(left, h)?;
+    frame.push(result)
+}
+
+fn return_value(ctx: &Context, frame: &mut Frame, kind: &VType) -> Result<bool, String> {
+    let return_type = match &ctx.return_type {
+        Some(return_type) => return_type,
+        None => return Err("Method does not expect a return value".to_string()),
+    };
+    let matches_kind = match kind {
+        VType::Reference(_) => return_type.is_reference(),
+        kind => return_type == kind,
+    };
+    if !matches_kind {
+        return Err(format!(
+            "Wrong return instruction for return type {}",
+            return_type
+        ));
+    }
+    frame.pop_type(return_type, ctx.hierarchy)?;
+    Ok(false)
+}
+
+fn member_ref(
+    ctx: &Context,
+    index: u16,
+    interface: Option<bool>,
+) -> Result<(String, String, String), String> {
+    let (class_index, name_and_type_index) = match (ctx.cp.get(index as usize), interface) {
+        (
+            Some(Constant::FieldRef {
+                class_index,
+                name_and_type_index,
+            }),
+            None,
+        )
+        | (
+            Some(Constant::MethodRef {
+                class_index,
+                name_and_type_index,
+            }),
+            Some(false),
+        )
+        | (
+            Some(Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            }),
+            Some(true),
+        ) => (*class_index, *name_and_type_index),
+        // invokestatic and invokespecial may name interface methods since Java 8
+        (
+            Some(Constant::InterfaceMethodRef {
+                class_index,
+                name_and_type_index,
+            }),
+            Some(false),
+        ) if ctx.major_version >= 52 => (*class_index, *name_and_type_index),
+        _ => {
+            return Err(format!(
+                "Illegal constant pool index {} for a member reference",
+                index
+            ))
+        }
+    };
+    let class = class_name(ctx.cp, class_index)?;
+    let (name, descriptor) = name_and_type(ctx.cp, name_and_type_index)?;
+    Ok((class, name, descriptor))
+}
+
+fn field_access(ctx: &Context, frame: &mut Frame, insn: &Insn) -> Result<(), String> {
+    let h = ctx.hierarchy;
+    let (class, name, descriptor) = member_ref(ctx, index(insn), None)?;
+    let field_type = from_field_type(&descriptor)
+        .ok_or_else(|| format!("Bad field descriptor {}", descriptor))?;
+    match insn.opcode {
+        OpCode::getstatic => frame.push(field_type)?,
+        OpCode::putstatic => {
+            frame.pop_type(&field_type, h)?;
+        }
+        OpCode::getfield => {
+            let object = frame.pop_type(&VType::Reference(class.clone()), h)?;
+            check_protected(ctx, &class, &name, &descriptor, &object)?;
+            frame.push(field_type)?;
+        }
+        _ => {
+            frame.pop_type(&field_type, h)?;
+            let object = frame.pop()?;
+            // a constructor may assign its own fields before calling super(), as inner classes do
+            if object == VType::UninitializedThis && class == ctx.class_name {
+                return Ok(());
+            }
+            if !object.is_assignable_to(&VType::Reference(class.clone()), h) {
+                return Err(format!(
+                    "Bad type on operand stack: {} is not assignable to '{}'",
+                    object, class
+                ));
+            }
+            check_protected(ctx, &class, &name, &descriptor, &object)?;
+        }
+    }
+    Ok(())
+}
+
+fn invoke(ctx: &Context, frame: &mut Frame, insn: &Insn) -> Result<(), String> {
+    let h = ctx.hierarchy;
+    let (class, name, descriptor) = match insn.operand {
+        Operand::Index(index) if insn.opcode == OpCode::invokedynamic => {
+            match ctx.cp.get(index as usize) {
+                Some(Constant::InvokeDynamic {
+                    name_and_type_index,
+                    ..
+                }) if ctx.major_version >= 51 => {
+                    let (name, descriptor) = name_and_type(ctx.cp, *name_and_type_index)?;
+                    (String::new(), name, descriptor)
+                }
+                _ => {
+                    return Err(format!(
+                        "Illegal constant pool index {} for invokedynamic",
+                        index
+                    ))
+                }
+            }
+        }
+        Operand::Index(index) => member_ref(ctx, index, Some(false))?,
+        Operand::InvokeInterface { index, .. } => member_ref(ctx, index, Some(true))?,
+        _ => unreachable!(),
+    };
+    let is_init = name == "<init>";
+    if name.starts_with('<') && !(is_init && insn.opcode == OpCode::invokespecial) {
+        return Err(format!("Illegal call to {}", name));
+    }
+    let (params, return_type) = parse_method_descriptor(&descriptor)
+        .ok_or_else(|| format!("Bad method descriptor {}", descriptor))?;
+    if is_init && return_type.is_some() {
+        return Err("Constructor must return void".to_string());
+    }
+    if let Operand::InvokeInterface { count, .. } = insn.operand {
+        let size: usize = params.iter().map(VType::size).sum();
+        if count as usize != size + 1 {
+            return Err("Inconsistent args count operand in invokeinterface".to_string());
+        }
+    }
+    for param in params.iter().rev() {
+        frame.pop_type(param, h)?;
+    }
+    match insn.opcode {
+        OpCode::invokespecial if is_init => {
+            let receiver = frame.pop()?;
+            let initialized = match &receiver {
+                VType::UninitializedThis => {
+                    if class != ctx.class_name && Some(&class) != ctx.super_name.as_ref() {
+                        return Err(format!("Bad <init> method call to {}", class));
+                    }
+                    frame.flag_this_uninit = false;
+                    VType::Reference(ctx.class_name.clone())
+                }
+                VType::Uninitialized(offset) => {
+                    let new = crate::verifier::code::decode(&ctx.code.code, *offset as usize)?;
+                    if new.opcode != OpCode::new || class_name(ctx.cp, index(&new))? != class {
+                        return Err(format!("Bad <init> method call to {}", class));
+                    }
+                    VType::Reference(class.clone())
+                }
+                _ => return Err(format!("Bad operand type {} for <init>", receiver)),
+            };
+            frame.initialize(&receiver, &initialized);
+        }
+        OpCode::invokespecial => {
+            frame.pop_type(&VType::Reference(ctx.class_name.clone()), h)?;
+        }
+        OpCode::invokevirtual => {
+            let receiver = frame.pop_type(&VType::Reference(class.clone()), h)?;
+            check_protected(ctx, &class, &name, &descriptor, &receiver)?;
+        }
+        OpCode::invokeinterface => {
+            frame.pop_reference()?;
+        }
+        _ => {}
+    }
+    if let Some(return_type) = return_type {
+        frame.push(return_type)?;
+    }
+    Ok(())
+}
+
+/// JVMS 4.10.1.8: a protected member of a superclass in another package may only be
+/// accessed through a reference to the current class or one of its subclasses.
+fn check_protected(
+    ctx: &Context,
+    class: &str,
+    name: &str,
+    descriptor: &str,
+    object: &VType,
+) -> Result<(), String> {
+    let h = ctx.hierarchy;
+    if class == ctx.class_name || !h.is_subclass_of(&ctx.class_name, class) {
+        return Ok(());
+    }
+    let owner = match h.protected_member_owner(class, name, descriptor) {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    if package(&owner) == package(&ctx.class_name) {
+        return Ok(());
+    }
+    // arrays override the protected Object.clone publicly
+    if object.is_array() && name == "clone" {
+        return Ok(());
+    }
+    if object.is_assignable_to(&VType::Reference(ctx.class_name.clone()), h) {
+        Ok(())
+    } else {
+        Err(format!("Bad access to protected data: {}.{}", class, name))
+    }
+}
+
+fn package(class: &str) -> &str {
+    class.rfind('/').map_or("", |i| &class[..i])
+}
@@ -0,0 +1,565 @@
+use crate::verifier::code::Insn;
+use crate::verifier::types::{parse_method_descriptor, VType};
+use classfile::{ClassFile, CodeAttribute, Constant, ConstantPoolRef, MethodInfo};
+use std::fmt::{Display, Formatter};
+
+pub use subroutines::{inline_class_subroutines, with_inlined_code};
+
+mod code;
+mod execute;
+mod subroutines;
+mod type_checker;
+mod type_inference;
+mod types;
+
+const ACC_STATIC: u16 = 0x0008;
+const ACC_ABSTRACT: u16 = 0x0400;
+const ACC_NATIVE: u16 = 0x0100;
+
+/// Class files from this version on carry a `StackMapTable` and are verified by type checking,
+/// older ones by type inference.
+const TYPE_CHECKING_MAJOR_VERSION: u16 = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub struct VerifyError {
+    pub class: String,
+    pub method: String,
+    pub descriptor: String,
+    /// The bytecode offset of the offending instruction, if there is one.
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "java.lang.VerifyError: (class: {}, method: {} signature: {}) ",
+            self.class, self.method, self.descriptor
+        )?;
+        if let Some(offset) = self.offset {
+            write!(f, "at offset {}: ", offset)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// What the verifier needs to know about other classes, which it may not load itself.
+pub trait ClassHierarchy {
+    /// The name of the direct superclass, `None` for `java/lang/Object` or an unknown class.
+    fn super_class(&self, class: &str) -> Option<String>;
+
+    fn is_interface(&self, class: &str) -> bool;
+
+    /// The class declaring the field or method found from `class` upwards, if it is protected.
+    fn protected_member_owner(&self, class: &str, name: &str, descriptor: &str) -> Option<String>;
+
+    fn is_subclass_of(&self, class: &str, super_class: &str) -> bool {
+        let mut class = Some(class.to_string());
+        while let Some(name) = class {
+            if name == super_class {
+                return true;
+            }
+            class = self.super_class(&name);
+        }
+        false
+    }
+}
+
+/// Verifies the code of every method, JVMS 4.10.
+pub fn verify_class(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<(), VerifyError> {
+    verify_methods(class_file, hierarchy, |context| {
+        match context.major_version {
+            // like HotSpot, fail over to type inference for version 50 class files
+            TYPE_CHECKING_MAJOR_VERSION => type_checker::verify_method(context)
+                .or_else(|_| type_inference::verify_method(context)),
+            major if major > TYPE_CHECKING_MAJOR_VERSION => type_checker::verify_method(context),
+            _ => type_inference::verify_method(context),
+        }
+    })
+}
+
+pub(crate) fn verify_methods(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    verify_method: impl Fn(&Context) -> Result<(), (Option<usize>, String)>,
+) -> Result<(), VerifyError> {
+    let cp = &class_file.constant_pool;
+    let this_class = class_name(cp, class_file.this_class).unwrap_or_default();
+    let super_name = match class_file.super_class {
+        0 => None,
+        index => class_name(cp, index).ok(),
+    };
+    for method in class_file.methods.iter() {
+        let name = utf8(cp, method.name_index).unwrap_or_default();
+        let descriptor = utf8(cp, method.descriptor_index).unwrap_or_default();
+        let error = |offset, message| VerifyError {
+            class: this_class.clone(),
+            method: name.clone(),
+            descriptor: descriptor.clone(),
+            offset,
+            message,
+        };
+        let code = match method.code_attribute() {
+            Some(code) => code,
+            None if method.access_flags & (ACC_ABSTRACT | ACC_NATIVE) != 0 => continue,
+            None => return Err(error(None, "Missing Code attribute".to_string())),
+        };
+        let (params, return_type) = parse_method_descriptor(&descriptor)
+            .ok_or_else(|| error(None, format!("Bad method descriptor {}", descriptor)))?;
+        let context = Context {
+            class_name: this_class.clone(),
+            super_name: super_name.clone(),
+            method,
+            name: name.clone(),
+            params,
+            return_type,
+            major_version: class_file.major_version,
+            cp,
+            code,
+            hierarchy,
+        };
+        verify_method(&context).map_err(|(offset, message)| error(offset, message))?;
+    }
+    Ok(())
+}
+
+/// The method being verified.
+pub(crate) struct Context<'a> {
+    pub class_name: String,
+    pub super_name: Option<String>,
+    pub method: &'a MethodInfo<'a>,
+    pub name: String,
+    pub params: Vec<VType>,
+    pub return_type: Option<VType>,
+    pub major_version: u16,
+    pub cp: &'a ConstantPoolRef<'a>,
+    pub code: &'a CodeAttribute<'a>,
+    pub hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl Context<'_> {
+    pub fn is_static(&self) -> bool {
+        self.method.access_flags & ACC_STATIC != 0
+    }
+
+    pub fn is_init(&self) -> bool {
+        self.name == "<init>"
+    }
+
+    /// The locals on entry: `this`, uninitialized in a constructor, followed by the parameters.
+    pub fn initial_frame(&self) -> Result<types::Frame, String> {
+        let mut frame =
+            types::Frame::new(self.code.max_locals as usize, self.code.max_stack as usize);
+        let mut index = 0;
+        if !self.is_static() {
+            let this = if self.is_init() && self.class_name != "java/lang/Object" {
+                frame.flag_this_uninit = true;
+                VType::UninitializedThis
+            } else {
+                VType::object(&self.class_name)
+            };
+            frame.store(0, this)?;
+            index += 1;
+        }
+        for param in self.params.iter() {
+            frame
+                .store(index, param.clone())
+                .map_err(|_| "Arguments can't fit into locals".to_string())?;
+            index += param.size();
+        }
+        Ok(frame)
+    }
+}
+
+/// An exception table entry, with the catch type resolved.
+pub(crate) struct Handler {
+    pub start: usize,
+    pub end: usize,
+    pub handler: usize,
+    pub catch_type: VType,
+}
+
+impl Handler {
+    pub fn covers(&self, insn: &Insn) -> bool {
+        self.start <= insn.offset && insn.offset < self.end
+    }
+
+    /// The frame on entry to the handler when an exception is thrown from `frame`.
+    pub fn frame(&self, frame: &types::Frame) -> types::Frame {
+        frame.with_stack(vec![self.catch_type.clone()])
+    }
+}
+
+pub(crate) fn exception_handlers(
+    ctx: &Context,
+    starts: &[bool],
+) -> Result<Vec<Handler>, (Option<usize>, String)> {
+    let is_start = |pc: u16| starts.get(pc as usize).copied().unwrap_or(false);
+    let mut handlers = vec![];
+    for entry in ctx.code.exception_table.iter() {
+        let error = |message: &str| (Some(entry.handler_pc as usize), message.to_string());
+        let end_ok = is_start(entry.end_pc) || entry.end_pc as usize == starts.len();
+        if !is_start(entry.start_pc) || !end_ok || entry.start_pc >= entry.end_pc {
+            return Err(error("Illegal exception table range"));
+        }
+        if !is_start(entry.handler_pc) {
+            return Err(error("Illegal exception table handler"));
+        }
+        let catch_type = match entry.catch_type {
+            0 => "java/lang/Throwable".to_string(),
+            index => class_name(ctx.cp, index).map_err(|e| error(&e))?,
+        };
+        let catch_type = VType::Reference(catch_type);
+        if !catch_type.is_assignable_to(&VType::object("java/lang/Throwable"), ctx.hierarchy) {
+            return Err(error("Catch type is not a subclass of Throwable"));
+        }
+        handlers.push(Handler {
+            start: entry.start_pc as usize,
+            end: entry.end_pc as usize,
+            handler: entry.handler_pc as usize,
+            catch_type,
+        });
+    }
+    Ok(handlers)
+}
+
+pub(crate) fn utf8(cp: &ConstantPoolRef, index: u16) -> Result<String, String> {
+    match cp.get(index as usize) {
+        Some(Constant::Utf8(bytes)) => classfile::mutf8::decode(bytes)
+            .map_err(|_| format!("Bad UTF-8 constant at index {}", index)),
+        _ => Err(format!(
+            "Constant pool index {} is not a Utf8 constant",
+            index
+        )),
+    }
+}
+
+pub(crate) fn class_name(cp: &ConstantPoolRef, index: u16) -> Result<String, String> {
+    match cp.get(index as usize) {
+        Some(Constant::Class { name_index }) => utf8(cp, *name_index),
+        _ => Err(format!(
+            "Constant pool index {} is not a Class constant",
+            index
+        )),
+    }
+}
+
+pub(crate) fn name_and_type(cp: &ConstantPoolRef, index: u16) -> Result<(String, String), String> {
+    match cp.get(index as usize) {
+        Some(Constant::NameAndType {
+            name_index,
+            descriptor_index,
+        }) => Ok((utf8(cp, *name_index)?, utf8(cp, *descriptor_index)?)),
+        _ => Err(format!(
+            "Constant pool index {} is not a NameAndType constant",
+            index
+        )),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::classpath::{ClassPath, Entry};
+    use crate::rtda::ClassLoader;
+    use crate::verifier::types::parse_method_descriptor;
+    use crate::verifier::{class_name, verify_class, ClassHierarchy, Context, VerifyError};
+    use classfile::builder::{ClassBuilder, ACC_STATIC};
+    use classfile::frames::{compute_frames, stack_map_table, VerificationType};
+    use classfile::{mutf8, AttributeType, CodeAttribute, Constant, MethodInfo};
+    use std::borrow::Cow;
+    use std::collections::{BTreeSet, HashMap};
+    use std::sync::Arc;
+
+    pub(crate) fn class_loader() -> ClassLoader {
+        ClassLoader::new(ClassPath::new("".to_string(), "../data/jvm8".to_string()))
+    }
+
+    /// `a/Sub` extends `b/Base`, which declares the protected field `f`.
+    struct Hierarchy;
+
+    impl ClassHierarchy for Hierarchy {
+        fn super_class(&self, class: &str) -> Option<String> {
+            match class {
+                "java/lang/Object" => None,
+                "a/Sub" => Some("b/Base".to_string()),
+                _ => Some("java/lang/Object".to_string()),
+            }
+        }
+
+        fn is_interface(&self, _: &str) -> bool {
+            false
+        }
+
+        fn protected_member_owner(&self, class: &str, name: &str, _: &str) -> Option<String> {
+            (class == "b/Base" && name == "f").then(|| "b/Base".to_string())
+        }
+    }
+
+    fn constant_pool() -> Vec<Constant<'static>> {
+        vec![
+            Constant::Placeholder,
+            Constant::Class { name_index: 2 },
+            Constant::Utf8(Cow::Borrowed(b"java/lang/Object")),
+            Constant::MethodRef {
+                class_index: 1,
+                name_and_type_index: 4,
+            },
+            Constant::NameAndType {
+                name_index: 5,
+                descriptor_index: 6,
+            },
+            Constant::Utf8(Cow::Borrowed(b"<init>")),
+            Constant::Utf8(Cow::Borrowed(b"()V")),
+            Constant::Class { name_index: 8 },
+            Constant::Utf8(Cow::Borrowed(b"b/Base")),
+            Constant::FieldRef {
+                class_index: 7,
+                name_and_type_index: 10,
+            },
+            Constant::NameAndType {
+                name_index: 11,
+                descriptor_index: 12,
+            },
+            Constant::Utf8(Cow::Borrowed(b"f")),
+            Constant::Utf8(Cow::Borrowed(b"I")),
+            Constant::MethodRef {
+                class_index: 7,
+                name_and_type_index: 4,
+            },
+        ]
+    }
+
+    /// Runs `f` on an instance method `name` of `a/Sub` with the given code.
+    pub(crate) fn with_context<R>(
+        name: &str,
+        descriptor: &str,
+        major_version: u16,
+        code: &CodeAttribute,
+        f: impl FnOnce(&Context) -> R,
+    ) -> R {
+        let cp = Arc::new(constant_pool());
+        let method = MethodInfo {
+            access_flags: 0,
+            name_index: 0,
+            descriptor_index: 0,
+            attributes: vec![],
+            code_attr_index: None,
+        };
+        let (params, return_type) = parse_method_descriptor(descriptor).unwrap();
+        let context = Context {
+            class_name: "a/Sub".to_string(),
+            super_name: Some("b/Base".to_string()),
+            method: &method,
+            name: name.to_string(),
+            params,
+            return_type,
+            major_version,
+            cp: &cp,
+            code,
+            hierarchy: &Hierarchy,
+        };
+        f(&context)
+    }
+
+    #[test]
+    fn test_verify_user_classes() {
+        let loader = class_loader();
+        for name in ["User", "GaussTest"] {
+            let data = loader.class_path().read_class(name).unwrap();
+            let class_file = classfile::parse(&data).unwrap();
+            verify_class(&class_file, &loader).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_verify_boot_classes() {
+        let loader = class_loader();
+        for name in [
+            "java/lang/Object",
+            "java/lang/String",
+            "java/lang/Thread",
+            "java/util/HashMap",
+            "java/util/ArrayList",
+            "java/util/concurrent/ConcurrentHashMap",
+        ] {
+            let data = loader.class_path().read_class(name).unwrap();
+            let class_file = classfile::parse(&data).unwrap();
+            if let Err(e) = verify_class(&class_file, &loader) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_built_class() {
+        let loader = class_loader();
+        let data = ClassBuilder::new("Gen")
+            .default_constructor()
+            .method_with_flags(ACC_STATIC, "parse", "(Ljava/lang/String;)I", |code| {
+                let start = code.new_label();
+                let end = code.new_label();
+                let handler = code.new_label();
+                code.bind(start)
+                    .aload(0)
+                    .invokestatic("java/lang/Integer", "parseInt", "(Ljava/lang/String;)I")
+                    .bind(end)
+                    .ireturn()
+                    .bind(handler)
+                    .pop()
+                    .iconst(-1)
+                    .ireturn()
+                    .try_catch(start, end, handler, Some("java/lang/NumberFormatException"))
+            })
+            .method_with_flags(ACC_STATIC, "sum", "(I)I", |code| {
+                let body = code.new_label();
+                let test = code.new_label();
+                code.iconst(0)
+                    .istore(1)
+                    .goto(test)
+                    .bind(body)
+                    .iload(1)
+                    .iload(0)
+                    .iadd()
+                    .istore(1)
+                    .iinc(0, -1)
+                    .bind(test)
+                    .iload(0)
+                    .ifgt(body)
+                    .iload(1)
+                    .ireturn()
+            })
+            .build()
+            .unwrap();
+        let class_file = classfile::parse(&data).unwrap();
+        verify_class(&class_file, &loader).unwrap();
+        let class = loader.define_class(&data).unwrap();
+        assert_eq!(class.name, "Gen");
+        assert!(class.look_up_method("sum", "(I)I").is_some());
+
+        // the size of either list, which needs a stack map merging both to AbstractList
+        let builder = || {
+            ClassBuilder::new("Gen").version(52, 0).method_with_flags(
+                ACC_STATIC,
+                "size",
+                "(Z)I",
+                |code| {
+                    let other = code.new_label();
+                    let join = code.new_label();
+                    code.iload(0)
+                        .ifeq(other)
+                        .new_instance("java/util/ArrayList")
+                        .dup()
+                        .invokespecial("java/util/ArrayList", "<init>", "()V")
+                        .goto(join)
+                        .bind(other)
+                        .new_instance("java/util/LinkedList")
+                        .dup()
+                        .invokespecial("java/util/LinkedList", "<init>", "()V")
+                        .bind(join)
+                        .invokevirtual("java/util/AbstractList", "size", "()I")
+                        .ireturn()
+                },
+            )
+        };
+        let data = builder().build_with_hierarchy(&loader).unwrap();
+        verify_class(&classfile::parse(&data).unwrap(), &loader).unwrap();
+        // without the hierarchy both lists merge to Object
+        let data = builder().build().unwrap();
+        let error = verify_class(&classfile::parse(&data).unwrap(), &loader).unwrap_err();
+        assert_eq!(error.offset, Some(21));
+    }
+
+    #[test]
+    fn test_verify_computed_frames() {
+        // the stack maps of javac are replaced by computed ones
+        let loader = class_loader();
+        for name in [
+            "java/lang/String",
+            "java/util/HashMap",
+            "java/util/concurrent/ConcurrentHashMap",
+            "java/util/regex/Pattern",
+        ] {
+            let data = loader.class_path().read_class(name).unwrap();
+            let mut class_file = classfile::parse(&data).unwrap();
+            let cp = class_file.constant_pool.clone();
+            let mut classes = HashMap::new();
+            for (index, constant) in cp.iter().enumerate() {
+                if let Constant::Class { .. } = constant {
+                    classes.insert(class_name(&cp, index as u16).unwrap(), index as u16);
+                }
+            }
+            let mut missing = BTreeSet::new();
+            for method in &class_file.methods {
+                if method.code_attribute().is_some() {
+                    for frame in compute_frames(&class_file, method, &loader).unwrap() {
+                        for vtype in frame.locals.iter().chain(frame.stack.iter()) {
+                            match vtype {
+                                VerificationType::Object(name) if !classes.contains_key(name) => {
+                                    missing.insert(name.clone());
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+            let missing: Vec<_> = missing.iter().map(|name| mutf8::encode(name)).collect();
+            let mut constants = (*cp).clone();
+            for name in &missing {
+                constants.push(Constant::Utf8(Cow::Borrowed(name)));
+                constants.push(Constant::Class {
+                    name_index: constants.len() as u16 - 1,
+                });
+                let name = mutf8::decode(name).unwrap();
+                classes.insert(name, constants.len() as u16 - 1);
+            }
+            let computed: Vec<_> = class_file
+                .methods
+                .iter()
+                .map(|method| {
+                    method.code_attribute().map(|_| {
+                        stack_map_table(&class_file, method, &loader, |name| classes[name]).unwrap()
+                    })
+                })
+                .collect();
+            class_file.constant_pool = Arc::new(constants);
+            for (method, computed) in class_file.methods.iter_mut().zip(computed) {
+                for attribute in method.attributes.iter_mut() {
+                    if let AttributeType::Code { code } = &mut attribute.attribute_type {
+                        for attribute in code.attributes.iter_mut() {
+                            if let AttributeType::StackMapTable { entries } =
+                                &mut attribute.attribute_type
+                            {
+                                *entries = computed.clone().unwrap();
+                            }
+                        }
+                    }
+                }
+            }
+            let data = classfile::write(&class_file).unwrap();
+            let class_file = classfile::parse(&data).unwrap();
+            if let Err(e) = verify_class(&class_file, &loader) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let error = VerifyError {
+            class: "Foo".to_string(),
+            method: "bar".to_string(),
+            descriptor: "()V".to_string(),
+            offset: Some(3),
+            message: "Operand stack underflow".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "java.lang.VerifyError: (class: Foo, method: bar signature: ()V) at offset 3: Operand stack underflow"
+        );
+    }
+}
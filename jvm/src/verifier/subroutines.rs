@@ -0,0 +1,406 @@
+use crate::instructions::OpCode;
+use crate::verifier::code::{decode_all, Insn, Operand};
+use crate::verifier::{class_name, utf8, VerifyError, TYPE_CHECKING_MAJOR_VERSION};
+use classfile::{Attribute, AttributeType, ClassFile, CodeAttribute, Exception};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+
+type Error = (Option<usize>, String);
+
+/// The code of a method with its subroutines inlined.
+#[derive(Debug, Clone)]
+pub struct InlinedCode {
+    pub code: Vec<u8>,
+    pub exception_table: Vec<Exception>,
+}
+
+/// Inlines the subroutines of every method calling one, in method order. From version 51 on
+/// class files may not contain `jsr` at all, which is left for the verifier to report.
+pub fn inline_class_subroutines(
+    class_file: &ClassFile,
+) -> Result<Vec<Option<InlinedCode>>, VerifyError> {
+    if class_file.major_version > TYPE_CHECKING_MAJOR_VERSION {
+        return Ok(vec![]);
+    }
+    let cp = &class_file.constant_pool;
+    let this_class = class_name(cp, class_file.this_class).unwrap_or_default();
+    class_file
+        .methods
+        .iter()
+        .map(|method| match method.code_attribute() {
+            Some(code) => inline_subroutines(code).map_err(|(offset, message)| VerifyError {
+                class: this_class.clone(),
+                method: utf8(cp, method.name_index).unwrap_or_default(),
+                descriptor: utf8(cp, method.descriptor_index).unwrap_or_default(),
+                offset,
+                message,
+            }),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+/// A copy of `class_file` running the inlined code, see [inline_class_subroutines].
+/// Attributes of the rewritten methods that refer to the old offsets are dropped.
+pub fn with_inlined_code<'a>(
+    class_file: &ClassFile<'a>,
+    inlined: &'a [Option<InlinedCode>],
+) -> ClassFile<'a> {
+    let mut class_file = class_file.clone();
+    for (method, inlined) in class_file.methods.iter_mut().zip(inlined) {
+        let (inlined, index) = match (inlined, method.code_attr_index) {
+            (Some(inlined), Some(index)) => (inlined, index),
+            _ => continue,
+        };
+        if let AttributeType::Code { code } = &mut method.attributes[index].attribute_type {
+            code.code = Cow::Borrowed(&inlined.code);
+            code.exception_table = inlined.exception_table.clone();
+            code.attributes.retain(|attribute: &Attribute| {
+                !matches!(
+                    attribute.attribute_type,
+                    AttributeType::StackMapTable { .. }
+                        | AttributeType::LineNumberTable { .. }
+                        | AttributeType::LocalVariableTable { .. }
+                        | AttributeType::LocalVariableTypeTable { .. }
+                )
+            });
+        }
+    }
+    class_file
+}
+
+// one copy of the main code or of a subroutine
+struct Instance {
+    /// The subroutine entry, `None` for the main code.
+    entry: Option<usize>,
+    /// Where `ret` continues, in the calling instance.
+    return_to: Option<(usize, usize)>,
+    /// The entries of this and the calling subroutines.
+    chain: Vec<usize>,
+}
+
+/// Replaces every `jsr` by a copy of the subroutine it calls, so that the code no longer
+/// contains `jsr`, `jsr_w` or `ret`. Returns `None` if there are no subroutines.
+///
+/// As `jsr` pushed a return address the subroutine stores away, it becomes `aconst_null`
+/// followed by a `goto_w` to the copy, and `ret` a `goto_w` back to the copy of its caller.
+pub fn inline_subroutines(code: &CodeAttribute) -> Result<Option<InlinedCode>, Error> {
+    let insns = decode_all(&code.code).map_err(|(offset, e)| (Some(offset), e))?;
+    if !insns
+        .iter()
+        .any(|insn| matches!(insn.opcode, OpCode::jsr | OpCode::jsr_w))
+    {
+        return Ok(None);
+    }
+    let mut index_of = HashMap::new();
+    for (index, insn) in insns.iter().enumerate() {
+        index_of.insert(insn.offset, index);
+    }
+
+    let mut owned: HashMap<Option<usize>, BTreeSet<usize>> = HashMap::new();
+    let mut instances = vec![Instance {
+        entry: None,
+        return_to: None,
+        chain: vec![],
+    }];
+    let mut labels: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut calls: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut length = 0;
+    let mut current = 0;
+    while current < instances.len() {
+        let entry = instances[current].entry;
+        if let Entry::Vacant(vacant) = owned.entry(entry) {
+            vacant.insert(owned_insns(code, &insns, &index_of, entry)?);
+        }
+        for &index in owned[&entry].iter() {
+            let insn = &insns[index];
+            labels.insert((current, insn.offset), length);
+            if matches!(insn.opcode, OpCode::jsr | OpCode::jsr_w) {
+                let target = insn.branch_targets()[0];
+                let mut chain = instances[current].chain.clone();
+                if chain.contains(&target) {
+                    return Err((Some(insn.offset), "Recursive call to jsr entry".to_string()));
+                }
+                chain.push(target);
+                calls.insert((current, insn.offset), instances.len());
+                instances.push(Instance {
+                    entry: Some(target),
+                    return_to: Some((current, insn.offset + insn.length)),
+                    chain,
+                });
+            }
+            length += inlined_length(insn, length);
+        }
+        current += 1;
+    }
+    if length > u16::MAX as usize {
+        return Err((
+            None,
+            "Code too large after inlining subroutines".to_string(),
+        ));
+    }
+
+    let label = |instance: usize, offset: usize| -> Result<usize, Error> {
+        labels.get(&(instance, offset)).copied().ok_or_else(|| {
+            (
+                Some(offset),
+                "Branch into or out of a subroutine".to_string(),
+            )
+        })
+    };
+    let mut out = Vec::with_capacity(length);
+    for (current, instance) in instances.iter().enumerate() {
+        for &index in owned[&instance.entry].iter() {
+            let insn = &insns[index];
+            let at = out.len() as i64;
+            let relative = |target: usize| label(current, target).map(|to| to as i64 - at);
+            match (&insn.operand, insn.opcode) {
+                (_, OpCode::jsr | OpCode::jsr_w) => {
+                    let callee = calls[&(current, insn.offset)];
+                    let entry = instances[callee].entry.unwrap();
+                    let offset = label(callee, entry)? as i64 - (at + 1);
+                    out.push(OpCode::aconst_null as u8);
+                    out.push(OpCode::goto_w as u8);
+                    out.extend_from_slice(&(offset as i32).to_be_bytes());
+                }
+                (_, OpCode::ret) => {
+                    let (caller, offset) = instance.return_to.ok_or_else(|| {
+                        (Some(insn.offset), "ret outside a subroutine".to_string())
+                    })?;
+                    let offset = label(caller, offset)? as i64 - at;
+                    out.push(OpCode::goto_w as u8);
+                    out.extend_from_slice(&(offset as i32).to_be_bytes());
+                }
+                (Operand::Branch(target), OpCode::goto_w) => {
+                    out.push(OpCode::goto_w as u8);
+                    out.extend_from_slice(&(relative(*target)? as i32).to_be_bytes());
+                }
+                (Operand::Branch(target), _) => {
+                    let offset = i16::try_from(relative(*target)?).map_err(|_| {
+                        (
+                            Some(insn.offset),
+                            "Branch offset too large after inlining subroutines".to_string(),
+                        )
+                    })?;
+                    out.push(insn.opcode as u8);
+                    out.extend_from_slice(&offset.to_be_bytes());
+                }
+                (Operand::TableSwitch { default, targets }, _) => {
+                    switch_header(&mut out, insn.opcode, relative(*default)?);
+                    let low = &code.code[insn.offset + 1 + 3 - insn.offset % 4 + 4..][..4];
+                    let low = i32::from_be_bytes(low.try_into().unwrap());
+                    out.extend_from_slice(&low.to_be_bytes());
+                    out.extend_from_slice(&(low + targets.len() as i32 - 1).to_be_bytes());
+                    for target in targets {
+                        out.extend_from_slice(&(relative(*target)? as i32).to_be_bytes());
+                    }
+                }
+                (Operand::LookupSwitch { default, pairs }, _) => {
+                    switch_header(&mut out, insn.opcode, relative(*default)?);
+                    out.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+                    for (key, target) in pairs {
+                        out.extend_from_slice(&key.to_be_bytes());
+                        out.extend_from_slice(&(relative(*target)? as i32).to_be_bytes());
+                    }
+                }
+                _ => out.extend_from_slice(&code.code[insn.offset..insn.offset + insn.length]),
+            }
+        }
+    }
+
+    // in the original order, as earlier entries take precedence
+    let mut exception_table = vec![];
+    for entry in code.exception_table.iter() {
+        let covered = entry.start_pc as usize..entry.end_pc as usize;
+        for (current, instance) in instances.iter().enumerate() {
+            let mut owned = owned[&instance.entry]
+                .iter()
+                .map(|index| &insns[*index])
+                .filter(|insn| covered.contains(&insn.offset));
+            let first = match owned.next() {
+                Some(first) => first,
+                None => continue,
+            };
+            let last = owned.next_back().unwrap_or(first);
+            let at = label(current, last.offset)?;
+            let end = at + inlined_length(last, at);
+            exception_table.push(Exception {
+                start_pc: label(current, first.offset)? as u16,
+                end_pc: end as u16,
+                handler_pc: label(current, entry.handler_pc as usize)? as u16,
+                catch_type: entry.catch_type,
+            });
+        }
+    }
+    Ok(Some(InlinedCode {
+        code: out,
+        exception_table,
+    }))
+}
+
+fn switch_header(out: &mut Vec<u8>, opcode: OpCode, default: i64) {
+    out.push(opcode as u8);
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+    out.extend_from_slice(&(default as i32).to_be_bytes());
+}
+
+// the length of `insn` when moved to offset `at`
+fn inlined_length(insn: &Insn, at: usize) -> usize {
+    match insn.opcode {
+        OpCode::jsr | OpCode::jsr_w => 6,
+        OpCode::ret => 5,
+        OpCode::tableswitch | OpCode::lookupswitch => {
+            insn.length - (3 - insn.offset % 4) + (3 - at % 4)
+        }
+        _ => insn.length,
+    }
+}
+
+// the instructions reachable from `entry` without entering another subroutine,
+// including the exception handlers of those instructions
+fn owned_insns(
+    code: &CodeAttribute,
+    insns: &[Insn],
+    index_of: &HashMap<usize, usize>,
+    entry: Option<usize>,
+) -> Result<BTreeSet<usize>, Error> {
+    let mut owned = BTreeSet::new();
+    let mut worklist = vec![entry.unwrap_or(0)];
+    while let Some(offset) = worklist.pop() {
+        let index = *index_of
+            .get(&offset)
+            .ok_or_else(|| (Some(offset), "Illegal target of jump or branch".to_string()))?;
+        if !owned.insert(index) {
+            continue;
+        }
+        let insn = &insns[index];
+        let next = insn.offset + insn.length;
+        match insn.opcode {
+            OpCode::ret => {}
+            // the subroutine itself is another instance, the caller continues after it
+            OpCode::jsr | OpCode::jsr_w => {
+                if next < code.code.len() {
+                    worklist.push(next);
+                }
+            }
+            OpCode::goto
+            | OpCode::goto_w
+            | OpCode::tableswitch
+            | OpCode::lookupswitch
+            | OpCode::athrow
+            | OpCode::ireturn
+            | OpCode::lreturn
+            | OpCode::freturn
+            | OpCode::dreturn
+            | OpCode::areturn
+            | OpCode::vreturn => worklist.extend(insn.branch_targets()),
+            _ => {
+                worklist.extend(insn.branch_targets());
+                worklist.push(next);
+            }
+        }
+        for handler in code.exception_table.iter() {
+            if (handler.start_pc as usize..handler.end_pc as usize).contains(&insn.offset) {
+                worklist.push(handler.handler_pc as usize);
+            }
+        }
+    }
+    Ok(owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instructions::OpCode;
+    use crate::verifier::code::decode_all;
+    use crate::verifier::subroutines::inline_subroutines;
+    use crate::verifier::tests::with_context;
+    use crate::verifier::type_inference::verify_method;
+    use classfile::{CodeAttribute, Exception};
+    use std::borrow::Cow;
+
+    fn code_attribute(code: &[u8], exception_table: Vec<Exception>) -> CodeAttribute<'_> {
+        CodeAttribute {
+            max_stack: 1,
+            max_locals: 4,
+            code: Cow::Borrowed(code),
+            exception_table,
+            attributes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_inline() {
+        // iconst_1, istore_1, jsr 7, iload_1, ireturn, astore_2, ret 2
+        let code = [0x04, 0x3c, 0xa8, 0x00, 0x05, 0x1b, 0xac, 0x4d, 0xa9, 0x02];
+        let inlined = inline_subroutines(&code_attribute(&code, vec![]))
+            .unwrap()
+            .unwrap();
+        // iconst_1, istore_1, aconst_null, goto_w 10, iload_1, ireturn, astore_2, goto_w 8
+        assert_eq!(
+            inlined.code,
+            [
+                0x04, 0x3c, 0x01, 0xc8, 0x00, 0x00, 0x00, 0x07, 0x1b, 0xac, 0x4d, 0xc8, 0xff, 0xff,
+                0xff, 0xfd
+            ]
+        );
+
+        // iconst_0, ireturn
+        assert!(inline_subroutines(&code_attribute(&[0x03, 0xac], vec![]))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_inline_finally() {
+        // int i = 1; try { i += 2; } finally { i++; } return i; as javac 1.4 compiles it:
+        //  0: iconst_1, istore_1, iinc 1 2, jsr 16, iload_1, ireturn
+        // 10: astore_2, jsr 16, aload_2, athrow
+        // 16: astore_3, iinc 1 1, ret 3
+        let code = [
+            0x04, 0x3c, 0x84, 0x01, 0x02, 0xa8, 0x00, 0x0b, 0x1b, 0xac, 0x4d, 0xa8, 0x00, 0x05,
+            0x2c, 0xbf, 0x4e, 0x84, 0x01, 0x01, 0xa9, 0x03,
+        ];
+        let handler = Exception {
+            start_pc: 2,
+            end_pc: 5,
+            handler_pc: 10,
+            catch_type: 0,
+        };
+        let code = code_attribute(&code, vec![handler]);
+        assert!(with_context("m", "()I", 49, &code, verify_method).is_ok());
+
+        let inlined = inline_subroutines(&code).unwrap().unwrap();
+        let insns = decode_all(&inlined.code).unwrap();
+        assert!(insns
+            .iter()
+            .all(|insn| !matches!(insn.opcode, OpCode::jsr | OpCode::jsr_w | OpCode::ret)));
+        // the subroutine is copied once for each call
+        let copies = insns
+            .iter()
+            .filter(|insn| insn.opcode == OpCode::astore_3)
+            .count();
+        assert_eq!(copies, 2);
+        assert_eq!(inlined.exception_table.len(), 1);
+        let handler = &inlined.exception_table[0];
+        assert_eq!((handler.start_pc, handler.end_pc), (2, 5));
+        assert_eq!(
+            inlined.code[handler.handler_pc as usize],
+            OpCode::astore_2 as u8
+        );
+
+        let code = code_attribute(&inlined.code, inlined.exception_table.clone());
+        assert!(with_context("m", "()I", 49, &code, verify_method).is_ok());
+    }
+
+    #[test]
+    fn test_recursive_subroutine() {
+        // jsr 3, astore_1, jsr 3
+        let code = [0xa8, 0x00, 0x03, 0x4c, 0xa8, 0xff, 0xff];
+        let (offset, message) = inline_subroutines(&code_attribute(&code, vec![])).unwrap_err();
+        assert_eq!(offset, Some(4));
+        assert_eq!(message, "Recursive call to jsr entry");
+    }
+}
@@ -0,0 +1,372 @@
+use crate::verifier::code::decode_all;
+use crate::verifier::execute::execute;
+use crate::verifier::types::{Frame, VType};
+use crate::verifier::{class_name, exception_handlers, Context};
+use classfile::{AttributeType, StackMapFrame, VerificationTypeInfo};
+use std::collections::BTreeMap;
+
+type Error = (Option<usize>, String);
+
+/// Verifies a method by type checking against its `StackMapTable`, JVMS 4.10.1.
+/// Each instruction is checked once, in order, with the frame inferred from the
+/// previous instruction or the stack map frame recorded at its offset.
+pub fn verify_method(ctx: &Context) -> Result<(), Error> {
+    let insns = decode_all(&ctx.code.code).map_err(|(offset, e)| (Some(offset), e))?;
+    let mut starts = vec![false; ctx.code.code.len()];
+    for insn in insns.iter() {
+        starts[insn.offset] = true;
+    }
+    let initial = ctx.initial_frame().map_err(|e| (None, e))?;
+    let maps = stack_map_frames(ctx, &initial, &starts)?;
+    let handlers = exception_handlers(ctx, &starts)?;
+
+    let h = ctx.hierarchy;
+    let mut current = Some(initial);
+    for insn in insns.iter() {
+        let at = |e: String| (Some(insn.offset), e);
+        if let Some(map) = maps.get(&insn.offset) {
+            if let Some(frame) = current.as_ref() {
+                frame
+                    .is_assignable_to(map, h)
+                    .map_err(|e| at(format!("Instruction type does not match stack map: {}", e)))?;
+            }
+            current = Some(map.clone());
+        }
+        let frame = current
+            .take()
+            .ok_or_else(|| at("Expecting a stackmap frame at branch target".to_string()))?;
+        let mut next = frame.clone();
+        let falls_through = execute(ctx, &mut next, insn).map_err(at)?;
+        for handler in handlers.iter().filter(|handler| handler.covers(insn)) {
+            check_target(&handler.frame(&frame), handler.handler, &maps, ctx).map_err(at)?;
+            // a store changes the locals the handler may observe
+            if next.locals != frame.locals {
+                let after = frame.with_locals(next.locals.clone());
+                check_target(&handler.frame(&after), handler.handler, &maps, ctx).map_err(at)?;
+            }
+        }
+        for target in insn.branch_targets() {
+            check_target(&next, target, &maps, ctx).map_err(at)?;
+        }
+        if falls_through {
+            current = Some(next);
+        }
+    }
+    match (current, insns.last()) {
+        (Some(_), Some(last)) => Err((
+            Some(last.offset),
+            "Falling off the end of the code".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_target(
+    frame: &Frame,
+    target: usize,
+    maps: &BTreeMap<usize, Frame>,
+    ctx: &Context,
+) -> Result<(), String> {
+    let map = maps
+        .get(&target)
+        .ok_or_else(|| format!("Expecting a stackmap frame at branch target {}", target))?;
+    frame
+        .is_assignable_to(map, ctx.hierarchy)
+        .map_err(|e| format!("Bad branch target {}: {}", target, e))
+}
+
+/// Expands the delta-encoded `StackMapTable` into full frames keyed by offset.
+fn stack_map_frames(
+    ctx: &Context,
+    initial: &Frame,
+    starts: &[bool],
+) -> Result<BTreeMap<usize, Frame>, Error> {
+    let entries =
+        ctx.code
+            .attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute_type {
+                AttributeType::StackMapTable { entries } => Some(entries),
+                _ => None,
+            });
+    let mut maps = BTreeMap::new();
+    let entries = match entries {
+        Some(entries) => entries,
+        None => return Ok(maps),
+    };
+    let max_locals = ctx.code.max_locals as usize;
+    // the locals as listed in a stack map, long and double as a single entry
+    let mut locals = compress(&initial.locals);
+    let mut offset: Option<usize> = None;
+    for entry in entries.iter() {
+        let vtype = |info: &VerificationTypeInfo| verification_type(ctx, info, starts);
+        let (delta, stack) = match &entry.frame {
+            StackMapFrame::SameFrame => (entry.frame_type as usize, vec![]),
+            StackMapFrame::SameLocals1StackItemFrame { stack } => {
+                (entry.frame_type as usize - 64, vec![vtype(stack)])
+            }
+            StackMapFrame::SameLocals1StackItemFrameExtended {
+                offset_delta,
+                stack,
+            } => (*offset_delta as usize, vec![vtype(stack)]),
+            StackMapFrame::SameFrameExtended { offset_delta } => (*offset_delta as usize, vec![]),
+            StackMapFrame::ChopFrame { offset_delta } => {
+                let chopped = 251 - entry.frame_type as usize;
+                if chopped > locals.len() {
+                    return Err((
+                        offset,
+                        "Chop frame removes more locals than there are".to_string(),
+                    ));
+                }
+                locals.truncate(locals.len() - chopped);
+                (*offset_delta as usize, vec![])
+            }
+            StackMapFrame::AppendFrame {
+                offset_delta,
+                locals: appended,
+            } => {
+                locals.extend(appended.iter().map(vtype));
+                (*offset_delta as usize, vec![])
+            }
+            StackMapFrame::FullFrame {
+                offset_delta,
+                locals: full,
+                stack,
+            } => {
+                locals = full.iter().map(vtype).collect();
+                (*offset_delta as usize, stack.iter().map(vtype).collect())
+            }
+        };
+        let at = offset.map_or(delta, |previous| previous + delta + 1);
+        if !starts.get(at).copied().unwrap_or(false) {
+            return Err((Some(at), "StackMapTable error: bad offset".to_string()));
+        }
+        offset = Some(at);
+        let locals = locals
+            .iter()
+            .cloned()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| (Some(at), e))?;
+        let stack = stack
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| (Some(at), e))?;
+        let mut frame = Frame::new(max_locals, ctx.code.max_stack as usize);
+        let mut index = 0;
+        for vtype in locals {
+            frame.store(index, vtype.clone()).map_err(|_| {
+                (
+                    Some(at),
+                    "StackMapTable error: local size exceeds max_locals".to_string(),
+                )
+            })?;
+            index += vtype.size();
+        }
+        for vtype in stack {
+            frame.push(vtype).map_err(|_| {
+                (
+                    Some(at),
+                    "StackMapTable error: stack size exceeds max_stack".to_string(),
+                )
+            })?;
+        }
+        frame.flag_this_uninit = frame.locals.contains(&VType::UninitializedThis)
+            || frame.stack.contains(&VType::UninitializedThis);
+        maps.insert(at, frame);
+    }
+    Ok(maps)
+}
+
+// the locals are kept unvalidated until a frame uses them, so a chop can still drop them
+fn compress(locals: &[VType]) -> Vec<Result<VType, String>> {
+    let mut compressed = vec![];
+    let mut index = 0;
+    while index < locals.len() {
+        compressed.push(Ok(locals[index].clone()));
+        index += locals[index].size();
+    }
+    while matches!(compressed.last(), Some(Ok(VType::Top))) {
+        compressed.pop();
+    }
+    compressed
+}
+
+fn verification_type(
+    ctx: &Context,
+    info: &VerificationTypeInfo,
+    starts: &[bool],
+) -> Result<VType, String> {
+    let vtype = match info {
+        VerificationTypeInfo::Top => VType::Top,
+        VerificationTypeInfo::Integer => VType::Integer,
+        VerificationTypeInfo::Float => VType::Float,
+        VerificationTypeInfo::Long => VType::Long,
+        VerificationTypeInfo::Double => VType::Double,
+        VerificationTypeInfo::Null => VType::Null,
+        VerificationTypeInfo::UninitializedThis => VType::UninitializedThis,
+        VerificationTypeInfo::Object { cpool_index } => {
+            VType::Reference(class_name(ctx.cp, *cpool_index)?)
+        }
+        VerificationTypeInfo::Uninitialized { offset } => {
+            let at = *offset as usize;
+            if !starts.get(at).copied().unwrap_or(false) || ctx.code.code[at] != 0xbb {
+                return Err(format!(
+                    "StackMapTable error: no new instruction at {}",
+                    offset
+                ));
+            }
+            VType::Uninitialized(*offset)
+        }
+    };
+    Ok(vtype)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verifier::tests::with_context;
+    use crate::verifier::type_checker::verify_method;
+    use classfile::{Attribute, AttributeType, CodeAttribute, StackMap, StackMapFrame};
+    use std::borrow::Cow;
+
+    fn verify(
+        name: &str,
+        descriptor: &str,
+        max_stack: u16,
+        max_locals: u16,
+        code: &[u8],
+        frames: Vec<StackMap>,
+    ) -> Result<(), (Option<usize>, String)> {
+        let attributes = vec![Attribute {
+            attribute_name_index: 0,
+            attribute_length: 0,
+            attribute_type: AttributeType::StackMapTable { entries: frames },
+        }];
+        let code = CodeAttribute {
+            max_stack,
+            max_locals,
+            code: Cow::Borrowed(code),
+            exception_table: vec![],
+            attributes,
+        };
+        with_context(name, descriptor, 52, &code, verify_method)
+    }
+
+    fn error(result: Result<(), (Option<usize>, String)>) -> (Option<usize>, String) {
+        result.expect_err("expected a VerifyError")
+    }
+
+    #[test]
+    fn test_operand_types() {
+        // iconst_0, ireturn
+        assert!(verify("m", "()I", 1, 1, &[0x03, 0xac], vec![]).is_ok());
+        // fconst_0, ireturn
+        let (offset, message) = error(verify("m", "()I", 1, 1, &[0x0b, 0xac], vec![]));
+        assert_eq!(offset, Some(1));
+        assert!(
+            message.starts_with("Bad type on operand stack"),
+            "{}",
+            message
+        );
+        // ireturn
+        let (offset, message) = error(verify("m", "()I", 1, 1, &[0xac], vec![]));
+        assert_eq!(
+            (offset, message.as_str()),
+            (Some(0), "Operand stack underflow")
+        );
+        // iconst_0, return
+        let (_, message) = error(verify("m", "()I", 1, 1, &[0x03, 0xb1], vec![]));
+        assert_eq!(message, "Method expects a return value");
+    }
+
+    #[test]
+    fn test_limits() {
+        // iconst_0, iconst_0 beyond max_stack
+        let (offset, message) = error(verify("m", "()V", 1, 1, &[0x03, 0x03, 0xb1], vec![]));
+        assert_eq!(
+            (offset, message.as_str()),
+            (Some(1), "Operand stack overflow")
+        );
+        // iload_2 beyond max_locals
+        let (offset, message) = error(verify("m", "(I)V", 1, 2, &[0x1c, 0xb1], vec![]));
+        assert_eq!(offset, Some(0));
+        assert!(message.contains("exceeds max_locals"), "{}", message);
+        // iconst_0 falls off the end
+        let (offset, message) = error(verify("m", "()V", 1, 1, &[0x03], vec![]));
+        assert_eq!(
+            (offset, message.as_str()),
+            (Some(0), "Falling off the end of the code")
+        );
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        // iconst_0, ifeq 5, return, return
+        let code = [0x03, 0x99, 0x00, 0x04, 0xb1, 0xb1];
+        let (offset, message) = error(verify("m", "()V", 1, 1, &code, vec![]));
+        assert_eq!(offset, Some(1));
+        assert!(
+            message.starts_with("Expecting a stackmap frame"),
+            "{}",
+            message
+        );
+        let same = StackMap {
+            frame_type: 5,
+            frame: StackMapFrame::SameFrame,
+        };
+        assert!(verify("m", "()V", 1, 1, &code, vec![same]).is_ok());
+        // a frame in the middle of the ifeq
+        let inside = StackMap {
+            frame_type: 2,
+            frame: StackMapFrame::SameFrame,
+        };
+        let (offset, _) = error(verify("m", "()V", 1, 1, &code, vec![inside]));
+        assert_eq!(offset, Some(2));
+    }
+
+    #[test]
+    fn test_uninitialized() {
+        // aload_0, invokespecial b/Base.<init>, return
+        let code = [0x2a, 0xb7, 0x00, 0x0d, 0xb1];
+        assert!(verify("<init>", "()V", 1, 1, &code, vec![]).is_ok());
+        // only this class or the direct superclass may initialize this
+        let code = [0x2a, 0xb7, 0x00, 0x03, 0xb1];
+        let (offset, message) = error(verify("<init>", "()V", 1, 1, &code, vec![]));
+        assert_eq!(offset, Some(1));
+        assert!(message.starts_with("Bad <init> method call"), "{}", message);
+        let (offset, message) = error(verify("<init>", "()V", 1, 1, &[0xb1], vec![]));
+        assert_eq!(offset, Some(0));
+        assert!(
+            message.starts_with("Constructor must call super()"),
+            "{}",
+            message
+        );
+        // new Object, dup, invokespecial Object.<init>, areturn
+        let code = [0xbb, 0x00, 0x01, 0x59, 0xb7, 0x00, 0x03, 0xb0];
+        assert!(verify("m", "()Ljava/lang/Object;", 2, 1, &code, vec![]).is_ok());
+        // new Object, areturn
+        let (offset, message) = error(verify(
+            "m",
+            "()Ljava/lang/Object;",
+            1,
+            1,
+            &[0xbb, 0x00, 0x01, 0xb0],
+            vec![],
+        ));
+        assert_eq!(offset, Some(3));
+        assert!(message.contains("uninitialized(0)"), "{}", message);
+    }
+
+    #[test]
+    fn test_protected_access() {
+        // aload_1, getfield b/Base.f, ireturn
+        let code = [0x2b, 0xb4, 0x00, 0x09, 0xac];
+        let (offset, message) = error(verify("m", "(Lb/Base;)I", 1, 2, &code, vec![]));
+        assert_eq!(offset, Some(1));
+        assert!(
+            message.starts_with("Bad access to protected data"),
+            "{}",
+            message
+        );
+        assert!(verify("m", "(La/Sub;)I", 1, 2, &code, vec![]).is_ok());
+    }
+}
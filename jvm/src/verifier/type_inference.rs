@@ -0,0 +1,272 @@
+use crate::instructions::OpCode;
+use crate::verifier::code::{decode_all, Operand};
+use crate::verifier::execute::execute;
+use crate::verifier::types::{Frame, VType};
+use crate::verifier::{exception_handlers, Context};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+type Error = (Option<usize>, String);
+
+// the frame before an instruction, and which locals were written since the
+// innermost subroutine was entered
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    frame: Frame,
+    modified: Vec<bool>,
+}
+
+impl State {
+    fn merge(&self, other: &State, ctx: &Context) -> Result<State, String> {
+        Ok(State {
+            frame: self.frame.merge(&other.frame, ctx.hierarchy)?,
+            modified: self
+                .modified
+                .iter()
+                .zip(other.modified.iter())
+                .map(|(a, b)| *a || *b)
+                .collect(),
+        })
+    }
+}
+
+/// Verifies a method without stack maps by data-flow analysis, JVMS 4.10.2.
+/// States are merged where control flow joins until none of them changes.
+///
+/// A subroutine returns to every `jsr` calling it. The locals it wrote come from
+/// the state at `ret`, all others from the caller, so a subroutine may be called
+/// with different types in locals it does not touch.
+pub fn verify_method(ctx: &Context) -> Result<(), Error> {
+    let code = &ctx.code.code;
+    let insns = decode_all(code).map_err(|(offset, e)| (Some(offset), e))?;
+    let mut index_of = vec![None; code.len()];
+    for (index, insn) in insns.iter().enumerate() {
+        index_of[insn.offset] = Some(index);
+    }
+    let starts = index_of.iter().map(Option::is_some).collect::<Vec<_>>();
+    let handlers = exception_handlers(ctx, &starts)?;
+    let jsr_sites = insns
+        .iter()
+        .enumerate()
+        .filter(|(_, insn)| matches!(insn.opcode, OpCode::jsr | OpCode::jsr_w))
+        .map(|(index, insn)| (index, insn.branch_targets()[0]))
+        .collect::<Vec<_>>();
+
+    let max_locals = ctx.code.max_locals as usize;
+    let initial = ctx.initial_frame().map_err(|e| (None, e))?;
+    let mut states: Vec<Option<State>> = vec![None; insns.len()];
+    states[0] = Some(State {
+        frame: initial,
+        modified: vec![false; max_locals],
+    });
+    let mut worklist = BTreeSet::from([0]);
+    // the ret instructions reached so far, by subroutine entry
+    let mut rets: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+    while let Some(index) = worklist.pop_first() {
+        let insn = &insns[index];
+        let at = |e: String| (Some(insn.offset), e);
+        let state = states[index].clone().unwrap();
+        let mut successors: Vec<(usize, State)> = vec![];
+        let mut next = state.clone();
+        match insn.opcode {
+            OpCode::jsr | OpCode::jsr_w => {
+                let entry = insn.branch_targets()[0];
+                next.frame
+                    .push(VType::ReturnAddress(entry as u16))
+                    .map_err(at)?;
+                next.modified = vec![false; max_locals];
+                successors.push((entry, next.clone()));
+                // the caller's state flows past the subroutine through its rets
+                if let Some(rets) = rets.get(&entry) {
+                    worklist.extend(rets.iter().copied());
+                }
+            }
+            OpCode::ret => {
+                let local = match insn.operand {
+                    Operand::Local(local) => local as usize,
+                    _ => unreachable!(),
+                };
+                let entry = match state.frame.local(local).map_err(at)? {
+                    VType::ReturnAddress(entry) => *entry as usize,
+                    other => return Err(at(format!("Bad ret: local {} is {}", local, other))),
+                };
+                rets.entry(entry).or_default().insert(index);
+                for (site, _) in jsr_sites.iter().filter(|(_, target)| *target == entry) {
+                    let caller = match &states[*site] {
+                        Some(caller) => caller,
+                        None => continue,
+                    };
+                    let locals = (0..max_locals)
+                        .map(|i| match state.modified[i] {
+                            true => state.frame.locals[i].clone(),
+                            false => caller.frame.locals[i].clone(),
+                        })
+                        .collect();
+                    let modified = (0..max_locals)
+                        .map(|i| state.modified[i] || caller.modified[i])
+                        .collect();
+                    let site = &insns[*site];
+                    let returned = State {
+                        frame: state.frame.with_locals(locals),
+                        modified,
+                    };
+                    successors.push((site.offset + site.length, returned));
+                }
+            }
+            _ => {
+                let falls_through = execute(ctx, &mut next.frame, insn).map_err(at)?;
+                if let Some(stored) = insn.stored_locals() {
+                    next.modified[stored].fill(true);
+                }
+                for target in insn.branch_targets() {
+                    successors.push((target, next.clone()));
+                }
+                if falls_through {
+                    successors.push((insn.offset + insn.length, next.clone()));
+                }
+            }
+        }
+        for handler in handlers.iter().filter(|handler| handler.covers(insn)) {
+            successors.push((
+                handler.handler,
+                State {
+                    frame: handler.frame(&state.frame),
+                    modified: state.modified.clone(),
+                },
+            ));
+            // a store changes the locals the handler may observe
+            if next.frame.locals != state.frame.locals {
+                let frame = state.frame.with_locals(next.frame.locals.clone());
+                successors.push((
+                    handler.handler,
+                    State {
+                        frame: handler.frame(&frame),
+                        modified: next.modified.clone(),
+                    },
+                ));
+            }
+        }
+        for (offset, successor) in successors {
+            let target = match index_of.get(offset) {
+                Some(Some(target)) => *target,
+                Some(None) => {
+                    return Err(at(format!("Illegal target of jump or branch {}", offset)))
+                }
+                None => return Err(at("Falling off the end of the code".to_string())),
+            };
+            let merged = match &states[target] {
+                Some(current) => current
+                    .merge(&successor, ctx)
+                    .map_err(|e| at(format!("Inconsistent frames at {}: {}", offset, e)))?,
+                None => successor,
+            };
+            if states[target].as_ref() != Some(&merged) {
+                states[target] = Some(merged);
+                worklist.insert(target);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::classpath::Entry;
+    use crate::verifier::tests::{class_loader, with_context};
+    use crate::verifier::type_inference::verify_method;
+    use crate::verifier::verify_methods;
+    use classfile::CodeAttribute;
+    use std::borrow::Cow;
+
+    fn verify(
+        descriptor: &str,
+        max_stack: u16,
+        max_locals: u16,
+        code: &[u8],
+    ) -> Result<(), (Option<usize>, String)> {
+        let code = CodeAttribute {
+            max_stack,
+            max_locals,
+            code: Cow::Borrowed(code),
+            exception_table: vec![],
+            attributes: vec![],
+        };
+        with_context("m", descriptor, 49, &code, verify_method)
+    }
+
+    #[test]
+    fn test_subroutine() {
+        // iconst_1, istore_1, jsr 7, iload_1, ireturn, astore_2, ret 2
+        let code = [0x04, 0x3c, 0xa8, 0x00, 0x05, 0x1b, 0xac, 0x4d, 0xa9, 0x02];
+        assert!(verify("()I", 1, 3, &code).is_ok());
+
+        // the subroutine stores a float in local 1: ... astore_2, fconst_0, fstore_1, ret 2
+        let code = [
+            0x04, 0x3c, 0xa8, 0x00, 0x05, 0x1b, 0xac, 0x4d, 0x0b, 0x44, 0xa9, 0x02,
+        ];
+        let (offset, _) = verify("()I", 1, 3, &code).unwrap_err();
+        assert_eq!(offset, Some(5));
+    }
+
+    #[test]
+    fn test_subroutine_callers() {
+        // local 1 holds an int at the first call and null at the second,
+        // which the subroutine leaves alone:
+        // iconst_0, istore_1, jsr 12, aconst_null, astore_1, jsr 12, aload_1, areturn,
+        // astore_2, ret 2
+        let code = [
+            0x03, 0x3c, 0xa8, 0x00, 0x0a, 0x01, 0x4c, 0xa8, 0x00, 0x05, 0x2b, 0xb0, 0x4d, 0xa9,
+            0x02,
+        ];
+        assert!(verify("()Ljava/lang/Object;", 1, 3, &code).is_ok());
+    }
+
+    #[test]
+    fn test_bad_ret() {
+        // iconst_0, istore_1, ret 1
+        let (offset, message) = verify("()V", 1, 2, &[0x03, 0x3c, 0xa9, 0x01]).unwrap_err();
+        assert_eq!(offset, Some(2));
+        assert!(message.starts_with("Bad ret"), "{}", message);
+    }
+
+    #[test]
+    fn test_merge() {
+        // a loop merges an int into local 1 which was a reference:
+        // aconst_null, astore_1, iconst_0, istore_1, goto 2
+        assert!(verify("()V", 1, 2, &[0x01, 0x4c, 0x03, 0x3c, 0xa7, 0xff, 0xfe]).is_ok());
+
+        // iconst_0, ifeq 8, iconst_0, goto 9, fconst_0, pop, return
+        let code = [
+            0x03, 0x99, 0x00, 0x07, 0x03, 0xa7, 0x00, 0x04, 0x0b, 0x57, 0xb1,
+        ];
+        let (_, message) = verify("()V", 1, 1, &code).unwrap_err();
+        assert!(
+            message.starts_with("Inconsistent frames at 9"),
+            "{}",
+            message
+        );
+        assert!(message.contains("Mismatched stack types"), "{}", message);
+
+        // falling off the end: iconst_0, pop
+        let (_, message) = verify("()V", 1, 1, &[0x03, 0x57]).unwrap_err();
+        assert_eq!(message, "Falling off the end of the code");
+    }
+
+    #[test]
+    fn test_without_stack_maps() {
+        let loader = class_loader();
+        for name in [
+            "User",
+            "GaussTest",
+            "java/lang/String",
+            "java/util/HashMap",
+            "java/util/concurrent/ConcurrentHashMap",
+        ] {
+            let data = loader.class_path().read_class(name).unwrap();
+            let class_file = classfile::parse(&data).unwrap();
+            if let Err(e) = verify_methods(&class_file, &loader, verify_method) {
+                panic!("{}", e);
+            }
+        }
+    }
+}
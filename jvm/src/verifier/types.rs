@@ -0,0 +1,518 @@
+use crate::verifier::ClassHierarchy;
+use classfile::descriptor::{BaseType, FieldType, MethodDescriptor};
+use std::fmt::{Display, Formatter};
+
+const OBJECT: &str = "java/lang/Object";
+
+/// A verification type, JVMS 4.10.1.2.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// An object created by the `new` at the given offset, not yet initialized.
+    Uninitialized(u16),
+    /// A class, interface or array type, by its internal name or array descriptor.
+    Reference(String),
+    /// The return address pushed by a `jsr` to the subroutine at the given offset.
+    ReturnAddress(u16),
+}
+
+impl VType {
+    pub fn object(name: &str) -> Self {
+        VType::Reference(name.to_string())
+    }
+
+    pub fn is_category2(&self) -> bool {
+        matches!(self, VType::Long | VType::Double)
+    }
+
+    pub fn size(&self) -> usize {
+        if self.is_category2() {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            VType::Null | VType::Reference(_) | VType::Uninitialized(_) | VType::UninitializedThis
+        )
+    }
+
+    pub fn is_initialized_reference(&self) -> bool {
+        matches!(self, VType::Null | VType::Reference(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, VType::Reference(name) if name.starts_with('['))
+    }
+
+    /// The type of the elements of an array type.
+    pub fn component(&self) -> Option<VType> {
+        match self {
+            VType::Reference(name) if name.starts_with('[') => Some(from_field_type(&name[1..])?),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of this type can be used where `to` is expected.
+    pub fn is_assignable_to(&self, to: &VType, hierarchy: &dyn ClassHierarchy) -> bool {
+        if self == to || *to == VType::Top {
+            return true;
+        }
+        match (self, to) {
+            (VType::Null, VType::Reference(_)) => true,
+            (VType::Reference(from), VType::Reference(to)) => {
+                is_reference_assignable(from, to, hierarchy)
+            }
+            _ => false,
+        }
+    }
+
+    /// The most specific type both types are assignable to, used where control
+    /// flow merges in the type-inference verifier.
+    pub fn merge(&self, other: &VType, hierarchy: &dyn ClassHierarchy) -> VType {
+        if self == other {
+            return self.clone();
+        }
+        match (self, other) {
+            (VType::Null, VType::Reference(_)) => other.clone(),
+            (VType::Reference(_), VType::Null) => self.clone(),
+            (VType::Reference(a), VType::Reference(b)) => {
+                VType::Reference(common_super_type(a, b, hierarchy))
+            }
+            _ => VType::Top,
+        }
+    }
+}
+
+impl Display for VType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VType::Top => write!(f, "top"),
+            VType::Integer => write!(f, "integer"),
+            VType::Float => write!(f, "float"),
+            VType::Long => write!(f, "long"),
+            VType::Double => write!(f, "double"),
+            VType::Null => write!(f, "null"),
+            VType::UninitializedThis => write!(f, "uninitializedThis"),
+            VType::Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+            VType::Reference(name) => write!(f, "'{}'", name),
+            VType::ReturnAddress(entry) => write!(f, "returnAddress({})", entry),
+        }
+    }
+}
+
+fn is_reference_assignable(from: &str, to: &str, hierarchy: &dyn ClassHierarchy) -> bool {
+    if to == OBJECT {
+        return true;
+    }
+    match (from.strip_prefix('['), to.strip_prefix('[')) {
+        (Some(from), Some(to)) => match (from.bytes().next(), to.bytes().next()) {
+            (Some(b'L' | b'['), Some(b'L' | b'[')) => {
+                is_reference_assignable(&component_name(from), &component_name(to), hierarchy)
+            }
+            _ => from == to,
+        },
+        (Some(_), None) => to == "java/lang/Cloneable" || to == "java/io/Serializable",
+        (None, Some(_)) => false,
+        // interfaces are treated like Object, invokeinterface checks at run time
+        (None, None) => hierarchy.is_interface(to) || hierarchy.is_subclass_of(from, to),
+    }
+}
+
+fn common_super_type(a: &str, b: &str, hierarchy: &dyn ClassHierarchy) -> String {
+    if is_reference_assignable(a, b, hierarchy) && !hierarchy.is_interface(b) {
+        return b.to_string();
+    }
+    if is_reference_assignable(b, a, hierarchy) && !hierarchy.is_interface(a) {
+        return a.to_string();
+    }
+    match (a.strip_prefix('['), b.strip_prefix('[')) {
+        (Some(a), Some(b)) => {
+            let (a0, b0) = (a.bytes().next(), b.bytes().next());
+            if matches!(a0, Some(b'L' | b'[')) && matches!(b0, Some(b'L' | b'[')) {
+                let component =
+                    common_super_type(&component_name(a), &component_name(b), hierarchy);
+                if component.starts_with('[') {
+                    format!("[{}", component)
+                } else {
+                    format!("[L{};", component)
+                }
+            } else {
+                OBJECT.to_string()
+            }
+        }
+        (None, None) if !hierarchy.is_interface(a) && !hierarchy.is_interface(b) => {
+            let mut class = Some(a.to_string());
+            while let Some(name) = class {
+                if hierarchy.is_subclass_of(b, &name) {
+                    return name;
+                }
+                class = hierarchy.super_class(&name);
+            }
+            OBJECT.to_string()
+        }
+        _ => OBJECT.to_string(),
+    }
+}
+
+// "Ljava/lang/String;" -> "java/lang/String", arrays keep their descriptor
+fn component_name(descriptor: &str) -> String {
+    match descriptor.strip_prefix('L') {
+        Some(name) => name.trim_end_matches(';').to_string(),
+        None => descriptor.to_string(),
+    }
+}
+
+impl From<&FieldType> for VType {
+    fn from(field_type: &FieldType) -> Self {
+        match field_type {
+            FieldType::Base(BaseType::Float) => VType::Float,
+            FieldType::Base(BaseType::Long) => VType::Long,
+            FieldType::Base(BaseType::Double) => VType::Double,
+            FieldType::Base(_) => VType::Integer,
+            FieldType::Object(name) => VType::object(name),
+            FieldType::Array(_) => VType::Reference(field_type.to_string()),
+        }
+    }
+}
+
+/// The verification type of a field descriptor, `None` if it is malformed.
+pub fn from_field_type(descriptor: &str) -> Option<VType> {
+    FieldType::parse(descriptor)
+        .ok()
+        .map(|field_type| VType::from(&field_type))
+}
+
+/// Parses a method descriptor into its parameter and return types, the return
+/// type is `None` for `void`.
+pub fn parse_method_descriptor(descriptor: &str) -> Option<(Vec<VType>, Option<VType>)> {
+    let descriptor = MethodDescriptor::parse(descriptor).ok()?;
+    let params = descriptor.parameters.iter().map(VType::from).collect();
+    Some((params, descriptor.return_type.as_ref().map(VType::from)))
+}
+
+/// The abstract state of locals and operand stack before an instruction.
+/// Long and double values take two entries, the second being `Top`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub locals: Vec<VType>,
+    pub stack: Vec<VType>,
+    pub flag_this_uninit: bool,
+    max_stack: usize,
+}
+
+impl Frame {
+    pub fn new(max_locals: usize, max_stack: usize) -> Self {
+        Frame {
+            locals: vec![VType::Top; max_locals],
+            stack: vec![],
+            flag_this_uninit: false,
+            max_stack,
+        }
+    }
+
+    pub fn stack_size(&self) -> usize {
+        self.stack.iter().map(VType::size).sum()
+    }
+
+    pub fn push(&mut self, vtype: VType) -> Result<(), String> {
+        if self.stack_size() + vtype.size() > self.max_stack {
+            return Err("Operand stack overflow".to_string());
+        }
+        self.stack.push(vtype);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<VType, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "Operand stack underflow".to_string())
+    }
+
+    /// Pops a value which must be assignable to `expected`.
+    pub fn pop_type(
+        &mut self,
+        expected: &VType,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<VType, String> {
+        let vtype = self.pop()?;
+        if vtype.is_assignable_to(expected, hierarchy) {
+            Ok(vtype)
+        } else {
+            Err(format!(
+                "Bad type on operand stack: {} is not assignable to {}",
+                vtype, expected
+            ))
+        }
+    }
+
+    pub fn pop_reference(&mut self) -> Result<VType, String> {
+        let vtype = self.pop()?;
+        if vtype.is_initialized_reference() {
+            Ok(vtype)
+        } else {
+            Err(format!(
+                "Bad type on operand stack: expected a reference, found {}",
+                vtype
+            ))
+        }
+    }
+
+    pub fn pop_array(&mut self) -> Result<VType, String> {
+        let vtype = self.pop()?;
+        if vtype == VType::Null || vtype.is_array() {
+            Ok(vtype)
+        } else {
+            Err(format!(
+                "Bad type on operand stack: expected an array, found {}",
+                vtype
+            ))
+        }
+    }
+
+    pub fn local(&self, index: usize) -> Result<&VType, String> {
+        self.locals
+            .get(index)
+            .ok_or_else(|| format!("Local variable index {} exceeds max_locals", index))
+    }
+
+    /// Loads a local which must be assignable to `expected`.
+    pub fn load(
+        &self,
+        index: usize,
+        expected: &VType,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<VType, String> {
+        let vtype = self.local(index)?.clone();
+        if expected.is_category2() && self.local(index + 1)? != &VType::Top {
+            return Err(format!("Bad local variable type at {}", index + 1));
+        }
+        if vtype.is_assignable_to(expected, hierarchy) {
+            Ok(vtype)
+        } else {
+            Err(format!(
+                "Bad local variable type at {}: {} is not assignable to {}",
+                index, vtype, expected
+            ))
+        }
+    }
+
+    pub fn store(&mut self, index: usize, vtype: VType) -> Result<(), String> {
+        let size = vtype.size();
+        if index + size > self.locals.len() {
+            return Err(format!(
+                "Local variable index {} exceeds max_locals",
+                index + size - 1
+            ));
+        }
+        // overwriting half of a long or double invalidates the other half
+        if index > 0 && self.locals[index - 1].is_category2() {
+            self.locals[index - 1] = VType::Top;
+        }
+        if size == 2 {
+            self.locals[index + 1] = VType::Top;
+        }
+        self.locals[index] = vtype;
+        Ok(())
+    }
+
+    /// Replaces every occurrence of an uninitialized type once its `<init>` is called.
+    pub fn initialize(&mut self, from: &VType, to: &VType) {
+        for vtype in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if vtype == from {
+                *vtype = to.clone();
+            }
+        }
+    }
+
+    pub fn is_assignable_to(
+        &self,
+        to: &Frame,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<(), String> {
+        if self.stack.len() != to.stack.len() {
+            return Err("Inconsistent stack height".to_string());
+        }
+        if self.flag_this_uninit && !to.flag_this_uninit {
+            return Err("Inconsistent uninitializedThis flag".to_string());
+        }
+        for (i, (from, to)) in self.locals.iter().zip(to.locals.iter()).enumerate() {
+            if !from.is_assignable_to(to, hierarchy) {
+                return Err(format!(
+                    "Type {} (local {}) is not assignable to {}",
+                    from, i, to
+                ));
+            }
+        }
+        for (i, (from, to)) in self.stack.iter().zip(to.stack.iter()).enumerate() {
+            if !from.is_assignable_to(to, hierarchy) {
+                return Err(format!(
+                    "Type {} (stack {}) is not assignable to {}",
+                    from, i, to
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the frames of two paths reaching the same instruction. Locals of
+    /// different types become unusable, the stacks must agree.
+    pub fn merge(&self, other: &Frame, hierarchy: &dyn ClassHierarchy) -> Result<Frame, String> {
+        if self.stack.len() != other.stack.len() {
+            return Err("Inconsistent stack height".to_string());
+        }
+        let mut stack = Vec::with_capacity(self.stack.len());
+        for (a, b) in self.stack.iter().zip(other.stack.iter()) {
+            match a.merge(b, hierarchy) {
+                VType::Top => return Err(format!("Mismatched stack types {} and {}", a, b)),
+                merged => stack.push(merged),
+            }
+        }
+        let locals = self
+            .locals
+            .iter()
+            .zip(other.locals.iter())
+            .map(|(a, b)| a.merge(b, hierarchy))
+            .collect();
+        Ok(Frame {
+            locals,
+            stack,
+            flag_this_uninit: self.flag_this_uninit || other.flag_this_uninit,
+            max_stack: self.max_stack,
+        })
+    }
+
+    pub fn with_locals(&self, locals: Vec<VType>) -> Frame {
+        Frame {
+            locals,
+            stack: self.stack.clone(),
+            flag_this_uninit: self.flag_this_uninit,
+            max_stack: self.max_stack,
+        }
+    }
+
+    pub fn with_stack(&self, stack: Vec<VType>) -> Frame {
+        Frame {
+            locals: self.locals.clone(),
+            stack,
+            flag_this_uninit: self.flag_this_uninit,
+            max_stack: self.max_stack,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::verifier::types::{from_field_type, parse_method_descriptor, Frame, VType};
+    use crate::verifier::ClassHierarchy;
+
+    struct Flat;
+
+    impl ClassHierarchy for Flat {
+        fn super_class(&self, class: &str) -> Option<String> {
+            match class {
+                "java/lang/Object" => None,
+                "java/lang/Integer" => Some("java/lang/Number".to_string()),
+                _ => Some("java/lang/Object".to_string()),
+            }
+        }
+
+        fn is_interface(&self, class: &str) -> bool {
+            class == "java/lang/Runnable"
+        }
+
+        fn protected_member_owner(&self, _: &str, _: &str, _: &str) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_descriptors() {
+        assert_eq!(from_field_type("I"), Some(VType::Integer));
+        assert_eq!(from_field_type("[[J"), Some(VType::object("[[J")));
+        assert_eq!(
+            from_field_type("Ljava/lang/String;"),
+            Some(VType::object("java/lang/String"))
+        );
+        assert_eq!(from_field_type("L;"), None);
+        assert_eq!(from_field_type("II"), None);
+        let (params, ret) = parse_method_descriptor("(IJ[Ljava/lang/Object;)D").unwrap();
+        assert_eq!(
+            params,
+            vec![
+                VType::Integer,
+                VType::Long,
+                VType::object("[Ljava/lang/Object;")
+            ]
+        );
+        assert_eq!(ret, Some(VType::Double));
+        assert_eq!(parse_method_descriptor("()V"), Some((vec![], None)));
+        assert_eq!(parse_method_descriptor("(V)V"), None);
+    }
+
+    #[test]
+    fn test_assignability() {
+        let integer = VType::object("java/lang/Integer");
+        let number = VType::object("java/lang/Number");
+        assert!(integer.is_assignable_to(&number, &Flat));
+        assert!(!number.is_assignable_to(&integer, &Flat));
+        assert!(VType::Null.is_assignable_to(&integer, &Flat));
+        assert!(integer.is_assignable_to(&VType::object("java/lang/Runnable"), &Flat));
+        assert!(VType::object("[Ljava/lang/Integer;")
+            .is_assignable_to(&VType::object("[Ljava/lang/Number;"), &Flat));
+        assert!(!VType::object("[I").is_assignable_to(&VType::object("[J"), &Flat));
+        assert!(VType::object("[I").is_assignable_to(&VType::object("java/lang/Cloneable"), &Flat));
+        assert!(!VType::Integer.is_assignable_to(&VType::Float, &Flat));
+        assert_eq!(
+            integer.merge(&VType::object("java/lang/Number"), &Flat),
+            number
+        );
+        assert_eq!(
+            integer.merge(&VType::object("java/lang/String"), &Flat),
+            VType::object("java/lang/Object")
+        );
+        assert_eq!(VType::Integer.merge(&VType::Float, &Flat), VType::Top);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut frame = Frame::new(3, 2);
+        frame.push(VType::Long).unwrap();
+        assert!(frame.push(VType::Integer).is_err());
+        frame.store(0, frame.stack[0].clone()).unwrap();
+        assert_eq!(frame.locals, vec![VType::Long, VType::Top, VType::Top]);
+        frame.store(1, VType::Integer).unwrap();
+        assert_eq!(frame.locals, vec![VType::Top, VType::Integer, VType::Top]);
+        assert!(frame.store(2, VType::Double).is_err());
+    }
+
+    #[test]
+    fn test_merge_frames() {
+        let mut a = Frame::new(2, 1);
+        let mut b = Frame::new(2, 1);
+        a.store(0, VType::Integer).unwrap();
+        b.store(0, VType::Float).unwrap();
+        a.store(1, VType::object("java/lang/Integer")).unwrap();
+        b.store(1, VType::Null).unwrap();
+        a.push(VType::object("java/lang/Integer")).unwrap();
+        b.push(VType::object("java/lang/Number")).unwrap();
+        let merged = a.merge(&b, &Flat).unwrap();
+        assert_eq!(
+            merged.locals,
+            vec![VType::Top, VType::object("java/lang/Integer")]
+        );
+        assert_eq!(merged.stack, vec![VType::object("java/lang/Number")]);
+        b.pop().unwrap();
+        b.push(VType::Integer).unwrap();
+        assert!(a.merge(&b, &Flat).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::rtda::{
+    safepoint, set_current, JavaThread, LockWord, Object, Park, ThreadState, Threads, NO_OWNER,
+};
+use anyhow::anyhow;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+
+// Virtual threads are continuations multiplexed onto the worker threads of a
+// tokio runtime, their carriers. A virtual thread runs until it parks at a
+// blocking point, which unmounts it and frees the carrier for other tasks:
+//
+//   - `Thread.sleep` and `Thread.yield`
+//   - a contended `monitorenter`, retried once the monitor is released
+//   - blocking natives such as `Object.wait`, which hand their call to tokio's
+//     blocking pool
+//
+// Holding a monitor does not pin the carrier either, its owner is the virtual
+// thread rather than the host thread.
+
+static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+
+/// A computation that can be suspended and resumed on any carrier.
+pub trait Continuation: Send {
+    /// Runs until the next park, or returns `None` once completed. `pending`
+    /// is an exception thrown at the blocking point the continuation parked
+    /// on, an error returned is an uncaught exception ending the thread.
+    fn resume(&mut self, pending: Option<anyhow::Error>) -> anyhow::Result<Option<Park>>;
+}
+
+pub fn scheduler() -> &'static Scheduler {
+    SCHEDULER.get_or_init(|| {
+        let carriers = std::thread::available_parallelism().map_or(1, usize::from);
+        Scheduler::new(carriers).expect("failed to start the virtual thread scheduler")
+    })
+}
+
+#[derive(Debug)]
+pub struct Scheduler {
+    runtime: Runtime,
+}
+
+impl Scheduler {
+    pub fn new(carriers: usize) -> std::io::Result<Self> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(carriers)
+            .thread_name("carrier")
+            .enable_all()
+            .build()?;
+        Ok(Self { runtime })
+    }
+
+    /// Starts a virtual thread for `object`, the continuation is created by
+    /// `body` once the thread is first mounted.
+    pub fn spawn<C, F>(
+        &self,
+        threads: &'static Threads,
+        object: *mut Object,
+        body: F,
+    ) -> Arc<JavaThread>
+    where
+        C: Continuation + 'static,
+        F: FnOnce() -> Option<C> + Send + 'static,
+    {
+        let thread = threads.add_virtual(object);
+        let task_thread = thread.clone();
+        self.runtime.spawn(async move {
+            // unregisters the thread however it ends, an uncaught exception included
+            let _exit = Exit(threads, task_thread.clone());
+            let mut continuation = match mount(&task_thread, body) {
+                Some(continuation) => continuation,
+                None => return,
+            };
+            let mut pending = None;
+            loop {
+                let park = match mount(&task_thread, || continuation.resume(pending.take())) {
+                    Ok(Some(park)) => park,
+                    Ok(None) => break,
+                    Err(e) => {
+                        eprintln!(
+                            "Exception in thread \"VirtualThread-{}\" {}",
+                            task_thread.id(),
+                            e
+                        );
+                        break;
+                    }
+                };
+                match park {
+                    Park::Yield => tokio::task::yield_now().await,
+                    Park::Sleep(duration) => {
+                        sleep(&task_thread, duration).await;
+                        if task_thread.is_interrupted(true) {
+                            pending =
+                                Some(anyhow!("java.lang.InterruptedException: sleep interrupted"));
+                        }
+                    }
+                    Park::Monitor(lock) => released(&task_thread, lock).await,
+                    Park::Blocking(call) => match tokio::task::spawn_blocking(call).await {
+                        Ok(result) => pending = result.err(),
+                        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                        Err(_) => {}
+                    },
+                }
+            }
+        });
+        thread
+    }
+}
+
+struct Exit(&'static Threads, Arc<JavaThread>);
+
+impl Drop for Exit {
+    fn drop(&mut self) {
+        self.0.remove(&self.1);
+    }
+}
+
+// restores the carrier's own identity even if the virtual thread throws
+struct Mounted(Option<Arc<JavaThread>>, Arc<JavaThread>);
+
+impl Drop for Mounted {
+    fn drop(&mut self) {
+        self.1.set_safepoint_state(ThreadState::InNative);
+        set_current(self.0.take());
+    }
+}
+
+fn mount<R, F: FnOnce() -> R>(thread: &Arc<JavaThread>, f: F) -> R {
+    let _mounted = Mounted(set_current(Some(thread.clone())), thread.clone());
+    safepoint().enter_java(thread);
+    f()
+}
+
+async fn sleep(thread: &JavaThread, duration: Duration) {
+    let deadline = tokio::time::Instant::now() + duration;
+    // an interrupt leaves a permit behind, so one racing this check is not lost
+    while !thread.is_interrupted(false) {
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline) => return,
+            _ = thread.unparker().notified() => {}
+        }
+    }
+}
+
+async fn released(thread: &JavaThread, lock: &LockWord) {
+    let Some(monitor) = lock.contended_monitor(thread.id()) else {
+        return;
+    };
+    let notified = monitor.released().notified();
+    tokio::pin!(notified);
+    // registered before checking the owner, so a release in between is not lost
+    notified.as_mut().enable();
+    if lock.is_inflated_to(monitor) && monitor.owner() != NO_OWNER {
+        notified.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rtda::{current, LockWord, Park, Threads};
+    use crate::vthread::{Continuation, Scheduler};
+    use std::collections::HashSet;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    struct Steps(Vec<Park>);
+
+    impl Continuation for Steps {
+        fn resume(&mut self, _: Option<anyhow::Error>) -> anyhow::Result<Option<Park>> {
+            Ok(self.0.pop())
+        }
+    }
+
+    fn fixtures() -> (&'static Scheduler, &'static Threads) {
+        (
+            Box::leak(Box::new(Scheduler::new(2).unwrap())),
+            Box::leak(Box::new(Threads::new())),
+        )
+    }
+
+    #[test]
+    fn test_many_sleeping_virtual_threads() {
+        let (scheduler, threads) = fixtures();
+        let carriers = Arc::new(Mutex::new(HashSet::new()));
+        let ids = Arc::new(Mutex::new(HashSet::new()));
+        let start = Instant::now();
+        let handles = (0..2000)
+            .map(|_| {
+                let carriers = carriers.clone();
+                let ids = ids.clone();
+                scheduler.spawn(threads, ptr::null_mut(), move || {
+                    carriers.lock().unwrap().insert(std::thread::current().id());
+                    ids.lock().unwrap().insert(current().id());
+                    Some(Steps(vec![Park::Sleep(Duration::from_millis(200))]))
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles.iter() {
+            assert!(handle.is_virtual());
+            assert!(handle.join(Some(Duration::from_secs(10))));
+        }
+        // slept concurrently on two carriers
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(carriers.lock().unwrap().len() <= 2);
+        // each virtual thread saw itself as the current thread
+        assert_eq!(ids.lock().unwrap().len(), 2000);
+        assert_eq!(threads.len(), 0);
+    }
+
+    #[test]
+    fn test_interrupt_wakes_sleeping_virtual_thread() {
+        let (scheduler, threads) = fixtures();
+        let thrown = Arc::new(Mutex::new(None));
+        let task_thrown = thrown.clone();
+        let thread = scheduler.spawn(threads, ptr::null_mut(), move || {
+            struct SleepThenCatch(bool, Arc<Mutex<Option<String>>>);
+            impl Continuation for SleepThenCatch {
+                fn resume(
+                    &mut self,
+                    pending: Option<anyhow::Error>,
+                ) -> anyhow::Result<Option<Park>> {
+                    if !self.0 {
+                        self.0 = true;
+                        return Ok(Some(Park::Sleep(Duration::from_secs(60))));
+                    }
+                    *self.1.lock().unwrap() = pending.map(|e| e.to_string());
+                    Ok(None)
+                }
+            }
+            Some(SleepThenCatch(false, task_thrown))
+        });
+        assert!(!thread.join(Some(Duration::from_millis(50))));
+        thread.interrupt();
+        assert!(thread.join(Some(Duration::from_secs(10))));
+        // the sleep threw into the continuation rather than returning normally
+        let thrown = thrown.lock().unwrap().take().unwrap();
+        assert!(thrown.starts_with("java.lang.InterruptedException"));
+        assert!(!thread.is_interrupted(false));
+    }
+
+    #[test]
+    fn test_yield_and_blocking_parks() {
+        let (scheduler, threads) = fixtures();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let blocking_calls = calls.clone();
+        let thread = scheduler.spawn(threads, ptr::null_mut(), move || {
+            let call = Box::new(move || {
+                std::thread::sleep(Duration::from_millis(10));
+                blocking_calls.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            });
+            Some(Steps(vec![Park::Blocking(call), Park::Yield, Park::Yield]))
+        });
+        assert!(thread.join(Some(Duration::from_secs(10))));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_uncaught_exception_ends_virtual_thread() {
+        let (scheduler, threads) = fixtures();
+        struct Throw;
+        impl Continuation for Throw {
+            fn resume(&mut self, _: Option<anyhow::Error>) -> anyhow::Result<Option<Park>> {
+                Err(anyhow::anyhow!("java.lang.IllegalStateException"))
+            }
+        }
+        let thread = scheduler.spawn(threads, ptr::null_mut(), || Some(Throw));
+        assert!(thread.join(Some(Duration::from_secs(10))));
+        assert_eq!(threads.len(), 0);
+    }
+
+    #[test]
+    fn test_contended_monitor_parks_until_released() {
+        let (scheduler, threads) = fixtures();
+        let lock: &'static LockWord = Box::leak(Box::new(LockWord::new()));
+        let holder = threads.add_virtual(ptr::null_mut());
+        lock.enter(holder.id());
+        let resumes = Arc::new(AtomicUsize::new(0));
+        let task_resumes = resumes.clone();
+        let thread = scheduler.spawn(threads, ptr::null_mut(), move || {
+            struct Enter(&'static LockWord, Arc<AtomicUsize>);
+            impl Continuation for Enter {
+                fn resume(&mut self, _: Option<anyhow::Error>) -> anyhow::Result<Option<Park>> {
+                    self.1.fetch_add(1, Ordering::Relaxed);
+                    let id = current().id();
+                    if !self.0.try_enter(id) {
+                        return Ok(Some(Park::Monitor(self.0)));
+                    }
+                    self.0.exit(id);
+                    Ok(None)
+                }
+            }
+            Some(Enter(lock, task_resumes))
+        });
+        assert!(!thread.join(Some(Duration::from_millis(200))));
+        lock.exit(holder.id());
+        threads.remove(&holder);
+        assert!(thread.join(Some(Duration::from_secs(10))));
+        // parked once instead of spinning while the monitor was held
+        assert_eq!(resumes.load(Ordering::Relaxed), 2);
+    }
+}